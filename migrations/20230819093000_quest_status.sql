@@ -0,0 +1,4 @@
+-- 既存のクエストは公開済みとして扱う
+ALTER TABLE quests
+ADD COLUMN status TEXT NOT NULL DEFAULT 'published'
+CHECK (status IN ('draft', 'published', 'archived'));
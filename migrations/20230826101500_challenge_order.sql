@@ -0,0 +1,7 @@
+-- クエスト内でのチャレンジの並び順
+ALTER TABLE challenges
+ADD COLUMN order_index INTEGER NOT NULL DEFAULT 0;
+
+-- trueのときは前のチャレンジを完了しないと次のチャレンジが解放されない
+ALTER TABLE quests
+ADD COLUMN sequential BOOLEAN NOT NULL DEFAULT FALSE;
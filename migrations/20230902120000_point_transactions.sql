@@ -0,0 +1,12 @@
+CREATE TABLE point_transactions
+(
+    id SERIAL PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users (id) DEFERRABLE INITIALLY DEFERRED,
+    challenge_id TEXT REFERENCES challenges (id) DEFERRABLE INITIALLY DEFERRED,
+    amount INTEGER NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- 同じチャレンジで二重にポイントを付与しない
+ALTER TABLE point_transactions
+ADD CONSTRAINT unique_user_challenge_point UNIQUE (user_id, challenge_id);
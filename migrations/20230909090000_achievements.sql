@@ -0,0 +1,25 @@
+CREATE TABLE achievements
+(
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    description TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('challenges_completed', 'quests_cleared')),
+    threshold INTEGER NOT NULL CHECK (threshold > 0)
+);
+
+CREATE TABLE user_achievements
+(
+    user_id TEXT NOT NULL REFERENCES users (id) DEFERRABLE INITIALLY DEFERRED,
+    achievement_id TEXT NOT NULL REFERENCES achievements (id) DEFERRABLE INITIALLY DEFERRED,
+    achieved_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- 同じ実績を二重に付与しない
+ALTER TABLE user_achievements
+ADD CONSTRAINT unique_user_achievement_pair UNIQUE (user_id, achievement_id);
+
+INSERT INTO achievements (id, name, description, kind, threshold) VALUES
+    ('first_challenge', 'はじめの一歩', 'チャレンジを1個完了する', 'challenges_completed', 1),
+    ('ten_challenges', 'スタンプコレクター', 'チャレンジを10個完了する', 'challenges_completed', 10),
+    ('first_quest', 'クエストクリア', 'クエストを1つ制覇する', 'quests_cleared', 1),
+    ('three_quests', '冒険家', 'クエストを3つ制覇する', 'quests_cleared', 3);
@@ -0,0 +1,8 @@
+-- プッシュ通知の送信先。同じ端末で別ユーザーがログインしたら付け替える
+CREATE TABLE devices
+(
+    token TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users (id) DEFERRABLE INITIALLY DEFERRED,
+    platform TEXT NOT NULL CHECK (platform IN ('ios', 'android', 'web')),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,5 @@
+-- NULLは期限なし。既存のクエストは常時開催として扱う
+ALTER TABLE quests
+ADD COLUMN start_at TIMESTAMPTZ,
+ADD COLUMN end_at TIMESTAMPTZ,
+ADD CONSTRAINT quest_schedule_order CHECK (start_at IS NULL OR end_at IS NULL OR start_at < end_at);
@@ -0,0 +1,9 @@
+-- クエストの修了証。completed_atは初回発行時に確定し、以降は同じ証明書を返す
+CREATE TABLE certificates
+(
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users (id) DEFERRABLE INITIALLY DEFERRED,
+    quest_id TEXT NOT NULL REFERENCES quests (id) DEFERRABLE INITIALLY DEFERRED,
+    completed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, quest_id)
+);
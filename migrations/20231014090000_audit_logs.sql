@@ -0,0 +1,11 @@
+-- 管理操作の記録。ユーザーを削除しても記録は残すので外部キーは張らない
+CREATE TABLE audit_logs
+(
+    id BIGSERIAL PRIMARY KEY,
+    actor_id TEXT,
+    action TEXT NOT NULL,
+    target_id TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX audit_logs_created_at_idx ON audit_logs (created_at DESC);
@@ -0,0 +1,17 @@
+-- 時間のかかる処理(エクスポート・インポートなど)の状態と結果
+CREATE TABLE jobs
+(
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('running', 'succeeded', 'failed', 'cancelled')),
+    progress SMALLINT NOT NULL DEFAULT 0 CHECK (progress BETWEEN 0 AND 100),
+    error TEXT,
+    result_content_type TEXT,
+    result_filename TEXT,
+    result BYTEA,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX jobs_user_id_created_at_idx ON jobs (user_id, created_at DESC);
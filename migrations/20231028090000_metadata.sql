@@ -0,0 +1,3 @@
+-- 主催者が付ける任意の情報。キーや大きさの制限はアプリ側で確認する
+ALTER TABLE quests ADD COLUMN metadata JSONB NOT NULL DEFAULT '{}';
+ALTER TABLE challenges ADD COLUMN metadata JSONB NOT NULL DEFAULT '{}';
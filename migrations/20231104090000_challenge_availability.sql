@@ -0,0 +1,5 @@
+-- チャレンジ単位の公開状態と受付期間。期間がNULLのときはクエストの開催期間に従う
+ALTER TABLE challenges ADD COLUMN active BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE challenges ADD COLUMN start_at TIMESTAMPTZ;
+ALTER TABLE challenges ADD COLUMN end_at TIMESTAMPTZ;
+ALTER TABLE challenges ADD CONSTRAINT challenges_schedule_check CHECK (start_at < end_at);
@@ -0,0 +1,6 @@
+-- 誤操作から戻せるように削除は日時を入れるだけにし、一定期間後にまとめて物理削除する
+ALTER TABLE quests ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX quests_deleted_at_idx ON quests (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX users_deleted_at_idx ON users (deleted_at) WHERE deleted_at IS NOT NULL;
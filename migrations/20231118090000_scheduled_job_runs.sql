@@ -0,0 +1,13 @@
+-- 定期処理の実行履歴。他のインスタンスが実行中でスキップした回は残さない
+CREATE TABLE scheduled_job_runs
+(
+    id BIGSERIAL PRIMARY KEY,
+    job_name TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('running', 'succeeded', 'failed')),
+    -- 成功時は処理内容の要約、失敗時はエラー
+    message TEXT,
+    started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX scheduled_job_runs_job_name_idx ON scheduled_job_runs (job_name, started_at DESC);
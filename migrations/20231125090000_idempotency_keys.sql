@@ -0,0 +1,17 @@
+-- Idempotency-Key付きのPOSTの結果。同じキーの再送には保存したレスポンスを返す
+CREATE TABLE idempotency_keys
+(
+    -- 同じキーでも利用者が違えば別のリクエストとして扱う
+    scope TEXT NOT NULL,
+    key TEXT NOT NULL,
+    -- メソッド・パス・本文のハッシュ。同じキーで別のリクエストが来たら弾く
+    request_hash TEXT NOT NULL,
+    -- 処理中はNULL
+    status SMALLINT,
+    headers JSONB,
+    body BYTEA,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (scope, key)
+);
+
+CREATE INDEX idempotency_keys_created_at_idx ON idempotency_keys (created_at);
@@ -0,0 +1,18 @@
+-- フィードに並べるためにチャレンジの完了日時を残す。既存の記録は移行した時刻になる
+ALTER TABLE user_completed_challenges
+ADD COLUMN completed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW();
+
+CREATE INDEX user_completed_challenges_user_id_completed_at_idx
+ON user_completed_challenges (user_id, completed_at DESC);
+
+CREATE TABLE user_follows
+(
+    follower_id TEXT NOT NULL REFERENCES users (id),
+    followee_id TEXT NOT NULL REFERENCES users (id),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (follower_id, followee_id),
+    CHECK (follower_id <> followee_id)
+);
+
+-- フォロワー一覧用。フォロー中の一覧は主キーで引ける
+CREATE INDEX user_follows_followee_id_idx ON user_follows (followee_id, created_at DESC);
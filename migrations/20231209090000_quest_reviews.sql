@@ -0,0 +1,13 @@
+-- クエストのレビュー。参加済みのユーザーだけが1クエストに1件投稿できる
+CREATE TABLE quest_reviews
+(
+    id TEXT PRIMARY KEY,
+    quest_id TEXT NOT NULL REFERENCES quests (id),
+    user_id TEXT NOT NULL REFERENCES users (id),
+    rating SMALLINT NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    comment TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (quest_id, user_id)
+);
+
+CREATE INDEX quest_reviews_quest_id_created_at_idx ON quest_reviews (quest_id, created_at DESC);
@@ -0,0 +1,6 @@
+-- チャレンジ完了時に現地で撮った写真。本体はS3に置き、URLだけを残す
+ALTER TABLE user_completed_challenges ADD COLUMN photo_url TEXT;
+
+CREATE INDEX user_completed_challenges_challenge_id_photo_idx
+ON user_completed_challenges (challenge_id, completed_at DESC)
+WHERE photo_url IS NOT NULL;
@@ -0,0 +1,2 @@
+-- 退会したユーザーは個人情報を匿名化して残す。参加・完了の記録は統計のために消さない
+ALTER TABLE users ADD COLUMN deactivated_at TIMESTAMP WITH TIME ZONE;
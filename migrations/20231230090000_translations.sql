@@ -0,0 +1,19 @@
+-- quests/challengesの本文は既定の言語(DEFAULT_LANGUAGE)として扱い、他の言語はここに持つ
+CREATE TABLE quest_translations
+(
+    quest_id TEXT NOT NULL REFERENCES quests (id) DEFERRABLE INITIALLY DEFERRED,
+    lang TEXT NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL,
+    PRIMARY KEY (quest_id, lang)
+);
+
+CREATE TABLE challenge_translations
+(
+    challenge_id TEXT NOT NULL REFERENCES challenges (id) DEFERRABLE INITIALLY DEFERRED,
+    lang TEXT NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT NOT NULL,
+    flavor_text TEXT NOT NULL,
+    PRIMARY KEY (challenge_id, lang)
+);
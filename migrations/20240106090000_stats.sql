@@ -0,0 +1,36 @@
+-- 管理画面の集計。全件を数えるので日次で作り直し、APIはビューを読むだけにする
+-- REFRESH ... CONCURRENTLYで読みながら更新できるよう、それぞれに一意インデックスを張る
+CREATE MATERIALIZED VIEW quest_stats AS
+SELECT
+    q.id AS quest_id,
+    q.title,
+    (SELECT COUNT(*) FROM user_participating_quests p WHERE p.quest_id = q.id) AS participants,
+    (SELECT COUNT(*) FROM certificates c WHERE c.quest_id = q.id) AS completers
+FROM quests q
+WHERE q.deleted_at IS NULL;
+
+CREATE UNIQUE INDEX quest_stats_quest_id_idx ON quest_stats (quest_id);
+
+CREATE MATERIALIZED VIEW challenge_stats AS
+SELECT
+    c.id AS challenge_id,
+    c.quest_id,
+    c.name,
+    COUNT(u.user_id) AS completions
+FROM challenges c
+JOIN quests q ON q.id = c.quest_id
+LEFT JOIN user_completed_challenges u ON u.challenge_id = c.id
+WHERE q.deleted_at IS NULL
+GROUP BY c.id, c.quest_id, c.name;
+
+CREATE UNIQUE INDEX challenge_stats_challenge_id_idx ON challenge_stats (challenge_id);
+
+-- チャレンジを完了したユーザーをその日のアクティブユーザーとする。日付は日本時間で区切る
+CREATE MATERIALIZED VIEW daily_active_user_stats AS
+SELECT
+    (completed_at AT TIME ZONE 'Asia/Tokyo')::DATE AS date,
+    COUNT(DISTINCT user_id) AS active_users
+FROM user_completed_challenges
+GROUP BY 1;
+
+CREATE UNIQUE INDEX daily_active_user_stats_date_idx ON daily_active_user_stats (date);
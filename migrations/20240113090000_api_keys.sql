@@ -0,0 +1,12 @@
+-- パートナーのサーバーから呼ぶためのキー。平文は発行時に一度だけ返し、ハッシュだけを残す
+CREATE TABLE api_keys
+(
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    -- 一覧でどのキーか見分けるための先頭部分
+    prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMP WITH TIME ZONE,
+    revoked_at TIMESTAMP WITH TIME ZONE
+);
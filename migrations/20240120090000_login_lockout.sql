@@ -0,0 +1,3 @@
+-- ログインの失敗が続いたアカウントを一定時間ロックする。成功するか管理者が解除すると0に戻る
+ALTER TABLE users ADD COLUMN failed_login_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN locked_until TIMESTAMP WITH TIME ZONE;
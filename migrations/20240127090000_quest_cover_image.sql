@@ -0,0 +1,3 @@
+-- クエストのカバー画像。一覧ではサムネイル、詳細ではフル画像を返す
+ALTER TABLE quests ADD COLUMN cover_image_url TEXT;
+ALTER TABLE quests ADD COLUMN cover_thumbnail_url TEXT;
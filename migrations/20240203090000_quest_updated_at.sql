@@ -0,0 +1,2 @@
+-- 条件付きGETのLast-Modifiedに使う。既存のクエストは追加した時点の日時になる
+ALTER TABLE quests ADD COLUMN updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW();
@@ -0,0 +1,3 @@
+-- 楽観ロック用。更新のたびに1ずつ増やす
+ALTER TABLE quests ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE challenges ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,7 @@
+-- 機能ごとのON/OFFを管理APIから上書きする。行がなければ環境変数で決めた既定値のまま
+CREATE TABLE feature_flags
+(
+    name TEXT PRIMARY KEY,
+    enabled BOOLEAN NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,11 @@
+-- あとで挑戦したいクエストを保存しておく
+CREATE TABLE user_bookmarks
+(
+    user_id TEXT NOT NULL REFERENCES users (id),
+    quest_id TEXT NOT NULL REFERENCES quests (id),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, quest_id)
+);
+
+-- クエストを物理削除するときに引く。ユーザー側は主キーで引ける
+CREATE INDEX user_bookmarks_quest_id_idx ON user_bookmarks (quest_id);
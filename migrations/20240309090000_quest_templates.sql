@@ -0,0 +1,8 @@
+-- 似たクエストを量産するための雛形。作成時の内容をそのまま保存し、元のクエストとは連動しない
+CREATE TABLE quest_templates
+(
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    quest JSONB NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
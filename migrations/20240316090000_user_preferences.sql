@@ -0,0 +1,9 @@
+-- ユーザーが変えた設定だけを持つ。NULLの項目と行がないユーザーは既定値のまま
+CREATE TABLE user_preferences
+(
+    user_id TEXT PRIMARY KEY REFERENCES users (id),
+    notifications_enabled BOOLEAN,
+    language TEXT,
+    display_name TEXT,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
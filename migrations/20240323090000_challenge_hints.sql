@@ -0,0 +1,28 @@
+-- チャレンジのヒント。order_indexの順に1つずつ開く
+CREATE TABLE challenge_hints
+(
+    id TEXT PRIMARY KEY,
+    challenge_id TEXT NOT NULL REFERENCES challenges (id),
+    body TEXT NOT NULL,
+    order_index INTEGER NOT NULL DEFAULT 0,
+    -- 開くときに差し引くポイント。0なら減らさない
+    cost INTEGER NOT NULL DEFAULT 0 CHECK (cost >= 0),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX challenge_hints_challenge_id_idx ON challenge_hints (challenge_id, order_index);
+
+-- 一度開いたヒントは何度見てもポイントを差し引かない
+CREATE TABLE user_opened_hints
+(
+    user_id TEXT NOT NULL REFERENCES users (id),
+    hint_id TEXT NOT NULL REFERENCES challenge_hints (id),
+    opened_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, hint_id)
+);
+
+CREATE INDEX user_opened_hints_hint_id_idx ON user_opened_hints (hint_id);
+
+-- ヒントを開いて差し引いたポイント。amountは負の値になる
+ALTER TABLE point_transactions
+ADD COLUMN hint_id TEXT REFERENCES challenge_hints (id) DEFERRABLE INITIALLY DEFERRED;
@@ -0,0 +1,7 @@
+-- 参加日時。既存の行は参加日時が分からないので追加した時点の日時になる
+ALTER TABLE user_participating_quests
+ADD COLUMN joined_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW();
+
+-- クエストから参加者を参加順に引く
+CREATE INDEX user_participating_quests_quest_id_idx
+ON user_participating_quests (quest_id, joined_at);
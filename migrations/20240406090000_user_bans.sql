@@ -0,0 +1,16 @@
+-- 凍結の記録。解除や期限切れの後も履歴として残す
+-- 操作した管理者は削除されても記録を残すので外部キーは張らない
+CREATE TABLE user_bans
+(
+    id BIGSERIAL PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users (id),
+    reason TEXT NOT NULL,
+    banned_by TEXT,
+    -- NULLなら無期限
+    expires_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    lifted_at TIMESTAMP WITH TIME ZONE,
+    lifted_by TEXT
+);
+
+CREATE INDEX user_bans_user_id_idx ON user_bans (user_id, created_at DESC);
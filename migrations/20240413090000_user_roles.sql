@@ -0,0 +1,4 @@
+-- 管理APIはadminのユーザーだけが呼べる。既存のユーザーは一般ユーザーとして扱う
+ALTER TABLE users
+ADD COLUMN role TEXT NOT NULL DEFAULT 'user'
+CHECK (role IN ('user', 'admin'));
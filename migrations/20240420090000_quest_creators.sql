@@ -0,0 +1,3 @@
+-- クエストを作ったユーザー。参加者一覧を見られるのは作成者と管理者だけ
+-- 作成者が削除されてもクエストは残すので外部キーは張らない。既存のクエストはNULLのまま
+ALTER TABLE quests ADD COLUMN created_by TEXT;
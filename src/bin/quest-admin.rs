@@ -0,0 +1,194 @@
+//! 運用コマンド。例: cargo quest-admin users list
+use std::io::BufRead;
+
+use anyhow::{anyhow, bail};
+use chrono::{Duration, Utc};
+use clap::{Parser, Subcommand};
+use dotenv::dotenv;
+
+use quest_api::{
+    config::AppConfig,
+    connect_repositories,
+    repositories::{
+        audit::AuditAction,
+        id::{QuestId, UserId},
+        quest::{QuestStatus, UpdateQuest, UpdateQuestError},
+        user::{validate_password, UserRole},
+        Repositories,
+    },
+    services::audit::record_audit,
+};
+
+/// 運用者向けの管理コマンド。APIと同じリポジトリを通すので、キャッシュの破棄やハッシュ化もそのまま行われる
+#[derive(Debug, Parser)]
+#[command(name = "quest-admin")]
+struct AdminCli {
+    #[command(subcommand)]
+    command: AdminCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum AdminCommand {
+    #[command(subcommand)]
+    Users(UserCommand),
+    #[command(subcommand)]
+    Quests(QuestCommand),
+    /// クエストが消えて残ったチャレンジを関連する記録ごと消す
+    PurgeOrphans {
+        /// 作られてからこの時間が経ったものだけを消す。作成途中のものを巻き込まないため
+        #[arg(long, default_value_t = 24)]
+        older_than_hours: i64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum UserCommand {
+    /// 削除・退会していないユーザーを登録順に出す
+    List,
+    /// 新しいパスワードは履歴に残らないよう標準入力の1行目から読む
+    ResetPassword { user_id: UserId },
+    /// user/adminを付け替える。最初の管理者はここで作る
+    SetRole { user_id: UserId, role: UserRole },
+}
+
+#[derive(Debug, Subcommand)]
+enum QuestCommand {
+    Publish { quest_id: QuestId },
+    Archive { quest_id: QuestId },
+}
+
+impl AdminCli {
+    async fn run(self, repositories: Repositories) -> anyhow::Result<()> {
+        match self.command {
+            AdminCommand::Users(UserCommand::List) => {
+                for user in repositories.user.all().await? {
+                    println!(
+                        "{}\t{}\t{}\t{}",
+                        user.id,
+                        user.username,
+                        user.email,
+                        user.created_at.to_rfc3339()
+                    );
+                }
+            }
+            AdminCommand::Users(UserCommand::ResetPassword { user_id }) => {
+                let password = read_password()?;
+                validate_password(&password).map_err(|e| anyhow!(e))?;
+                repositories
+                    .user
+                    .reset_password(user_id.clone(), password)
+                    .await?;
+                record_audit(
+                    repositories.audit.as_ref(),
+                    None,
+                    AuditAction::UserPasswordReset,
+                    user_id.to_string(),
+                )
+                .await;
+                println!("reset password of {}", user_id);
+            }
+            AdminCommand::Users(UserCommand::SetRole { user_id, role }) => {
+                repositories.user.set_role(user_id.clone(), role).await?;
+                record_audit(
+                    repositories.audit.as_ref(),
+                    None,
+                    AuditAction::UserRoleChange,
+                    user_id.to_string(),
+                )
+                .await;
+                println!("set role of {} to {}", user_id, role);
+            }
+            AdminCommand::Quests(QuestCommand::Publish { quest_id }) => {
+                change_status(&repositories, quest_id, QuestStatus::Published).await?;
+            }
+            AdminCommand::Quests(QuestCommand::Archive { quest_id }) => {
+                change_status(&repositories, quest_id, QuestStatus::Archived).await?;
+            }
+            AdminCommand::PurgeOrphans { older_than_hours } => {
+                if older_than_hours < 0 {
+                    bail!("--older-than-hours must not be negative");
+                }
+                let before = Utc::now() - Duration::hours(older_than_hours);
+                let purged = repositories.challenge.purge_orphans(before).await?;
+                println!("purged {} orphan challenges", purged);
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn change_status(
+    repositories: &Repositories,
+    quest_id: QuestId,
+    status: QuestStatus,
+) -> anyhow::Result<()> {
+    let quest = repositories
+        .quest
+        .update(quest_id.clone(), UpdateQuest::status_only(status))
+        .await
+        .map_err(|e| match e {
+            UpdateQuestError::NotFound => anyhow!("quest not found: {}", quest_id),
+            UpdateQuestError::VersionConflict => {
+                anyhow!("quest {} was updated concurrently, retry", quest_id)
+            }
+            UpdateQuestError::Repository(e) => e,
+        })?;
+    record_audit(
+        repositories.audit.as_ref(),
+        None,
+        AuditAction::QuestUpdate,
+        quest.id.to_string(),
+    )
+    .await;
+    println!("{} is now {}", quest.id, status);
+    Ok(())
+}
+
+fn read_password() -> anyhow::Result<String> {
+    let mut password = String::new();
+    std::io::stdin().lock().read_line(&mut password)?;
+    Ok(password.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv().ok();
+    let cli = AdminCli::parse();
+    let config = AppConfig::from_env()?;
+    let (repositories, _) = connect_repositories(&config).await?;
+    cli.run(repositories).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_parse_nested_commands() {
+        let cli =
+            AdminCli::try_parse_from(["quest-admin", "users", "reset-password", "user-1"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            AdminCommand::Users(UserCommand::ResetPassword { user_id }) if user_id == "user-1"
+        ));
+
+        let cli = AdminCli::try_parse_from(["quest-admin", "users", "set-role", "user-1", "admin"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            AdminCommand::Users(UserCommand::SetRole {
+                role: UserRole::Admin,
+                ..
+            })
+        ));
+
+        let cli = AdminCli::try_parse_from(["quest-admin", "purge-orphans"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            AdminCommand::PurgeOrphans {
+                older_than_hours: 24
+            }
+        ));
+        assert!(AdminCli::try_parse_from(["quest-admin", "quests", "publish"]).is_err());
+    }
+}
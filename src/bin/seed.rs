@@ -0,0 +1,20 @@
+//! 開発用のデータを投入する。例: cargo seed --scenario heavy --users 100
+use anyhow::anyhow;
+use dotenv::dotenv;
+
+use quest_api::{
+    config::AppConfig,
+    connect_repositories,
+    repositories::factory::{Factory, SeedPlan},
+};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv().ok();
+    let plan = SeedPlan::from_args(std::env::args().skip(1)).map_err(|e| anyhow!(e))?;
+    let config = AppConfig::from_env()?;
+    let (repositories, _) = connect_repositories(&config).await?;
+    let report = Factory::new(repositories).seed(&plan).await?;
+    println!("seeded {:?} with {:?}", report, plan);
+    Ok(())
+}
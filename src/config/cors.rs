@@ -0,0 +1,185 @@
+use http::{header::CONTENT_TYPE, request::Parts, HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::middleware::{csrf::CSRF_HEADER, idempotency::IDEMPOTENCY_KEY};
+
+/// `ALLOWED_ORIGINS` が未設定のときに許可するオリジン
+const DEFAULT_ALLOWED_ORIGINS: &str = "http://localhost:5173,https://quest-web-cli.vercel.app";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OriginPattern {
+    /// 完全一致
+    Exact(String),
+    /// `https://*.vercel.app` のようなワイルドカードサブドメイン
+    WildcardSubdomain { scheme: String, domain: String },
+}
+
+impl OriginPattern {
+    pub fn parse(pattern: &str) -> Self {
+        if let Some((scheme, domain)) = pattern.split_once("://*.") {
+            return OriginPattern::WildcardSubdomain {
+                scheme: scheme.to_string(),
+                domain: domain.to_string(),
+            };
+        }
+        OriginPattern::Exact(pattern.to_string())
+    }
+
+    pub fn matches(&self, origin: &str) -> bool {
+        match self {
+            OriginPattern::Exact(allowed) => allowed == origin,
+            OriginPattern::WildcardSubdomain { scheme, domain } => {
+                let Some(host) = origin.strip_prefix(&format!("{}://", scheme)) else {
+                    return false;
+                };
+                let Some(subdomain) = host.strip_suffix(&format!(".{}", domain)) else {
+                    return false;
+                };
+                // サブドメイン部分にパスやポートが紛れ込んでいないこと
+                !subdomain.is_empty()
+                    && subdomain
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    /// 開発モードでは全オリジンを許可する
+    allow_all: bool,
+    origins: Vec<OriginPattern>,
+}
+
+impl CorsConfig {
+    pub fn new(allow_all: bool, origins: Vec<OriginPattern>) -> Self {
+        Self { allow_all, origins }
+    }
+
+    /// `ALLOWED_ORIGINS`(カンマ区切り)と開発モードかどうかから設定を組み立てる
+    pub fn from_origins(allowed_origins: Option<String>, is_development: bool) -> Self {
+        let allowed_origins =
+            allowed_origins.unwrap_or_else(|| DEFAULT_ALLOWED_ORIGINS.to_string());
+
+        Self::new(is_development, Self::parse_origins(&allowed_origins))
+    }
+
+    pub fn parse_origins(origins: &str) -> Vec<OriginPattern> {
+        origins
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(OriginPattern::parse)
+            .collect()
+    }
+
+    pub fn is_allowed(&self, origin: &str) -> bool {
+        self.allow_all || self.origins.iter().any(|pattern| pattern.matches(origin))
+    }
+
+    pub fn layer(self) -> CorsLayer {
+        // credentialsを許可する場合はワイルドカード(*)を返せないので、
+        // 判定に通ったオリジンをそのまま返す
+        let allow_origin = AllowOrigin::predicate(move |origin: &HeaderValue, _: &Parts| {
+            origin
+                .to_str()
+                .map(|origin| self.is_allowed(origin))
+                .unwrap_or(false)
+        });
+
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_credentials(true)
+            .allow_methods([
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
+            .allow_headers(vec![
+                CONTENT_TYPE,
+                HeaderName::from_static(CSRF_HEADER),
+                HeaderName::from_static(IDEMPOTENCY_KEY),
+            ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{body::Body, extract::Request, response::Response, routing::post, Router};
+    use http::header::{
+        ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_REQUEST_HEADERS,
+        ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+    };
+    use tower::ServiceExt;
+
+    const WEB_ORIGIN: &str = "http://localhost:5173";
+
+    /// webフロントからのプリフライトに返すレスポンス
+    async fn preflight(method: Method, headers: &str) -> Response {
+        let app = Router::new()
+            .route("/", post(|| async {}))
+            .layer(CorsConfig::new(false, CorsConfig::parse_origins(WEB_ORIGIN)).layer());
+        let req = Request::builder()
+            .uri("/")
+            .method(Method::OPTIONS)
+            .header(ORIGIN, WEB_ORIGIN)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, method.as_str())
+            .header(ACCESS_CONTROL_REQUEST_HEADERS, headers)
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_allow_idempotency_key_in_preflight() {
+        let res = preflight(Method::POST, "content-type,idempotency-key").await;
+
+        let allowed = res.headers()[ACCESS_CONTROL_ALLOW_HEADERS]
+            .to_str()
+            .unwrap();
+        assert!(allowed.contains(IDEMPOTENCY_KEY));
+    }
+
+    #[tokio::test]
+    async fn should_allow_put_and_patch_in_preflight() {
+        // PUT /quests/:id/cover_image と PATCH /me/preferences
+        for method in [Method::PUT, Method::PATCH] {
+            let res = preflight(method.clone(), "content-type").await;
+
+            let allowed = res.headers()[ACCESS_CONTROL_ALLOW_METHODS]
+                .to_str()
+                .unwrap();
+            assert!(allowed.contains(method.as_str()), "{}", allowed);
+        }
+    }
+
+    #[test]
+    fn should_match_exact_origin() {
+        let config = CorsConfig::new(false, CorsConfig::parse_origins("http://localhost:5173"));
+
+        assert!(config.is_allowed("http://localhost:5173"));
+        assert!(!config.is_allowed("http://localhost:3000"));
+    }
+
+    #[test]
+    fn should_match_wildcard_subdomain() {
+        let config = CorsConfig::new(false, CorsConfig::parse_origins("https://*.vercel.app"));
+
+        assert!(config.is_allowed("https://quest-web-cli.vercel.app"));
+        assert!(config.is_allowed("https://preview.quest-web-cli.vercel.app"));
+        assert!(!config.is_allowed("https://vercel.app"));
+        assert!(!config.is_allowed("http://quest-web-cli.vercel.app"));
+        assert!(!config.is_allowed("https://evil.com/.vercel.app"));
+    }
+
+    #[test]
+    fn should_allow_all_origins_in_development() {
+        let config = CorsConfig::new(true, Vec::new());
+
+        assert!(config.is_allowed("http://localhost:8080"));
+    }
+}
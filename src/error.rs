@@ -0,0 +1,247 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+/// クライアントが分岐に使うエラーコード。一度公開したコードは変えずに追加だけする
+/// 一覧はdocs/openapi.yamlのErrorCodeにも載せる
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    // ステータスコードしか分からないときの汎用コード
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    RequestTimeout,
+    Conflict,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    ValidationFailed,
+    TooManyRequests,
+    InternalServerError,
+    ServiceUnavailable,
+    // 認証
+    TokenExpired,
+    InvalidToken,
+    MalformedToken,
+    InvalidCredentials,
+    AccountLocked,
+    CaptchaFailed,
+    CaptchaUnavailable,
+    InvalidApiKey,
+    CsrfTokenMismatch,
+    UserBanned,
+    // リソース
+    QuestNotFound,
+    ChallengeNotFound,
+    UserNotFound,
+    JobNotFound,
+    OfflinePackNotFound,
+    ApiKeyNotFound,
+    QuestTemplateNotFound,
+    HintNotFound,
+    BanNotFound,
+    // ドメイン
+    AlreadyParticipating,
+    QuestNotParticipated,
+    AlreadyReviewed,
+    QuestOutOfSchedule,
+    QuestNotPublished,
+    ChallengeLocked,
+    ChallengeUnavailable,
+    ChallengeTooFar,
+    QuestNotCompleted,
+    JobAlreadyFinished,
+    VersionConflict,
+    FeatureDisabled,
+    HintLocked,
+    InsufficientPoints,
+    // 冪等キー
+    IdempotencyKeyReused,
+    IdempotencyRequestInProgress,
+}
+
+impl ErrorCode {
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::BAD_REQUEST => Self::BadRequest,
+            StatusCode::UNAUTHORIZED => Self::Unauthorized,
+            StatusCode::FORBIDDEN => Self::Forbidden,
+            StatusCode::NOT_FOUND => Self::NotFound,
+            StatusCode::METHOD_NOT_ALLOWED => Self::MethodNotAllowed,
+            StatusCode::REQUEST_TIMEOUT => Self::RequestTimeout,
+            StatusCode::CONFLICT => Self::Conflict,
+            StatusCode::PAYLOAD_TOO_LARGE => Self::PayloadTooLarge,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => Self::UnsupportedMediaType,
+            StatusCode::UNPROCESSABLE_ENTITY => Self::ValidationFailed,
+            StatusCode::TOO_MANY_REQUESTS => Self::TooManyRequests,
+            StatusCode::SERVICE_UNAVAILABLE => Self::ServiceUnavailable,
+            status if status.is_client_error() => Self::BadRequest,
+            _ => Self::InternalServerError,
+        }
+    }
+}
+
+/// エラー時のレスポンスボディ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub error: ErrorCode,
+    /// 人が読むための補足。分岐には使わない
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// ハンドラのエラー型。ステータスコードは従来のまま、ボディにエラーコードを載せる
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: ErrorCode,
+    message: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: ErrorCode) -> Self {
+        Self {
+            status,
+            code,
+            message: None,
+        }
+    }
+
+    pub fn with_message(self, message: impl Into<String>) -> Self {
+        Self {
+            message: Some(message.into()),
+            ..self
+        }
+    }
+
+    /// validate()の理由をそのままmessageにする
+    pub fn validation(message: String) -> Self {
+        Self::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::ValidationFailed,
+        )
+        .with_message(message)
+    }
+}
+
+/// 個別のコードがないエラーはステータスコードから汎用コードを付ける
+impl From<StatusCode> for ApiError {
+    fn from(status: StatusCode) -> Self {
+        Self::new(status, ErrorCode::from_status(status))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody {
+            error: self.code,
+            message: self.message,
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// 追加したコードがOpenAPIに載っているか確認するため、全コードを並べておく
+    const ALL_CODES: [ErrorCode; 48] = [
+        ErrorCode::BadRequest,
+        ErrorCode::Unauthorized,
+        ErrorCode::Forbidden,
+        ErrorCode::NotFound,
+        ErrorCode::MethodNotAllowed,
+        ErrorCode::RequestTimeout,
+        ErrorCode::Conflict,
+        ErrorCode::PayloadTooLarge,
+        ErrorCode::UnsupportedMediaType,
+        ErrorCode::ValidationFailed,
+        ErrorCode::TooManyRequests,
+        ErrorCode::InternalServerError,
+        ErrorCode::ServiceUnavailable,
+        ErrorCode::TokenExpired,
+        ErrorCode::InvalidToken,
+        ErrorCode::MalformedToken,
+        ErrorCode::InvalidCredentials,
+        ErrorCode::AccountLocked,
+        ErrorCode::CaptchaFailed,
+        ErrorCode::CaptchaUnavailable,
+        ErrorCode::InvalidApiKey,
+        ErrorCode::CsrfTokenMismatch,
+        ErrorCode::UserBanned,
+        ErrorCode::QuestNotFound,
+        ErrorCode::ChallengeNotFound,
+        ErrorCode::UserNotFound,
+        ErrorCode::JobNotFound,
+        ErrorCode::OfflinePackNotFound,
+        ErrorCode::ApiKeyNotFound,
+        ErrorCode::QuestTemplateNotFound,
+        ErrorCode::HintNotFound,
+        ErrorCode::BanNotFound,
+        ErrorCode::AlreadyParticipating,
+        ErrorCode::QuestNotParticipated,
+        ErrorCode::AlreadyReviewed,
+        ErrorCode::QuestOutOfSchedule,
+        ErrorCode::QuestNotPublished,
+        ErrorCode::ChallengeLocked,
+        ErrorCode::ChallengeUnavailable,
+        ErrorCode::ChallengeTooFar,
+        ErrorCode::QuestNotCompleted,
+        ErrorCode::JobAlreadyFinished,
+        ErrorCode::VersionConflict,
+        ErrorCode::FeatureDisabled,
+        ErrorCode::HintLocked,
+        ErrorCode::InsufficientPoints,
+        ErrorCode::IdempotencyKeyReused,
+        ErrorCode::IdempotencyRequestInProgress,
+    ];
+
+    #[test]
+    fn should_serialize_code_as_snake_case() {
+        let body = ErrorBody {
+            error: ErrorCode::QuestNotFound,
+            message: None,
+        };
+
+        assert_eq!(
+            r#"{"error":"quest_not_found"}"#,
+            serde_json::to_string(&body).unwrap()
+        );
+    }
+
+    #[test]
+    fn should_map_status_to_generic_code() {
+        assert_eq!(
+            ErrorCode::ValidationFailed,
+            ErrorCode::from_status(StatusCode::UNPROCESSABLE_ENTITY)
+        );
+        assert_eq!(
+            ErrorCode::BadRequest,
+            ErrorCode::from_status(StatusCode::IM_A_TEAPOT)
+        );
+        assert_eq!(
+            ErrorCode::InternalServerError,
+            ErrorCode::from_status(StatusCode::BAD_GATEWAY)
+        );
+    }
+
+    #[test]
+    fn should_document_all_codes_in_openapi() {
+        let spec = include_str!("../docs/openapi.yaml");
+        for code in ALL_CODES {
+            let code = serde_json::to_value(code).unwrap();
+            let code = code.as_str().unwrap();
+            assert!(
+                spec.contains(&format!("- {}\n", code)),
+                "{} is not documented",
+                code
+            );
+        }
+    }
+}
@@ -0,0 +1,88 @@
+mod loader;
+mod mutation;
+mod query;
+mod types;
+
+use std::fmt;
+
+use async_graphql::{
+    dataloader::DataLoader, EmptySubscription, Error, ErrorExtensions, Schema, ID,
+};
+
+use self::{
+    loader::{ChallengeLoader, CompletedChallengesLoader, ParticipantsCountLoader, QuestLoader},
+    mutation::MutationRoot,
+    query::QueryRoot,
+};
+use crate::{
+    repositories::{id::UserId, Repositories},
+    services::event_bus::EventBus,
+};
+
+pub type QuestSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+/// ログイン中のユーザーID。未ログインのリクエストには入らない
+pub struct Viewer(pub UserId);
+
+pub fn build_schema(repositories: Repositories, event_bus: EventBus) -> QuestSchema {
+    Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .data(DataLoader::new(
+            QuestLoader(repositories.quest.clone()),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            ChallengeLoader(repositories.challenge.clone()),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            ParticipantsCountLoader(repositories.userquest.clone()),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            CompletedChallengesLoader(repositories.userchallenge.clone()),
+            tokio::spawn,
+        ))
+        .data(repositories)
+        .data(event_bus)
+        .finish()
+}
+
+/// RelayのNode用に型名を付けたID("Quest:xxxx")
+fn to_global_id(type_name: &str, id: &str) -> ID {
+    ID(format!("{}:{}", type_name, id))
+}
+
+fn from_global_id(id: &str) -> Option<(&str, String)> {
+    id.split_once(':')
+        .filter(|(type_name, id)| !type_name.is_empty() && !id.is_empty())
+        .map(|(type_name, id)| (type_name, id.to_string()))
+}
+
+/// DBのエラーなどはクライアントに見せずにログに残す
+fn internal_error(e: &dyn fmt::Display) -> Error {
+    tracing::error!("graphql resolver failed: {}", e);
+    Error::new("internal server error").extend_with(|_, e| e.set("code", "INTERNAL_SERVER_ERROR"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_round_trip_global_id() {
+        let id = to_global_id("Quest", "V1StGXR8_Z5jdHi6B-myT");
+
+        assert_eq!("Quest:V1StGXR8_Z5jdHi6B-myT", id.as_str());
+        assert_eq!(
+            Some(("Quest", "V1StGXR8_Z5jdHi6B-myT".to_string())),
+            from_global_id(&id)
+        );
+    }
+
+    #[test]
+    fn should_reject_malformed_global_id() {
+        assert_eq!(None, from_global_id("V1StGXR8_Z5jdHi6B-myT"));
+        assert_eq!(None, from_global_id("Quest:"));
+        assert_eq!(None, from_global_id(":V1StGXR8_Z5jdHi6B-myT"));
+    }
+}
@@ -0,0 +1,79 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_graphql::dataloader::Loader;
+
+use crate::repositories::{
+    challenge::{Challenge, ChallengeRepository},
+    id::{ChallengeId, QuestId, UserId},
+    quest::{QuestEntity, QuestRepository},
+    user_challenge::UserChallengeRepository,
+    user_quest::UserQuestRepository,
+};
+
+// 同じtickに解決されるフィールドのキーをまとめて1回のクエリで取得する
+
+pub struct QuestLoader(pub Arc<dyn QuestRepository>);
+
+impl Loader<QuestId> for QuestLoader {
+    type Value = QuestEntity;
+    type Error = Arc<anyhow::Error>;
+
+    async fn load(&self, keys: &[QuestId]) -> Result<HashMap<QuestId, Self::Value>, Self::Error> {
+        let quests = self.0.find_by_ids(keys.to_vec()).await.map_err(Arc::new)?;
+
+        Ok(quests.into_iter().map(|q| (q.id.clone(), q)).collect())
+    }
+}
+
+pub struct ChallengeLoader(pub Arc<dyn ChallengeRepository>);
+
+impl Loader<ChallengeId> for ChallengeLoader {
+    type Value = Challenge;
+    type Error = Arc<anyhow::Error>;
+
+    async fn load(
+        &self,
+        keys: &[ChallengeId],
+    ) -> Result<HashMap<ChallengeId, Self::Value>, Self::Error> {
+        let challenges = self.0.find_by_ids(keys.to_vec()).await.map_err(Arc::new)?;
+
+        Ok(challenges.into_iter().map(|c| (c.id.clone(), c)).collect())
+    }
+}
+
+pub struct ParticipantsCountLoader(pub Arc<dyn UserQuestRepository>);
+
+impl Loader<QuestId> for ParticipantsCountLoader {
+    type Value = i64;
+    type Error = Arc<anyhow::Error>;
+
+    async fn load(&self, keys: &[QuestId]) -> Result<HashMap<QuestId, Self::Value>, Self::Error> {
+        self.0
+            .count_participants_by_quest_ids(keys.to_vec())
+            .await
+            .map_err(Arc::new)
+    }
+}
+
+/// 1リクエストで問い合わせるのはログイン中のユーザーだけなので、
+/// クエストごとに取り直さないようにまとめるのが目的
+pub struct CompletedChallengesLoader(pub Arc<dyn UserChallengeRepository>);
+
+impl Loader<UserId> for CompletedChallengesLoader {
+    type Value = Vec<ChallengeId>;
+    type Error = Arc<anyhow::Error>;
+
+    async fn load(&self, keys: &[UserId]) -> Result<HashMap<UserId, Self::Value>, Self::Error> {
+        let mut completed = HashMap::new();
+        for user_id in keys {
+            let ids = self
+                .0
+                .get_completed_challenges_by_user_id(user_id.clone())
+                .await
+                .map_err(Arc::new)?;
+            completed.insert(user_id.clone(), ids);
+        }
+
+        Ok(completed)
+    }
+}
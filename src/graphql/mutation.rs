@@ -0,0 +1,125 @@
+use async_graphql::{Context, Error, ErrorExtensions, Object, Result, ID};
+use chrono::Utc;
+
+use super::{
+    from_global_id, internal_error,
+    types::{Challenge, Quest, CHALLENGE_TYPE, QUEST_TYPE},
+    Viewer,
+};
+use crate::{
+    repositories::{
+        id::{ChallengeId, QuestId},
+        Repositories,
+    },
+    services::{
+        challenge::{complete_challenge_and_publish, CompleteChallengeError},
+        event_bus::{DomainEvent, EventBus},
+        quest::{self, ParticipateQuestError},
+    },
+};
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    async fn participate_quest(&self, ctx: &Context<'_>, quest_id: ID) -> Result<Quest> {
+        let Viewer(user_id) = viewer(ctx)?;
+        let quest_id = QuestId::from(expect_global_id(&quest_id, QUEST_TYPE)?);
+        let repositories = ctx.data::<Repositories>()?;
+
+        quest::participate_quest(
+            repositories.quest.as_ref(),
+            repositories.userquest.as_ref(),
+            user_id.clone(),
+            quest_id.clone(),
+        )
+        .await
+        .map_err(|e| match e {
+            ParticipateQuestError::NotFound => not_found(QUEST_TYPE),
+            ParticipateQuestError::NotPublished => not_published(),
+            ParticipateQuestError::OutOfSchedule => out_of_schedule(),
+            ParticipateQuestError::AlreadyParticipating => {
+                Error::new("already participating").extend_with(|_, e| e.set("code", "CONFLICT"))
+            }
+            ParticipateQuestError::Repository(e) => internal_error(&e),
+        })?;
+        let quest = repositories
+            .quest
+            .find(quest_id.clone())
+            .await
+            .map_err(|e| internal_error(&e))?;
+
+        ctx.data::<EventBus>()?
+            .publish(DomainEvent::QuestParticipated {
+                user_id: user_id.clone(),
+                quest_id,
+            });
+
+        Ok(Quest(quest))
+    }
+
+    async fn complete_challenge(&self, ctx: &Context<'_>, challenge_id: ID) -> Result<Challenge> {
+        let Viewer(user_id) = viewer(ctx)?;
+        let challenge_id = ChallengeId::from(expect_global_id(&challenge_id, CHALLENGE_TYPE)?);
+        let repositories = ctx.data::<Repositories>()?;
+
+        let challenge = repositories
+            .challenge
+            .find(challenge_id.clone())
+            .await
+            .map_err(|_| not_found(CHALLENGE_TYPE))?;
+
+        complete_challenge_and_publish(
+            repositories.challenge.as_ref(),
+            repositories.quest.as_ref(),
+            repositories.userchallenge.as_ref(),
+            repositories.achievement.as_ref(),
+            repositories.unit_of_work.as_ref(),
+            ctx.data::<EventBus>()?,
+            user_id.clone(),
+            challenge_id,
+            None,
+            Utc::now(),
+        )
+        .await
+        .map_err(|e| match e {
+            CompleteChallengeError::Locked => {
+                Error::new("challenge is locked").extend_with(|_, e| e.set("code", "FORBIDDEN"))
+            }
+            CompleteChallengeError::NotPublished => not_published(),
+            CompleteChallengeError::OutOfSchedule => out_of_schedule(),
+            CompleteChallengeError::Unavailable => Error::new("challenge is not available")
+                .extend_with(|_, e| e.set("code", "FORBIDDEN")),
+            CompleteChallengeError::Repository(e) | CompleteChallengeError::Transaction(e) => {
+                internal_error(&e)
+            }
+        })?;
+
+        Ok(Challenge(challenge))
+    }
+}
+
+fn viewer<'a>(ctx: &Context<'a>) -> Result<&'a Viewer> {
+    ctx.data_opt::<Viewer>().ok_or_else(|| {
+        Error::new("login required").extend_with(|_, e| e.set("code", "UNAUTHENTICATED"))
+    })
+}
+
+fn expect_global_id(id: &ID, type_name: &str) -> Result<String> {
+    match from_global_id(id) {
+        Some((decoded_type, id)) if decoded_type == type_name => Ok(id),
+        _ => Err(not_found(type_name)),
+    }
+}
+
+fn not_found(type_name: &str) -> Error {
+    Error::new(format!("{} not found", type_name)).extend_with(|_, e| e.set("code", "NOT_FOUND"))
+}
+
+fn out_of_schedule() -> Error {
+    Error::new("quest is not open").extend_with(|_, e| e.set("code", "FORBIDDEN"))
+}
+
+fn not_published() -> Error {
+    Error::new("quest is not published").extend_with(|_, e| e.set("code", "FORBIDDEN"))
+}
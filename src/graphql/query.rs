@@ -0,0 +1,214 @@
+use async_graphql::{
+    connection::{query, Connection, Edge},
+    dataloader::DataLoader,
+    Context, ErrorExtensions, Object, Result, ID,
+};
+
+use super::{
+    from_global_id, internal_error,
+    loader::{ChallengeLoader, QuestLoader},
+    types::{
+        Challenge, Node, Quest, QuestStatusFilter, User, CHALLENGE_TYPE, QUEST_TYPE, USER_TYPE,
+    },
+    Viewer,
+};
+use crate::{
+    repositories::{
+        id::{ChallengeId, QuestId, UserId},
+        quest, Repositories,
+    },
+    services::quest::{can_view, is_admin},
+};
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn node(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Node>> {
+        let (type_name, id) = match from_global_id(&id) {
+            Some(decoded) => decoded,
+            None => return Ok(None),
+        };
+
+        match type_name {
+            QUEST_TYPE => Ok(load_quest(ctx, id.into()).await?.map(Node::Quest)),
+            CHALLENGE_TYPE => Ok(load_challenge(ctx, id.into()).await?.map(Node::Challenge)),
+            // 他のユーザーの情報は返さない
+            USER_TYPE => match ctx.data_opt::<Viewer>() {
+                Some(Viewer(user_id)) if *user_id == *id => {
+                    Ok(load_user(ctx, user_id.clone()).await?.map(Node::User))
+                }
+                _ => Ok(None),
+            },
+            _ => Ok(None),
+        }
+    }
+
+    async fn quest(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Quest>> {
+        match from_global_id(&id) {
+            Some((QUEST_TYPE, id)) => load_quest(ctx, id.into()).await,
+            _ => Ok(None),
+        }
+    }
+
+    /// statusの指定がなければ公開中のクエストのみ。公開中以外を指定できるのは管理者だけ
+    async fn quests(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<QuestStatusFilter>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<usize, Quest>> {
+        let status: Option<quest::QuestStatusFilter> = status.map(Into::into);
+        let repositories = ctx.data::<Repositories>()?;
+        if quest::QuestStatusFilter::is_restricted(status)
+            && !is_admin(repositories.user.as_ref(), viewer_id(ctx))
+                .await
+                .map_err(|e| internal_error(&e))?
+        {
+            return Err(async_graphql::Error::new("admin role is required")
+                .extend_with(|_, e| e.set("code", "FORBIDDEN")));
+        }
+        let mut quests = repositories
+            .quest
+            .all(quest::QuestStatusFilter::resolve(status), None)
+            .await
+            .map_err(|e| internal_error(&e))?;
+        // カーソルは位置なので、リクエストをまたいで並びが変わらないようにする
+        quests.sort_by(|a, b| a.id.cmp(&b.id));
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after: Option<usize>, before: Option<usize>, first, last| async move {
+                let (start, end) = page_range(quests.len(), after, before, first, last);
+                let mut connection = Connection::new(start > 0, end < quests.len());
+                connection.edges.extend(
+                    quests
+                        .into_iter()
+                        .enumerate()
+                        .skip(start)
+                        .take(end - start)
+                        .map(|(cursor, quest)| Edge::new(cursor, Quest(quest))),
+                );
+                Ok::<_, async_graphql::Error>(connection)
+            },
+        )
+        .await
+    }
+
+    async fn challenge(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Challenge>> {
+        match from_global_id(&id) {
+            Some((CHALLENGE_TYPE, id)) => load_challenge(ctx, id.into()).await,
+            _ => Ok(None),
+        }
+    }
+
+    /// 未ログインならnull
+    async fn me(&self, ctx: &Context<'_>) -> Result<Option<User>> {
+        match ctx.data_opt::<Viewer>() {
+            Some(Viewer(user_id)) => load_user(ctx, user_id.clone()).await,
+            None => Ok(None),
+        }
+    }
+}
+
+/// 見られない下書きはnullにする
+async fn load_quest(ctx: &Context<'_>, id: QuestId) -> Result<Option<Quest>> {
+    let quest = match ctx
+        .data::<DataLoader<QuestLoader>>()?
+        .load_one(id)
+        .await
+        .map_err(|e| internal_error(&e))?
+    {
+        Some(quest) => quest,
+        None => return Ok(None),
+    };
+    let repositories = ctx.data::<Repositories>()?;
+    let visible = can_view(
+        repositories.quest.as_ref(),
+        repositories.user.as_ref(),
+        &quest,
+        viewer_id(ctx),
+    )
+    .await
+    .map_err(|e| internal_error(&e))?;
+
+    Ok(visible.then_some(Quest(quest)))
+}
+
+fn viewer_id(ctx: &Context<'_>) -> Option<UserId> {
+    ctx.data_opt::<Viewer>()
+        .map(|Viewer(user_id)| user_id.clone())
+}
+
+async fn load_challenge(ctx: &Context<'_>, id: ChallengeId) -> Result<Option<Challenge>> {
+    let challenge = ctx
+        .data::<DataLoader<ChallengeLoader>>()?
+        .load_one(id)
+        .await
+        .map_err(|e| internal_error(&e))?;
+
+    Ok(challenge.map(Challenge))
+}
+
+async fn load_user(ctx: &Context<'_>, id: UserId) -> Result<Option<User>> {
+    // 退会済みなどで見つからなければnullにする
+    let user = ctx.data::<Repositories>()?.user.find(id).await.ok();
+
+    Ok(user.map(User))
+}
+
+/// Relayのconnectionの引数から、返す範囲[start, end)を求める
+fn page_range(
+    len: usize,
+    after: Option<usize>,
+    before: Option<usize>,
+    first: Option<usize>,
+    last: Option<usize>,
+) -> (usize, usize) {
+    let mut start = after.map_or(0, |after| after + 1).min(len);
+    let mut end = before.map_or(len, |before| before.min(len)).max(start);
+
+    if let Some(first) = first {
+        end = end.min(start + first);
+    }
+    if let Some(last) = last {
+        start = start.max(end.saturating_sub(last));
+    }
+
+    (start, end)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_return_all_without_arguments() {
+        assert_eq!((0, 5), page_range(5, None, None, None, None));
+    }
+
+    #[test]
+    fn should_page_forward() {
+        assert_eq!((0, 2), page_range(5, None, None, Some(2), None));
+        assert_eq!((2, 4), page_range(5, Some(1), None, Some(2), None));
+        assert_eq!((4, 5), page_range(5, Some(3), None, Some(2), None));
+    }
+
+    #[test]
+    fn should_page_backward() {
+        assert_eq!((3, 5), page_range(5, None, None, None, Some(2)));
+        assert_eq!((1, 3), page_range(5, None, Some(3), None, Some(2)));
+    }
+
+    #[test]
+    fn should_return_empty_range_for_out_of_range_cursor() {
+        assert_eq!((5, 5), page_range(5, Some(10), None, Some(2), None));
+        assert_eq!((3, 3), page_range(5, Some(2), Some(1), None, None));
+    }
+}
@@ -0,0 +1,246 @@
+use async_graphql::{dataloader::DataLoader, Context, Enum, Interface, Object, Result, ID};
+
+use super::{
+    internal_error,
+    loader::{ChallengeLoader, CompletedChallengesLoader, ParticipantsCountLoader, QuestLoader},
+    to_global_id, Viewer,
+};
+use crate::{
+    repositories::{
+        challenge::Challenge as ChallengeEntity,
+        quest::{self, QuestEntity},
+        user::UserEntity,
+        Repositories,
+    },
+    services::challenge::apply_lock_states,
+};
+
+pub const QUEST_TYPE: &str = "Quest";
+pub const CHALLENGE_TYPE: &str = "Challenge";
+pub const USER_TYPE: &str = "User";
+
+/// RelayのGlobal Object Identification用
+#[derive(Interface)]
+#[graphql(field(name = "id", ty = "ID"))]
+pub enum Node {
+    Quest(Quest),
+    Challenge(Challenge),
+    User(User),
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuestStatus {
+    Draft,
+    Published,
+    Archived,
+}
+
+impl From<quest::QuestStatus> for QuestStatus {
+    fn from(status: quest::QuestStatus) -> Self {
+        match status {
+            quest::QuestStatus::Draft => QuestStatus::Draft,
+            quest::QuestStatus::Published => QuestStatus::Published,
+            quest::QuestStatus::Archived => QuestStatus::Archived,
+        }
+    }
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuestStatusFilter {
+    All,
+    Draft,
+    Published,
+    Archived,
+}
+
+impl From<QuestStatusFilter> for quest::QuestStatusFilter {
+    fn from(filter: QuestStatusFilter) -> Self {
+        match filter {
+            QuestStatusFilter::All => quest::QuestStatusFilter::All,
+            QuestStatusFilter::Draft => quest::QuestStatusFilter::Draft,
+            QuestStatusFilter::Published => quest::QuestStatusFilter::Published,
+            QuestStatusFilter::Archived => quest::QuestStatusFilter::Archived,
+        }
+    }
+}
+
+pub struct Quest(pub QuestEntity);
+
+#[Object]
+impl Quest {
+    async fn id(&self) -> ID {
+        to_global_id(QUEST_TYPE, self.0.id.as_str())
+    }
+
+    async fn title(&self) -> &str {
+        &self.0.title
+    }
+
+    async fn description(&self) -> &str {
+        &self.0.description
+    }
+
+    async fn status(&self) -> QuestStatus {
+        self.0.status.into()
+    }
+
+    async fn sequential(&self) -> bool {
+        self.0.sequential
+    }
+
+    /// 詳細画面用のフル画像
+    async fn cover_image_url(&self) -> Option<&str> {
+        self.0.cover_image_url.as_deref()
+    }
+
+    /// 一覧用の縮小画像
+    async fn cover_thumbnail_url(&self) -> Option<&str> {
+        self.0.cover_thumbnail_url.as_deref()
+    }
+
+    /// 未ログインの場合は何も完了していないユーザーとして解放状態を返す
+    async fn challenges(&self, ctx: &Context<'_>) -> Result<Vec<Challenge>> {
+        let completed_challenge_ids = match ctx.data_opt::<Viewer>() {
+            Some(Viewer(user_id)) => ctx
+                .data::<DataLoader<CompletedChallengesLoader>>()?
+                .load_one(user_id.clone())
+                .await
+                .map_err(|e| internal_error(&e))?
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+
+        let mut quest = self.0.clone();
+        apply_lock_states(&mut quest, &completed_challenge_ids);
+
+        Ok(quest.challenges.into_iter().map(Challenge).collect())
+    }
+
+    async fn participants_count(&self, ctx: &Context<'_>) -> Result<i64> {
+        let count = ctx
+            .data::<DataLoader<ParticipantsCountLoader>>()?
+            .load_one(self.0.id.clone())
+            .await
+            .map_err(|e| internal_error(&e))?;
+
+        Ok(count.unwrap_or(0))
+    }
+}
+
+pub struct Challenge(pub ChallengeEntity);
+
+#[Object]
+impl Challenge {
+    async fn id(&self) -> ID {
+        to_global_id(CHALLENGE_TYPE, self.0.id.as_str())
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn description(&self) -> &str {
+        &self.0.description
+    }
+
+    async fn latitude(&self) -> f64 {
+        self.0.latitude
+    }
+
+    async fn longitude(&self) -> f64 {
+        self.0.longitude
+    }
+
+    async fn stamp_name(&self) -> &str {
+        &self.0.stamp_name
+    }
+
+    async fn stamp_color_image_url(&self) -> &str {
+        &self.0.stamp_color_image_url
+    }
+
+    async fn stamp_gray_image_url(&self) -> &str {
+        &self.0.stamp_gray_image_url
+    }
+
+    async fn flavor_text(&self) -> &str {
+        &self.0.flavor_text
+    }
+
+    async fn order_index(&self) -> i32 {
+        self.0.order_index
+    }
+
+    /// Quest.challengesから辿ったときだけログイン中のユーザーの解放状態が入る
+    async fn locked(&self) -> bool {
+        self.0.locked
+    }
+
+    async fn quest(&self, ctx: &Context<'_>) -> Result<Option<Quest>> {
+        let quest = ctx
+            .data::<DataLoader<QuestLoader>>()?
+            .load_one(self.0.quest_id.clone())
+            .await
+            .map_err(|e| internal_error(&e))?;
+
+        Ok(quest.map(Quest))
+    }
+}
+
+pub struct User(pub UserEntity);
+
+#[Object]
+impl User {
+    async fn id(&self) -> ID {
+        to_global_id(USER_TYPE, self.0.id.as_str())
+    }
+
+    async fn username(&self) -> &str {
+        &self.0.username
+    }
+
+    async fn email(&self) -> &str {
+        &self.0.email
+    }
+
+    async fn participated_quests(&self, ctx: &Context<'_>) -> Result<Vec<Quest>> {
+        let quest_ids = ctx
+            .data::<Repositories>()?
+            .userquest
+            .get_participated_quests_by_user_id(self.0.id.clone())
+            .await
+            .map_err(|e| internal_error(&e))?;
+        let mut quests = ctx
+            .data::<DataLoader<QuestLoader>>()?
+            .load_many(quest_ids.clone())
+            .await
+            .map_err(|e| internal_error(&e))?;
+
+        // load_manyは順序を保たないので参加順に並べ直す
+        Ok(quest_ids
+            .into_iter()
+            .filter_map(|id| quests.remove(&id))
+            .map(Quest)
+            .collect())
+    }
+
+    async fn completed_challenges(&self, ctx: &Context<'_>) -> Result<Vec<Challenge>> {
+        let challenge_ids = ctx
+            .data::<Repositories>()?
+            .userchallenge
+            .get_completed_challenges_by_user_id(self.0.id.clone())
+            .await
+            .map_err(|e| internal_error(&e))?;
+        let mut challenges = ctx
+            .data::<DataLoader<ChallengeLoader>>()?
+            .load_many(challenge_ids.clone())
+            .await
+            .map_err(|e| internal_error(&e))?;
+
+        Ok(challenge_ids
+            .into_iter()
+            .filter_map(|id| challenges.remove(&id))
+            .map(Challenge)
+            .collect())
+    }
+}
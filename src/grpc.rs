@@ -0,0 +1,59 @@
+mod quest;
+mod user;
+
+use std::{future::Future, net::SocketAddr};
+
+use opentelemetry::global;
+use opentelemetry_http::HeaderExtractor;
+use tonic::transport::Server;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use self::{
+    proto::{quest_service_server::QuestServiceServer, user_service_server::UserServiceServer},
+    quest::QuestGrpcService,
+    user::UserGrpcService,
+};
+use crate::{repositories::Repositories, services::event_bus::EventBus};
+
+pub mod proto {
+    tonic::include_proto!("quest.v1");
+}
+
+/// 社内サービス向けのgRPCサーバー。RESTとは別のポートで待ち受ける
+/// 認証は行わないので、このポートはVPCの外に公開しないこと
+pub async fn serve<F>(
+    addr: SocketAddr,
+    repositories: Repositories,
+    event_bus: EventBus,
+    shutdown: F,
+) -> Result<(), tonic::transport::Error>
+where
+    F: Future<Output = ()>,
+{
+    Server::builder()
+        .trace_fn(request_span)
+        .add_service(QuestServiceServer::new(QuestGrpcService::new(
+            repositories.quest,
+            event_bus,
+        )))
+        .add_service(UserServiceServer::new(UserGrpcService::new(
+            repositories.user,
+        )))
+        .serve_with_shutdown(addr, shutdown)
+        .await
+}
+
+/// RESTと同じく、traceparentメタデータがあれば呼び出し元のトレースに繋げる
+fn request_span(req: &tonic::codegen::http::Request<()>) -> tracing::Span {
+    let span = tracing::info_span!(
+        "grpc_request",
+        otel.name = %req.uri().path(),
+        otel.kind = "server",
+        rpc.system = "grpc",
+    );
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(req.headers()))
+    });
+    span.set_parent(parent);
+    span
+}
@@ -0,0 +1,188 @@
+use std::sync::Arc;
+
+use tonic::{Request, Response, Status};
+
+use super::proto::{
+    self, quest_service_server::QuestService, CreateQuestRequest, GetQuestRequest,
+    ListQuestsRequest, ListQuestsResponse,
+};
+use crate::{
+    repositories::{
+        challenge::Challenge,
+        quest::{CreateQuest, QuestEntity, QuestRepository, QuestStatus, QuestStatusFilter},
+    },
+    services::event_bus::{DomainEvent, EventBus},
+};
+
+pub struct QuestGrpcService {
+    repository: Arc<dyn QuestRepository>,
+    event_bus: EventBus,
+}
+
+impl QuestGrpcService {
+    pub fn new(repository: Arc<dyn QuestRepository>, event_bus: EventBus) -> Self {
+        Self {
+            repository,
+            event_bus,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl QuestService for QuestGrpcService {
+    async fn list_quests(
+        &self,
+        request: Request<ListQuestsRequest>,
+    ) -> Result<Response<ListQuestsResponse>, Status> {
+        let request = request.into_inner();
+        let filter = if request.include_all {
+            Some(QuestStatusFilter::All)
+        } else {
+            status_from_proto(request.status)?.map(|status| match status {
+                QuestStatus::Draft => QuestStatusFilter::Draft,
+                QuestStatus::Published => QuestStatusFilter::Published,
+                QuestStatus::Archived => QuestStatusFilter::Archived,
+            })
+        };
+
+        let quests = self
+            .repository
+            .all(QuestStatusFilter::resolve(filter), None)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(ListQuestsResponse {
+            quests: quests.into_iter().map(proto::Quest::from).collect(),
+        }))
+    }
+
+    async fn get_quest(
+        &self,
+        request: Request<GetQuestRequest>,
+    ) -> Result<Response<proto::Quest>, Status> {
+        let quest = self
+            .repository
+            .find(request.into_inner().id.into())
+            .await
+            .or(Err(Status::not_found("quest not found")))?;
+
+        Ok(Response::new(quest.into()))
+    }
+
+    async fn create_quest(
+        &self,
+        request: Request<CreateQuestRequest>,
+    ) -> Result<Response<proto::Quest>, Status> {
+        let request = request.into_inner();
+        let mut payload = CreateQuest::new(request.title, request.description)
+            .with_sequential(request.sequential);
+        if let Some(status) = status_from_proto(request.status)? {
+            payload = payload.with_status(status);
+        }
+        payload.validate().map_err(Status::invalid_argument)?;
+
+        let quest = self
+            .repository
+            .create(payload)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        self.event_bus.publish(DomainEvent::QuestCreated {
+            quest: quest.clone(),
+        });
+
+        Ok(Response::new(quest.into()))
+    }
+}
+
+/// UNSPECIFIEDはNone
+fn status_from_proto(value: i32) -> Result<Option<QuestStatus>, Status> {
+    match proto::QuestStatus::from_i32(value) {
+        Some(proto::QuestStatus::Unspecified) => Ok(None),
+        Some(proto::QuestStatus::Draft) => Ok(Some(QuestStatus::Draft)),
+        Some(proto::QuestStatus::Published) => Ok(Some(QuestStatus::Published)),
+        Some(proto::QuestStatus::Archived) => Ok(Some(QuestStatus::Archived)),
+        None => Err(Status::invalid_argument(format!(
+            "invalid quest status: {}",
+            value
+        ))),
+    }
+}
+
+impl From<QuestStatus> for proto::QuestStatus {
+    fn from(status: QuestStatus) -> Self {
+        match status {
+            QuestStatus::Draft => proto::QuestStatus::Draft,
+            QuestStatus::Published => proto::QuestStatus::Published,
+            QuestStatus::Archived => proto::QuestStatus::Archived,
+        }
+    }
+}
+
+impl From<QuestEntity> for proto::Quest {
+    fn from(quest: QuestEntity) -> Self {
+        Self {
+            id: quest.id.into(),
+            title: quest.title,
+            description: quest.description,
+            status: proto::QuestStatus::from(quest.status) as i32,
+            sequential: quest.sequential,
+            challenges: quest
+                .challenges
+                .into_iter()
+                .map(proto::Challenge::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<Challenge> for proto::Challenge {
+    fn from(challenge: Challenge) -> Self {
+        Self {
+            id: challenge.id.into(),
+            name: challenge.name,
+            description: challenge.description,
+            quest_id: challenge.quest_id.into(),
+            latitude: challenge.latitude,
+            longitude: challenge.longitude,
+            stamp_name: challenge.stamp_name,
+            stamp_color_image_url: challenge.stamp_color_image_url,
+            stamp_gray_image_url: challenge.stamp_gray_image_url,
+            flavor_text: challenge.flavor_text,
+            order_index: challenge.order_index,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_convert_quest_status_from_proto() {
+        assert_eq!(
+            None,
+            status_from_proto(proto::QuestStatus::Unspecified as i32).unwrap()
+        );
+        assert_eq!(
+            Some(QuestStatus::Archived),
+            status_from_proto(proto::QuestStatus::Archived as i32).unwrap()
+        );
+        assert!(status_from_proto(99).is_err());
+    }
+
+    #[test]
+    fn should_convert_quest_to_proto() {
+        let quest = QuestEntity::new(
+            "quest".into(),
+            "Test Quest".to_string(),
+            "This is a test quest.".to_string(),
+            QuestStatus::Published,
+        );
+
+        let converted = proto::Quest::from(quest);
+
+        assert_eq!("quest", converted.id);
+        assert_eq!(proto::QuestStatus::Published as i32, converted.status);
+        assert!(converted.challenges.is_empty());
+    }
+}
@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use tonic::{Request, Response, Status};
+
+use super::proto::{self, user_service_server::UserService, GetUserRequest};
+use crate::repositories::user::{UserEntity, UserRepository};
+
+pub struct UserGrpcService {
+    repository: Arc<dyn UserRepository>,
+}
+
+impl UserGrpcService {
+    pub fn new(repository: Arc<dyn UserRepository>) -> Self {
+        Self { repository }
+    }
+}
+
+#[tonic::async_trait]
+impl UserService for UserGrpcService {
+    async fn get_user(
+        &self,
+        request: Request<GetUserRequest>,
+    ) -> Result<Response<proto::User>, Status> {
+        let user = self
+            .repository
+            .find(request.into_inner().id.into())
+            .await
+            .or(Err(Status::not_found("user not found")))?;
+
+        Ok(Response::new(user.into()))
+    }
+}
+
+impl From<UserEntity> for proto::User {
+    fn from(user: UserEntity) -> Self {
+        Self {
+            id: user.id.into(),
+            username: user.username,
+            email: user.email,
+        }
+    }
+}
@@ -1,5 +1,24 @@
+pub mod achievement;
+pub mod admin;
+pub mod bookmark;
+pub mod certificate;
 pub mod challenge;
+pub mod device;
+pub mod export;
+pub mod follow;
+pub mod graphql;
+pub mod hint;
+pub mod job;
+pub mod metrics;
+pub mod offline_pack;
+pub mod point;
+pub mod preference;
 pub mod quest;
+pub mod quest_template;
+pub mod realtime;
+pub mod review;
+pub mod summary;
 pub mod user;
 pub mod user_challenge;
 pub mod user_quest;
+pub mod webhook;
@@ -0,0 +1,18 @@
+use axum::{extract::Extension, http::StatusCode, response::IntoResponse, Json};
+use std::sync::Arc;
+
+use crate::{
+    middleware::auth::AuthenticatedUser, repositories::achievement::AchievementRepository,
+};
+
+pub async fn get_achievements(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Extension(repository): Extension<Arc<dyn AchievementRepository>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let achievements = repository
+        .find_by_user_id(user_id)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(achievements)))
+}
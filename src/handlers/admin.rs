@@ -0,0 +1,647 @@
+use axum::{
+    body::Bytes,
+    extract::{Extension, Path, Query},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        StatusCode,
+    },
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    middleware::{auth::AuthenticatedUser, language::is_valid_language_tag},
+    repositories::{
+        api_key::{ApiKeyRepository, IssueApiKey},
+        audit::{AuditAction, AuditRepository, SearchAuditLogs},
+        ban::{BanError, BanRepository, CreateBan},
+        challenge::ChallengeRepository,
+        feature_flag::{Feature, SetFeatureFlag},
+        id::{ChallengeId, QuestId, UserId},
+        job::{JobKind, JobOutput},
+        metadata::MetadataFilter,
+        quest::QuestRepository,
+        scheduled_job::ScheduledJobRepository,
+        stats::{stats_today, DailyActiveUsersQuery, StatsRepository},
+        translation::{ChallengeTranslation, QuestTranslation, TranslationRepository},
+        user::UserRepository,
+    },
+    services::{
+        audit::record_audit,
+        csv_transfer::{export_entity, import_entity, CsvEntityQuery, ImportReport},
+        feature_flag::FeatureFlagService,
+        job::{JobProgress, JobRunner},
+    },
+};
+
+pub async fn import_csv(
+    Query(query): Query<CsvEntityQuery>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(challenge_repository): Extension<Arc<dyn ChallengeRepository>>,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, Json<ImportReport>)> {
+    let report = import_entity(
+        quest_repository.as_ref(),
+        challenge_repository.as_ref(),
+        query.entity,
+        &body,
+        &JobProgress::default(),
+    )
+    .await
+    .map_err(|errors| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ImportReport {
+                imported: 0,
+                errors,
+            }),
+        )
+    })?;
+
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+pub async fn export_csv(
+    Query(query): Query<CsvEntityQuery>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let (filename, body) = export_entity(quest_repository.as_ref(), query.entity)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((
+        StatusCode::OK,
+        [
+            (CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    ))
+}
+
+/// エクスポートをジョブとして実行する。結果は`GET /jobs/:id/result`で取得する
+pub async fn start_export_job(
+    Query(query): Query<CsvEntityQuery>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(job_runner): Extension<JobRunner>,
+    AuthenticatedUser(user_id_from_token): AuthenticatedUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let job = job_runner
+        .spawn(
+            user_id_from_token,
+            JobKind::CsvExport,
+            move |_| async move {
+                let (filename, body) =
+                    export_entity(quest_repository.as_ref(), query.entity).await?;
+                Ok(JobOutput {
+                    content_type: "text/csv; charset=utf-8".to_string(),
+                    filename: Some(filename.to_string()),
+                    body,
+                })
+            },
+        )
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// インポートをジョブとして実行する。結果は同期版と同じ形式のレポート
+pub async fn start_import_job(
+    Query(query): Query<CsvEntityQuery>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(challenge_repository): Extension<Arc<dyn ChallengeRepository>>,
+    Extension(job_runner): Extension<JobRunner>,
+    AuthenticatedUser(user_id_from_token): AuthenticatedUser,
+    body: Bytes,
+) -> Result<impl IntoResponse, StatusCode> {
+    let job = job_runner
+        .spawn(
+            user_id_from_token,
+            JobKind::CsvImport,
+            move |progress| async move {
+                let report = import_entity(
+                    quest_repository.as_ref(),
+                    challenge_repository.as_ref(),
+                    query.entity,
+                    &body,
+                    &progress,
+                )
+                .await
+                .unwrap_or_else(|errors| ImportReport {
+                    imported: 0,
+                    errors,
+                });
+                Ok(JobOutput {
+                    content_type: mime::APPLICATION_JSON.to_string(),
+                    filename: None,
+                    body: serde_json::to_vec(&report)?,
+                })
+            },
+        )
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+pub async fn search_audit_logs(
+    Query(query): Query<SearchAuditLogs>,
+    Extension(audit_repository): Extension<Arc<dyn AuditRepository>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let logs = audit_repository
+        .search(query)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(logs)))
+}
+
+/// `GET /admin/quests?status=all&metadata.key=value`。statusの省略時は全件
+pub async fn find_quests_by_metadata(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let status = match params.get("status").map(String::as_str) {
+        None | Some("all") => None,
+        Some(status) => Some(status.parse().or(Err(StatusCode::BAD_REQUEST))?),
+    };
+    let quests = quest_repository
+        .find_by_metadata(status, MetadataFilter::from_query(&params))
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(quests)))
+}
+
+/// `GET /admin/challenges?metadata.key=value`
+pub async fn find_challenges_by_metadata(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(challenge_repository): Extension<Arc<dyn ChallengeRepository>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let challenges = challenge_repository
+        .find_by_metadata(MetadataFilter::from_query(&params))
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(challenges)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FindScheduledJobRuns {
+    job: Option<String>,
+}
+
+/// `GET /admin/scheduled_jobs/runs?job=name`。定期処理の実行履歴を新しいものから返す
+pub async fn find_scheduled_job_runs(
+    Query(query): Query<FindScheduledJobRuns>,
+    Extension(repository): Extension<Arc<dyn ScheduledJobRepository>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let runs = repository
+        .find_runs(query.job)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(runs)))
+}
+
+/// 論理削除したクエストを戻す。物理削除された後は戻せない
+pub async fn restore_quest(
+    Path(id): Path<QuestId>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(audit_repository): Extension<Arc<dyn AuditRepository>>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let quest = quest_repository
+        .restore(id.clone())
+        .await
+        .or(Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::QuestNotFound,
+        )))?;
+    record_audit(
+        audit_repository.as_ref(),
+        Some(user_id),
+        AuditAction::QuestRestore,
+        id.into(),
+    )
+    .await;
+
+    Ok((StatusCode::OK, Json(quest)))
+}
+
+/// `PUT /admin/quests/:id/translations/:lang`。既定の言語の本文はクエスト自体を更新する
+pub async fn put_quest_translation(
+    Path((id, lang)): Path<(QuestId, String)>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(translation_repository): Extension<Arc<dyn TranslationRepository>>,
+    Json(payload): Json<QuestTranslation>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !is_valid_language_tag(&lang) {
+        return Err(ApiError::validation(format!("invalid language: {}", lang)));
+    }
+    payload.validate().map_err(ApiError::validation)?;
+    quest_repository
+        .find(id.clone())
+        .await
+        .or(Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::QuestNotFound,
+        )))?;
+    translation_repository
+        .upsert_quest(id, lang, payload)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `PUT /admin/challenges/:id/translations/:lang`
+pub async fn put_challenge_translation(
+    Path((id, lang)): Path<(ChallengeId, String)>,
+    Extension(challenge_repository): Extension<Arc<dyn ChallengeRepository>>,
+    Extension(translation_repository): Extension<Arc<dyn TranslationRepository>>,
+    Json(payload): Json<ChallengeTranslation>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !is_valid_language_tag(&lang) {
+        return Err(ApiError::validation(format!("invalid language: {}", lang)));
+    }
+    payload.validate().map_err(ApiError::validation)?;
+    challenge_repository
+        .find(id.clone())
+        .await
+        .or(Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::ChallengeNotFound,
+        )))?;
+    translation_repository
+        .upsert_challenge(id, lang, payload)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `GET /admin/stats`。件数はその場で数える
+pub async fn get_stats_overview(
+    Extension(stats_repository): Extension<Arc<dyn StatsRepository>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let overview = stats_repository
+        .overview()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(overview)))
+}
+
+/// `GET /admin/stats/quests`。日次で更新する集計なので当日の参加はまだ含まれない
+pub async fn get_quest_stats(
+    Extension(stats_repository): Extension<Arc<dyn StatsRepository>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let stats = stats_repository
+        .find_quest_stats()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(stats)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FindChallengeStats {
+    quest_id: Option<QuestId>,
+}
+
+/// `GET /admin/stats/challenges?quest_id=id`
+pub async fn get_challenge_stats(
+    Query(query): Query<FindChallengeStats>,
+    Extension(stats_repository): Extension<Arc<dyn StatsRepository>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let stats = stats_repository
+        .find_challenge_stats(query.quest_id)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(stats)))
+}
+
+/// `GET /admin/stats/daily_active_users?from=2024-01-01&to=2024-01-31`
+pub async fn get_daily_active_users(
+    Query(query): Query<DailyActiveUsersQuery>,
+    Extension(stats_repository): Extension<Arc<dyn StatsRepository>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (from, to) = query
+        .range(stats_today(Utc::now()))
+        .map_err(ApiError::validation)?;
+    let stats = stats_repository
+        .find_daily_active_users(from, to)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(stats)))
+}
+
+/// `POST /admin/api_keys`。平文のキーはこのレスポンスでしか返さない
+pub async fn issue_api_key(
+    Extension(api_key_repository): Extension<Arc<dyn ApiKeyRepository>>,
+    Extension(audit_repository): Extension<Arc<dyn AuditRepository>>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Json(payload): Json<IssueApiKey>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(ApiError::validation)?;
+    let issued = api_key_repository
+        .issue(payload)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    record_audit(
+        audit_repository.as_ref(),
+        Some(user_id),
+        AuditAction::ApiKeyIssue,
+        issued.api_key.id.clone(),
+    )
+    .await;
+
+    Ok((StatusCode::CREATED, Json(issued)))
+}
+
+/// `GET /admin/api_keys`
+pub async fn find_api_keys(
+    Extension(api_key_repository): Extension<Arc<dyn ApiKeyRepository>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let api_keys = api_key_repository
+        .find_all()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(api_keys)))
+}
+
+/// `DELETE /admin/api_keys/:id`。記録は残し、以降の認証だけを拒否する
+pub async fn revoke_api_key(
+    Path(id): Path<String>,
+    Extension(api_key_repository): Extension<Arc<dyn ApiKeyRepository>>,
+    Extension(audit_repository): Extension<Arc<dyn AuditRepository>>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    api_key_repository
+        .revoke(id.clone())
+        .await
+        .or(Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::ApiKeyNotFound,
+        )))?;
+    record_audit(
+        audit_repository.as_ref(),
+        Some(user_id),
+        AuditAction::ApiKeyRevoke,
+        id,
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `POST /admin/users/:id/unlock`。ロックの期限を待たずにログインできるようにする
+pub async fn unlock_user(
+    Path(id): Path<UserId>,
+    Extension(user_repository): Extension<Arc<dyn UserRepository>>,
+    Extension(audit_repository): Extension<Arc<dyn AuditRepository>>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    user_repository
+        .unlock(id.clone())
+        .await
+        .or(Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::UserNotFound,
+        )))?;
+    record_audit(
+        audit_repository.as_ref(),
+        Some(user_id),
+        AuditAction::UserUnlock,
+        id.into(),
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `POST /admin/users/:id/ban`。凍結中なら理由と期限を置き換える
+pub async fn ban_user(
+    Path(id): Path<UserId>,
+    Extension(ban_repository): Extension<Arc<dyn BanRepository>>,
+    Extension(audit_repository): Extension<Arc<dyn AuditRepository>>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Json(payload): Json<CreateBan>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate(Utc::now()).map_err(ApiError::validation)?;
+    let ban = ban_repository
+        .ban(id.clone(), user_id.clone(), payload)
+        .await
+        .map_err(|e| match e {
+            BanError::NotFound => ApiError::new(StatusCode::NOT_FOUND, ErrorCode::UserNotFound),
+            BanError::Repository(_) => StatusCode::INTERNAL_SERVER_ERROR.into(),
+        })?;
+    record_audit(
+        audit_repository.as_ref(),
+        Some(user_id),
+        AuditAction::UserBan,
+        id.into(),
+    )
+    .await;
+
+    Ok((StatusCode::CREATED, Json(ban)))
+}
+
+/// `POST /admin/users/:id/unban`。期限を待たずに凍結を解除する
+pub async fn unban_user(
+    Path(id): Path<UserId>,
+    Extension(ban_repository): Extension<Arc<dyn BanRepository>>,
+    Extension(audit_repository): Extension<Arc<dyn AuditRepository>>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let ban = ban_repository
+        .lift(id.clone(), user_id.clone())
+        .await
+        .map_err(|e| match e {
+            BanError::NotFound => ApiError::new(StatusCode::NOT_FOUND, ErrorCode::BanNotFound),
+            BanError::Repository(_) => StatusCode::INTERNAL_SERVER_ERROR.into(),
+        })?;
+    record_audit(
+        audit_repository.as_ref(),
+        Some(user_id),
+        AuditAction::UserUnban,
+        id.into(),
+    )
+    .await;
+
+    Ok((StatusCode::OK, Json(ban)))
+}
+
+/// 解除・期限切れも含めた凍結の履歴
+pub async fn find_user_bans(
+    Path(id): Path<UserId>,
+    Extension(ban_repository): Extension<Arc<dyn BanRepository>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let bans = ban_repository
+        .find_by_user_id(id)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(bans)))
+}
+
+pub async fn find_feature_flags(
+    Extension(feature_flags): Extension<Arc<FeatureFlagService>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let flags = feature_flags
+        .all()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(flags)))
+}
+
+/// 再デプロイせずに機能を出し入れする。ほかのインスタンスにはキャッシュが切れてから反映される
+pub async fn set_feature_flag(
+    Path(name): Path<String>,
+    Extension(feature_flags): Extension<Arc<FeatureFlagService>>,
+    Extension(audit_repository): Extension<Arc<dyn AuditRepository>>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Json(payload): Json<SetFeatureFlag>,
+) -> Result<impl IntoResponse, ApiError> {
+    let feature: Feature = name.parse().or(Err(ApiError::new(
+        StatusCode::NOT_FOUND,
+        ErrorCode::NotFound,
+    )
+    .with_message(format!("unknown feature: {}", name))))?;
+    let flag = feature_flags
+        .set(feature, payload.enabled)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    record_audit(
+        audit_repository.as_ref(),
+        Some(user_id),
+        AuditAction::FeatureFlagUpdate,
+        feature.to_string(),
+    )
+    .await;
+
+    Ok((StatusCode::OK, Json(flag)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        repositories::{
+            challenge::MockChallengeRepository, quest::MockQuestRepository,
+            translation::MockTranslationRepository,
+        },
+        test_helpers::error_of,
+    };
+
+    fn quest_translation() -> QuestTranslation {
+        QuestTranslation {
+            title: "Quest".to_string(),
+            description: "description".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_reject_invalid_language_tag() {
+        let res = put_quest_translation(
+            Path(("quest".into(), "EN_us".to_string())),
+            Extension(Arc::new(MockQuestRepository::new())),
+            Extension(Arc::new(MockTranslationRepository::new())),
+            Json(quest_translation()),
+        )
+        .await;
+
+        assert_eq!(
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::ValidationFailed
+            ),
+            error_of(res).await
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_save_translation_for_missing_quest() {
+        let mut quest_repository = MockQuestRepository::new();
+        quest_repository
+            .expect_find()
+            .returning(|_| Err(anyhow::anyhow!("not found")));
+        let mut translation_repository = MockTranslationRepository::new();
+        translation_repository.expect_upsert_quest().never();
+
+        let res = put_quest_translation(
+            Path(("missing".into(), "en".to_string())),
+            Extension(Arc::new(quest_repository)),
+            Extension(Arc::new(translation_repository)),
+            Json(quest_translation()),
+        )
+        .await;
+
+        assert_eq!(
+            (StatusCode::NOT_FOUND, ErrorCode::QuestNotFound),
+            error_of(res).await
+        );
+    }
+
+    #[tokio::test]
+    async fn should_return_404_when_translating_missing_challenge() {
+        let mut challenge_repository = MockChallengeRepository::new();
+        challenge_repository
+            .expect_find()
+            .returning(|_| Err(anyhow::anyhow!("not found")));
+
+        let res = put_challenge_translation(
+            Path(("missing".into(), "en".to_string())),
+            Extension(Arc::new(challenge_repository)),
+            Extension(Arc::new(MockTranslationRepository::new())),
+            Json(ChallengeTranslation {
+                name: "Challenge".to_string(),
+                description: "description".to_string(),
+                flavor_text: String::new(),
+            }),
+        )
+        .await;
+
+        assert_eq!(
+            (StatusCode::NOT_FOUND, ErrorCode::ChallengeNotFound),
+            error_of(res).await
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_record_audit_when_unlocking_missing_user() {
+        let mut user_repository = crate::repositories::user::MockUserRepository::new();
+        user_repository
+            .expect_unlock()
+            .returning(|_| Err(anyhow::anyhow!("not found")));
+
+        // 監査ログのモックに期待値がないので、記録しようとするとpanicする
+        let res = unlock_user(
+            Path("missing".into()),
+            Extension(Arc::new(user_repository)),
+            Extension(Arc::new(
+                crate::repositories::audit::MockAuditRepository::new(),
+            )),
+            AuthenticatedUser("admin".into()),
+        )
+        .await;
+
+        assert_eq!(
+            (StatusCode::NOT_FOUND, ErrorCode::UserNotFound),
+            error_of(res).await
+        );
+    }
+}
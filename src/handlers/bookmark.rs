@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    middleware::{auth::AuthenticatedUser, language::Language},
+    repositories::{
+        bookmark::BookmarkRepository,
+        id::QuestId,
+        quest::{QuestRepository, QuestSummary},
+        translation::TranslationRepository,
+    },
+    services::i18n::translate_quests,
+};
+
+pub async fn bookmark_quest(
+    Path(quest_id): Path<QuestId>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(bookmark_repository): Extension<Arc<dyn BookmarkRepository>>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    quest_repository
+        .find(quest_id.clone())
+        .await
+        .or(Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::QuestNotFound,
+        )))?;
+    bookmark_repository
+        .bookmark(user_id, quest_id)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn unbookmark_quest(
+    Path(quest_id): Path<QuestId>,
+    Extension(bookmark_repository): Extension<Arc<dyn BookmarkRepository>>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    bookmark_repository
+        .unbookmark(user_id, quest_id)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 新しくブックマークした順。削除されたクエストは含めない
+pub async fn get_bookmarks(
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(bookmark_repository): Extension<Arc<dyn BookmarkRepository>>,
+    Extension(translation_repository): Extension<Arc<dyn TranslationRepository>>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    language: Language,
+) -> Result<impl IntoResponse, ApiError> {
+    let quest_ids = bookmark_repository
+        .find_quest_ids(user_id)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let mut quests = quest_repository
+        .find_by_ids(quest_ids.clone())
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    quests.sort_by_key(|quest| quest_ids.iter().position(|id| *id == quest.id));
+    translate_quests(translation_repository.as_ref(), &language, &mut quests)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let quests = quests
+        .into_iter()
+        .map(|quest| QuestSummary {
+            is_bookmarked: Some(true),
+            ..QuestSummary::from(quest)
+        })
+        .collect::<Vec<_>>();
+
+    Ok((StatusCode::OK, Json(quests)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        repositories::{bookmark::MockBookmarkRepository, quest::MockQuestRepository},
+        test_helpers::error_of,
+    };
+
+    #[tokio::test]
+    async fn should_not_bookmark_missing_quest() {
+        let mut quest_repository = MockQuestRepository::new();
+        quest_repository
+            .expect_find()
+            .returning(|_| Err(anyhow::anyhow!("not found")));
+        let mut bookmark_repository = MockBookmarkRepository::new();
+        bookmark_repository.expect_bookmark().never();
+
+        let res = bookmark_quest(
+            Path("missing".into()),
+            Extension(Arc::new(quest_repository)),
+            Extension(Arc::new(bookmark_repository)),
+            AuthenticatedUser("user".into()),
+        )
+        .await;
+
+        assert_eq!(
+            (StatusCode::NOT_FOUND, ErrorCode::QuestNotFound),
+            error_of(res).await
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use axum::{
+    extract::{Extension, Path, Query},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    middleware::auth::AuthenticatedUser,
+    repositories::{certificate::Certificate, id::QuestId},
+    services::certificate::{
+        issue_certificate, render_certificate, sign_certificate, verify_signature,
+        CertificateDetails, CertificateVerification, IssueCertificateError,
+    },
+    CertificateHandlerState,
+};
+
+pub async fn get_certificate(
+    Path(quest_id): Path<QuestId>,
+    Extension(state): Extension<CertificateHandlerState>,
+    AuthenticatedUser(user_id_from_token): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    // 存在しないクエストはチャレンジが0件なので未制覇として扱われる
+    let certificate = issue_certificate(
+        state.challenge_repository.as_ref(),
+        state.userchallenge_repository.as_ref(),
+        state.certificate_repository.as_ref(),
+        user_id_from_token,
+        quest_id,
+    )
+    .await
+    .map_err(|e| match e {
+        IssueCertificateError::NotCompleted => {
+            ApiError::new(StatusCode::FORBIDDEN, ErrorCode::QuestNotCompleted)
+        }
+        IssueCertificateError::Repository(_) => StatusCode::INTERNAL_SERVER_ERROR.into(),
+    })?;
+    let details = certificate_details(&state, &certificate).await?;
+    let signature = sign_certificate(state.key_ring.signing_secret(), &certificate);
+
+    Ok((
+        StatusCode::OK,
+        [(CONTENT_TYPE, "image/svg+xml")],
+        render_certificate(&certificate, &details, &signature),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyCertificate {
+    signature: String,
+}
+
+pub async fn verify_certificate(
+    Path(id): Path<String>,
+    Query(query): Query<VerifyCertificate>,
+    Extension(state): Extension<CertificateHandlerState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let certificate = state
+        .certificate_repository
+        .find(id)
+        .await
+        .or(Err(StatusCode::NOT_FOUND))?;
+
+    // 鍵を入れ替える前に発行した修了証も検証できるよう、すべての鍵で試す
+    let valid = state
+        .key_ring
+        .secrets()
+        .any(|secret| verify_signature(secret, &certificate, &query.signature));
+    let verification = if valid {
+        CertificateVerification {
+            valid: true,
+            details: Some(certificate_details(&state, &certificate).await?),
+        }
+    } else {
+        CertificateVerification {
+            valid: false,
+            details: None,
+        }
+    };
+
+    Ok((StatusCode::OK, Json(verification)))
+}
+
+async fn certificate_details(
+    state: &CertificateHandlerState,
+    certificate: &Certificate,
+) -> Result<CertificateDetails, StatusCode> {
+    let user = state
+        .user_repository
+        .find(certificate.user_id.clone())
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let quest = state
+        .quest_repository
+        .find(certificate.quest_id.clone())
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok(CertificateDetails {
+        username: user.username,
+        quest_title: quest.title,
+        completed_at: certificate.completed_at,
+    })
+}
@@ -1,20 +1,33 @@
+use std::sync::Arc;
+
 use axum::{
     extract::{Extension, Path, Query},
     http::StatusCode,
     response::IntoResponse,
     Json,
 };
-use std::sync::Arc;
 
-use crate::repositories::challenge::{
-    ChallengeRepository, CreateChallenge, FindChallengeByQuestId,
+use crate::{
+    error::{ApiError, ErrorCode},
+    middleware::{auth::AuthenticatedUser, language::Language},
+    repositories::{
+        challenge::{BulkUpdateChallenges, CreateChallenge, FindChallengeByQuestId},
+        id::{ChallengeId, QuestId},
+        quest::QuestRepository,
+        user::UserRepository,
+    },
+    services::{
+        challenge::{ChallengeError, ChallengeService},
+        quest::authorize_quest_owner,
+    },
 };
 
-pub async fn create_challenge<T: ChallengeRepository>(
+pub async fn create_challenge(
+    Extension(service): Extension<ChallengeService>,
     Json(payload): Json<CreateChallenge>,
-    Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let challenge = repository
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(ApiError::validation)?;
+    let challenge = service
         .create(payload)
         .await
         .or(Err(StatusCode::NOT_FOUND))?;
@@ -22,23 +35,68 @@ pub async fn create_challenge<T: ChallengeRepository>(
     Ok((StatusCode::CREATED, Json(challenge)))
 }
 
-pub async fn find_challenge<T: ChallengeRepository>(
-    Path(id): Path<String>,
-    Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let challenge = repository.find(id).await.or(Err(StatusCode::NOT_FOUND))?;
+pub async fn find_challenge(
+    Path(id): Path<ChallengeId>,
+    Extension(service): Extension<ChallengeService>,
+    language: Language,
+) -> Result<impl IntoResponse, ApiError> {
+    let challenge = service.find(id, &language).await.map_err(|e| match e {
+        ChallengeError::NotFound => {
+            ApiError::new(StatusCode::NOT_FOUND, ErrorCode::ChallengeNotFound)
+        }
+        ChallengeError::Repository(_) => StatusCode::INTERNAL_SERVER_ERROR.into(),
+    })?;
 
     Ok((StatusCode::OK, Json(challenge)))
 }
 
-pub async fn find_challenge_by_quest_id<T: ChallengeRepository>(
+pub async fn find_challenge_by_quest_id(
     Query(payload): Query<FindChallengeByQuestId>,
-    Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let challenges = repository
-        .find_by_quest_id(payload.quest_id)
+    Extension(service): Extension<ChallengeService>,
+    language: Language,
+) -> Result<impl IntoResponse, ApiError> {
+    let challenges = service
+        .find_by_quest_id(payload.quest_id, &language)
         .await
-        .or(Err(StatusCode::NOT_FOUND))?;
+        .map_err(|e| match e {
+            ChallengeError::NotFound => StatusCode::NOT_FOUND,
+            ChallengeError::Repository(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        })?;
 
     Ok((StatusCode::OK, Json(challenges)))
 }
+
+/// 1件でも適用できなければ何も変更せず、項目ごとの結果を422で返す
+/// クエストの作成者と管理者だけが呼べる
+pub async fn bulk_update_challenges(
+    Path(quest_id): Path<QuestId>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Extension(service): Extension<ChallengeService>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(user_repository): Extension<Arc<dyn UserRepository>>,
+    Json(payload): Json<BulkUpdateChallenges>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(ApiError::validation)?;
+    authorize_quest_owner(
+        quest_repository.as_ref(),
+        user_repository.as_ref(),
+        quest_id.clone(),
+        user_id,
+    )
+    .await?;
+    let result = service
+        .bulk_update(quest_id, payload)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    if !result.applied {
+        let status = if result.has_version_conflict() {
+            StatusCode::CONFLICT
+        } else {
+            StatusCode::UNPROCESSABLE_ENTITY
+        };
+        return Ok((status, Json(result)));
+    }
+
+    Ok((StatusCode::OK, Json(result)))
+}
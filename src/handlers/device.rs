@@ -0,0 +1,22 @@
+use axum::{extract::Extension, http::StatusCode, response::IntoResponse, Json};
+use std::sync::Arc;
+
+use crate::{
+    error::ApiError,
+    middleware::auth::AuthenticatedUser,
+    repositories::device::{DeviceRepository, RegisterDevice},
+};
+
+pub async fn register_device(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Extension(repository): Extension<Arc<dyn DeviceRepository>>,
+    Json(payload): Json<RegisterDevice>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(ApiError::validation)?;
+    let device = repository
+        .register(user_id, payload)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::CREATED, Json(device)))
+}
@@ -0,0 +1,57 @@
+use axum::{extract::Extension, http::StatusCode, response::IntoResponse, Json};
+use chrono::Utc;
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    middleware::auth::AuthenticatedUser,
+    repositories::job::{JobKind, JobOutput},
+    services::export::export_user,
+    ExportHandlerState,
+};
+
+/// 自分の記録のエクスポートをジョブとして始める
+/// 結果(`GET /jobs/:id/result`)はダウンロード用の署名付きURL
+pub async fn start_user_export(
+    Extension(state): Extension<ExportHandlerState>,
+    AuthenticatedUser(user_id_from_token): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    // 退会済みのユーザーはエクスポートできない
+    state
+        .user_repository
+        .find(user_id_from_token.clone())
+        .await
+        .or(Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::UserNotFound,
+        )))?;
+
+    let user_repository = state.user_repository.clone();
+    let store = state.store.clone();
+    let url_ttl = state.url_ttl;
+    let user_id = user_id_from_token.clone();
+    let job = state
+        .job_runner
+        .spawn(
+            user_id_from_token,
+            JobKind::UserExport,
+            move |_| async move {
+                let link = export_user(
+                    user_repository.as_ref(),
+                    store.as_ref(),
+                    user_id,
+                    url_ttl,
+                    Utc::now(),
+                )
+                .await?;
+                Ok(JobOutput {
+                    content_type: mime::APPLICATION_JSON.to_string(),
+                    filename: None,
+                    body: serde_json::to_vec(&link)?,
+                })
+            },
+        )
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
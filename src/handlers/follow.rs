@@ -0,0 +1,96 @@
+use crate::{
+    error::{ApiError, ErrorCode},
+    middleware::auth::AuthenticatedUser,
+    repositories::{follow::FeedQuery, id::UserId},
+    services::follow::{self, FollowError},
+    FollowHandlerState,
+};
+use axum::{
+    extract::{Extension, Path, Query},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+
+pub async fn follow_user(
+    Path(followee_id): Path<UserId>,
+    Extension(state): Extension<FollowHandlerState>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    follow::follow_user(
+        state.user_repository.as_ref(),
+        state.follow_repository.as_ref(),
+        user_id,
+        followee_id,
+    )
+    .await
+    .map_err(|e| match e {
+        FollowError::SelfFollow => ApiError::validation("cannot follow yourself".to_string()),
+        FollowError::UserNotFound => ApiError::new(StatusCode::NOT_FOUND, ErrorCode::UserNotFound),
+        FollowError::Repository(_) => StatusCode::INTERNAL_SERVER_ERROR.into(),
+    })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn unfollow_user(
+    Path(followee_id): Path<UserId>,
+    Extension(state): Extension<FollowHandlerState>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    state
+        .follow_repository
+        .unfollow(user_id, followee_id)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_following(
+    Extension(state): Extension<FollowHandlerState>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let users = state
+        .follow_repository
+        .find_following(user_id)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(users)))
+}
+
+pub async fn get_followers(
+    Path(id): Path<UserId>,
+    Extension(state): Extension<FollowHandlerState>,
+) -> Result<impl IntoResponse, ApiError> {
+    state
+        .user_repository
+        .find(id.clone())
+        .await
+        .or(Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::UserNotFound,
+        )))?;
+    let users = state
+        .follow_repository
+        .find_followers(id)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(users)))
+}
+
+pub async fn get_feed(
+    Query(query): Query<FeedQuery>,
+    Extension(state): Extension<FollowHandlerState>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let activities = state
+        .follow_repository
+        .find_feed(user_id, query)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(activities)))
+}
@@ -0,0 +1,20 @@
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::extract::Extension;
+
+use crate::{
+    graphql::{QuestSchema, Viewer},
+    middleware::auth::AuthenticatedUser,
+};
+
+pub async fn graphql_handler(
+    Extension(schema): Extension<QuestSchema>,
+    user_id: Option<AuthenticatedUser>,
+    req: GraphQLRequest,
+) -> GraphQLResponse {
+    let mut req = req.into_inner();
+    if let Some(AuthenticatedUser(user_id)) = user_id {
+        req = req.data(Viewer(user_id));
+    }
+
+    schema.execute(req).await.into()
+}
@@ -0,0 +1,96 @@
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    middleware::auth::AuthenticatedUser,
+    repositories::{
+        challenge::ChallengeRepository,
+        hint::{CreateHint, HintError, HintRepository},
+        id::{ChallengeId, HintId},
+        quest::QuestRepository,
+        user::UserRepository,
+    },
+    services::quest::authorize_quest_owner,
+};
+
+/// チャレンジが属するクエストの作成者と管理者だけが呼べる
+pub async fn create_hint(
+    Path(challenge_id): Path<ChallengeId>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Extension(repository): Extension<Arc<dyn HintRepository>>,
+    Extension(challenge_repository): Extension<Arc<dyn ChallengeRepository>>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(user_repository): Extension<Arc<dyn UserRepository>>,
+    Json(payload): Json<CreateHint>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(ApiError::validation)?;
+    let challenge = challenge_repository
+        .find(challenge_id.clone())
+        .await
+        .or(Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::ChallengeNotFound,
+        )))?;
+    authorize_quest_owner(
+        quest_repository.as_ref(),
+        user_repository.as_ref(),
+        challenge.quest_id,
+        user_id,
+    )
+    .await?;
+    let hint = repository
+        .create(challenge_id, payload)
+        .await
+        .map_err(|e| match e {
+            HintError::NotFound => {
+                ApiError::new(StatusCode::NOT_FOUND, ErrorCode::ChallengeNotFound)
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR.into(),
+        })?;
+
+    Ok((StatusCode::CREATED, Json(hint)))
+}
+
+/// 本文は開いたヒントの分だけ返す。ログインしていなければどれも伏せる
+pub async fn find_hints(
+    Path(challenge_id): Path<ChallengeId>,
+    Extension(repository): Extension<Arc<dyn HintRepository>>,
+    user_id: Option<AuthenticatedUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    let hints = repository
+        .find_by_challenge_id(
+            challenge_id,
+            user_id.map(|AuthenticatedUser(user_id)| user_id),
+        )
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(hints)))
+}
+
+pub async fn open_hint(
+    Path((challenge_id, hint_id)): Path<(ChallengeId, HintId)>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Extension(repository): Extension<Arc<dyn HintRepository>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let hint = repository
+        .open(user_id, challenge_id, hint_id)
+        .await
+        .map_err(|e| match e {
+            HintError::NotFound => ApiError::new(StatusCode::NOT_FOUND, ErrorCode::HintNotFound),
+            HintError::Locked => ApiError::new(StatusCode::FORBIDDEN, ErrorCode::HintLocked),
+            HintError::InsufficientPoints { balance, cost } => {
+                ApiError::new(StatusCode::CONFLICT, ErrorCode::InsufficientPoints)
+                    .with_message(format!("balance {} is less than cost {}", balance, cost))
+            }
+            HintError::Repository(_) => StatusCode::INTERNAL_SERVER_ERROR.into(),
+        })?;
+
+    Ok((StatusCode::OK, Json(hint)))
+}
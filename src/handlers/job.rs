@@ -0,0 +1,103 @@
+use axum::{
+    extract::{Extension, Path},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    middleware::auth::AuthenticatedUser,
+    repositories::id::UserId,
+    repositories::job::{Job, JobRepository},
+};
+
+pub async fn find_jobs(
+    Extension(repository): Extension<Arc<dyn JobRepository>>,
+    AuthenticatedUser(user_id_from_token): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let jobs = repository
+        .find_by_user_id(user_id_from_token)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(jobs)))
+}
+
+pub async fn find_job(
+    Path(id): Path<String>,
+    Extension(repository): Extension<Arc<dyn JobRepository>>,
+    AuthenticatedUser(user_id_from_token): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let job = find_own_job(repository.as_ref(), id, &user_id_from_token).await?;
+
+    Ok((StatusCode::OK, Json(job)))
+}
+
+pub async fn cancel_job(
+    Path(id): Path<String>,
+    Extension(repository): Extension<Arc<dyn JobRepository>>,
+    AuthenticatedUser(user_id_from_token): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    find_own_job(repository.as_ref(), id.clone(), &user_id_from_token).await?;
+    let cancelled = repository
+        .cancel(id, user_id_from_token)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    // 終わったジョブはキャンセルできない
+    if cancelled {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::new(
+            StatusCode::CONFLICT,
+            ErrorCode::JobAlreadyFinished,
+        ))
+    }
+}
+
+pub async fn find_job_result(
+    Path(id): Path<String>,
+    Extension(repository): Extension<Arc<dyn JobRepository>>,
+    AuthenticatedUser(user_id_from_token): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    find_own_job(repository.as_ref(), id.clone(), &user_id_from_token).await?;
+    let output = repository
+        .find_output(id)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or(ApiError::new(StatusCode::NOT_FOUND, ErrorCode::JobNotFound))?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_str(&output.content_type).or(Err(StatusCode::INTERNAL_SERVER_ERROR))?,
+    );
+    if let Some(filename) = output.filename {
+        headers.insert(
+            CONTENT_DISPOSITION,
+            HeaderValue::from_str(&format!("attachment; filename=\"{}\"", filename))
+                .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?,
+        );
+    }
+
+    Ok((StatusCode::OK, headers, output.body))
+}
+
+/// 他人のジョブは存在しないものとして扱う
+async fn find_own_job(
+    repository: &dyn JobRepository,
+    id: String,
+    user_id: &UserId,
+) -> Result<Job, ApiError> {
+    repository
+        .find(id)
+        .await
+        .ok()
+        .filter(|job| job.user_id == *user_id)
+        .ok_or(ApiError::new(StatusCode::NOT_FOUND, ErrorCode::JobNotFound))
+}
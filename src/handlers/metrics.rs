@@ -0,0 +1,18 @@
+use axum::{extract::Extension, http::header::CONTENT_TYPE, response::IntoResponse};
+use metrics::{absolute_counter, gauge};
+
+use crate::{middleware::recovery::panic_count, MetricsHandlerState};
+
+/// Prometheusのスクレイプ用。DBプールの状態はスクレイプのたびに取り直す
+pub async fn render_metrics(Extension(state): Extension<MetricsHandlerState>) -> impl IntoResponse {
+    let size = state.pool.size();
+    let idle = state.pool.num_idle() as u32;
+    gauge!("db_pool_connections", f64::from(size.saturating_sub(idle)), "state" => "in_use");
+    gauge!("db_pool_connections", f64::from(idle), "state" => "idle");
+    absolute_counter!("http_panics_total", panic_count());
+
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.handle.render(),
+    )
+}
@@ -0,0 +1,38 @@
+use axum::{
+    extract::{Extension, Path},
+    http::{header::CACHE_CONTROL, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    services::offline_pack::is_valid_region,
+    OfflinePackHandlerState,
+};
+
+/// 内容は定期的に作り直すだけなので、CDNにキャッシュさせる
+pub async fn get_offline_pack(
+    Path(region): Path<String>,
+    Extension(state): Extension<OfflinePackHandlerState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let not_found = || ApiError::new(StatusCode::NOT_FOUND, ErrorCode::OfflinePackNotFound);
+    if !is_valid_region(&region) {
+        return Err(not_found());
+    }
+    let pack = state
+        .store
+        .get(&region)
+        .await
+        .or(Err(StatusCode::SERVICE_UNAVAILABLE))?
+        .ok_or_else(not_found)?;
+
+    let mut headers = HeaderMap::new();
+    let cache_control = format!("public, max-age={}", state.max_age.as_secs());
+    headers.insert(
+        CACHE_CONTROL,
+        HeaderValue::from_str(&cache_control).or(Err(StatusCode::INTERNAL_SERVER_ERROR))?,
+    );
+
+    Ok((StatusCode::OK, headers, Json(pack)))
+}
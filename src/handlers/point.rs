@@ -0,0 +1,61 @@
+use axum::{
+    extract::{Extension, Query},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use std::sync::Arc;
+
+use crate::{
+    error::ApiError,
+    middleware::{auth::AuthenticatedUser, feature_flag::FeatureFlags},
+    repositories::{
+        feature_flag::Feature,
+        point::{FindLeaderboard, Leaderboard, PointRepository, PointSummary},
+    },
+    services::leaderboard::LeaderboardCache,
+};
+
+pub async fn get_points(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Extension(repository): Extension<Arc<dyn PointRepository>>,
+    flags: FeatureFlags,
+) -> Result<impl IntoResponse, ApiError> {
+    flags.require(Feature::Points).await?;
+    let balance = repository
+        .get_balance(user_id.clone())
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let history = repository
+        .get_history(user_id)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(PointSummary { balance, history })))
+}
+
+pub async fn get_leaderboard(
+    Query(query): Query<FindLeaderboard>,
+    Extension(repository): Extension<Arc<dyn PointRepository>>,
+    Extension(cache): Extension<Arc<LeaderboardCache>>,
+    user_id: Option<AuthenticatedUser>,
+    flags: FeatureFlags,
+) -> Result<impl IntoResponse, ApiError> {
+    flags.require(Feature::Points).await?;
+    let entries = cache
+        .top_entries(repository.as_ref(), query.period)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    // 自分の順位はユーザーごとに異なるのでキャッシュしない
+    let me = match user_id {
+        Some(AuthenticatedUser(user_id)) => repository
+            .find_rank(user_id, query.period.since(Utc::now()))
+            .await
+            .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?,
+        None => None,
+    };
+
+    Ok((StatusCode::OK, Json(Leaderboard { entries, me })))
+}
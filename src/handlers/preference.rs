@@ -0,0 +1,42 @@
+use axum::{extract::Extension, http::StatusCode, response::IntoResponse, Json};
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    middleware::auth::AuthenticatedUser,
+    repositories::preference::UpdatePreferences,
+    services::preference::{PreferenceError, PreferenceService},
+};
+
+fn preference_error(e: PreferenceError) -> ApiError {
+    match e {
+        PreferenceError::NotFound => ApiError::new(StatusCode::NOT_FOUND, ErrorCode::UserNotFound),
+        PreferenceError::UnsupportedLanguage(language) => {
+            ApiError::validation(format!("unsupported language: {}", language))
+        }
+        PreferenceError::Repository(_) => StatusCode::INTERNAL_SERVER_ERROR.into(),
+    }
+}
+
+pub async fn get_preferences(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Extension(service): Extension<PreferenceService>,
+) -> Result<impl IntoResponse, ApiError> {
+    let preferences = service.find(user_id).await.map_err(preference_error)?;
+
+    Ok((StatusCode::OK, Json(preferences)))
+}
+
+/// 送った項目だけを変える
+pub async fn update_preferences(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Extension(service): Extension<PreferenceService>,
+    Json(payload): Json<UpdatePreferences>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(ApiError::validation)?;
+    let preferences = service
+        .update(user_id, payload)
+        .await
+        .map_err(preference_error)?;
+
+    Ok((StatusCode::OK, Json(preferences)))
+}
@@ -1,60 +1,373 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
 use axum::{
-    extract::{Extension, Path},
+    extract::{Extension, Multipart, Path, Query},
     http::StatusCode,
     response::IntoResponse,
     Json,
 };
 
-use crate::repositories::quest::{CreateQuest, QuestRepository, UpdateQuest};
+use crate::{
+    error::{ApiError, ErrorCode},
+    handlers::user_challenge::read_photo,
+    middleware::{auth::AuthenticatedUser, etag::last_modified, language::Language},
+    repositories::{
+        bookmark::BookmarkRepository,
+        id::QuestId,
+        quest::{
+            BatchFindQuests, BulkCreateQuest, CreateQuest, FindQuests, QuestRepository,
+            QuestSummary, UpdateQuest, UpdateQuestError,
+        },
+        user::UserRepository,
+    },
+    services::{
+        cover_image::{CoverImageError, CoverImageUploader},
+        quest::{authorize_quest_owner, QuestError, QuestService, UpdateCoverImageError},
+    },
+};
 
-pub async fn create_quest<T: QuestRepository>(
+pub async fn create_quest(
+    Extension(service): Extension<QuestService>,
+    user_id: Option<AuthenticatedUser>,
     Json(payload): Json<CreateQuest>,
-    Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let quest = repository
-        .create(payload)
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(ApiError::validation)?;
+    let quest = service
+        .create(payload, user_id.map(|AuthenticatedUser(user_id)| user_id))
         .await
         .or(Err(StatusCode::NOT_FOUND))?;
 
     Ok((StatusCode::CREATED, Json(quest)))
 }
 
-pub async fn find_quest<T: QuestRepository>(
-    Path(id): Path<String>,
-    Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let quest = repository.find(id).await.or(Err(StatusCode::NOT_FOUND))?;
+pub async fn create_quest_with_challenges(
+    Extension(service): Extension<QuestService>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Json(payload): Json<BulkCreateQuest>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(ApiError::validation)?;
+    let quest = service
+        .create_with_challenges(payload, Some(user_id))
+        .await
+        .or(Err(StatusCode::BAD_REQUEST))?;
 
-    Ok((StatusCode::OK, Json(quest)))
+    Ok((StatusCode::CREATED, Json(quest)))
 }
 
-pub async fn all_quests<T: QuestRepository>(
-    Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let quests = repository.all().await.unwrap();
+pub async fn find_quest(
+    Path(id): Path<QuestId>,
+    Extension(service): Extension<QuestService>,
+    language: Language,
+    user_id: Option<AuthenticatedUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    let quest = service
+        .find(
+            id,
+            &language,
+            user_id.map(|AuthenticatedUser(user_id)| user_id),
+        )
+        .await
+        .map_err(|e| match e {
+            QuestError::NotFound => ApiError::new(StatusCode::NOT_FOUND, ErrorCode::QuestNotFound),
+            QuestError::Forbidden => StatusCode::FORBIDDEN.into(),
+            QuestError::Repository(_) => StatusCode::INTERNAL_SERVER_ERROR.into(),
+        })?;
+    let headers = last_modified(Some(quest.quest.updated_at));
+
+    Ok((StatusCode::OK, headers, Json(quest)))
+}
+
+/// チャレンジも含めて複製する。複製したクエストは下書きになる
+/// 複製元の作成者と管理者だけが呼べる
+pub async fn clone_quest(
+    Path(id): Path<QuestId>,
+    Extension(service): Extension<QuestService>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(user_repository): Extension<Arc<dyn UserRepository>>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    authorize_quest_owner(
+        quest_repository.as_ref(),
+        user_repository.as_ref(),
+        id.clone(),
+        user_id.clone(),
+    )
+    .await?;
+    let quest = service
+        .duplicate(id, Some(user_id))
+        .await
+        .or(Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::QuestNotFound,
+        )))?;
+
+    Ok((StatusCode::CREATED, Json(quest)))
+}
+
+/// 存在しないIDは結果のマップに含めない
+pub async fn batch_find_quests(
+    Extension(service): Extension<QuestService>,
+    language: Language,
+    user_id: Option<AuthenticatedUser>,
+    Json(payload): Json<BatchFindQuests>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(ApiError::validation)?;
+    let quests = service
+        .batch_find(
+            payload.into_ids(),
+            &language,
+            user_id.map(|AuthenticatedUser(user_id)| user_id),
+        )
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
 
     Ok((StatusCode::OK, Json(quests)))
 }
 
-pub async fn update_quest<T: QuestRepository>(
-    Path(id): Path<String>,
+pub async fn all_quests(
+    Query(query): Query<FindQuests>,
+    Extension(service): Extension<QuestService>,
+    bookmark_repository: Option<Extension<Arc<dyn BookmarkRepository>>>,
+    language: Language,
+    user_id: Option<AuthenticatedUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    let ranking = query.ranking().map_err(ApiError::validation)?;
+    let quests = service
+        .all(
+            &query,
+            ranking,
+            &language,
+            user_id.clone().map(|AuthenticatedUser(user_id)| user_id),
+        )
+        .await
+        .map_err(|e| match e {
+            QuestError::Forbidden => ApiError::from(StatusCode::FORBIDDEN),
+            QuestError::NotFound | QuestError::Repository(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR.into()
+            }
+        })?;
+    let headers = last_modified(quests.iter().map(|quest| quest.quest.updated_at).max());
+    let mut quests = quests
+        .into_iter()
+        .map(|quest| quest.map(QuestSummary::from))
+        .collect::<Vec<_>>();
+
+    // 未ログインのときやパートナー向けのルートでは付けない
+    if let (true, Some(Extension(bookmark_repository)), Some(AuthenticatedUser(user_id))) =
+        (query.with_bookmarks, bookmark_repository, user_id)
+    {
+        let quest_ids = quests.iter().map(|quest| quest.quest.id.clone()).collect();
+        let bookmarked: HashSet<QuestId> = bookmark_repository
+            .find_bookmarked(user_id, quest_ids)
+            .await
+            .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?
+            .into_iter()
+            .collect();
+        for quest in quests.iter_mut() {
+            quest.quest.is_bookmarked = Some(bookmarked.contains(&quest.quest.id));
+        }
+    }
+
+    Ok((StatusCode::OK, headers, Json(quests)))
+}
+
+pub async fn update_quest(
+    Path(id): Path<QuestId>,
+    Extension(service): Extension<QuestService>,
+    user_id: Option<AuthenticatedUser>,
     Json(payload): Json<UpdateQuest>,
-    Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let quest = repository.update(id, payload).await.unwrap();
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(ApiError::validation)?;
+    let quest = service
+        .update(
+            id,
+            payload,
+            user_id.map(|AuthenticatedUser(user_id)| user_id),
+        )
+        .await
+        .map_err(|e| match e {
+            UpdateQuestError::NotFound => {
+                ApiError::new(StatusCode::NOT_FOUND, ErrorCode::QuestNotFound)
+            }
+            UpdateQuestError::VersionConflict => {
+                ApiError::new(StatusCode::CONFLICT, ErrorCode::VersionConflict)
+            }
+            UpdateQuestError::Repository(_) => StatusCode::INTERNAL_SERVER_ERROR.into(),
+        })?;
+
+    Ok((StatusCode::OK, Json(quest)))
+}
+
+/// multipartのphotoフィールドで受け取り、サイズごとに縮小して保存する
+/// クエストの作成者と管理者だけが呼べる
+pub async fn upload_quest_cover_image(
+    Path(id): Path<QuestId>,
+    Extension(service): Extension<QuestService>,
+    Extension(uploader): Extension<CoverImageUploader>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(user_repository): Extension<Arc<dyn UserRepository>>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    multipart: Multipart,
+) -> Result<impl IntoResponse, ApiError> {
+    authorize_quest_owner(
+        quest_repository.as_ref(),
+        user_repository.as_ref(),
+        id.clone(),
+        user_id.clone(),
+    )
+    .await?;
+    let photo = read_photo(multipart, uploader.max_bytes())
+        .await?
+        .ok_or_else(|| ApiError::validation("photo is required".to_string()))?;
+    let quest = service
+        .update_cover_image(id, photo, &uploader, Some(user_id))
+        .await
+        .map_err(|e| match e {
+            UpdateCoverImageError::NotFound => {
+                ApiError::new(StatusCode::NOT_FOUND, ErrorCode::QuestNotFound)
+            }
+            UpdateCoverImageError::Image(CoverImageError::Unsupported) => {
+                StatusCode::UNSUPPORTED_MEDIA_TYPE.into()
+            }
+            UpdateCoverImageError::Image(CoverImageError::Store(_)) => {
+                StatusCode::SERVICE_UNAVAILABLE.into()
+            }
+        })?;
 
     Ok((StatusCode::OK, Json(quest)))
 }
 
-pub async fn delete_quest<T: QuestRepository>(
-    Path(id): Path<String>,
-    Extension(repository): Extension<Arc<T>>,
-) -> StatusCode {
-    repository
-        .delete(id)
+pub async fn delete_quest(
+    Path(id): Path<QuestId>,
+    Extension(service): Extension<QuestService>,
+    user_id: Option<AuthenticatedUser>,
+) -> Result<StatusCode, ApiError> {
+    service
+        .delete(id, user_id.map(|AuthenticatedUser(user_id)| user_id))
         .await
-        .map(|_| StatusCode::NO_CONTENT)
-        .unwrap_or(StatusCode::NOT_FOUND)
+        .or(Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::QuestNotFound,
+        )))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        config::I18nConfig,
+        repositories::{
+            audit::MockAuditRepository, quest::MockQuestRepository, review::MockReviewRepository,
+            translation::MockTranslationRepository, user::MockUserRepository,
+            user_challenge::MockUserChallengeRepository,
+        },
+        services::event_bus::EventBus,
+        test_helpers::error_of,
+    };
+    use serde_json::json;
+
+    fn language() -> Language {
+        Language::negotiate(&I18nConfig::default(), None, None)
+    }
+
+    /// クエスト以外のリポジトリは期待値を設定していないので、呼ばれるとpanicする
+    fn service(repository: MockQuestRepository) -> Extension<QuestService> {
+        Extension(QuestService::new(
+            Arc::new(repository),
+            Arc::new(MockUserRepository::new()),
+            Arc::new(MockUserChallengeRepository::new()),
+            Arc::new(MockReviewRepository::new()),
+            Arc::new(MockTranslationRepository::new()),
+            Arc::new(MockAuditRepository::new()),
+            EventBus::new(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn should_return_404_when_quest_not_found() {
+        let mut repository = MockQuestRepository::new();
+        repository
+            .expect_find()
+            .returning(|_| Err(anyhow::anyhow!("not found")));
+
+        let res = find_quest(
+            Path("missing".into()),
+            service(repository),
+            language(),
+            None,
+        )
+        .await;
+
+        assert_eq!(
+            (StatusCode::NOT_FOUND, ErrorCode::QuestNotFound),
+            error_of(res).await
+        );
+    }
+
+    #[tokio::test]
+    async fn should_reject_empty_batch_without_finding() {
+        let payload: BatchFindQuests = serde_json::from_value(json!({ "ids": [] })).unwrap();
+
+        let res = batch_find_quests(
+            service(MockQuestRepository::new()),
+            language(),
+            None,
+            Json(payload),
+        )
+        .await;
+
+        assert_eq!(
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::ValidationFailed
+            ),
+            error_of(res).await
+        );
+    }
+
+    #[tokio::test]
+    async fn should_reject_empty_title_without_updating() {
+        let payload: UpdateQuest = serde_json::from_value(json!({ "title": " " })).unwrap();
+
+        // 期待値を設定していないので、リポジトリが呼ばれるとpanicする
+        let res = update_quest(
+            Path("quest".into()),
+            service(MockQuestRepository::new()),
+            None,
+            Json(payload),
+        )
+        .await;
+
+        assert_eq!(
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::ValidationFailed
+            ),
+            error_of(res).await
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_record_audit_when_deleting_missing_quest() {
+        let mut repository = MockQuestRepository::new();
+        repository
+            .expect_delete()
+            .withf(|id| id == "missing")
+            .times(1)
+            .returning(|_| Err(anyhow::anyhow!("not found")));
+
+        let res = delete_quest(
+            Path("missing".into()),
+            service(repository),
+            Some(AuthenticatedUser("admin".into())),
+        )
+        .await;
+
+        assert_eq!(
+            (StatusCode::NOT_FOUND, ErrorCode::QuestNotFound),
+            error_of(res).await
+        );
+    }
 }
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    middleware::auth::AuthenticatedUser,
+    repositories::{
+        id::QuestId, quest::QuestRepository, quest_template::SaveQuestTemplate,
+        user::UserRepository,
+    },
+    services::{
+        quest::{authorize_quest_owner, QuestError},
+        quest_template::QuestTemplateService,
+    },
+};
+
+/// 元のクエストの作成者と管理者だけが呼べる
+pub async fn save_quest_template(
+    Path(id): Path<QuestId>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Extension(service): Extension<QuestTemplateService>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(user_repository): Extension<Arc<dyn UserRepository>>,
+    Json(payload): Json<SaveQuestTemplate>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(ApiError::validation)?;
+    authorize_quest_owner(
+        quest_repository.as_ref(),
+        user_repository.as_ref(),
+        id.clone(),
+        user_id,
+    )
+    .await?;
+    let template = service.save(id, payload.name).await.map_err(|e| match e {
+        QuestError::NotFound => ApiError::new(StatusCode::NOT_FOUND, ErrorCode::QuestNotFound),
+        QuestError::Forbidden => StatusCode::FORBIDDEN.into(),
+        QuestError::Repository(_) => StatusCode::INTERNAL_SERVER_ERROR.into(),
+    })?;
+
+    Ok((StatusCode::CREATED, Json(template)))
+}
+
+pub async fn all_quest_templates(
+    Extension(service): Extension<QuestTemplateService>,
+) -> Result<impl IntoResponse, ApiError> {
+    let templates = service
+        .all()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(templates)))
+}
+
+/// テンプレートから下書きのクエストを作る
+pub async fn create_quest_from_template(
+    Path(id): Path<String>,
+    Extension(service): Extension<QuestTemplateService>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let quest = service
+        .instantiate(id, Some(user_id))
+        .await
+        .map_err(|e| match e {
+            QuestError::NotFound => {
+                ApiError::new(StatusCode::NOT_FOUND, ErrorCode::QuestTemplateNotFound)
+            }
+            QuestError::Forbidden => StatusCode::FORBIDDEN.into(),
+            QuestError::Repository(_) => StatusCode::BAD_REQUEST.into(),
+        })?;
+
+    Ok((StatusCode::CREATED, Json(quest)))
+}
@@ -0,0 +1,65 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Extension,
+    },
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{
+    middleware::auth::AuthenticatedUser,
+    repositories::user_quest::UserQuestRepository,
+    services::realtime::{ProgressFilter, RealtimeEvent, RealtimeHub},
+};
+
+pub async fn connect_progress(
+    ws: WebSocketUpgrade,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Extension(hub): Extension<RealtimeHub>,
+    Extension(userquest_repository): Extension<Arc<dyn UserQuestRepository>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    // 参加中のクエストを取得する前に購読して、その間のイベントを取りこぼさないようにする
+    let receiver = hub.subscribe();
+    let quest_ids = userquest_repository
+        .get_participated_quests_by_user_id(user_id.clone())
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let filter = ProgressFilter::new(user_id, quest_ids.into_iter().collect());
+
+    Ok(ws.on_upgrade(move |socket| stream_progress(socket, filter, receiver)))
+}
+
+async fn stream_progress(
+    mut socket: WebSocket,
+    mut filter: ProgressFilter,
+    mut receiver: broadcast::Receiver<RealtimeEvent>,
+) {
+    loop {
+        tokio::select! {
+            event = receiver.recv() => match event {
+                Ok(event) => {
+                    let message = match filter.accept(event) {
+                        Some(message) => message,
+                        None => continue,
+                    };
+                    let text = serde_json::to_string(&message).unwrap();
+                    if socket.send(Message::Text(text)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("websocket client lagged, skipped {} events", skipped);
+                }
+                Err(RecvError::Closed) => break,
+            },
+            // クライアントからのメッセージは使わない。切断だけ検知する
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    middleware::{auth::AuthenticatedUser, feature_flag::FeatureFlags},
+    repositories::{
+        feature_flag::Feature,
+        id::QuestId,
+        quest::QuestRepository,
+        review::{CreateReview, CreateReviewError, ReviewRepository},
+    },
+};
+
+/// 閲覧と同じルートに置くため任意認証で受け、未ログインはここで弾く
+pub async fn create_review(
+    Path(quest_id): Path<QuestId>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(review_repository): Extension<Arc<dyn ReviewRepository>>,
+    user_id: Option<AuthenticatedUser>,
+    flags: FeatureFlags,
+    Json(payload): Json<CreateReview>,
+) -> Result<impl IntoResponse, ApiError> {
+    flags.require(Feature::Reviews).await?;
+    let AuthenticatedUser(user_id) = user_id.ok_or(StatusCode::UNAUTHORIZED)?;
+    payload.validate().map_err(ApiError::validation)?;
+    quest_repository
+        .find(quest_id.clone())
+        .await
+        .or(Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::QuestNotFound,
+        )))?;
+    let review = review_repository
+        .create(user_id, quest_id, payload)
+        .await
+        .map_err(|e| match e {
+            CreateReviewError::NotParticipating => {
+                ApiError::new(StatusCode::FORBIDDEN, ErrorCode::QuestNotParticipated)
+            }
+            CreateReviewError::AlreadyReviewed => {
+                ApiError::new(StatusCode::CONFLICT, ErrorCode::AlreadyReviewed)
+            }
+            CreateReviewError::Repository(_) => StatusCode::INTERNAL_SERVER_ERROR.into(),
+        })?;
+
+    Ok((StatusCode::CREATED, Json(review)))
+}
+
+pub async fn find_reviews(
+    Path(quest_id): Path<QuestId>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(review_repository): Extension<Arc<dyn ReviewRepository>>,
+    flags: FeatureFlags,
+) -> Result<impl IntoResponse, ApiError> {
+    flags.require(Feature::Reviews).await?;
+    quest_repository
+        .find(quest_id.clone())
+        .await
+        .or(Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::QuestNotFound,
+        )))?;
+    let reviews = review_repository
+        .find_by_quest_id(quest_id)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(reviews)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        config::FeatureFlagConfig,
+        repositories::{
+            feature_flag::MockFeatureFlagRepository,
+            quest::{MockQuestRepository, QuestEntity, QuestStatus},
+            review::MockReviewRepository,
+        },
+        services::feature_flag::FeatureFlagService,
+        test_helpers::error_of,
+    };
+    use std::time::Duration;
+
+    fn existing_quest_repository() -> MockQuestRepository {
+        let mut repository = MockQuestRepository::new();
+        repository.expect_find().returning(|id| {
+            Ok(QuestEntity::new(
+                id,
+                "title".to_string(),
+                "description".to_string(),
+                QuestStatus::Published,
+            ))
+        });
+        repository
+    }
+
+    async fn create(
+        quest_repository: MockQuestRepository,
+        review_repository: MockReviewRepository,
+        rating: i16,
+        user_id: Option<&str>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        create_review(
+            Path("quest".into()),
+            Extension(Arc::new(quest_repository)),
+            Extension(Arc::new(review_repository)),
+            user_id.map(|user_id| AuthenticatedUser(user_id.into())),
+            FeatureFlags::default(),
+            Json(CreateReview::new(rating, String::new())),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn should_return_401_without_login() {
+        let res = create(
+            MockQuestRepository::new(),
+            MockReviewRepository::new(),
+            5,
+            None,
+        )
+        .await;
+
+        assert_eq!(
+            (StatusCode::UNAUTHORIZED, ErrorCode::Unauthorized),
+            error_of(res).await
+        );
+    }
+
+    #[tokio::test]
+    async fn should_return_422_for_out_of_range_rating() {
+        let res = create(
+            MockQuestRepository::new(),
+            MockReviewRepository::new(),
+            0,
+            Some("user"),
+        )
+        .await;
+
+        assert_eq!(
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::ValidationFailed
+            ),
+            error_of(res).await
+        );
+    }
+
+    #[tokio::test]
+    async fn should_return_404_when_reviewing_missing_quest() {
+        let mut quest_repository = MockQuestRepository::new();
+        quest_repository
+            .expect_find()
+            .returning(|_| Err(anyhow::anyhow!("not found")));
+
+        let res = create(
+            quest_repository,
+            MockReviewRepository::new(),
+            5,
+            Some("user"),
+        )
+        .await;
+
+        assert_eq!(
+            (StatusCode::NOT_FOUND, ErrorCode::QuestNotFound),
+            error_of(res).await
+        );
+    }
+
+    #[tokio::test]
+    async fn should_map_review_errors_to_status() {
+        let cases = [
+            (
+                CreateReviewError::NotParticipating,
+                StatusCode::FORBIDDEN,
+                ErrorCode::QuestNotParticipated,
+            ),
+            (
+                CreateReviewError::AlreadyReviewed,
+                StatusCode::CONFLICT,
+                ErrorCode::AlreadyReviewed,
+            ),
+        ];
+        for (error, status, code) in cases {
+            let mut review_repository = MockReviewRepository::new();
+            review_repository
+                .expect_create()
+                .return_once(move |_, _, _| Err(error));
+
+            let res = create(
+                existing_quest_repository(),
+                review_repository,
+                5,
+                Some("user"),
+            )
+            .await;
+
+            assert_eq!((status, code), error_of(res).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_return_404_when_reviews_are_disabled() {
+        let mut flag_repository = MockFeatureFlagRepository::new();
+        flag_repository.expect_all().returning(|| Ok(vec![]));
+        let flags = FeatureFlags(Some(Arc::new(FeatureFlagService::new(
+            Arc::new(flag_repository),
+            FeatureFlagConfig {
+                disabled: vec![Feature::Reviews],
+                cache_ttl: Duration::from_secs(60),
+            },
+        ))));
+
+        let res = create_review(
+            Path("quest".into()),
+            Extension(Arc::new(MockQuestRepository::new())),
+            Extension(Arc::new(MockReviewRepository::new())),
+            Some(AuthenticatedUser("user".into())),
+            flags,
+            Json(CreateReview::new(5, String::new())),
+        )
+        .await;
+
+        assert_eq!(
+            (StatusCode::NOT_FOUND, ErrorCode::FeatureDisabled),
+            error_of(res).await
+        );
+    }
+}
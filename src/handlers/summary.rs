@@ -0,0 +1,23 @@
+use axum::{extract::Extension, http::StatusCode, response::IntoResponse, Json};
+use chrono::Utc;
+
+use crate::{
+    middleware::auth::AuthenticatedUser, repositories::stats::stats_today,
+    services::summary::build_user_summary, UserInfoHandlerState,
+};
+
+pub async fn get_user_summary(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Extension(state): Extension<UserInfoHandlerState>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let summary = build_user_summary(
+        state.userquest_repository.as_ref(),
+        state.userchallenge_repository.as_ref(),
+        user_id,
+        stats_today(Utc::now()),
+    )
+    .await
+    .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(summary)))
+}
@@ -1,78 +1,105 @@
 use axum::{
     extract::{Extension, Path},
-    http::{header::SET_COOKIE, StatusCode},
+    http::{
+        header::{CACHE_CONTROL, SET_COOKIE},
+        StatusCode,
+    },
     response::{IntoResponse, Response},
-    Json, TypedHeader,
+    Json,
 };
-use chrono::{Duration, Utc};
-use cookie::{time::OffsetDateTime, Cookie, Expiration, SameSite};
+use axum_extra::{headers::Cookie, TypedHeader};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    repositories::user::{LoginUser, RegisterUser, UserRepository},
-    services::user::{create_jwt, decode_jwt},
+    error::{ApiError, ErrorCode},
+    middleware::{auth::AuthenticatedUser, csrf::generate_csrf_token},
+    repositories::{
+        id::UserId,
+        user::{LoginError, LoginUser, RegisterUser},
+    },
+    services::user::{create_jwt, decode_jwt, JwtError},
     UserHandlerState,
 };
 
-pub async fn register_user<T: UserRepository>(
+pub async fn register_user(
+    Extension(state): Extension<UserHandlerState>,
     Json(payload): Json<RegisterUser>,
-    Extension(state): Extension<UserHandlerState<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let secret_key = state.secret_key;
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(ApiError::validation)?;
+    if let Some(captcha) = &state.captcha {
+        let token = payload.captcha_token().ok_or(ApiError::new(
+            StatusCode::FORBIDDEN,
+            ErrorCode::CaptchaFailed,
+        ))?;
+        let passed = captcha.verify(token).await.or(Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::CaptchaUnavailable,
+        )))?;
+        if !passed {
+            return Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                ErrorCode::CaptchaFailed,
+            ));
+        }
+    }
+    let key_ring = state.key_ring;
 
     let user = state
-        .user_repository
+        .user_service
         .register(payload)
         .await
         .or(Err(StatusCode::NOT_FOUND))?;
 
     let now = Utc::now();
-    let iat = now.timestamp();
-    let exp = (now + Duration::hours(8)).timestamp();
-
-    let token = create_jwt(&user.id, iat, &exp, &secret_key);
-    let cookie = Cookie::build("session_token", &token)
-        .path("/")
-        .expires(Expiration::from(
-            OffsetDateTime::from_unix_timestamp(exp).unwrap(),
-        ))
-        .secure(true)
-        .http_only(true)
-        .same_site(SameSite::None)
-        .finish();
+    let expires_at = state.cookies.expires_at(now);
+
+    let token = create_jwt(
+        &user.id,
+        now.timestamp(),
+        &expires_at.timestamp(),
+        &key_ring,
+    );
+    let cookie = state.cookies.session(&token, expires_at);
 
     Ok((
         StatusCode::CREATED,
         [(SET_COOKIE, cookie.to_string())],
-        Json(user.clone()),
+        Json(user),
     ))
 }
 
-pub async fn login_user<T: UserRepository>(
+pub async fn login_user(
+    Extension(state): Extension<UserHandlerState>,
     Json(payload): Json<LoginUser>,
-    Extension(state): Extension<UserHandlerState<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let secret_key = state.secret_key;
+) -> Result<impl IntoResponse, ApiError> {
+    let key_ring = state.key_ring;
 
     let user = state
-        .user_repository
+        .user_service
         .login(payload)
         .await
-        .or(Err(StatusCode::NOT_FOUND))?;
+        .map_err(|e| match e {
+            LoginError::InvalidCredentials => {
+                ApiError::new(StatusCode::NOT_FOUND, ErrorCode::InvalidCredentials)
+            }
+            LoginError::Locked { until } => {
+                ApiError::new(StatusCode::LOCKED, ErrorCode::AccountLocked)
+                    .with_message(format!("locked until {}", until.to_rfc3339()))
+            }
+            LoginError::Repository(_) => StatusCode::INTERNAL_SERVER_ERROR.into(),
+        })?;
 
     let now = Utc::now();
-    let iat = now.timestamp();
-    let exp = (now + Duration::hours(8)).timestamp();
-
-    let token = create_jwt(&user.id, iat, &exp, &secret_key);
-    let cookie = Cookie::build("session_token", &token)
-        .path("/")
-        .expires(Expiration::from(
-            OffsetDateTime::from_unix_timestamp(exp).unwrap(),
-        ))
-        .secure(true)
-        .http_only(true)
-        .same_site(SameSite::None)
-        .finish();
+    let expires_at = state.cookies.expires_at(now);
+
+    let token = create_jwt(
+        &user.id,
+        now.timestamp(),
+        &expires_at.timestamp(),
+        &key_ring,
+    );
+    let cookie = state.cookies.session(&token, expires_at);
 
     Ok((
         StatusCode::CREATED,
@@ -81,43 +108,94 @@ pub async fn login_user<T: UserRepository>(
     ))
 }
 
-pub async fn find_user<T: UserRepository>(
-    Path(id): Path<String>,
-    Extension(state): Extension<UserHandlerState<T>>,
-    Extension(user_id_from_token): Extension<String>,
-) -> Result<impl IntoResponse, StatusCode> {
+pub async fn find_user(
+    Path(id): Path<UserId>,
+    Extension(state): Extension<UserHandlerState>,
+    AuthenticatedUser(user_id_from_token): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
     if id != user_id_from_token {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(StatusCode::FORBIDDEN.into());
     }
 
-    let user = state
-        .user_repository
-        .find(id)
-        .await
-        .or(Err(StatusCode::NOT_FOUND))?;
+    let user = state.user_service.find(id).await.or(Err(ApiError::new(
+        StatusCode::NOT_FOUND,
+        ErrorCode::UserNotFound,
+    )))?;
 
     Ok((StatusCode::CREATED, Json(user)))
 }
 
-pub async fn delete_user<T: UserRepository>(
-    Path(id): Path<String>,
-    Extension(state): Extension<UserHandlerState<T>>,
-    Extension(user_id_from_token): Extension<String>,
-) -> StatusCode {
+pub async fn delete_user(
+    Path(id): Path<UserId>,
+    Extension(state): Extension<UserHandlerState>,
+    AuthenticatedUser(user_id_from_token): AuthenticatedUser,
+) -> Result<StatusCode, ApiError> {
     if id != user_id_from_token {
-        return StatusCode::FORBIDDEN;
+        return Err(StatusCode::FORBIDDEN.into());
     }
 
     state
-        .user_repository
-        .delete(id)
+        .user_service
+        .delete(id, user_id_from_token)
+        .await
+        .or(Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::UserNotFound,
+        )))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 退会する。記録は匿名化して残し、セッションのクッキーも消す
+pub async fn deactivate_user(
+    Extension(state): Extension<UserHandlerState>,
+    AuthenticatedUser(user_id_from_token): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    state
+        .user_service
+        .deactivate(user_id_from_token)
         .await
-        .map(|_| StatusCode::NO_CONTENT)
-        .unwrap_or(StatusCode::NOT_FOUND)
+        .or(Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::UserNotFound,
+        )))?;
+
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(SET_COOKIE, state.cookies.removal().to_string())],
+    ))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CsrfToken {
+    pub csrf_token: String,
+}
+
+/// `GET /csrf_token`。同じ値をクッキーとボディで返す
+/// APIと別ドメインのフロントはクッキーを読めないので、ボディの値をX-CSRF-Tokenに付けて送る
+pub async fn issue_csrf_token(Extension(state): Extension<UserHandlerState>) -> impl IntoResponse {
+    let token = generate_csrf_token();
+    (
+        StatusCode::OK,
+        [
+            (SET_COOKIE, state.cookies.csrf(&token).to_string()),
+            (CACHE_CONTROL, "no-store".to_string()),
+        ],
+        Json(CsrfToken { csrf_token: token }),
+    )
+}
+
+/// セッションのクッキーを消す。期限切れのトークンでもログアウトできるよう認証は求めない
+pub async fn logout_user(Extension(state): Extension<UserHandlerState>) -> impl IntoResponse {
+    (
+        StatusCode::NO_CONTENT,
+        [(SET_COOKIE, state.cookies.removal().to_string())],
+    )
 }
 
 pub enum AuthError {
     NotFoundCookie,
+    InvalidToken(JwtError),
     NotFoundUser,
 }
 
@@ -126,28 +204,31 @@ impl IntoResponse for AuthError {
         match self {
             AuthError::NotFoundCookie => {
                 tracing::error!("Not found cookie");
-                return StatusCode::UNAUTHORIZED.into_response();
+                return ApiError::from(StatusCode::UNAUTHORIZED).into_response();
+            }
+            AuthError::InvalidToken(e) => {
+                tracing::info!("Invalid session token: {:?}", e);
+                return ApiError::from(e).into_response();
             }
             AuthError::NotFoundUser => {
                 tracing::error!("Not found user");
-                return StatusCode::NOT_FOUND.into_response();
+                return ApiError::new(StatusCode::NOT_FOUND, ErrorCode::UserNotFound)
+                    .into_response();
             }
         };
     }
 }
 
-pub async fn auth_user<T: UserRepository>(
-    TypedHeader(cookie): TypedHeader<axum::headers::Cookie>,
-    Extension(state): Extension<UserHandlerState<T>>,
+pub async fn auth_user(
+    TypedHeader(cookie): TypedHeader<Cookie>,
+    Extension(state): Extension<UserHandlerState>,
 ) -> Result<impl IntoResponse, AuthError> {
     if let Some(cookie_token) = cookie.get("session_token") {
-        let secret_key = &state.secret_key;
-
-        let decoded_token = decode_jwt(cookie_token, &secret_key).unwrap();
+        let claims = decode_jwt(cookie_token, &state.key_ring).map_err(AuthError::InvalidToken)?;
 
         let user = state
-            .user_repository
-            .find(decoded_token.claims.user_id)
+            .user_service
+            .find(claims.user_id)
             .await
             .or(Err(AuthError::NotFoundUser))?;
 
@@ -1,41 +1,205 @@
 use axum::{
-    extract::{Extension, Path},
+    extract::{Extension, Multipart, Path},
     http::StatusCode,
     response::IntoResponse,
     Json,
 };
-use std::sync::Arc;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    repositories::{
-        user_challenge::{CompleteChallengePayload, UserChallengeRepository},
-        user_quest::UserQuestRepository,
+    error::{ApiError, ErrorCode},
+    middleware::auth::AuthenticatedUser,
+    repositories::id::ChallengeId,
+    services::{
+        challenge::{
+            ChallengeError, ChallengeService, CompleteChallengeError, SyncCompletedChallenges,
+            SyncOutcome,
+        },
+        photo::Photo,
     },
-    UserInfoHandlerState,
+    PhotoHandlerState, UserInfoHandlerState,
 };
 
-pub async fn complete_challenge<T: UserChallengeRepository>(
-    Path(challenge_id): Path<String>,
-    Json(payload): Json<CompleteChallengePayload>,
-    Extension(repository): Extension<Arc<T>>,
-    Extension(user_id_from_token): Extension<String>,
-) -> Result<impl IntoResponse, StatusCode> {
-    if payload.user_id != user_id_from_token {
-        return Err(StatusCode::FORBIDDEN);
+pub async fn complete_challenge(
+    Path(challenge_id): Path<ChallengeId>,
+    Extension(service): Extension<ChallengeService>,
+    AuthenticatedUser(user_id_from_token): AuthenticatedUser,
+    Extension(photo_state): Extension<PhotoHandlerState>,
+    multipart: Option<Multipart>,
+) -> Result<impl IntoResponse, ApiError> {
+    // 写真はmultipartのphotoフィールドで任意に添付できる
+    let photo = match multipart {
+        Some(multipart) => read_photo(multipart, photo_state.max_bytes).await?,
+        None => None,
+    };
+    // 完了記録に URL を残すため、先にアップロードしておく
+    let uploaded = match photo {
+        Some(photo) => {
+            let key = photo.key(&challenge_id);
+            let url = photo_state
+                .store
+                .put(&key, &photo)
+                .await
+                .or(Err(StatusCode::SERVICE_UNAVAILABLE))?;
+            Some((key, url))
+        }
+        None => None,
+    };
+
+    let result = service
+        .complete(
+            user_id_from_token,
+            challenge_id,
+            uploaded.as_ref().map(|(_, url)| url.clone()),
+            Utc::now(),
+        )
+        .await;
+    if result.is_err() {
+        // 完了できなかったときの写真は参照されないので消しておく
+        if let Some((key, _)) = &uploaded {
+            if let Err(e) = photo_state.store.delete(key).await {
+                tracing::warn!("failed to delete orphan photo {}: {:?}", key, e);
+            }
+        }
+    }
+    result.map_err(|e| match e {
+        CompleteChallengeError::Locked => {
+            ApiError::new(StatusCode::FORBIDDEN, ErrorCode::ChallengeLocked)
+        }
+        CompleteChallengeError::NotPublished => {
+            ApiError::new(StatusCode::FORBIDDEN, ErrorCode::QuestNotPublished)
+        }
+        CompleteChallengeError::OutOfSchedule => {
+            ApiError::new(StatusCode::FORBIDDEN, ErrorCode::QuestOutOfSchedule)
+        }
+        CompleteChallengeError::Unavailable => {
+            ApiError::new(StatusCode::FORBIDDEN, ErrorCode::ChallengeUnavailable)
+        }
+        CompleteChallengeError::Repository(_) => StatusCode::BAD_REQUEST.into(),
+        CompleteChallengeError::Transaction(_) => StatusCode::INTERNAL_SERVER_ERROR.into(),
+    })?;
+
+    Ok(StatusCode::CREATED)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatus {
+    Completed,
+    /// 以前の同期などで完了済み
+    AlreadyCompleted,
+    /// 同じリクエストの別の項目として扱った
+    Duplicate,
+    /// 記録しなかった。理由はerrorに入る
+    Rejected,
+}
+
+/// リクエストと同じ順に返す。rejected以外は端末から消してよい
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncResult {
+    pub challenge_id: ChallengeId,
+    pub status: SyncStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorCode>,
+}
+
+impl SyncResult {
+    fn new(challenge_id: ChallengeId, outcome: SyncOutcome) -> Self {
+        let (status, error) = match outcome {
+            SyncOutcome::Completed => (SyncStatus::Completed, None),
+            SyncOutcome::AlreadyCompleted => (SyncStatus::AlreadyCompleted, None),
+            SyncOutcome::Duplicate => (SyncStatus::Duplicate, None),
+            SyncOutcome::InvalidTime => (SyncStatus::Rejected, Some(ErrorCode::ValidationFailed)),
+            SyncOutcome::TooFar => (SyncStatus::Rejected, Some(ErrorCode::ChallengeTooFar)),
+            SyncOutcome::NotFound => (SyncStatus::Rejected, Some(ErrorCode::ChallengeNotFound)),
+            SyncOutcome::Failed(e) => (
+                SyncStatus::Rejected,
+                Some(match e {
+                    CompleteChallengeError::Locked => ErrorCode::ChallengeLocked,
+                    CompleteChallengeError::NotPublished => ErrorCode::QuestNotPublished,
+                    CompleteChallengeError::OutOfSchedule => ErrorCode::QuestOutOfSchedule,
+                    CompleteChallengeError::Unavailable => ErrorCode::ChallengeUnavailable,
+                    CompleteChallengeError::Repository(_)
+                    | CompleteChallengeError::Transaction(_) => ErrorCode::InternalServerError,
+                }),
+            ),
+        };
+        Self {
+            challenge_id,
+            status,
+            error,
+        }
     }
+}
 
-    repository
-        .save_challenge_complete_event(payload.user_id, challenge_id)
+/// オフラインで完了したチャレンジをまとめて送る。一部が記録できなくても200で項目ごとの結果を返す
+pub async fn sync_completed_challenges(
+    Extension(service): Extension<ChallengeService>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Json(payload): Json<SyncCompletedChallenges>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(ApiError::validation)?;
+    let outcomes = service
+        .sync(user_id, &payload.completions)
         .await
-        .or(Err(StatusCode::BAD_REQUEST))?;
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let results: Vec<SyncResult> = payload
+        .completions
+        .into_iter()
+        .zip(outcomes)
+        .map(|(completion, outcome)| SyncResult::new(completion.challenge_id, outcome))
+        .collect();
 
-    Ok(StatusCode::CREATED)
+    Ok((StatusCode::OK, Json(results)))
+}
+
+/// photo以外のフィールドは読み飛ばす。上限を超えた時点で読むのをやめる
+pub async fn read_photo(
+    mut multipart: Multipart,
+    max_bytes: usize,
+) -> Result<Option<Photo>, ApiError> {
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .or(Err(StatusCode::BAD_REQUEST))?
+    {
+        if field.name() != Some("photo") {
+            continue;
+        }
+        let content_type = field.content_type().unwrap_or_default().to_string();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.chunk().await.or(Err(StatusCode::BAD_REQUEST))? {
+            if bytes.len() + chunk.len() > max_bytes {
+                return Err(StatusCode::PAYLOAD_TOO_LARGE.into());
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        let photo = Photo::new(&content_type, bytes).ok_or(StatusCode::UNSUPPORTED_MEDIA_TYPE)?;
+        return Ok(Some(photo));
+    }
+
+    Ok(None)
+}
+
+pub async fn get_challenge_photos(
+    Path(challenge_id): Path<ChallengeId>,
+    Extension(service): Extension<ChallengeService>,
+) -> Result<impl IntoResponse, ApiError> {
+    let photos = service.photos(challenge_id).await.map_err(|e| match e {
+        ChallengeError::NotFound => {
+            ApiError::new(StatusCode::NOT_FOUND, ErrorCode::ChallengeNotFound)
+        }
+        ChallengeError::Repository(_) => StatusCode::INTERNAL_SERVER_ERROR.into(),
+    })?;
+
+    Ok((StatusCode::OK, Json(photos)))
 }
 
-pub async fn get_completed_challenges<T: UserQuestRepository, S: UserChallengeRepository>(
-    Extension(user_id): Extension<String>,
-    Extension(state): Extension<UserInfoHandlerState<T, S>>,
-) -> Result<impl IntoResponse, StatusCode> {
+pub async fn get_completed_challenges(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Extension(state): Extension<UserInfoHandlerState>,
+) -> Result<impl IntoResponse, ApiError> {
     let quest_ids = state
         .userchallenge_repository
         .get_completed_challenges_by_user_id(user_id)
@@ -1,46 +1,273 @@
 use axum::{
-    extract::{Extension, Path},
+    extract::{Extension, Path, Query},
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Json,
 };
+use serde::Deserialize;
 use std::sync::Arc;
 
 use crate::{
+    error::{ApiError, ErrorCode},
+    middleware::auth::AuthenticatedUser,
     repositories::{
-        user_challenge::UserChallengeRepository,
-        user_quest::{ParticipateQuestPayload, UserQuestRepository},
+        id::QuestId,
+        quest::QuestRepository,
+        user::UserRepository,
+        user_quest::{FindParticipants, LeaveQuestError, ParticipantsCount, UserQuestRepository},
+    },
+    services::{
+        event_bus::{DomainEvent, EventBus},
+        quest::{self, ParticipateQuestError},
     },
     UserInfoHandlerState,
 };
 
-pub async fn participate_quest<T: UserQuestRepository>(
-    Path(quest_id): Path<String>,
-    Json(payload): Json<ParticipateQuestPayload>,
-    Extension(repository): Extension<Arc<T>>,
-    Extension(user_id_from_token): Extension<String>,
-) -> Result<impl IntoResponse, StatusCode> {
-    if payload.user_id != user_id_from_token {
-        return Err(StatusCode::FORBIDDEN);
-    }
+pub async fn participate_quest(
+    Path(quest_id): Path<QuestId>,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(repository): Extension<Arc<dyn UserQuestRepository>>,
+    Extension(event_bus): Extension<EventBus>,
+    AuthenticatedUser(user_id_from_token): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    quest::participate_quest(
+        quest_repository.as_ref(),
+        repository.as_ref(),
+        user_id_from_token.clone(),
+        quest_id.clone(),
+    )
+    .await
+    .map_err(|e| match e {
+        ParticipateQuestError::NotFound => {
+            ApiError::new(StatusCode::NOT_FOUND, ErrorCode::QuestNotFound)
+        }
+        ParticipateQuestError::NotPublished => {
+            ApiError::new(StatusCode::FORBIDDEN, ErrorCode::QuestNotPublished)
+        }
+        ParticipateQuestError::OutOfSchedule => {
+            ApiError::new(StatusCode::FORBIDDEN, ErrorCode::QuestOutOfSchedule)
+        }
+        ParticipateQuestError::AlreadyParticipating => {
+            ApiError::new(StatusCode::CONFLICT, ErrorCode::AlreadyParticipating)
+        }
+        ParticipateQuestError::Repository(_) => StatusCode::BAD_REQUEST.into(),
+    })?;
+
+    event_bus.publish(DomainEvent::QuestParticipated {
+        user_id: user_id_from_token,
+        quest_id,
+    });
+
+    Ok(StatusCode::CREATED)
+}
 
+pub async fn leave_quest(
+    Path(quest_id): Path<QuestId>,
+    Extension(repository): Extension<Arc<dyn UserQuestRepository>>,
+    AuthenticatedUser(user_id_from_token): AuthenticatedUser,
+) -> Result<StatusCode, ApiError> {
     repository
-        .save_quest_participate_event(payload.user_id, quest_id)
+        .delete_quest_participate_event(user_id_from_token, quest_id)
         .await
-        .or(Err(StatusCode::BAD_REQUEST))?;
+        .map_err(|e| match e {
+            LeaveQuestError::NotParticipating => StatusCode::NOT_FOUND,
+            LeaveQuestError::Repository(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        })?;
 
-    Ok(StatusCode::CREATED)
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn count_quest_participants(
+    Path(quest_id): Path<QuestId>,
+    Extension(repository): Extension<Arc<dyn UserQuestRepository>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let count = repository
+        .count_participants(quest_id)
+        .await
+        .or(Err(StatusCode::NOT_FOUND))?;
+
+    Ok((StatusCode::OK, Json(ParticipantsCount { count })))
+}
+
+/// 管理画面向け。参加者の名前・参加日時・進捗を参加した順に返す
+/// 他のユーザーの参加状況が見えるので、クエストの作成者と管理者だけが呼べる
+pub async fn find_quest_participants(
+    Path(quest_id): Path<QuestId>,
+    Query(page): Query<FindParticipants>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Extension(quest_repository): Extension<Arc<dyn QuestRepository>>,
+    Extension(user_repository): Extension<Arc<dyn UserRepository>>,
+    Extension(repository): Extension<Arc<dyn UserQuestRepository>>,
+) -> Result<impl IntoResponse, ApiError> {
+    quest::authorize_quest_owner(
+        quest_repository.as_ref(),
+        user_repository.as_ref(),
+        quest_id.clone(),
+        user_id,
+    )
+    .await?;
+    let participants = repository
+        .find_participants(quest_id, page)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(participants)))
 }
 
-pub async fn get_participated_quests<T: UserQuestRepository, S: UserChallengeRepository>(
-    Extension(user_id): Extension<String>,
-    Extension(state): Extension<UserInfoHandlerState<T, S>>,
-) -> Result<impl IntoResponse, StatusCode> {
+#[derive(Debug, Deserialize)]
+pub struct ParticipatedQuestsQuery {
+    expand: Option<String>,
+}
+
+/// `?expand=quest`のときはクエストの詳細と進捗を返す。指定がなければ従来どおりIDの配列
+pub async fn get_participated_quests(
+    Query(query): Query<ParticipatedQuestsQuery>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Extension(state): Extension<UserInfoHandlerState>,
+) -> Result<Response, ApiError> {
+    match query.expand.as_deref() {
+        None => {}
+        Some("quest") => {
+            let quests = quest::find_participated_quests(
+                state.quest_repository.as_ref(),
+                state.userquest_repository.as_ref(),
+                state.userchallenge_repository.as_ref(),
+                user_id,
+            )
+            .await
+            .or(Err(StatusCode::NOT_FOUND))?;
+            return Ok((StatusCode::OK, Json(quests)).into_response());
+        }
+        Some(expand) => return Err(ApiError::validation(format!("unknown expand: {}", expand))),
+    }
+
     let quest_ids = state
         .userquest_repository
         .get_participated_quests_by_user_id(user_id)
         .await
         .or(Err(StatusCode::NOT_FOUND))?;
 
-    Ok((StatusCode::OK, Json(quest_ids)))
+    Ok((StatusCode::OK, Json(quest_ids)).into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        repositories::{
+            quest::{MockQuestRepository, QuestEntity, QuestStatus},
+            user_quest::MockUserQuestRepository,
+        },
+        test_helpers::error_of,
+    };
+    use chrono::{Duration, Utc};
+
+    fn quest_repository(quest: Option<QuestEntity>) -> MockQuestRepository {
+        let mut repository = MockQuestRepository::new();
+        repository.expect_find().returning(move |_| {
+            quest
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("quest not found"))
+        });
+        repository
+    }
+
+    fn quest(id: &str) -> QuestEntity {
+        QuestEntity::new(
+            id.into(),
+            "title".to_string(),
+            "description".to_string(),
+            QuestStatus::Published,
+        )
+    }
+
+    async fn participate(
+        quest_repository: MockQuestRepository,
+        repository: MockUserQuestRepository,
+    ) -> Result<impl IntoResponse, ApiError> {
+        participate_quest(
+            Path("quest".into()),
+            Extension(Arc::new(quest_repository)),
+            Extension(Arc::new(repository)),
+            Extension(EventBus::new()),
+            AuthenticatedUser("user".into()),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn should_return_404_when_participating_missing_quest() {
+        let res = participate(quest_repository(None), MockUserQuestRepository::new()).await;
+
+        assert_eq!(
+            (StatusCode::NOT_FOUND, ErrorCode::QuestNotFound),
+            error_of(res).await
+        );
+    }
+
+    #[tokio::test]
+    async fn should_return_403_when_quest_is_closed() {
+        let closed = QuestEntity {
+            end_at: Some(Utc::now() - Duration::days(1)),
+            ..quest("quest")
+        };
+
+        let res = participate(
+            quest_repository(Some(closed)),
+            MockUserQuestRepository::new(),
+        )
+        .await;
+
+        assert_eq!(
+            (StatusCode::FORBIDDEN, ErrorCode::QuestOutOfSchedule),
+            error_of(res).await
+        );
+    }
+
+    #[tokio::test]
+    async fn should_return_409_without_saving_when_already_participating() {
+        let mut repository = MockUserQuestRepository::new();
+        repository
+            .expect_get_participated_quests_by_user_id()
+            .returning(|_| Ok(vec!["quest".into()]));
+        repository.expect_save_quest_participate_event().never();
+
+        let res = participate(quest_repository(Some(quest("quest"))), repository).await;
+
+        assert_eq!(
+            (StatusCode::CONFLICT, ErrorCode::AlreadyParticipating),
+            error_of(res).await
+        );
+    }
+
+    #[tokio::test]
+    async fn should_map_leave_errors_to_status() {
+        let cases = [
+            (
+                LeaveQuestError::NotParticipating,
+                StatusCode::NOT_FOUND,
+                ErrorCode::NotFound,
+            ),
+            (
+                LeaveQuestError::Repository(anyhow::anyhow!("connection refused")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorCode::InternalServerError,
+            ),
+        ];
+        for (error, status, code) in cases {
+            let mut repository = MockUserQuestRepository::new();
+            repository
+                .expect_delete_quest_participate_event()
+                .return_once(move |_, _| Err(error));
+
+            let res = leave_quest(
+                Path("quest".into()),
+                Extension(Arc::new(repository)),
+                AuthenticatedUser("user".into()),
+            )
+            .await;
+
+            assert_eq!((status, code), error_of(res).await);
+        }
+    }
 }
@@ -0,0 +1,51 @@
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+
+use crate::{
+    error::ApiError,
+    middleware::auth::AuthenticatedUser,
+    repositories::webhook::{CreateWebhook, CreatedWebhook, WebhookRepository},
+};
+
+pub async fn create_webhook(
+    Extension(repository): Extension<Arc<dyn WebhookRepository>>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    Json(payload): Json<CreateWebhook>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(ApiError::validation)?;
+    let webhook = repository
+        .create(user_id, payload)
+        .await
+        .or(Err(StatusCode::BAD_REQUEST))?;
+
+    Ok((StatusCode::CREATED, Json(CreatedWebhook::from(webhook))))
+}
+
+pub async fn find_webhooks(
+    Extension(repository): Extension<Arc<dyn WebhookRepository>>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let webhooks = repository
+        .find_by_user_id(user_id)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok((StatusCode::OK, Json(webhooks)))
+}
+
+pub async fn delete_webhook(
+    Path(id): Path<String>,
+    Extension(repository): Extension<Arc<dyn WebhookRepository>>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> StatusCode {
+    repository
+        .delete(user_id, id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .unwrap_or(StatusCode::NOT_FOUND)
+}
@@ -1 +1,10 @@
+pub mod cloudfront;
 pub mod dynamodb;
+pub mod fcm;
+pub mod http;
+#[cfg(feature = "lambda")]
+pub mod lambda;
+pub mod redis;
+pub mod s3;
+pub mod ses;
+pub mod telemetry;
@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use rsa::{
+    pkcs1::DecodeRsaPrivateKey,
+    pkcs1v15::SigningKey,
+    pkcs8::DecodePrivateKey,
+    signature::{SignatureEncoding, Signer},
+    RsaPrivateKey,
+};
+use sha1::Sha1;
+
+use crate::{config::CloudFrontConfig, services::image_url::ImageUrlSigner};
+
+/// 期限を分単位に丸め、同じ分のうちは同じURLを返してブラウザのキャッシュを効かせる
+const EXPIRES_GRANULARITY_SECS: i64 = 60;
+
+/// CloudFrontの署名付きURL(既定ポリシー)を作る
+/// https://docs.aws.amazon.com/AmazonCloudFront/latest/DeveloperGuide/private-content-creating-signed-url-canned-policy.html
+pub struct CloudFrontSigner {
+    base_url: String,
+    key_pair_id: String,
+    key: SigningKey<Sha1>,
+    url_ttl: Duration,
+}
+
+impl CloudFrontSigner {
+    /// 鍵はCloudFrontのコンソールで作るPKCS#1と、opensslで変換したPKCS#8のどちらでもよい
+    pub fn new(config: CloudFrontConfig) -> anyhow::Result<Self> {
+        let private_key = RsaPrivateKey::from_pkcs1_pem(&config.private_key)
+            .or_else(|_| RsaPrivateKey::from_pkcs8_pem(&config.private_key))?;
+        Ok(Self {
+            base_url: format!("{}/", config.base_url),
+            key_pair_id: config.key_pair_id,
+            key: SigningKey::new(private_key),
+            url_ttl: config.url_ttl,
+        })
+    }
+
+    fn expires(&self, now: DateTime<Utc>) -> i64 {
+        let now = now.timestamp() / EXPIRES_GRANULARITY_SECS * EXPIRES_GRANULARITY_SECS;
+        now + self.url_ttl.as_secs() as i64
+    }
+}
+
+impl ImageUrlSigner for CloudFrontSigner {
+    fn sign(&self, url: &str, now: DateTime<Utc>) -> Option<String> {
+        if !url.starts_with(&self.base_url) {
+            return None;
+        }
+        let expires = self.expires(now);
+        let signature = self.key.sign(canned_policy(url, expires).as_bytes());
+        let separator = if url.contains('?') { '&' } else { '?' };
+        Some(format!(
+            "{}{}Expires={}&Signature={}&Key-Pair-Id={}",
+            url,
+            separator,
+            expires,
+            url_safe_base64(&signature.to_bytes()),
+            self.key_pair_id
+        ))
+    }
+}
+
+/// 空白を入れるとCloudFront側の検証と一致しないので詰めて書く
+fn canned_policy(url: &str, expires: i64) -> String {
+    format!(
+        r#"{{"Statement":[{{"Resource":"{}","Condition":{{"DateLessThan":{{"AWS:EpochTime":{}}}}}}}]}}"#,
+        url, expires
+    )
+}
+
+/// CloudFront独自のURLセーフなbase64
+fn url_safe_base64(bytes: &[u8]) -> String {
+    STANDARD
+        .encode(bytes)
+        .replace('+', "-")
+        .replace('=', "_")
+        .replace('/', "~")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use rsa::{
+        pkcs1::EncodeRsaPrivateKey,
+        pkcs1v15::{Signature, VerifyingKey},
+        pkcs8::LineEnding,
+        rand_core::OsRng,
+        signature::Verifier,
+        RsaPublicKey,
+    };
+
+    fn signer() -> (CloudFrontSigner, RsaPublicKey) {
+        let private_key = RsaPrivateKey::new(&mut OsRng, 1024).unwrap();
+        let public_key = RsaPublicKey::from(&private_key);
+        let signer = CloudFrontSigner::new(CloudFrontConfig {
+            base_url: "https://images.example.com".to_string(),
+            key_pair_id: "K2JCJMDEHXQW5F".to_string(),
+            private_key: private_key
+                .to_pkcs1_pem(LineEnding::LF)
+                .unwrap()
+                .to_string(),
+            url_ttl: Duration::from_secs(3600),
+        })
+        .unwrap();
+        (signer, public_key)
+    }
+
+    #[test]
+    fn should_sign_canned_policy() {
+        let (signer, public_key) = signer();
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 30).unwrap();
+        let url = "https://images.example.com/quest_covers/a/full.jpg";
+
+        let signed = signer.sign(url, now).unwrap();
+
+        let expires = now.timestamp() - 30 + 3600;
+        let prefix = format!("{}?Expires={}&Signature=", url, expires);
+        assert!(signed.starts_with(&prefix));
+        assert!(signed.ends_with("&Key-Pair-Id=K2JCJMDEHXQW5F"));
+        let signature = signed[prefix.len()..]
+            .split('&')
+            .next()
+            .unwrap()
+            .replace('-', "+")
+            .replace('_', "=")
+            .replace('~', "/");
+        let signature = STANDARD.decode(signature).unwrap();
+        VerifyingKey::<Sha1>::new(public_key)
+            .verify(
+                canned_policy(url, expires).as_bytes(),
+                &Signature::try_from(signature.as_slice()).unwrap(),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn should_not_sign_other_origins() {
+        let (signer, _) = signer();
+
+        assert_eq!(
+            None,
+            signer.sign("https://images.example.com.evil/a.jpg", Utc::now())
+        );
+        assert_eq!(None, signer.sign("http://localhost:4566/a.jpg", Utc::now()));
+    }
+
+    #[test]
+    fn should_keep_same_url_within_a_minute() {
+        let (signer, _) = signer();
+        let url = "https://images.example.com/a.jpg";
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        assert_eq!(
+            signer.sign(url, now),
+            signer.sign(url, now + chrono::Duration::seconds(59))
+        );
+    }
+}
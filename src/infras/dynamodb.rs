@@ -1,5 +1,11 @@
-use aws_sdk_dynamodb::{types::AttributeValue, Client};
-use std::collections::HashMap;
+use aws_sdk_dynamodb::{
+    types::{
+        AttributeValue, DeleteRequest, Put, PutRequest, Select, TransactWriteItem, WriteRequest,
+    },
+    Client,
+};
+use chrono::{DateTime, Utc};
+use std::{collections::HashMap, time::Duration};
 use tokio_stream::StreamExt as _;
 
 pub struct DynamoDB {
@@ -28,6 +34,7 @@ pub struct UserItem {
 impl DynamoDB {
     pub const USER_TABLE_NAME: &'static str = "users";
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn put_user(&self, user: UserItem) -> anyhow::Result<()> {
         self.client
             .put_item()
@@ -50,6 +57,7 @@ impl DynamoDB {
         }
     }
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn get_user_by_id(&self, id: String) -> anyhow::Result<Option<UserItem>> {
         let result = self
             .client
@@ -64,6 +72,7 @@ impl DynamoDB {
         Ok(Some(Self::map_item_to_user_item(item)))
     }
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn get_user_by_email(&self, email: String) -> anyhow::Result<Option<UserItem>> {
         // NOTE: グローバルセカンダリインデックスからクエリするときにはGetItemは使えない。
         // Queryを使う必要がある
@@ -85,6 +94,7 @@ impl DynamoDB {
         Ok(Some(Self::map_item_to_user_item(items.first().unwrap())))
     }
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn update_user(&self, user: UserItem) -> anyhow::Result<()> {
         self.client
             .update_item()
@@ -99,6 +109,7 @@ impl DynamoDB {
         Ok(())
     }
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn delete_user(&self, id: String) -> anyhow::Result<()> {
         self.client
             .delete_item()
@@ -117,7 +128,11 @@ impl DynamoDB {
  */
 impl DynamoDB {
     pub const USER_PARTICIPATING_QUESTS_TABLE_NAME: &'static str = "user_participating_quests";
+    /// クエストから参加者を逆引きするGSI
+    pub const QUEST_USER_INDEX_NAME: &'static str = "QuestUserIndex";
 
+    /// Postgresの主キー制約と同じく、すでに参加していればエラーにする
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn put_user_participate_quest(
         &self,
         user_id: String,
@@ -128,33 +143,74 @@ impl DynamoDB {
             .table_name(Self::USER_PARTICIPATING_QUESTS_TABLE_NAME)
             .item("UserId", AttributeValue::S(user_id))
             .item("QuestId", AttributeValue::S(quest_id))
+            .condition_expression("attribute_not_exists(QuestId)")
             .send()
             .await?;
         Ok(())
     }
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn query_user_participate_quest_ids(
         &self,
         user_id: String,
     ) -> anyhow::Result<Vec<String>> {
-        let result = self
+        Ok(self
             .client
             .query()
             .table_name(Self::USER_PARTICIPATING_QUESTS_TABLE_NAME)
             .key_condition_expression("UserId = :user_id")
             .expression_attribute_values(":user_id", AttributeValue::S(user_id))
+            .into_paginator()
+            .items()
             .send()
-            .await?;
-        let Some(items) = result.items() else {
-            return Ok(Vec::new());
-        };
-        let quest_ids = items
-            .iter()
-            .map(|item| item.get("QuestId").unwrap().as_s().unwrap().clone())
-            .collect::<Vec<String>>();
-        Ok(quest_ids)
+            .map(|res| res.map(|item| item["QuestId"].as_s().unwrap().clone()))
+            .collect::<Result<Vec<String>, _>>()
+            .await?)
+    }
+
+    /// このクエストの参加者一覧
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
+    pub async fn query_quest_participant_user_ids(
+        &self,
+        quest_id: String,
+    ) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .client
+            .query()
+            .table_name(Self::USER_PARTICIPATING_QUESTS_TABLE_NAME)
+            .index_name(Self::QUEST_USER_INDEX_NAME)
+            .key_condition_expression("QuestId = :quest_id")
+            .expression_attribute_values(":quest_id", AttributeValue::S(quest_id))
+            .into_paginator()
+            .items()
+            .send()
+            .map(|res| res.map(|item| item["UserId"].as_s().unwrap().clone()))
+            .collect::<Result<Vec<String>, _>>()
+            .await?)
     }
 
+    /// 項目は読まずに件数だけを数える
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
+    pub async fn count_quest_participants(&self, quest_id: String) -> anyhow::Result<i64> {
+        Ok(self
+            .client
+            .query()
+            .table_name(Self::USER_PARTICIPATING_QUESTS_TABLE_NAME)
+            .index_name(Self::QUEST_USER_INDEX_NAME)
+            .key_condition_expression("QuestId = :quest_id")
+            .expression_attribute_values(":quest_id", AttributeValue::S(quest_id))
+            .select(Select::Count)
+            .into_paginator()
+            .send()
+            .map(|res| res.map(|page| page.count() as i64))
+            .collect::<Result<Vec<i64>, _>>()
+            .await?
+            .into_iter()
+            .sum())
+    }
+
+    /// 参加を取り消す。参加していなくても成功にする
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn delete_participating_quest_ids(
         &self,
         user_id: String,
@@ -218,23 +274,36 @@ impl ToString for Difficulty {
 impl DynamoDB {
     pub const QUEST_TABLE_NAME: &'static str = "quests";
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn put_quest(&self, quest: QuestItem) -> anyhow::Result<()> {
         self.client
             .put_item()
             .table_name(Self::QUEST_TABLE_NAME)
-            .item("QuestId", AttributeValue::S(quest.id))
-            .item("QuestTitle", AttributeValue::S(quest.title))
-            .item("QuestDescription", AttributeValue::S(quest.description))
-            .item("QuestPrice", AttributeValue::N(quest.price.to_string()))
-            .item(
-                "QuestDifficulty",
-                AttributeValue::S(quest.difficulty.to_string()),
-            )
+            .set_item(Some(Self::map_quest_item_to_item(quest)))
             .send()
             .await?;
         Ok(())
     }
 
+    fn map_quest_item_to_item(quest: QuestItem) -> HashMap<String, AttributeValue> {
+        HashMap::from([
+            ("QuestId".to_string(), AttributeValue::S(quest.id)),
+            ("QuestTitle".to_string(), AttributeValue::S(quest.title)),
+            (
+                "QuestDescription".to_string(),
+                AttributeValue::S(quest.description),
+            ),
+            (
+                "QuestPrice".to_string(),
+                AttributeValue::N(quest.price.to_string()),
+            ),
+            (
+                "QuestDifficulty".to_string(),
+                AttributeValue::S(quest.difficulty.to_string()),
+            ),
+        ])
+    }
+
     fn map_item_to_quest_item(item: &HashMap<String, AttributeValue>) -> QuestItem {
         QuestItem {
             id: item["QuestId"].as_s().unwrap().clone(),
@@ -249,6 +318,7 @@ impl DynamoDB {
         }
     }
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn get_quest_by_id(&self, id: String) -> anyhow::Result<Option<QuestItem>> {
         let result = self
             .client
@@ -263,6 +333,7 @@ impl DynamoDB {
         Ok(Some(Self::map_item_to_quest_item(item)))
     }
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn get_quests(&self) -> anyhow::Result<Vec<QuestItem>> {
         Ok(self
             .client
@@ -276,6 +347,7 @@ impl DynamoDB {
             .await?)
     }
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn update_quest(&self, item: QuestItem) -> anyhow::Result<()> {
         self.client
             .update_item()
@@ -298,6 +370,7 @@ impl DynamoDB {
         Ok(())
     }
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn delete_quest(&self, id: String) -> anyhow::Result<()> {
         self.client
             .delete_item()
@@ -331,34 +404,56 @@ pub struct ChallengeItem {
 impl DynamoDB {
     pub const CHALLENGE_TABLE_NAME: &'static str = "challenges";
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn put_challenge(&self, challenge: ChallengeItem) -> anyhow::Result<()> {
         self.client
             .put_item()
             .table_name(Self::CHALLENGE_TABLE_NAME)
-            .item("ChallengeId", AttributeValue::S(challenge.id))
-            .item("QuestId", AttributeValue::S(challenge.quest_id))
-            .item("ChallengeTitle", AttributeValue::S(challenge.title))
-            .item(
-                "ChallengeDescription",
-                AttributeValue::S(challenge.description),
-            )
-            .item("ChallengeLat", AttributeValue::N(challenge.lat.to_string()))
-            .item("ChallengeLon", AttributeValue::N(challenge.lon.to_string()))
-            .item("StampName", AttributeValue::S(challenge.stamp_name))
-            .item(
-                "StampColorImageUrl",
-                AttributeValue::S(challenge.stamp_color_image_url),
-            )
-            .item(
-                "StampGrayImageUrl",
-                AttributeValue::S(challenge.stamp_gray_image_url),
-            )
-            .item("FlavorText", AttributeValue::S(challenge.flavor_text))
+            .set_item(Some(Self::map_challenge_item_to_item(challenge)))
             .send()
             .await?;
         Ok(())
     }
 
+    fn map_challenge_item_to_item(challenge: ChallengeItem) -> HashMap<String, AttributeValue> {
+        HashMap::from([
+            ("ChallengeId".to_string(), AttributeValue::S(challenge.id)),
+            ("QuestId".to_string(), AttributeValue::S(challenge.quest_id)),
+            (
+                "ChallengeTitle".to_string(),
+                AttributeValue::S(challenge.title),
+            ),
+            (
+                "ChallengeDescription".to_string(),
+                AttributeValue::S(challenge.description),
+            ),
+            (
+                "ChallengeLat".to_string(),
+                AttributeValue::N(challenge.lat.to_string()),
+            ),
+            (
+                "ChallengeLon".to_string(),
+                AttributeValue::N(challenge.lon.to_string()),
+            ),
+            (
+                "StampName".to_string(),
+                AttributeValue::S(challenge.stamp_name),
+            ),
+            (
+                "StampColorImageUrl".to_string(),
+                AttributeValue::S(challenge.stamp_color_image_url),
+            ),
+            (
+                "StampGrayImageUrl".to_string(),
+                AttributeValue::S(challenge.stamp_gray_image_url),
+            ),
+            (
+                "FlavorText".to_string(),
+                AttributeValue::S(challenge.flavor_text),
+            ),
+        ])
+    }
+
     fn map_item_to_challenge_item(item: &HashMap<String, AttributeValue>) -> ChallengeItem {
         ChallengeItem {
             id: item["ChallengeId"].as_s().unwrap().clone(),
@@ -374,6 +469,7 @@ impl DynamoDB {
         }
     }
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn get_challenge_by_id_and_quest_id(
         &self,
         id: String,
@@ -393,6 +489,7 @@ impl DynamoDB {
         Ok(Some(Self::map_item_to_challenge_item(item)))
     }
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn get_challenges_by_quest_id(
         &self,
         quest_id: String,
@@ -415,6 +512,7 @@ impl DynamoDB {
         Ok(challenges)
     }
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn update_challenge(&self, item: ChallengeItem) -> anyhow::Result<()> {
         self.client
             .update_item()
@@ -450,6 +548,7 @@ impl DynamoDB {
         Ok(())
     }
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn delete_challenge(&self, id: String, quest_id: String) -> anyhow::Result<()> {
         self.client
             .delete_item()
@@ -467,46 +566,113 @@ impl DynamoDB {
  * "user_completed_challenges" Table
  * ==============
  */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserCompletedChallengeItem {
+    pub user_id: String,
+    pub challenge_id: String,
+    pub completed_at: DateTime<Utc>,
+    pub photo_url: Option<String>,
+}
+
 impl DynamoDB {
     pub const USER_COMPLETED_CHALLENGES_TABLE_NAME: &'static str = "user_completed_challenges";
+    /// チャレンジから完了したユーザーを逆引きするGSI
+    pub const CHALLENGE_USER_INDEX_NAME: &'static str = "ChallengeUserIndex";
 
+    /// Postgresの主キー制約と同じく、すでに完了していればエラーにする
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn put_user_complete_challenge(
         &self,
-        user_id: String,
-        challenge_id: String,
+        item: UserCompletedChallengeItem,
     ) -> anyhow::Result<()> {
-        self.client
+        let mut request = self
+            .client
             .put_item()
             .table_name(Self::USER_COMPLETED_CHALLENGES_TABLE_NAME)
-            .item("UserId", AttributeValue::S(user_id))
-            .item("ChallengeId", AttributeValue::S(challenge_id))
-            .send()
-            .await?;
+            .item("UserId", AttributeValue::S(item.user_id))
+            .item("ChallengeId", AttributeValue::S(item.challenge_id))
+            .item(
+                "CompletedAt",
+                AttributeValue::S(item.completed_at.to_rfc3339()),
+            )
+            .condition_expression("attribute_not_exists(ChallengeId)");
+        if let Some(photo_url) = item.photo_url {
+            request = request.item("PhotoUrl", AttributeValue::S(photo_url));
+        }
+        request.send().await?;
         Ok(())
     }
 
-    pub async fn query_user_complete_challenge_ids(
+    fn map_item_to_user_completed_challenge_item(
+        item: &HashMap<String, AttributeValue>,
+    ) -> UserCompletedChallengeItem {
+        UserCompletedChallengeItem {
+            user_id: item["UserId"].as_s().unwrap().clone(),
+            challenge_id: item["ChallengeId"].as_s().unwrap().clone(),
+            completed_at: DateTime::parse_from_rfc3339(item["CompletedAt"].as_s().unwrap())
+                .unwrap()
+                .with_timezone(&Utc),
+            photo_url: item
+                .get("PhotoUrl")
+                .map(|photo_url| photo_url.as_s().unwrap().clone()),
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
+    pub async fn query_user_completed_challenges(
         &self,
         user_id: String,
-    ) -> anyhow::Result<Vec<String>> {
-        let result = self
+    ) -> anyhow::Result<Vec<UserCompletedChallengeItem>> {
+        Ok(self
             .client
             .query()
             .table_name(Self::USER_COMPLETED_CHALLENGES_TABLE_NAME)
             .key_condition_expression("UserId = :user_id")
             .expression_attribute_values(":user_id", AttributeValue::S(user_id))
+            .into_paginator()
+            .items()
             .send()
-            .await?;
-        let Some(items) = result.items() else {
-            return Ok(Vec::new());
-        };
-        let challenge_ids = items
-            .iter()
-            .map(|item| item.get("ChallengeId").unwrap().as_s().unwrap().clone())
+            .map(|res| res.map(|item| Self::map_item_to_user_completed_challenge_item(&item)))
+            .collect::<Result<Vec<UserCompletedChallengeItem>, _>>()
+            .await?)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
+    pub async fn query_user_complete_challenge_ids(
+        &self,
+        user_id: String,
+    ) -> anyhow::Result<Vec<String>> {
+        let challenge_ids = self
+            .query_user_completed_challenges(user_id)
+            .await?
+            .into_iter()
+            .map(|item| item.challenge_id)
             .collect::<Vec<String>>();
         Ok(challenge_ids)
     }
 
+    /// このチャレンジを完了したユーザーの記録
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
+    pub async fn query_challenge_completed_users(
+        &self,
+        challenge_id: String,
+    ) -> anyhow::Result<Vec<UserCompletedChallengeItem>> {
+        Ok(self
+            .client
+            .query()
+            .table_name(Self::USER_COMPLETED_CHALLENGES_TABLE_NAME)
+            .index_name(Self::CHALLENGE_USER_INDEX_NAME)
+            .key_condition_expression("ChallengeId = :challenge_id")
+            .expression_attribute_values(":challenge_id", AttributeValue::S(challenge_id))
+            .into_paginator()
+            .items()
+            .send()
+            .map(|res| res.map(|item| Self::map_item_to_user_completed_challenge_item(&item)))
+            .collect::<Result<Vec<UserCompletedChallengeItem>, _>>()
+            .await?)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
     pub async fn delete_completed_challenge_ids(
         &self,
         user_id: String,
@@ -523,6 +689,187 @@ impl DynamoDB {
     }
 }
 
+/*
+ * ==============
+ * Batch / Transaction
+ * ==============
+ */
+/// BatchWriteItemで1回に送れる件数
+const BATCH_WRITE_LIMIT: usize = 25;
+/// TransactWriteItemsで1回に送れる件数。分けると原子性がなくなるので、超えたらエラーにする
+const TRANSACT_WRITE_LIMIT: usize = 100;
+/// 未処理(UnprocessedItems)で返された項目を送り直す回数
+const BATCH_WRITE_MAX_ATTEMPTS: u32 = 5;
+
+/// BatchWriteItemの1件。テーブルをまたいでまとめて送れる
+#[derive(Debug, Clone)]
+pub struct BatchWriteRequest {
+    pub table_name: &'static str,
+    pub request: WriteRequest,
+}
+
+impl BatchWriteRequest {
+    pub fn put(table_name: &'static str, item: HashMap<String, AttributeValue>) -> Self {
+        Self {
+            table_name,
+            request: WriteRequest::builder()
+                .put_request(PutRequest::builder().set_item(Some(item)).build())
+                .build(),
+        }
+    }
+
+    pub fn delete(table_name: &'static str, key: HashMap<String, AttributeValue>) -> Self {
+        Self {
+            table_name,
+            request: WriteRequest::builder()
+                .delete_request(DeleteRequest::builder().set_key(Some(key)).build())
+                .build(),
+        }
+    }
+}
+
+impl DynamoDB {
+    /// 25件ずつに分けて書き込む。原子性はないので、途中で失敗したら残りは書き込まれない
+    /// 同じ項目を2回書き込むと結果が変わらない操作(上書きや削除)だけに使うこと
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
+    pub async fn batch_write(&self, requests: Vec<BatchWriteRequest>) -> anyhow::Result<()> {
+        for chunk in chunk_write_requests(requests) {
+            self.batch_write_chunk(chunk).await?;
+        }
+        Ok(())
+    }
+
+    /// スロットリング等で処理されなかった項目は間隔を空けて送り直す
+    async fn batch_write_chunk(
+        &self,
+        mut request_items: HashMap<String, Vec<WriteRequest>>,
+    ) -> anyhow::Result<()> {
+        for attempt in 0..BATCH_WRITE_MAX_ATTEMPTS {
+            let output = self
+                .client
+                .batch_write_item()
+                .set_request_items(Some(request_items))
+                .send()
+                .await?;
+            request_items = match output.unprocessed_items() {
+                Some(unprocessed) if !unprocessed.is_empty() => unprocessed.clone(),
+                _ => return Ok(()),
+            };
+            if attempt + 1 < BATCH_WRITE_MAX_ATTEMPTS {
+                tokio::time::sleep(batch_write_retry_delay(attempt)).await;
+            }
+        }
+
+        let remaining: usize = request_items.values().map(Vec::len).sum();
+        anyhow::bail!(
+            "{} items were not processed after {} attempts",
+            remaining,
+            BATCH_WRITE_MAX_ATTEMPTS
+        )
+    }
+
+    /// すべて成功するか、すべて書き込まれないかのどちらかになる
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "dynamodb"))]
+    pub async fn transact_write(&self, items: Vec<TransactWriteItem>) -> anyhow::Result<()> {
+        if items.len() > TRANSACT_WRITE_LIMIT {
+            anyhow::bail!(
+                "a transaction can contain at most {} items: {}",
+                TRANSACT_WRITE_LIMIT,
+                items.len()
+            );
+        }
+        self.client
+            .transact_write_items()
+            .set_transact_items(Some(items))
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// クエストとチャレンジをまとめて作る。同じIDのクエストがあれば何も書き込まない
+    pub async fn put_quest_with_challenges(
+        &self,
+        quest: QuestItem,
+        challenges: Vec<ChallengeItem>,
+    ) -> anyhow::Result<()> {
+        let quest_put = Put::builder()
+            .table_name(Self::QUEST_TABLE_NAME)
+            .set_item(Some(Self::map_quest_item_to_item(quest)))
+            .condition_expression("attribute_not_exists(QuestId)")
+            .build();
+        let items = std::iter::once(quest_put)
+            .chain(challenges.into_iter().map(|challenge| {
+                Put::builder()
+                    .table_name(Self::CHALLENGE_TABLE_NAME)
+                    .set_item(Some(Self::map_challenge_item_to_item(challenge)))
+                    .build()
+            }))
+            .map(|put| TransactWriteItem::builder().put(put).build())
+            .collect();
+
+        self.transact_write(items).await
+    }
+
+    /// 参加・完了の記録を消してからユーザーを消す
+    /// 途中で失敗してもユーザーは残るので、もう一度呼べば最後まで消せる
+    pub async fn delete_user_cascade(&self, user_id: String) -> anyhow::Result<()> {
+        let quest_ids = self
+            .query_user_participate_quest_ids(user_id.clone())
+            .await?;
+        let challenge_ids = self
+            .query_user_complete_challenge_ids(user_id.clone())
+            .await?;
+        let key = |id_name: &str, id: String| {
+            HashMap::from([
+                ("UserId".to_string(), AttributeValue::S(user_id.clone())),
+                (id_name.to_string(), AttributeValue::S(id)),
+            ])
+        };
+        let requests = quest_ids
+            .into_iter()
+            .map(|quest_id| {
+                BatchWriteRequest::delete(
+                    Self::USER_PARTICIPATING_QUESTS_TABLE_NAME,
+                    key("QuestId", quest_id),
+                )
+            })
+            .chain(challenge_ids.into_iter().map(|challenge_id| {
+                BatchWriteRequest::delete(
+                    Self::USER_COMPLETED_CHALLENGES_TABLE_NAME,
+                    key("ChallengeId", challenge_id),
+                )
+            }))
+            .collect();
+        self.batch_write(requests).await?;
+
+        self.delete_user(user_id).await
+    }
+}
+
+/// 25件ずつに分け、BatchWriteItemのテーブルごとの形にする
+fn chunk_write_requests(
+    requests: Vec<BatchWriteRequest>,
+) -> Vec<HashMap<String, Vec<WriteRequest>>> {
+    requests
+        .chunks(BATCH_WRITE_LIMIT)
+        .map(|chunk| {
+            let mut request_items: HashMap<String, Vec<WriteRequest>> = HashMap::new();
+            for request in chunk {
+                request_items
+                    .entry(request.table_name.to_string())
+                    .or_default()
+                    .push(request.request.clone());
+            }
+            request_items
+        })
+        .collect()
+}
+
+/// 50ミリ秒, 100ミリ秒, 200ミリ秒...と間隔を空けて再送する
+fn batch_write_retry_delay(attempt: u32) -> Duration {
+    Duration::from_millis(50 * 2u64.pow(attempt))
+}
+
 /// 実行前にdocker composeでdynamodb-localを起動しておく必要がある
 #[cfg(all(test, feature = "db-tests"))]
 mod tests {
@@ -724,41 +1071,231 @@ mod tests {
     async fn test_participate_quest() {
         let db = create_client().await;
 
-        let quest_id = "test-quest".to_string();
-        let user_id = "test-user".to_string();
+        let quest_id = nanoid::nanoid!();
+        let user_ids = ["test-user-1".to_string(), "test-user-2".to_string()];
 
-        db.put_user_participate_quest(user_id.clone(), quest_id.clone())
-            .await;
+        for user_id in user_ids.iter() {
+            db.put_user_participate_quest(user_id.clone(), quest_id.clone())
+                .await
+                .unwrap();
+        }
+        // 同じクエストには2回参加できない
+        assert!(db
+            .put_user_participate_quest(user_ids[0].clone(), quest_id.clone())
+            .await
+            .is_err());
 
         let queried_quest_ids = db
-            .query_user_participate_quest_ids(user_id.clone())
+            .query_user_participate_quest_ids(user_ids[0].clone())
             .await
             .unwrap();
-        println!("{:?}", queried_quest_ids.clone());
-        assert_eq!(queried_quest_ids.len(), 1);
-        assert_eq!(queried_quest_ids[0], quest_id.clone());
+        assert!(queried_quest_ids.contains(&quest_id));
 
-        db.delete_participating_quest_ids(user_id, quest_id).await;
+        let participant_ids = db
+            .query_quest_participant_user_ids(quest_id.clone())
+            .await
+            .unwrap();
+        assert_eq!(participant_ids, user_ids.to_vec());
+        assert_eq!(
+            db.count_quest_participants(quest_id.clone()).await.unwrap(),
+            2
+        );
+
+        // 参加を取り消すと逆引きからも消える
+        db.delete_participating_quest_ids(user_ids[0].clone(), quest_id.clone())
+            .await
+            .unwrap();
+        let participant_ids = db
+            .query_quest_participant_user_ids(quest_id.clone())
+            .await
+            .unwrap();
+        assert_eq!(participant_ids, vec![user_ids[1].clone()]);
+
+        // 後片付け
+        db.delete_participating_quest_ids(user_ids[1].clone(), quest_id)
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
     async fn test_complete_challenge() {
         let db = create_client().await;
 
-        let challenge_id = "test-challenge".to_string();
-        let user_id = "test-user".to_string();
+        let user_id = nanoid::nanoid!();
+        let completed_at = DateTime::parse_from_rfc3339("2024-03-01T10:00:00+09:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let completion = UserCompletedChallengeItem {
+            user_id: user_id.clone(),
+            challenge_id: "test-challenge".to_string(),
+            completed_at,
+            photo_url: Some("https://example.com/photo.jpg".to_string()),
+        };
+        db.put_user_complete_challenge(completion.clone())
+            .await
+            .unwrap();
+        // 同じチャレンジは2回完了できない
+        assert!(db
+            .put_user_complete_challenge(completion.clone())
+            .await
+            .is_err());
 
-        db.put_user_complete_challenge(user_id.clone(), challenge_id.clone())
-            .await;
+        let queried_challenges = db
+            .query_user_completed_challenges(user_id.clone())
+            .await
+            .unwrap();
+        assert_eq!(queried_challenges, vec![completion.clone()]);
 
         let queried_challenge_ids = db
             .query_user_complete_challenge_ids(user_id.clone())
             .await
             .unwrap();
-        assert_eq!(queried_challenge_ids.len(), 1);
-        assert_eq!(queried_challenge_ids[0], challenge_id.clone());
+        assert_eq!(queried_challenge_ids, vec![completion.challenge_id.clone()]);
+
+        let completed_users = db
+            .query_challenge_completed_users(completion.challenge_id.clone())
+            .await
+            .unwrap();
+        assert!(completed_users.contains(&completion));
+
+        db.delete_completed_challenge_ids(user_id.clone(), completion.challenge_id.clone())
+            .await
+            .unwrap();
+        let queried_challenge_ids = db.query_user_complete_challenge_ids(user_id).await.unwrap();
+        assert!(queried_challenge_ids.is_empty());
+    }
+
+    #[test]
+    fn should_split_write_requests_by_batch_limit() {
+        let requests = (0..30)
+            .map(|i| {
+                let table_name = if i % 2 == 0 {
+                    DynamoDB::USER_PARTICIPATING_QUESTS_TABLE_NAME
+                } else {
+                    DynamoDB::USER_COMPLETED_CHALLENGES_TABLE_NAME
+                };
+                BatchWriteRequest::delete(
+                    table_name,
+                    HashMap::from([("UserId".to_string(), AttributeValue::S(i.to_string()))]),
+                )
+            })
+            .collect();
+
+        let chunks = chunk_write_requests(requests);
+
+        assert_eq!(
+            vec![25, 5],
+            chunks
+                .iter()
+                .map(|chunk| chunk.values().map(Vec::len).sum::<usize>())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(2, chunks[0].len());
+    }
+
+    #[tokio::test]
+    async fn test_put_quest_with_challenges() {
+        let db = create_client().await;
 
-        db.delete_completed_challenge_ids(user_id, challenge_id)
-            .await;
+        let quest = QuestItem {
+            id: nanoid::nanoid!(),
+            title: "Test Quest".to_string(),
+            description: "This is a test quest".to_string(),
+            price: 100,
+            difficulty: Difficulty::Easy,
+        };
+        let challenges = (1..=3)
+            .map(|i| ChallengeItem {
+                id: format!("test-challenge-{}", i),
+                quest_id: quest.id.clone(),
+                title: format!("Test Challenge {}", i),
+                description: "This is a test challenge".to_string(),
+                lat: 35.681236,
+                lon: 139.767125,
+                stamp_name: "Test Stamp".to_string(),
+                stamp_color_image_url: "test-stamp-color".to_string(),
+                stamp_gray_image_url: "test-stamp-gray".to_string(),
+                flavor_text: "This is a test stamp".to_string(),
+            })
+            .collect::<Vec<_>>();
+        db.put_quest_with_challenges(quest.clone(), challenges.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            db.get_quest_by_id(quest.id.clone()).await.unwrap(),
+            Some(quest.clone())
+        );
+        assert_eq!(
+            db.get_challenges_by_quest_id(quest.id.clone())
+                .await
+                .unwrap(),
+            challenges
+        );
+
+        // 同じクエストを作ろうとするとチャレンジも書き込まれない
+        let extra_challenge = ChallengeItem {
+            id: "test-challenge-4".to_string(),
+            ..challenges[0].clone()
+        };
+        assert!(db
+            .put_quest_with_challenges(quest.clone(), vec![extra_challenge.clone()])
+            .await
+            .is_err());
+        assert_eq!(
+            db.get_challenge_by_id_and_quest_id(extra_challenge.id, quest.id.clone())
+                .await
+                .unwrap(),
+            None
+        );
+
+        // 後片付け
+        for challenge in challenges {
+            db.delete_challenge(challenge.id, challenge.quest_id)
+                .await
+                .unwrap();
+        }
+        db.delete_quest(quest.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_user_cascade() {
+        let db = create_client().await;
+
+        let user = UserItem {
+            id: nanoid::nanoid!(),
+            name: "Test User".to_string(),
+            email: format!("{}@nouse.ink", nanoid::nanoid!()),
+            hashed_password: "hogehoge".to_string(),
+        };
+        db.put_user(user.clone()).await.unwrap();
+        // 1回のBatchWriteItemに収まらない件数にする
+        for i in 0..30 {
+            db.put_user_participate_quest(user.id.clone(), format!("test-quest-{}", i))
+                .await
+                .unwrap();
+        }
+        db.put_user_complete_challenge(UserCompletedChallengeItem {
+            user_id: user.id.clone(),
+            challenge_id: "test-challenge".to_string(),
+            completed_at: Utc::now(),
+            photo_url: None,
+        })
+        .await
+        .unwrap();
+
+        db.delete_user_cascade(user.id.clone()).await.unwrap();
+
+        assert_eq!(db.get_user_by_id(user.id.clone()).await.unwrap(), None);
+        assert!(db
+            .query_user_participate_quest_ids(user.id.clone())
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(db
+            .query_user_complete_challenge_ids(user.id)
+            .await
+            .unwrap()
+            .is_empty());
     }
 }
@@ -0,0 +1,191 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::async_trait;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use crate::services::notification::{PushError, PushMessage, PushSender};
+
+const FCM_SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+const JWT_BEARER_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+/// 有効期限ぎりぎりのトークンは使わずに取り直す
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Firebaseのサービスアカウント鍵(JSON)のうち使う項目
+#[derive(Debug, Clone, Deserialize)]
+struct ServiceAccount {
+    project_id: String,
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+struct AssertionClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+struct AccessToken {
+    value: String,
+    expires_at: SystemTime,
+}
+
+/// FCM HTTP v1 APIのクライアント
+pub struct FcmClient {
+    client: reqwest::Client,
+    account: ServiceAccount,
+    key: EncodingKey,
+    token: Mutex<Option<AccessToken>>,
+}
+
+impl FcmClient {
+    pub fn from_service_account_json(json: &str, client: reqwest::Client) -> anyhow::Result<Self> {
+        let account: ServiceAccount = serde_json::from_str(json)?;
+        let key = EncodingKey::from_rsa_pem(account.private_key.as_bytes())?;
+
+        Ok(Self {
+            client,
+            account,
+            key,
+            token: Mutex::new(None),
+        })
+    }
+
+    pub fn from_service_account_file(path: &str, client: reqwest::Client) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        Self::from_service_account_json(&json, client)
+    }
+
+    /// OAuthのアクセストークン。有効な間は使い回す
+    async fn access_token(&self) -> anyhow::Result<String> {
+        let mut token = self.token.lock().await;
+        let now = SystemTime::now();
+        if let Some(token) = token.as_ref() {
+            if now + TOKEN_REFRESH_MARGIN < token.expires_at {
+                return Ok(token.value.clone());
+            }
+        }
+
+        let iat = now.duration_since(UNIX_EPOCH)?.as_secs();
+        let claims = AssertionClaims {
+            iss: &self.account.client_email,
+            scope: FCM_SCOPE,
+            aud: &self.account.token_uri,
+            iat,
+            exp: iat + 3600,
+        };
+        let assertion = encode(&Header::new(Algorithm::RS256), &claims, &self.key)?;
+
+        let res: TokenResponse = self
+            .client
+            .post(&self.account.token_uri)
+            .form(&[
+                ("grant_type", JWT_BEARER_GRANT_TYPE),
+                ("assertion", assertion.as_str()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        *token = Some(AccessToken {
+            value: res.access_token.clone(),
+            expires_at: now + Duration::from_secs(res.expires_in),
+        });
+
+        Ok(res.access_token)
+    }
+
+    fn endpoint(&self) -> String {
+        format!(
+            "https://fcm.googleapis.com/v1/projects/{}/messages:send",
+            self.account.project_id
+        )
+    }
+}
+
+#[async_trait]
+impl PushSender for FcmClient {
+    async fn send(&self, token: &str, message: &PushMessage) -> Result<(), PushError> {
+        let access_token = self.access_token().await.map_err(PushError::Other)?;
+
+        let res = self
+            .client
+            .post(self.endpoint())
+            .bearer_auth(access_token)
+            .json(&message_body(token, message))
+            .send()
+            .await
+            .map_err(|e| PushError::Other(e.into()))?;
+
+        match res.status() {
+            status if status.is_success() => Ok(()),
+            // 端末側でトークンが無効になっている
+            StatusCode::NOT_FOUND => Err(PushError::Unregistered),
+            status => Err(PushError::Other(anyhow::anyhow!(
+                "fcm responded with {}",
+                status
+            ))),
+        }
+    }
+}
+
+fn message_body(token: &str, message: &PushMessage) -> Value {
+    json!({
+        "message": {
+            "token": token,
+            "notification": {
+                "title": message.title,
+                "body": message.body,
+            },
+            "data": message.data,
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn should_build_message_body() {
+        let message = PushMessage {
+            title: "title".to_string(),
+            body: "body".to_string(),
+            data: HashMap::from([("quest_id".to_string(), "quest".to_string())]),
+        };
+
+        let body = message_body("token", &message);
+
+        assert_eq!(
+            json!({
+                "message": {
+                    "token": "token",
+                    "notification": { "title": "title", "body": "body" },
+                    "data": { "quest_id": "quest" },
+                }
+            }),
+            body
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_service_account() {
+        assert!(FcmClient::from_service_account_json("{}", reqwest::Client::new()).is_err());
+    }
+}
@@ -0,0 +1,9 @@
+use std::time::Duration;
+
+/// 外部APIの呼び出しで共有するクライアント。コネクションプールを使い回すため1つだけ作る
+pub fn build_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("failed to build http client")
+}
@@ -0,0 +1,14 @@
+use axum::{body::Body, Router};
+use lambda_http::{Error, Request};
+use tower::ServiceBuilder;
+
+/// API Gatewayのイベントをリクエストに変換してRouterに渡す
+/// REST APIのステージ名をパスから外すには AWS_LAMBDA_HTTP_IGNORE_STAGE_IN_PATH=true を設定する
+pub async fn run(app: Router) -> Result<(), Error> {
+    let service = ServiceBuilder::new()
+        // ボディはイベントに含まれて届くので、まとめてhyperのBodyにする
+        .map_request(|req: Request| req.map(|body| Body::from(body.to_vec())))
+        .service(app);
+
+    lambda_http::run(service).await
+}
@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use redis::{aio::ConnectionManager, AsyncCommands};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Redisのクライアント。値はJSONで保存し、キーには環境ごとのprefixを付ける
+#[derive(Clone)]
+pub struct RedisClient {
+    conn: ConnectionManager,
+    prefix: String,
+}
+
+impl RedisClient {
+    /// 切断されてもConnectionManagerが再接続するので、起動時に1つだけ作る
+    pub async fn connect(url: &str, prefix: String) -> anyhow::Result<Self> {
+        let client = redis::Client::open(url)?;
+        let conn = ConnectionManager::new(client).await?;
+
+        Ok(Self { conn, prefix })
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}:{}", self.prefix, key)
+    }
+
+    #[tracing::instrument(skip(self), fields(otel.kind = "client", db.system = "redis"))]
+    pub async fn get_json<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        let value: Option<String> = self.conn.clone().get(self.key(key)).await?;
+        match value {
+            Some(value) => Ok(Some(serde_json::from_str(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    #[tracing::instrument(skip(self, value), fields(otel.kind = "client", db.system = "redis"))]
+    pub async fn set_json<T: Serialize + Sync>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Duration,
+    ) -> anyhow::Result<()> {
+        let value = serde_json::to_string(value)?;
+        self.conn
+            .clone()
+            .set_ex::<_, _, ()>(self.key(key), value, ttl.as_secs() as usize)
+            .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), fields(otel.kind = "client", db.system = "redis"))]
+    pub async fn delete(&self, keys: &[String]) -> anyhow::Result<()> {
+        let keys = keys.iter().map(|key| self.key(key)).collect::<Vec<_>>();
+        self.conn.clone().del::<_, ()>(keys).await?;
+
+        Ok(())
+    }
+}
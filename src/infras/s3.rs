@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use aws_sdk_s3::{presigning::PresigningConfig, primitives::ByteStream, Client};
+use axum::async_trait;
+
+use crate::services::{
+    export::ExportStore,
+    offline_pack::{OfflinePack, OfflinePackStore},
+    photo::{Photo, PhotoStore},
+};
+
+const OFFLINE_PACK_PREFIX: &str = "offline_packs";
+
+/// オフラインパックをS3にJSONで保存する。CDNはこのバケットを直接配信してもよい
+pub struct S3OfflinePackStore {
+    client: Client,
+    bucket: String,
+}
+
+impl S3OfflinePackStore {
+    pub fn new(client: Client, bucket: String) -> Self {
+        Self { client, bucket }
+    }
+}
+
+fn key(region: &str) -> String {
+    format!("{}/{}.json", OFFLINE_PACK_PREFIX, region)
+}
+
+#[async_trait]
+impl OfflinePackStore for S3OfflinePackStore {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", region = %pack.region))]
+    async fn put(&self, pack: &OfflinePack) -> anyhow::Result<()> {
+        let body = serde_json::to_vec(pack)?;
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key(&pack.region))
+            .content_type(mime::APPLICATION_JSON.as_ref())
+            .body(ByteStream::from(body))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), fields(otel.kind = "client"))]
+    async fn get(&self, region: &str) -> anyhow::Result<Option<OfflinePack>> {
+        let output = match self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key(region))
+            .send()
+            .await
+        {
+            Ok(output) => output,
+            Err(e) => {
+                let e = e.into_service_error();
+                if e.is_no_such_key() {
+                    return Ok(None);
+                }
+                return Err(e.into());
+            }
+        };
+        let bytes = output.body.collect().await?.into_bytes();
+
+        Ok(Some(serde_json::from_slice(&bytes)?))
+    }
+}
+
+/// チャレンジの写真をS3に保存する。URLはCDN等の公開用のベースURLにキーを付けたもの
+pub struct S3PhotoStore {
+    client: Client,
+    bucket: String,
+    base_url: String,
+}
+
+impl S3PhotoStore {
+    pub fn new(client: Client, bucket: String, base_url: String) -> Self {
+        Self {
+            client,
+            bucket,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl PhotoStore for S3PhotoStore {
+    #[tracing::instrument(skip(self, photo), fields(otel.kind = "client"))]
+    async fn put(&self, key: &str, photo: &Photo) -> anyhow::Result<String> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(&photo.content_type)
+            .body(ByteStream::from(photo.bytes.clone()))
+            .send()
+            .await?;
+
+        Ok(format!("{}/{}", self.base_url, key))
+    }
+
+    #[tracing::instrument(skip(self), fields(otel.kind = "client"))]
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// ユーザーのエクスポートを保存する。バケットは非公開にして署名付きURLで渡す
+pub struct S3ExportStore {
+    client: Client,
+    bucket: String,
+}
+
+impl S3ExportStore {
+    pub fn new(client: Client, bucket: String) -> Self {
+        Self { client, bucket }
+    }
+}
+
+#[async_trait]
+impl ExportStore for S3ExportStore {
+    #[tracing::instrument(skip(self, body), fields(otel.kind = "client"))]
+    async fn put(&self, key: &str, body: Vec<u8>) -> anyhow::Result<()> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(mime::APPLICATION_JSON.as_ref())
+            .body(ByteStream::from(body))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), fields(otel.kind = "client"))]
+    async fn signed_url(&self, key: &str, expires_in: Duration) -> anyhow::Result<String> {
+        let request = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .response_content_disposition("attachment; filename=\"export.json\"")
+            .presigned(PresigningConfig::expires_in(expires_in)?)
+            .await?;
+
+        Ok(request.uri().to_string())
+    }
+}
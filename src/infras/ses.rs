@@ -0,0 +1,45 @@
+use aws_sdk_sesv2::{
+    types::{Body, Content, Destination, EmailContent, Message},
+    Client,
+};
+use axum::async_trait;
+
+use crate::services::mail::{Mail, Mailer};
+
+const CHARSET: &str = "UTF-8";
+
+/// Amazon SES(v2)でメールを送る
+pub struct Ses {
+    client: Client,
+    from: String,
+}
+
+impl Ses {
+    pub fn new(client: Client, from: String) -> Self {
+        Self { client, from }
+    }
+}
+
+#[async_trait]
+impl Mailer for Ses {
+    async fn send(&self, mail: &Mail) -> anyhow::Result<()> {
+        let message = Message::builder()
+            .subject(utf8_content(&mail.subject))
+            .body(Body::builder().text(utf8_content(&mail.body)).build())
+            .build();
+
+        self.client
+            .send_email()
+            .from_email_address(&self.from)
+            .destination(Destination::builder().to_addresses(&mail.to).build())
+            .content(EmailContent::builder().simple(message).build())
+            .send()
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn utf8_content(data: &str) -> Content {
+    Content::builder().data(data).charset(CHARSET).build()
+}
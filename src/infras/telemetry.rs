@@ -0,0 +1,36 @@
+use opentelemetry::{
+    global,
+    sdk::{propagation::TraceContextPropagator, trace, Resource},
+    KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+
+const SERVICE_NAME: &str = "quest-api";
+
+/// OTLP(gRPC)でJaeger/Tempoにスパンを送るtracerを作る
+/// traceparentの読み書きができるよう、W3C Trace Contextのpropagatorも登録する
+pub fn init_tracer(endpoint: &str) -> anyhow::Result<trace::Tracer> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            trace::config().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                SERVICE_NAME,
+            )])),
+        )
+        .install_batch(opentelemetry::runtime::Tokio)?;
+
+    Ok(tracer)
+}
+
+/// バッファに残っているスパンを送り切ってから終了する
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
@@ -1 +1,15 @@
+pub mod admin;
 pub mod auth;
+pub mod ban;
+pub mod csrf;
+pub mod error;
+pub mod etag;
+pub mod feature_flag;
+pub mod idempotency;
+pub mod language;
+pub mod limit;
+pub mod metrics;
+pub mod recovery;
+pub mod signed_url;
+pub mod trace;
+pub mod version;
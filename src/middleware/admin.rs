@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use axum::{extract::Request, http::StatusCode, middleware::Next, response::Response};
+
+use crate::{
+    error::ApiError,
+    repositories::{
+        id::UserId,
+        user::{UserRepository, UserRole},
+    },
+};
+
+/// 管理者だけを通す。auth_middlewareの内側に置き、extensionのuser_idでロールを確認する
+/// ロールを確認できなかったときは通さず503を返す
+pub async fn admin_middleware(
+    repository: Arc<dyn UserRepository>,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let user_id = req
+        .extensions()
+        .get::<UserId>()
+        .cloned()
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    match repository.find_role(user_id).await {
+        Ok(Some(UserRole::Admin)) => Ok(next.run(req).await),
+        Ok(_) => Err(StatusCode::FORBIDDEN.into()),
+        Err(e) => {
+            tracing::error!("failed to check role: {}", e);
+            Err(StatusCode::SERVICE_UNAVAILABLE.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        error::{ErrorBody, ErrorCode},
+        middleware::auth::auth_middleware,
+        repositories::user::MockUserRepository,
+        services::user::{create_jwt, JwtKeyRing},
+    };
+    use axum::body::Body;
+    use axum::{
+        http::header::COOKIE, middleware::from_fn, response::IntoResponse, routing::get, Router,
+    };
+    use chrono::{Duration, Utc};
+    use tower::ServiceExt;
+
+    async fn handler() -> impl IntoResponse {
+        StatusCode::OK
+    }
+
+    fn app(repository: MockUserRepository, key_ring: JwtKeyRing) -> Router {
+        let repository: Arc<dyn UserRepository> = Arc::new(repository);
+        let auth_key_ring = key_ring.clone();
+        Router::new()
+            .route("/", get(handler))
+            .layer(from_fn(move |req, next| {
+                admin_middleware(repository.clone(), req, next)
+            }))
+            .layer(from_fn(move |req, next| {
+                auth_middleware(auth_key_ring.clone(), req, next)
+            }))
+    }
+
+    fn req(user_id: &str, key_ring: &JwtKeyRing) -> Request<Body> {
+        let now = Utc::now();
+        let token = create_jwt(
+            &UserId::from(user_id),
+            now.timestamp(),
+            &(now + Duration::hours(8)).timestamp(),
+            key_ring,
+        );
+        Request::builder()
+            .uri("/")
+            .header(COOKIE, format!("session_token={}", token))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_pass_only_admin() {
+        let key_ring = JwtKeyRing::from_secret("secret_key");
+        let mut repository = MockUserRepository::new();
+        repository
+            .expect_find_role()
+            .returning(|user_id| match user_id.as_str() {
+                "admin" => Ok(Some(UserRole::Admin)),
+                "user" => Ok(Some(UserRole::User)),
+                _ => Ok(None),
+            });
+        let app = app(repository, key_ring.clone());
+
+        let res = app.clone().oneshot(req("admin", &key_ring)).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        for user_id in ["user", "missing"] {
+            let res = app.clone().oneshot(req(user_id, &key_ring)).await.unwrap();
+            assert_eq!(StatusCode::FORBIDDEN, res.status());
+            let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: ErrorBody = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(ErrorCode::Forbidden, body.error);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_fail_closed_when_role_is_unavailable() {
+        let key_ring = JwtKeyRing::from_secret("secret_key");
+        let mut repository = MockUserRepository::new();
+        repository
+            .expect_find_role()
+            .returning(|_| Err(anyhow::anyhow!("connection refused")));
+
+        let res = app(repository, key_ring.clone())
+            .oneshot(req("admin", &key_ring))
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, res.status());
+    }
+}
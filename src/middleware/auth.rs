@@ -1,34 +1,189 @@
 use axum::{
-    headers::HeaderMapExt,
-    http::{Request, StatusCode},
+    async_trait,
+    extract::{FromRequestParts, Request},
+    http::{request::Parts, HeaderMap, StatusCode},
     middleware::Next,
     response::Response,
 };
+use axum_extra::headers::HeaderMapExt;
+use std::sync::Arc;
 
-use crate::services::user::decode_jwt;
-
-pub async fn auth_middleware<B>(
-    secret_key: String,
-    mut req: Request<B>,
-    next: Next<B>,
-) -> Result<Response, StatusCode> {
-    if let Some(cookies) = req.headers().typed_get::<axum::headers::Cookie>() {
-        if let Some(session_token) = cookies.get("session_token") {
-            let decoded_token = decode_jwt(session_token, &secret_key).unwrap();
-            req.extensions_mut().insert(decoded_token.claims.user_id);
+use crate::{
+    error::{ApiError, ErrorCode},
+    repositories::{api_key::ApiKeyRepository, id::UserId},
+    services::user::{decode_jwt, JwtKeyRing},
+};
+
+/// サーバー間連携でAPIキーを送るヘッダ
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// 認証できた呼び出し元。ハンドラはExtension<Principal>で受け取れる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Principal {
+    User(UserId),
+    /// APIキーのid
+    ApiKey(String),
+}
+
+/// ログイン中のユーザー。auth_middlewareなどが検証したセッションのuser_idを取り出す
+/// ミドルウェアを通っていないルートで使うと401になる。任意のログインはOption<AuthenticatedUser>で受ける
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticatedUser(pub UserId);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuthenticatedUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<UserId>()
+            .cloned()
+            .map(Self)
+            .ok_or_else(|| StatusCode::UNAUTHORIZED.into())
+    }
+}
+
+/// 認証方式ごとの実装。Authenticatorsに並べて使う
+#[async_trait]
+pub trait Authenticator: Send + Sync + 'static {
+    /// この方式の資格情報が付いていなければOk(None)を返し、次の方式に任せる
+    async fn authenticate(&self, headers: &HeaderMap) -> Result<Option<Principal>, ApiError>;
+}
+
+/// Cookieのセッショントークンを検証する
+pub struct JwtAuthenticator {
+    key_ring: JwtKeyRing,
+}
+
+impl JwtAuthenticator {
+    pub fn new(key_ring: JwtKeyRing) -> Self {
+        Self { key_ring }
+    }
+}
+
+#[async_trait]
+impl Authenticator for JwtAuthenticator {
+    async fn authenticate(&self, headers: &HeaderMap) -> Result<Option<Principal>, ApiError> {
+        let session_token = match headers
+            .typed_get::<axum_extra::headers::Cookie>()
+            .and_then(|cookies| cookies.get("session_token").map(str::to_string))
+        {
+            Some(session_token) => session_token,
+            None => return Ok(None),
+        };
+        let claims = decode_jwt(&session_token, &self.key_ring)?;
+
+        Ok(Some(Principal::User(claims.user_id)))
+    }
+}
+
+/// X-Api-Keyヘッダのキーを検証する
+pub struct ApiKeyAuthenticator {
+    repository: Arc<dyn ApiKeyRepository>,
+}
+
+impl ApiKeyAuthenticator {
+    pub fn new(repository: Arc<dyn ApiKeyRepository>) -> Self {
+        Self { repository }
+    }
+}
+
+#[async_trait]
+impl Authenticator for ApiKeyAuthenticator {
+    async fn authenticate(&self, headers: &HeaderMap) -> Result<Option<Principal>, ApiError> {
+        let key = match headers.get(API_KEY_HEADER) {
+            Some(key) => key.to_str().or(Err(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                ErrorCode::InvalidApiKey,
+            )))?,
+            None => return Ok(None),
+        };
+        let api_key = self
+            .repository
+            .authenticate(key)
+            .await
+            .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?
+            .ok_or(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                ErrorCode::InvalidApiKey,
+            ))?;
+
+        Ok(Some(Principal::ApiKey(api_key.id)))
+    }
+}
+
+/// 受け付ける認証方式の一覧。並べた順に試す
+#[derive(Clone, Default)]
+pub struct Authenticators(Vec<Arc<dyn Authenticator>>);
+
+impl Authenticators {
+    pub fn with(mut self, authenticator: impl Authenticator) -> Self {
+        self.0.push(Arc::new(authenticator));
+        self
+    }
+}
+
+/// 最初に資格情報が見つかった方式で認証する。ユーザーならuser_idもextensionに入れる
+pub async fn authenticate_middleware(
+    authenticators: Authenticators,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    for authenticator in &authenticators.0 {
+        if let Some(principal) = authenticator.authenticate(req.headers()).await? {
+            if let Principal::User(user_id) = &principal {
+                req.extensions_mut().insert(user_id.clone());
+            }
+            req.extensions_mut().insert(principal);
             return Ok(next.run(req).await);
-        } else {
-            return Err(StatusCode::UNAUTHORIZED);
         }
     }
-    Err(StatusCode::UNAUTHORIZED)
+    Err(StatusCode::UNAUTHORIZED.into())
+}
+
+/// ログイン中のユーザーだけを通す
+pub async fn auth_middleware(
+    key_ring: JwtKeyRing,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let authenticators = Authenticators::default().with(JwtAuthenticator::new(key_ring));
+    authenticate_middleware(authenticators, req, next).await
+}
+
+/// 有効なセッションがあればuser_idをextensionに入れる。なくても弾かない
+pub async fn optional_auth_middleware(
+    key_ring: JwtKeyRing,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let user_id = req
+        .headers()
+        .typed_get::<axum_extra::headers::Cookie>()
+        .and_then(|cookies| {
+            cookies
+                .get("session_token")
+                .and_then(|session_token| decode_jwt(session_token, &key_ring).ok())
+        })
+        .map(|claims| claims.user_id);
+
+    if let Some(user_id) = user_id {
+        req.extensions_mut().insert(user_id);
+    }
+    next.run(req).await
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::services::user::create_jwt;
+    use crate::{
+        error::ErrorBody,
+        repositories::api_key::{ApiKey, MockApiKeyRepository},
+        services::user::create_jwt,
+    };
     use axum::{
+        body::Body,
         http::{Request, StatusCode},
         middleware::from_fn,
         response::IntoResponse,
@@ -36,26 +191,54 @@ mod test {
         Router,
     };
     use chrono::{Duration, Utc};
-    use hyper::Body;
     use tower::ServiceExt;
 
     async fn handler() -> impl IntoResponse {
         StatusCode::OK
     }
 
+    const VALID_API_KEY: &str = "qk_valid";
+
+    /// VALID_API_KEYだけを有効なキーとして扱う
+    fn api_key_repository() -> MockApiKeyRepository {
+        let mut repository = MockApiKeyRepository::new();
+        repository.expect_authenticate().returning(|key| {
+            Ok((key == VALID_API_KEY).then(|| ApiKey {
+                id: "partner_key".to_string(),
+                name: "partner".to_string(),
+                prefix: VALID_API_KEY.to_string(),
+                created_at: Utc::now(),
+                last_used_at: Some(Utc::now()),
+                revoked_at: None,
+            }))
+        });
+        repository
+    }
+
+    fn partner_app(key_ring: JwtKeyRing) -> Router {
+        let authenticators = Authenticators::default()
+            .with(ApiKeyAuthenticator::new(Arc::new(api_key_repository())))
+            .with(JwtAuthenticator::new(key_ring));
+        Router::new()
+            .route("/", get(handler))
+            .layer(from_fn(move |req, next| {
+                authenticate_middleware(authenticators.clone(), req, next)
+            }))
+    }
+
     #[tokio::test]
     async fn test_auth_middleware_with_valid_cookie() {
-        let secret_key = "secret_key".to_string();
-        let test_user_id = "test_user".to_string();
+        let key_ring = JwtKeyRing::from_secret("secret_key");
+        let test_user_id = UserId::from("test_user");
         let now = Utc::now();
         let iat = now.timestamp();
         let exp = (now + Duration::hours(8)).timestamp();
-        let valid_session_token = create_jwt(&test_user_id, iat, &exp, &secret_key);
+        let valid_session_token = create_jwt(&test_user_id, iat, &exp, &key_ring);
 
         let app = Router::new()
             .route("/", get(handler))
             .layer(from_fn(move |req, next| {
-                auth_middleware(secret_key.clone(), req, next)
+                auth_middleware(key_ring.clone(), req, next)
             }));
 
         let req = Request::builder()
@@ -67,4 +250,151 @@ mod test {
 
         assert_eq!(res.status(), StatusCode::OK)
     }
+
+    #[tokio::test]
+    async fn test_auth_middleware_with_expired_cookie() {
+        let key_ring = JwtKeyRing::from_secret("secret_key");
+        let now = Utc::now();
+        let iat = (now - Duration::hours(10)).timestamp();
+        let exp = (now - Duration::hours(2)).timestamp();
+        let expired_session_token = create_jwt(&UserId::from("test_user"), iat, &exp, &key_ring);
+
+        let app = Router::new()
+            .route("/", get(handler))
+            .layer(from_fn(move |req, next| {
+                auth_middleware(key_ring.clone(), req, next)
+            }));
+
+        let req = Request::builder()
+            .header("cookie", format!("session_token={}", expired_session_token))
+            .body(Body::empty())
+            .unwrap();
+
+        let res = app.oneshot(req).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: ErrorBody = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(ErrorCode::TokenExpired, body.error);
+    }
+
+    #[tokio::test]
+    async fn test_auth_middleware_with_malformed_cookie() {
+        let key_ring = JwtKeyRing::from_secret("secret_key");
+        let app = Router::new()
+            .route("/", get(handler))
+            .layer(from_fn(move |req, next| {
+                auth_middleware(key_ring.clone(), req, next)
+            }));
+
+        let req = Request::builder()
+            .header("cookie", "session_token=not-a-jwt")
+            .body(Body::empty())
+            .unwrap();
+
+        let res = app.oneshot(req).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: ErrorBody = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(ErrorCode::MalformedToken, body.error);
+    }
+
+    #[tokio::test]
+    async fn should_accept_api_key_or_session() {
+        let key_ring = JwtKeyRing::from_secret("secret_key");
+        let now = Utc::now();
+        let iat = now.timestamp();
+        let exp = (now + Duration::hours(8)).timestamp();
+        let session_token = create_jwt(&UserId::from("test_user"), iat, &exp, &key_ring);
+        let app = partner_app(key_ring);
+
+        let req = Request::builder()
+            .header(API_KEY_HEADER, VALID_API_KEY)
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let req = Request::builder()
+            .header("cookie", format!("session_token={}", session_token))
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let req = Request::builder().body(Body::empty()).unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_reject_unknown_api_key() {
+        let req = Request::builder()
+            .header(API_KEY_HEADER, "qk_unknown")
+            .body(Body::empty())
+            .unwrap();
+
+        let res = partner_app(JwtKeyRing::from_secret("secret_key"))
+            .oneshot(req)
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: ErrorBody = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(ErrorCode::InvalidApiKey, body.error);
+    }
+
+    #[tokio::test]
+    async fn should_extract_authenticated_user_from_session() {
+        async fn whoami(AuthenticatedUser(user_id): AuthenticatedUser) -> String {
+            user_id.into_string()
+        }
+
+        let key_ring = JwtKeyRing::from_secret("secret_key");
+        let now = Utc::now();
+        let iat = now.timestamp();
+        let exp = (now + Duration::hours(8)).timestamp();
+        let session_token = create_jwt(&UserId::from("test_user"), iat, &exp, &key_ring);
+        let app = Router::new()
+            .route("/", get(whoami))
+            .layer(from_fn(move |req, next| {
+                auth_middleware(key_ring.clone(), req, next)
+            }));
+
+        let req = Request::builder()
+            .header("cookie", format!("session_token={}", session_token))
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!("test_user", String::from_utf8(bytes.to_vec()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn should_reject_authenticated_user_without_middleware() {
+        async fn whoami(AuthenticatedUser(user_id): AuthenticatedUser) -> String {
+            user_id.into_string()
+        }
+
+        let req = Request::builder().body(Body::empty()).unwrap();
+        let res = Router::new()
+            .route("/", get(whoami))
+            .oneshot(req)
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+    }
 }
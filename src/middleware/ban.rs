@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use axum::{extract::Request, http::StatusCode, middleware::Next, response::Response};
+use axum_extra::headers::HeaderMapExt;
+use chrono::Utc;
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    repositories::ban::BanRepository,
+    services::{
+        session_cookie::SESSION_COOKIE_NAME,
+        user::{decode_jwt, JwtKeyRing},
+    },
+};
+
+/// 凍結中のユーザーのリクエストはどのルートでも403で弾く
+/// 凍結を確認できなかったときは通さず503を返す
+pub async fn ban_middleware(
+    repository: Arc<dyn BanRepository>,
+    key_ring: JwtKeyRing,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let user_id = req
+        .headers()
+        .typed_get::<axum_extra::headers::Cookie>()
+        .and_then(|cookies| {
+            cookies
+                .get(SESSION_COOKIE_NAME)
+                .and_then(|session_token| decode_jwt(session_token, &key_ring).ok())
+        })
+        .map(|claims| claims.user_id);
+    let user_id = match user_id {
+        Some(user_id) => user_id,
+        None => return Ok(next.run(req).await),
+    };
+
+    match repository.find_active(user_id, Utc::now()).await {
+        Ok(Some(ban)) => {
+            let message = match ban.expires_at {
+                Some(expires_at) => format!("banned until {}", expires_at.to_rfc3339()),
+                None => "banned indefinitely".to_string(),
+            };
+            Err(ApiError::new(StatusCode::FORBIDDEN, ErrorCode::UserBanned).with_message(message))
+        }
+        Ok(None) => Ok(next.run(req).await),
+        Err(e) => {
+            tracing::error!("failed to check ban: {}", e);
+            Err(StatusCode::SERVICE_UNAVAILABLE.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        error::ErrorBody,
+        repositories::{
+            ban::{Ban, MockBanRepository},
+            id::UserId,
+        },
+        services::user::create_jwt,
+    };
+    use axum::body::Body;
+    use axum::{
+        http::header::COOKIE, middleware::from_fn, response::IntoResponse, routing::get, Router,
+    };
+    use chrono::Duration;
+    use tower::ServiceExt;
+
+    async fn handler() -> impl IntoResponse {
+        StatusCode::OK
+    }
+
+    fn app(repository: MockBanRepository, key_ring: JwtKeyRing) -> Router {
+        let repository: Arc<dyn BanRepository> = Arc::new(repository);
+        Router::new()
+            .route("/", get(handler))
+            .layer(from_fn(move |req, next| {
+                ban_middleware(repository.clone(), key_ring.clone(), req, next)
+            }))
+    }
+
+    fn req(user_id: Option<&str>, key_ring: &JwtKeyRing) -> Request<Body> {
+        let mut builder = Request::builder().uri("/");
+        if let Some(user_id) = user_id {
+            let now = Utc::now();
+            let token = create_jwt(
+                &UserId::from(user_id),
+                now.timestamp(),
+                &(now + Duration::hours(8)).timestamp(),
+                key_ring,
+            );
+            builder = builder.header(COOKIE, format!("{}={}", SESSION_COOKIE_NAME, token));
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_reject_banned_user() {
+        let key_ring = JwtKeyRing::from_secret("secret_key");
+        let mut repository = MockBanRepository::new();
+        repository.expect_find_active().returning(|user_id, now| {
+            Ok((user_id.as_str() == "banned").then(|| Ban {
+                id: 1,
+                user_id,
+                reason: "spam".to_string(),
+                banned_by: Some("admin".into()),
+                expires_at: None,
+                created_at: now,
+                lifted_at: None,
+                lifted_by: None,
+            }))
+        });
+        let app = app(repository, key_ring.clone());
+
+        let res = app
+            .clone()
+            .oneshot(req(Some("banned"), &key_ring))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: ErrorBody = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(ErrorCode::UserBanned, body.error);
+
+        let res = app
+            .clone()
+            .oneshot(req(Some("other"), &key_ring))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_skip_requests_without_session() {
+        let key_ring = JwtKeyRing::from_secret("secret_key");
+        // 呼ばれたらexpectがないのでpanicする
+        let repository = MockBanRepository::new();
+
+        let res = app(repository, key_ring.clone())
+            .oneshot(req(None, &key_ring))
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_fail_closed_when_ban_is_unavailable() {
+        let key_ring = JwtKeyRing::from_secret("secret_key");
+        let mut repository = MockBanRepository::new();
+        repository
+            .expect_find_active()
+            .returning(|_, _| Err(anyhow::anyhow!("connection refused")));
+
+        let res = app(repository, key_ring.clone())
+            .oneshot(req(Some("user"), &key_ring))
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: ErrorBody = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(ErrorCode::ServiceUnavailable, body.error);
+    }
+}
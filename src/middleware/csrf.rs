@@ -0,0 +1,136 @@
+use axum::{
+    extract::Request,
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use axum_extra::headers::HeaderMapExt;
+use nanoid::nanoid;
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    services::session_cookie::{CSRF_COOKIE_NAME, SESSION_COOKIE_NAME},
+};
+
+/// `GET /csrf_token`で受け取った値を付けるヘッダ
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+pub fn generate_csrf_token() -> String {
+    nanoid!(32)
+}
+
+/// Cookieでログインしている状態変更リクエストは、csrf_tokenクッキーと同じ値をヘッダにも付ける
+/// 他サイトのフォームはヘッダを付けられず、クッキーの値も読めないので弾ける
+/// APIキーなどクッキーを使わない呼び出しはブラウザから偽造されないので検証しない
+pub async fn csrf_middleware(req: Request, next: Next) -> Result<Response, ApiError> {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return Ok(next.run(req).await);
+    }
+    let cookies = match req.headers().typed_get::<axum_extra::headers::Cookie>() {
+        Some(cookies) if cookies.get(SESSION_COOKIE_NAME).is_some() => cookies,
+        _ => return Ok(next.run(req).await),
+    };
+    let header = req
+        .headers()
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let valid = match (cookies.get(CSRF_COOKIE_NAME), header) {
+        (Some(cookie), Some(header)) => constant_time_eq(cookie, header),
+        _ => false,
+    };
+    if !valid {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            ErrorCode::CsrfTokenMismatch,
+        ));
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// 比較にかかる時間からトークンを推測されないようにする
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::ErrorBody;
+    use axum::body::Body;
+    use axum::{
+        http::header::COOKIE, middleware::from_fn, response::IntoResponse, routing::post, Router,
+    };
+    use tower::ServiceExt;
+
+    async fn handler() -> impl IntoResponse {
+        StatusCode::OK
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route("/", post(handler).get(handler))
+            .layer(from_fn(csrf_middleware))
+    }
+
+    fn req(method: Method, cookie: Option<&str>, header: Option<&str>) -> Request<Body> {
+        let mut builder = Request::builder().method(method).uri("/");
+        if let Some(cookie) = cookie {
+            builder = builder.header(COOKIE, cookie);
+        }
+        if let Some(header) = header {
+            builder = builder.header(CSRF_HEADER, header);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_require_matching_token_with_session_cookie() {
+        let cookie = "session_token=jwt; csrf_token=token";
+
+        let res = app()
+            .oneshot(req(Method::POST, Some(cookie), Some("token")))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        for header in [None, Some("other")] {
+            let res = app()
+                .oneshot(req(Method::POST, Some(cookie), header))
+                .await
+                .unwrap();
+            assert_eq!(StatusCode::FORBIDDEN, res.status());
+            let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: ErrorBody = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(ErrorCode::CsrfTokenMismatch, body.error);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_skip_safe_methods_and_requests_without_session() {
+        let res = app()
+            .oneshot(req(Method::GET, Some("session_token=jwt"), None))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let res = app()
+            .oneshot(req(Method::POST, Some("csrf_token=token"), None))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    #[test]
+    fn should_compare_tokens() {
+        assert!(constant_time_eq("token", "token"));
+        assert!(!constant_time_eq("token", "tokem"));
+        assert!(!constant_time_eq("token", "token2"));
+    }
+}
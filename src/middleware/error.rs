@@ -0,0 +1,105 @@
+use axum::{
+    extract::Request,
+    http::header::{CONTENT_LENGTH, CONTENT_TYPE},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::error::ApiError;
+
+/// ステータスコードだけで返されたエラー(extractorのrejectionや未定義のルート等)にも
+/// エラーコード付きのJSONを返す。すでにJSONのエラーはそのまま通す
+pub async fn error_body_middleware(req: Request, next: Next) -> Response {
+    let res = next.run(req).await;
+    let status = res.status();
+    if !(status.is_client_error() || status.is_server_error()) || is_json(&res) {
+        return res;
+    }
+
+    let (parts, body) = res.into_parts();
+    // rejectionは本文に理由が入っているのでmessageとして残す
+    let message = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes.to_vec()).ok())
+        .filter(|message| !message.is_empty());
+    let mut error = ApiError::from(status);
+    if let Some(message) = message {
+        error = error.with_message(message);
+    }
+
+    // WWW-AuthenticateやRetry-After等の元のヘッダは残す
+    let mut res = error.into_response();
+    for (name, value) in parts.headers.iter() {
+        if name != CONTENT_TYPE && name != CONTENT_LENGTH {
+            res.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+    res
+}
+
+pub fn is_json(res: &Response) -> bool {
+    res.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| {
+            value.starts_with(mime::APPLICATION_JSON.as_ref())
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::{ErrorBody, ErrorCode};
+    use axum::body::Body;
+    use axum::{http::StatusCode, middleware::from_fn, routing::post, Json, Router};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    async fn json_handler(Json(_): Json<Value>) -> StatusCode {
+        StatusCode::OK
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route("/json", post(json_handler))
+            .layer(from_fn(error_body_middleware))
+    }
+
+    async fn error_body(res: Response) -> ErrorBody {
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_add_code_to_unmatched_route() {
+        let req = Request::builder()
+            .uri("/missing")
+            .body(Body::empty())
+            .unwrap();
+
+        let res = app().oneshot(req).await.unwrap();
+
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        assert_eq!(ErrorCode::NotFound, error_body(res).await.error);
+    }
+
+    #[tokio::test]
+    async fn should_keep_rejection_reason_as_message() {
+        let req = Request::builder()
+            .uri("/json")
+            .method("POST")
+            .header(CONTENT_TYPE, "text/plain")
+            .body(Body::from("{}"))
+            .unwrap();
+
+        let res = app().oneshot(req).await.unwrap();
+
+        assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, res.status());
+        let body = error_body(res).await;
+        assert_eq!(ErrorCode::UnsupportedMediaType, body.error);
+        assert!(body.message.is_some());
+    }
+}
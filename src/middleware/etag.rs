@@ -0,0 +1,152 @@
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_NONE_MATCH, LAST_MODIFIED},
+        HeaderMap, HeaderValue, Method, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+/// GETの200のレスポンスにボディのハッシュからETagを付け、If-None-Matchと一致すれば304を返す
+/// 署名付きURLに変換する前のボディから作るので、署名の期限が変わるだけではETagは変わらない
+/// Last-Modifiedは評価や翻訳の変更を反映しないので、If-Modified-Sinceは見ない
+pub async fn etag_middleware(req: Request, next: Next) -> Response {
+    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return next.run(req).await;
+    }
+    let if_none_match = req.headers().get(IF_NONE_MATCH).cloned();
+    let res = next.run(req).await;
+    if res.status() != StatusCode::OK {
+        return res;
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let etag = etag_of(&bytes);
+    let not_modified = if_none_match.map_or(false, |value| matches_etag(&value, &etag));
+    parts.headers.insert(ETAG, etag);
+    if !not_modified {
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+
+    // Last-ModifiedやCache-Controlは200と同じものを返す
+    parts.status = StatusCode::NOT_MODIFIED;
+    parts.headers.remove(CONTENT_TYPE);
+    parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, Body::empty())
+}
+
+/// ハンドラが返すLast-Modified。Noneなら付けない
+pub fn last_modified(updated_at: Option<DateTime<Utc>>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(updated_at) = updated_at {
+        let value = updated_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(LAST_MODIFIED, value);
+        }
+    }
+    headers
+}
+
+fn etag_of(bytes: &[u8]) -> HeaderValue {
+    let hash = hex::encode(Sha256::digest(bytes));
+    // 16進数なので必ずヘッダの値にできる
+    HeaderValue::from_str(&format!("\"{}\"", &hash[..32])).unwrap()
+}
+
+/// カンマ区切りで複数指定できる。GETなので弱い比較でW/は無視する
+fn matches_etag(if_none_match: &HeaderValue, etag: &HeaderValue) -> bool {
+    let etag = match etag.to_str() {
+        Ok(etag) => etag,
+        Err(_) => return false,
+    };
+    if_none_match.to_str().map_or(false, |value| {
+        value
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{middleware::from_fn, routing::get, Json, Router};
+    use chrono::TimeZone;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/quest",
+                get(|| async { Json(json!({ "title": "quest" })) })
+                    .post(|| async { Json(json!({ "title": "quest" })) }),
+            )
+            .layer(from_fn(etag_middleware))
+    }
+
+    fn request(method: Method, if_none_match: Option<&str>) -> Request<Body> {
+        let mut req = Request::builder().uri("/quest").method(method);
+        if let Some(if_none_match) = if_none_match {
+            req = req.header(IF_NONE_MATCH, if_none_match);
+        }
+        req.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_return_304_when_etag_matches() {
+        let res = app().oneshot(request(Method::GET, None)).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let etag = res.headers()[ETAG].to_str().unwrap().to_string();
+
+        let res = app()
+            .oneshot(request(
+                Method::GET,
+                Some(&format!("\"other\", W/{}", etag)),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NOT_MODIFIED, res.status());
+        assert_eq!(etag, res.headers()[ETAG]);
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(bytes.is_empty());
+
+        let res = app()
+            .oneshot(request(Method::GET, Some("\"other\"")))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_not_tag_other_than_get() {
+        let res = app()
+            .oneshot(request(Method::POST, Some("*")))
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::OK, res.status());
+        assert!(res.headers().get(ETAG).is_none());
+    }
+
+    #[test]
+    fn should_format_last_modified_as_http_date() {
+        let updated_at = Utc.with_ymd_and_hms(2024, 2, 3, 9, 0, 0).unwrap();
+
+        assert_eq!(
+            "Sat, 03 Feb 2024 09:00:00 GMT",
+            last_modified(Some(updated_at))[LAST_MODIFIED]
+        );
+        assert!(last_modified(None).is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+};
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    repositories::feature_flag::Feature,
+    services::feature_flag::FeatureFlagService,
+};
+
+/// ハンドラから機能のON/OFFを見る
+#[derive(Clone, Default)]
+pub struct FeatureFlags(pub Option<Arc<FeatureFlagService>>);
+
+impl FeatureFlags {
+    pub async fn is_enabled(&self, feature: Feature) -> bool {
+        match &self.0 {
+            Some(service) => service.is_enabled(feature).await,
+            None => true,
+        }
+    }
+
+    /// 無効な機能はルートがないのと同じに見せる
+    pub async fn require(&self, feature: Feature) -> Result<(), ApiError> {
+        if self.is_enabled(feature).await {
+            Ok(())
+        } else {
+            Err(ApiError::new(
+                StatusCode::NOT_FOUND,
+                ErrorCode::FeatureDisabled,
+            ))
+        }
+    }
+}
+
+/// サービスはcreate_appでExtensionとして渡す。なければすべて有効とみなす(ルータ単体のテスト等)
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for FeatureFlags {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(
+            parts.extensions.get::<Arc<FeatureFlagService>>().cloned(),
+        ))
+    }
+}
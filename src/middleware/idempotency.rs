@@ -0,0 +1,490 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{
+        header::{CONTENT_LANGUAGE, CONTENT_TYPE, ETAG, LOCATION},
+        HeaderName, HeaderValue, Method, StatusCode, Uri,
+    },
+    middleware::Next,
+    response::Response,
+};
+use axum_extra::headers::HeaderMapExt;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    repositories::idempotency::{IdempotencyRepository, StoredResponse},
+    services::user::{decode_jwt, JwtKeyRing},
+};
+
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+/// 保存済みのレスポンスを返したときに付ける
+pub const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
+const MAX_KEY_LENGTH: usize = 255;
+
+/// 再送時に返すヘッダ。Set-Cookieなどを保存すると最初のセッションを別の再送にも配ってしまう
+const REPLAYED_HEADERS: [HeaderName; 4] = [CONTENT_TYPE, CONTENT_LANGUAGE, LOCATION, ETAG];
+
+/// POSTにIdempotency-Keyが付いていれば、同じキーの再送には最初のレスポンスをそのまま返す
+/// キーはユーザーごとに分けるので、ログインしていないリクエストには付けられない
+/// 5xxは保存しないので同じキーで再試行できる
+pub async fn idempotency_middleware(
+    repository: Arc<dyn IdempotencyRepository>,
+    key_ring: JwtKeyRing,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if req.method() != Method::POST {
+        return Ok(next.run(req).await);
+    }
+    let key = match req.headers().get(IDEMPOTENCY_KEY) {
+        Some(value) => parse_key(value)?,
+        None => return Ok(next.run(req).await),
+    };
+    // 未ログインで共通の範囲にすると、別のクライアントに同じキーのレスポンスを返してしまう
+    let scope = scope(&req, &key_ring).ok_or_else(|| {
+        ApiError::new(StatusCode::BAD_REQUEST, ErrorCode::BadRequest)
+            .with_message("Idempotency-Key requires a signed-in session")
+    })?;
+
+    let (parts, body) = req.into_parts();
+    let bytes = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .or(Err(StatusCode::BAD_REQUEST))?;
+    let request_hash = request_hash(&parts.method, &parts.uri, &bytes);
+
+    let record = repository
+        .begin(&scope, &key, &request_hash)
+        .await
+        .map_err(|e| {
+            tracing::error!("failed to begin idempotent request: {}", e);
+            ApiError::from(StatusCode::SERVICE_UNAVAILABLE)
+        })?;
+    if let Some(record) = record {
+        if record.request_hash != request_hash {
+            return Err(ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::IdempotencyKeyReused,
+            ));
+        }
+        return match record.response {
+            Some(response) => Ok(replay(response)),
+            None => Err(ApiError::new(
+                StatusCode::CONFLICT,
+                ErrorCode::IdempotencyRequestInProgress,
+            )),
+        };
+    }
+
+    let res = next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await;
+    // 旧パスからの308転送は処理していないので、転送先に同じキーで送り直せるようにする
+    if res.status().is_server_error() || res.status().is_redirection() {
+        release(repository.as_ref(), &scope, &key).await;
+        return Ok(res);
+    }
+
+    let (parts, body) = res.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            release(repository.as_ref(), &scope, &key).await;
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
+        }
+    };
+    let response = StoredResponse {
+        status: parts.status.as_u16(),
+        headers: parts
+            .headers
+            .iter()
+            .filter(|(name, _)| REPLAYED_HEADERS.contains(name))
+            .filter_map(|(name, value)| {
+                value
+                    .to_str()
+                    .ok()
+                    .map(|value| (name.to_string(), value.to_string()))
+            })
+            .collect(),
+        body: bytes.to_vec(),
+    };
+    // 保存に失敗してもレスポンスは返す。キーは処理中のまま残り、時間が経てば引き継げる
+    if let Err(e) = repository.complete(&scope, &key, &response).await {
+        tracing::error!("failed to save idempotent response: {}", e);
+    }
+
+    Ok(Response::from_parts(parts, Body::from(bytes)))
+}
+
+fn parse_key(value: &HeaderValue) -> Result<String, ApiError> {
+    value
+        .to_str()
+        .ok()
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LENGTH)
+        .map(|key| key.to_string())
+        .ok_or_else(|| {
+            ApiError::new(StatusCode::BAD_REQUEST, ErrorCode::BadRequest).with_message(format!(
+                "Idempotency-Key must be 1 to {} visible ASCII characters",
+                MAX_KEY_LENGTH
+            ))
+        })
+}
+
+/// ログイン中のユーザーごとにキーを扱う。未ログインならNone
+fn scope(req: &Request, key_ring: &JwtKeyRing) -> Option<String> {
+    req.headers()
+        .typed_get::<axum_extra::headers::Cookie>()
+        .and_then(|cookies| {
+            cookies
+                .get("session_token")
+                .and_then(|session_token| decode_jwt(session_token, key_ring).ok())
+        })
+        .map(|claims| format!("user:{}", claims.user_id))
+}
+
+fn request_hash(method: &Method, uri: &Uri, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str());
+    hasher.update(b"\n");
+    hasher.update(uri.to_string());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+fn replay(response: StoredResponse) -> Response {
+    let mut res = Response::new(Body::from(response.body));
+    *res.status_mut() = StatusCode::from_u16(response.status).unwrap_or(StatusCode::OK);
+    for (name, value) in response.headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            res.headers_mut().append(name, value);
+        }
+    }
+    res.headers_mut()
+        .insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    res
+}
+
+async fn release(repository: &dyn IdempotencyRepository, scope: &str, key: &str) {
+    if let Err(e) = repository.release(scope, key).await {
+        tracing::warn!("failed to release idempotency key: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        error::ErrorBody,
+        repositories::{id::UserId, idempotency::IdempotencyRecord},
+        services::user::create_jwt,
+    };
+    use axum::{
+        async_trait,
+        http::header::{COOKIE, SET_COOKIE},
+        middleware::from_fn,
+        routing::post,
+        Router,
+    };
+    use chrono::{DateTime, Duration, Utc};
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+    };
+    use tower::ServiceExt;
+
+    const SECRET_KEY: &str = "secret_key";
+    const USER_ID: &str = "user-a";
+
+    #[derive(Default)]
+    struct FakeRepository {
+        records: Mutex<HashMap<(String, String), IdempotencyRecord>>,
+    }
+
+    #[async_trait]
+    impl IdempotencyRepository for FakeRepository {
+        async fn begin(
+            &self,
+            scope: &str,
+            key: &str,
+            request_hash: &str,
+        ) -> anyhow::Result<Option<IdempotencyRecord>> {
+            let mut records = self.records.lock().unwrap();
+            let id = (scope.to_string(), key.to_string());
+            if let Some(record) = records.get(&id) {
+                return Ok(Some(record.clone()));
+            }
+            records.insert(
+                id,
+                IdempotencyRecord {
+                    request_hash: request_hash.to_string(),
+                    response: None,
+                },
+            );
+            Ok(None)
+        }
+
+        async fn complete(
+            &self,
+            scope: &str,
+            key: &str,
+            response: &StoredResponse,
+        ) -> anyhow::Result<()> {
+            let mut records = self.records.lock().unwrap();
+            if let Some(record) = records.get_mut(&(scope.to_string(), key.to_string())) {
+                record.response = Some(response.clone());
+            }
+            Ok(())
+        }
+
+        async fn release(&self, scope: &str, key: &str) -> anyhow::Result<()> {
+            self.records
+                .lock()
+                .unwrap()
+                .remove(&(scope.to_string(), key.to_string()));
+            Ok(())
+        }
+
+        async fn delete_expired(&self, _before: DateTime<Utc>) -> anyhow::Result<u64> {
+            Ok(0)
+        }
+    }
+
+    /// 呼ばれた回数を返すハンドラ。fail_firstなら1回目だけ500にする
+    fn app(repository: Arc<FakeRepository>, fail_first: bool) -> (Router, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let repository = repository as Arc<dyn IdempotencyRepository>;
+        let router = Router::new()
+            .route(
+                "/participate",
+                post(move |body: String| {
+                    let counter = counter.clone();
+                    async move {
+                        let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                        if fail_first && n == 1 {
+                            return (StatusCode::INTERNAL_SERVER_ERROR, String::new());
+                        }
+                        (StatusCode::CREATED, format!("{}:{}", n, body))
+                    }
+                }),
+            )
+            .route(
+                "/login",
+                post(|| async { ([(SET_COOKIE, "session_token=first")], "logged in") }),
+            )
+            .layer(from_fn(move |req, next| {
+                idempotency_middleware(
+                    repository.clone(),
+                    JwtKeyRing::from_secret(SECRET_KEY),
+                    req,
+                    next,
+                )
+            }));
+        (router, calls)
+    }
+
+    /// user_idがあればそのユーザーでログインしたリクエストにする
+    fn request(key: Option<&str>, body: &str, user_id: Option<&str>) -> Request<Body> {
+        let mut builder = Request::builder().uri("/participate").method("POST");
+        if let Some(key) = key {
+            builder = builder.header(IDEMPOTENCY_KEY, key);
+        }
+        if let Some(user_id) = user_id {
+            let session_token = session_token(&UserId::from(user_id));
+            builder = builder.header(COOKIE, format!("session_token={}", session_token));
+        }
+        builder.body(Body::from(body.to_string())).unwrap()
+    }
+
+    async fn body_string(res: Response) -> String {
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    async fn error_code(res: Response) -> ErrorCode {
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice::<ErrorBody>(&bytes).unwrap().error
+    }
+
+    fn session_token(user_id: &UserId) -> String {
+        let iat = Utc::now().timestamp();
+        let exp = (Utc::now() + Duration::hours(1)).timestamp();
+        create_jwt(user_id, iat, &exp, &JwtKeyRing::from_secret(SECRET_KEY))
+    }
+
+    #[tokio::test]
+    async fn should_replay_response_for_same_key() {
+        let (app, calls) = app(Arc::new(FakeRepository::default()), false);
+
+        let first = app
+            .clone()
+            .oneshot(request(Some("key-1"), "quest", Some(USER_ID)))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, first.status());
+        assert!(first.headers().get(IDEMPOTENT_REPLAYED).is_none());
+        assert_eq!("1:quest", body_string(first).await);
+
+        let second = app
+            .oneshot(request(Some("key-1"), "quest", Some(USER_ID)))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, second.status());
+        assert_eq!("true", second.headers()[IDEMPOTENT_REPLAYED]);
+        assert_eq!("1:quest", body_string(second).await);
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn should_pass_through_without_key() {
+        let (app, calls) = app(Arc::new(FakeRepository::default()), false);
+
+        app.clone()
+            .oneshot(request(None, "quest", None))
+            .await
+            .unwrap();
+        let res = app.oneshot(request(None, "quest", None)).await.unwrap();
+
+        assert_eq!("2:quest", body_string(res).await);
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn should_reject_key_reused_for_different_request() {
+        let (app, calls) = app(Arc::new(FakeRepository::default()), false);
+
+        app.clone()
+            .oneshot(request(Some("key-1"), "quest-a", Some(USER_ID)))
+            .await
+            .unwrap();
+        let res = app
+            .oneshot(request(Some("key-1"), "quest-b", Some(USER_ID)))
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+        assert_eq!(ErrorCode::IdempotencyKeyReused, error_code(res).await);
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn should_reject_while_in_progress() {
+        let repository = Arc::new(FakeRepository::default());
+        let hash = request_hash(&Method::POST, &"/participate".parse().unwrap(), b"quest");
+        repository
+            .begin(&format!("user:{}", USER_ID), "key-1", &hash)
+            .await
+            .unwrap();
+        let (app, calls) = app(repository, false);
+
+        let res = app
+            .oneshot(request(Some("key-1"), "quest", Some(USER_ID)))
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::CONFLICT, res.status());
+        assert_eq!(
+            ErrorCode::IdempotencyRequestInProgress,
+            error_code(res).await
+        );
+        assert_eq!(0, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn should_allow_retry_after_server_error() {
+        let (app, calls) = app(Arc::new(FakeRepository::default()), true);
+
+        let first = app
+            .clone()
+            .oneshot(request(Some("key-1"), "quest", Some(USER_ID)))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, first.status());
+
+        let second = app
+            .oneshot(request(Some("key-1"), "quest", Some(USER_ID)))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, second.status());
+        assert!(second.headers().get(IDEMPOTENT_REPLAYED).is_none());
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn should_separate_keys_by_user() {
+        let (app, calls) = app(Arc::new(FakeRepository::default()), false);
+
+        app.clone()
+            .oneshot(request(Some("key-1"), "quest", Some("user-a")))
+            .await
+            .unwrap();
+        let res = app
+            .oneshot(request(Some("key-1"), "quest", Some("user-b")))
+            .await
+            .unwrap();
+
+        assert!(res.headers().get(IDEMPOTENT_REPLAYED).is_none());
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn should_reject_key_without_session() {
+        let (app, calls) = app(Arc::new(FakeRepository::default()), false);
+
+        let res = app
+            .oneshot(request(Some("key-1"), "quest", None))
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        assert_eq!(ErrorCode::BadRequest, error_code(res).await);
+        assert_eq!(0, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn should_not_replay_set_cookie() {
+        let (app, _) = app(Arc::new(FakeRepository::default()), false);
+        let login = || {
+            let mut req = request(Some("key-1"), "", Some(USER_ID));
+            *req.uri_mut() = "/login".parse().unwrap();
+            req
+        };
+
+        let first = app.clone().oneshot(login()).await.unwrap();
+        assert!(first.headers().get(SET_COOKIE).is_some());
+
+        let second = app.oneshot(login()).await.unwrap();
+        assert_eq!("true", second.headers()[IDEMPOTENT_REPLAYED]);
+        assert!(second.headers().get(SET_COOKIE).is_none());
+        assert!(second.headers().get(CONTENT_TYPE).is_some());
+        assert_eq!("logged in", body_string(second).await);
+    }
+
+    #[tokio::test]
+    async fn should_reject_too_long_key() {
+        let (app, calls) = app(Arc::new(FakeRepository::default()), false);
+        let key = "k".repeat(MAX_KEY_LENGTH + 1);
+
+        let res = app
+            .oneshot(request(Some(&key), "quest", Some(USER_ID)))
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        assert_eq!(0, calls.load(Ordering::SeqCst));
+    }
+}
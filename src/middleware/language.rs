@@ -0,0 +1,205 @@
+use std::convert::Infallible;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::{header::ACCEPT_LANGUAGE, request::Parts},
+};
+use serde::Deserialize;
+
+use crate::config::I18nConfig;
+
+/// レスポンスの本文に使う言語。`?lang=` を`Accept-Language`より優先する
+/// 既定の言語なら翻訳は探さず、quests/challengesの本文をそのまま返す
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Language {
+    /// 翻訳を探す順。既定の言語は含まない
+    candidates: Vec<String>,
+}
+
+impl Language {
+    pub fn negotiate(
+        config: &I18nConfig,
+        lang: Option<&str>,
+        accept_language: Option<&str>,
+    ) -> Self {
+        let requested = lang
+            .and_then(|lang| supported(config, lang))
+            .or_else(|| {
+                accept_language.and_then(|header| {
+                    parse_accept_language(header)
+                        .into_iter()
+                        .find_map(|tag| supported(config, &tag))
+                })
+            })
+            .unwrap_or_else(|| config.default_language.clone());
+
+        let mut candidates = Vec::new();
+        for language in std::iter::once(requested).chain(config.fallback_language.clone()) {
+            if language == config.default_language {
+                // 本文が既定の言語なので、ここから先の候補を探す必要はない
+                break;
+            }
+            if !candidates.contains(&language) {
+                candidates.push(language);
+            }
+        }
+
+        Self { candidates }
+    }
+
+    pub fn candidates(&self) -> &[String] {
+        &self.candidates
+    }
+}
+
+/// 保存できる言語タグ。`ja`や`zh-tw`のように小文字で書く
+pub fn is_valid_language_tag(tag: &str) -> bool {
+    let mut parts = tag.split('-');
+    let primary = parts.next().unwrap_or_default();
+    (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_lowercase())
+        && parts.all(|part| {
+            (2..=8).contains(&part.len())
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        })
+}
+
+/// 完全一致がなければ`en-US`を`en`のように主言語で探す
+fn supported(config: &I18nConfig, tag: &str) -> Option<String> {
+    let tag = tag.trim().to_lowercase();
+    if config.supported_languages.contains(&tag) {
+        return Some(tag);
+    }
+    let primary = tag.split('-').next()?;
+    config
+        .supported_languages
+        .iter()
+        .find(|language| language.as_str() == primary)
+        .cloned()
+}
+
+/// q値の高い順に並べる。q=0と`*`は除く
+fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut tags: Vec<(String, f32)> = header
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then(|| (tag.to_string(), quality))
+        })
+        .collect();
+    // 同じq値なら書かれた順を保つ
+    tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+    tags.into_iter().map(|(tag, _)| tag).collect()
+}
+
+#[derive(Deserialize)]
+struct LangQuery {
+    lang: Option<String>,
+}
+
+/// 設定はcreate_appでExtensionとして渡す。なければ既定の設定を使う(ルータ単体のテスト等)
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Language {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = parts
+            .extensions
+            .get::<I18nConfig>()
+            .cloned()
+            .unwrap_or_default();
+        let lang = Query::<LangQuery>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .and_then(|Query(query)| query.lang);
+        let accept_language = parts
+            .headers
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok());
+
+        Ok(Self::negotiate(&config, lang.as_deref(), accept_language))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(fallback_language: Option<&str>) -> I18nConfig {
+        I18nConfig {
+            default_language: "ja".to_string(),
+            supported_languages: vec!["ja".to_string(), "en".to_string(), "zh-tw".to_string()],
+            fallback_language: fallback_language.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn should_prefer_query_over_accept_language() {
+        let language = Language::negotiate(&config(None), Some("en"), Some("zh-TW"));
+
+        assert_eq!(vec!["en".to_string()], language.candidates());
+    }
+
+    #[test]
+    fn should_pick_best_supported_accept_language() {
+        let language = Language::negotiate(
+            &config(None),
+            None,
+            Some("fr;q=0.9, en-US;q=0.8, zh-TW, *;q=0.1"),
+        );
+
+        assert_eq!(vec!["zh-tw".to_string()], language.candidates());
+
+        let language = Language::negotiate(&config(None), None, Some("fr, en-GB;q=0.5"));
+
+        assert_eq!(vec!["en".to_string()], language.candidates());
+    }
+
+    #[test]
+    fn should_not_translate_default_or_unsupported_language() {
+        assert!(Language::negotiate(&config(None), Some("ja"), None)
+            .candidates()
+            .is_empty());
+        assert!(Language::negotiate(&config(None), Some("fr"), Some("de"))
+            .candidates()
+            .is_empty());
+        assert!(Language::negotiate(&config(None), None, None)
+            .candidates()
+            .is_empty());
+    }
+
+    #[test]
+    fn should_append_fallback_language() {
+        let language = Language::negotiate(&config(Some("en")), Some("zh-tw"), None);
+
+        assert_eq!(
+            vec!["zh-tw".to_string(), "en".to_string()],
+            language.candidates()
+        );
+        let language = Language::negotiate(&config(Some("en")), None, None);
+
+        assert!(language.candidates().is_empty());
+    }
+
+    #[test]
+    fn should_validate_language_tag() {
+        assert!(is_valid_language_tag("en"));
+        assert!(is_valid_language_tag("zh-tw"));
+        assert!(!is_valid_language_tag("EN"));
+        assert!(!is_valid_language_tag("english"));
+        assert!(!is_valid_language_tag("en-"));
+        assert!(!is_valid_language_tag("../en"));
+    }
+
+    #[test]
+    fn should_ignore_zero_quality() {
+        assert_eq!(vec!["ja".to_string()], parse_accept_language("en;q=0, ja"));
+    }
+}
@@ -0,0 +1,145 @@
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{DefaultBodyLimit, Request},
+    http::StatusCode,
+    middleware::{from_fn, Next},
+    response::{IntoResponse, Response},
+    BoxError, Router,
+};
+use tower::ServiceBuilder;
+use tower_http::{
+    limit::RequestBodyLimitLayer, map_request_body::MapRequestBodyLayer, timeout::TimeoutLayer,
+};
+
+use crate::{config::RequestLimitConfig, error::ApiError, middleware::error::is_json};
+
+/// 巨大な本文や遅いクライアントでworkerを占有されないよう、すべてのルートに上限を掛ける
+/// Content-Lengthが上限を超えるものはハンドラに渡さずに413を返す
+/// Content-Lengthなしで上限を超えたときも、本文を読んだextractorのrejectionとして413になる
+pub fn with_request_limit(router: Router, config: RequestLimitConfig) -> Router {
+    router.layer(
+        ServiceBuilder::new()
+            .layer(from_fn(limit_error_middleware))
+            .layer(RequestBodyLimitLayer::new(config.max_body_bytes))
+            .layer(TimeoutLayer::new(config.timeout))
+            // 上限付きの本文をルーターが受け取れるBodyに戻す
+            .layer(MapRequestBodyLayer::new(into_body))
+            // 上限はここで掛けるので、extractorごとの既定の上限(2MB)は外す
+            .layer(DefaultBodyLimit::disable()),
+    )
+}
+
+/// tower-httpが返す413と408は本文がテキストか空なので、エラーコード付きのJSONにする
+async fn limit_error_middleware(req: Request, next: Next) -> Response {
+    let res = next.run(req).await;
+    match res.status() {
+        StatusCode::PAYLOAD_TOO_LARGE | StatusCode::REQUEST_TIMEOUT if !is_json(&res) => {
+            ApiError::from(res.status()).into_response()
+        }
+        _ => res,
+    }
+}
+
+fn into_body<B>(body: B) -> Body
+where
+    B: HttpBody<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    Body::new(body)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::{ErrorBody, ErrorCode};
+    use axum::{http::header::CONTENT_LENGTH, routing::post, Json};
+    use serde_json::Value;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    async fn echo(Json(payload): Json<Value>) -> Json<Value> {
+        Json(payload)
+    }
+
+    async fn slow() -> StatusCode {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        StatusCode::OK
+    }
+
+    fn app() -> Router {
+        let router = Router::new()
+            .route("/echo", post(echo))
+            .route("/slow", post(slow));
+        with_request_limit(
+            router,
+            RequestLimitConfig {
+                max_body_bytes: 16,
+                timeout: Duration::from_millis(10),
+            },
+        )
+    }
+
+    fn request(uri: &str, body: &str) -> Request<Body> {
+        Request::builder()
+            .uri(uri)
+            .method("POST")
+            .header("content-type", "application/json")
+            .header(CONTENT_LENGTH, body.len())
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn error_body(res: Response) -> ErrorBody {
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_pass_body_within_limit() {
+        let res = app().oneshot(request("/echo", r#"{"a":1}"#)).await.unwrap();
+
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(r#"{"a":1}"#.as_bytes(), &bytes[..]);
+    }
+
+    #[tokio::test]
+    async fn should_pass_body_over_extractor_default_limit() {
+        let router = Router::new().route("/echo", post(echo));
+        let app = with_request_limit(
+            router,
+            RequestLimitConfig {
+                max_body_bytes: 4 * 1024 * 1024,
+                timeout: Duration::from_secs(10),
+            },
+        );
+        let body = format!(r#"{{"a":"{}"}}"#, "0".repeat(3 * 1024 * 1024));
+
+        let res = app.oneshot(request("/echo", &body)).await.unwrap();
+
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_reject_too_large_body_as_json() {
+        let res = app()
+            .oneshot(request("/echo", r#"{"a":"0123456789"}"#))
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, res.status());
+        assert_eq!(ErrorCode::PayloadTooLarge, error_body(res).await.error);
+    }
+
+    #[tokio::test]
+    async fn should_time_out_slow_handler_as_json() {
+        let res = app().oneshot(request("/slow", "{}")).await.unwrap();
+
+        assert_eq!(StatusCode::REQUEST_TIMEOUT, res.status());
+        assert_eq!(ErrorCode::RequestTimeout, error_body(res).await.error);
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use metrics::{histogram, increment_counter};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+const REQUEST_DURATION_METRIC: &str = "http_request_duration_seconds";
+/// 5ms〜10sのレイテンシを見分けられるバケット
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// プロセス全体で1回だけ呼ぶ。2回目以降はpanicする
+pub fn install_metrics_recorder() -> PrometheusHandle {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(REQUEST_DURATION_METRIC.to_string()),
+            &LATENCY_BUCKETS,
+        )
+        .expect("invalid latency buckets")
+        .install_recorder()
+        .expect("failed to install metrics recorder")
+}
+
+/// ハンドラごとのリクエスト数・レイテンシ・5xxの数を記録する
+/// MatchedPathを使うので、Router::route_layerで付けること
+pub async fn metrics_middleware(req: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    // パスそのままだとIDごとに系列が増えるので、"/quests/:id"のようなルート定義を使う
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let response = next.run(req).await;
+
+    let status = response.status();
+    increment_counter!(
+        "http_requests_total",
+        "method" => method.clone(),
+        "route" => route.clone(),
+        "status" => status.as_u16().to_string(),
+    );
+    histogram!(
+        REQUEST_DURATION_METRIC,
+        start.elapsed().as_secs_f64(),
+        "method" => method.clone(),
+        "route" => route.clone(),
+    );
+    if status.is_server_error() {
+        increment_counter!(
+            "http_requests_errors_total",
+            "method" => method,
+            "route" => route,
+        );
+    }
+
+    response
+}
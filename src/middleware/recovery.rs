@@ -0,0 +1,149 @@
+use std::{
+    panic::AssertUnwindSafe,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use axum::{
+    extract::Request,
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::FutureExt;
+use nanoid::nanoid;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ErrorCode;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// 起動してからのpanic回数
+static PANIC_COUNT: AtomicU64 = AtomicU64::new(0);
+
+pub fn panic_count() -> u64 {
+    PANIC_COUNT.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InternalErrorBody {
+    pub error: ErrorCode,
+    pub request_id: String,
+}
+
+/// handlerのpanicを500のJSONに変換し、プロセスを落とさずに次のリクエストを受け付ける
+/// 問い合わせ時にログと突き合わせられるよう、すべてのレスポンスにリクエストIDを付ける
+pub async fn recovery_middleware(req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid_request_id(value))
+        .map(str::to_string)
+        .unwrap_or_else(|| nanoid!());
+
+    let mut res = match AssertUnwindSafe(next.run(req)).catch_unwind().await {
+        Ok(res) => res,
+        Err(panic) => {
+            PANIC_COUNT.fetch_add(1, Ordering::Relaxed);
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            tracing::error!("request {} panicked: {}", request_id, message);
+
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(InternalErrorBody {
+                    error: ErrorCode::InternalServerError,
+                    request_id: request_id.clone(),
+                }),
+            )
+                .into_response()
+        }
+    };
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    res
+}
+
+/// クライアントから渡されたIDはログに出すので、長さと文字種を制限する
+fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 64
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::Body;
+    use axum::{middleware::from_fn, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn ok_handler() -> impl IntoResponse {
+        StatusCode::OK
+    }
+
+    async fn panic_handler() -> StatusCode {
+        panic!("boom")
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route("/ok", get(ok_handler))
+            .route("/panic", get(panic_handler))
+            .layer(from_fn(recovery_middleware))
+    }
+
+    #[tokio::test]
+    async fn should_convert_panic_into_500_json() {
+        let before = panic_count();
+        let req = Request::builder()
+            .uri("/panic")
+            .body(Body::empty())
+            .unwrap();
+
+        let res = app().oneshot(req).await.unwrap();
+
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, res.status());
+        let header = res.headers()[REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: InternalErrorBody = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(ErrorCode::InternalServerError, body.error);
+        assert_eq!(header, body.request_id);
+        assert!(panic_count() > before);
+    }
+
+    #[tokio::test]
+    async fn should_keep_valid_request_id_from_client() {
+        let req = Request::builder()
+            .uri("/ok")
+            .header(REQUEST_ID_HEADER, "client-request-1")
+            .body(Body::empty())
+            .unwrap();
+
+        let res = app().oneshot(req).await.unwrap();
+
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("client-request-1", res.headers()[REQUEST_ID_HEADER]);
+    }
+
+    #[test]
+    fn should_reject_unsafe_request_id() {
+        assert!(is_valid_request_id("abc-123_XYZ"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("has space"));
+        assert!(!is_valid_request_id(&"a".repeat(65)));
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header::CONTENT_LENGTH, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use serde_json::Value;
+
+use crate::{
+    middleware::error::is_json,
+    services::image_url::{sign_image_urls, ImageUrlSigner},
+};
+
+/// JSONのレスポンスに含まれる画像URLを署名付きに置き換える
+/// エンティティやキャッシュには署名前のURLを持たせ、期限切れのURLが残らないよう返す直前で変換する
+pub async fn signed_url_middleware(
+    signer: Option<Arc<dyn ImageUrlSigner>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let res = next.run(req).await;
+    let signer = match signer {
+        Some(signer) if is_json(&res) => signer,
+        _ => return res,
+    };
+
+    let (mut parts, body) = res.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let mut value = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(value) => value,
+        Err(_) => return Response::from_parts(parts, Body::from(bytes)),
+    };
+    if !sign_image_urls(&mut value, signer.as_ref(), Utc::now()) {
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+
+    parts.headers.remove(CONTENT_LENGTH);
+    let bytes = serde_json::to_vec(&value).unwrap_or_default();
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{middleware::from_fn, routing::get, Json, Router};
+    use chrono::DateTime;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    struct PrefixSigner;
+
+    impl ImageUrlSigner for PrefixSigner {
+        fn sign(&self, url: &str, _now: DateTime<Utc>) -> Option<String> {
+            url.starts_with("https://cdn/")
+                .then(|| format!("{}?signed", url))
+        }
+    }
+
+    fn app(signer: Option<Arc<dyn ImageUrlSigner>>) -> Router {
+        Router::new()
+            .route(
+                "/quest",
+                get(|| async { Json(json!({ "cover_image_url": "https://cdn/cover.jpg" })) }),
+            )
+            .route("/text", get(|| async { "https://cdn/cover.jpg" }))
+            .layer(from_fn(move |req, next| {
+                signed_url_middleware(signer.clone(), req, next)
+            }))
+    }
+
+    async fn body_of(app: Router, path: &str) -> String {
+        let req = Request::builder().uri(path).body(Body::empty()).unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_sign_urls_in_json() {
+        let body = body_of(app(Some(Arc::new(PrefixSigner))), "/quest").await;
+
+        assert_eq!(
+            json!({ "cover_image_url": "https://cdn/cover.jpg?signed" }),
+            serde_json::from_str::<Value>(&body).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn should_pass_through_without_signer_or_json() {
+        let body = body_of(app(None), "/quest").await;
+        assert_eq!(
+            json!({ "cover_image_url": "https://cdn/cover.jpg" }),
+            serde_json::from_str::<Value>(&body).unwrap()
+        );
+
+        let body = body_of(app(Some(Arc::new(PrefixSigner))), "/text").await;
+        assert_eq!("https://cdn/cover.jpg", body);
+    }
+}
@@ -0,0 +1,236 @@
+use std::convert::Infallible;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request},
+    http::{
+        header::{LINK, LOCATION},
+        request::Parts,
+        HeaderValue, StatusCode, Uri,
+    },
+    middleware::{from_fn, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use chrono::{DateTime, Utc};
+
+use crate::config::{LegacyRoutes, LegacyRoutesConfig};
+
+/// APIのメジャーバージョン。破壊的変更のときだけ増やす
+/// ハンドラは引数に取ると、呼ばれたバージョンに合わせてレスポンスの形を切り替えられる
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiVersion {
+    V1,
+}
+
+impl ApiVersion {
+    pub const LATEST: Self = Self::V1;
+
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::V1 => "/v1",
+        }
+    }
+}
+
+/// バージョン付きのパスを通っていなければ最新版として扱う(ルータ単体のテスト等)
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ApiVersion {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<ApiVersion>()
+            .copied()
+            .unwrap_or(Self::LATEST))
+    }
+}
+
+/// apiを/v1配下に置き、旧パスは設定に従って互換ハンドラか308転送で残す
+/// バージョンを増やすときは、同じようにprefixを付けてnestする
+pub fn with_versions(api: Router, legacy_routes: LegacyRoutesConfig) -> Router {
+    let router = Router::new().nest(
+        ApiVersion::V1.prefix(),
+        api.clone().layer(from_fn(|req, next| {
+            version_middleware(ApiVersion::V1, req, next)
+        })),
+    );
+
+    let sunset = legacy_routes.sunset;
+    match legacy_routes.mode {
+        LegacyRoutes::Serve => router.merge(api.layer(from_fn(move |req, next| {
+            legacy_middleware(sunset, req, next)
+        }))),
+        LegacyRoutes::Redirect => router.fallback(move |uri: Uri| legacy_redirect(sunset, uri)),
+        LegacyRoutes::Disabled => router,
+    }
+}
+
+async fn version_middleware(version: ApiVersion, mut req: Request, next: Next) -> Response {
+    req.extensions_mut().insert(version);
+    next.run(req).await
+}
+
+/// 旧パスは互換のためv1として処理し、移行先をヘッダで知らせる
+async fn legacy_middleware(
+    sunset: Option<DateTime<Utc>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let successor = successor(req.uri());
+    req.extensions_mut().insert(ApiVersion::V1);
+    let mut res = next.run(req).await;
+    add_deprecation_headers(&mut res, &successor, sunset);
+    res
+}
+
+/// 308なのでメソッドと本文を保ったまま転送される
+async fn legacy_redirect(sunset: Option<DateTime<Utc>>, uri: Uri) -> Response {
+    // /v1配下で見つからなかったものは転送しても見つからない
+    if is_versioned(uri.path()) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let successor = successor(&uri);
+    let mut res = StatusCode::PERMANENT_REDIRECT.into_response();
+    if let Ok(location) = HeaderValue::from_str(&successor) {
+        res.headers_mut().insert(LOCATION, location);
+    }
+    add_deprecation_headers(&mut res, &successor, sunset);
+    res
+}
+
+fn is_versioned(path: &str) -> bool {
+    let prefix = ApiVersion::V1.prefix();
+    path == prefix || path.starts_with(&format!("{}/", prefix))
+}
+
+/// 旧パスに対応する/v1のパス。クエリはそのまま引き継ぐ
+fn successor(uri: &Uri) -> String {
+    let path_and_query = uri
+        .path_and_query()
+        .map_or(uri.path(), |path_and_query| path_and_query.as_str());
+    format!("{}{}", ApiVersion::V1.prefix(), path_and_query)
+}
+
+fn add_deprecation_headers(res: &mut Response, successor: &str, sunset: Option<DateTime<Utc>>) {
+    let headers = res.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    if let Ok(link) = HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor))
+    {
+        headers.insert(LINK, link);
+    }
+    if let Some(sunset) = sunset {
+        // SunsetヘッダはHTTP-date形式
+        if let Ok(sunset) =
+            HeaderValue::from_str(&sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+        {
+            headers.insert("sunset", sunset);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{
+        body::Body,
+        routing::{get, post},
+    };
+    use tower::ServiceExt;
+
+    async fn version(version: ApiVersion) -> String {
+        format!("{:?}", version)
+    }
+
+    async fn echo(body: String) -> String {
+        body
+    }
+
+    fn app(mode: LegacyRoutes, sunset: Option<DateTime<Utc>>) -> Router {
+        let api = Router::new()
+            .route("/version", get(version))
+            .route("/echo", post(echo));
+        with_versions(api, LegacyRoutesConfig { mode, sunset })
+    }
+
+    fn request(uri: &str, method: &str) -> Request<Body> {
+        Request::builder()
+            .uri(uri)
+            .method(method)
+            .body(Body::from("payload"))
+            .unwrap()
+    }
+
+    async fn body_string(res: Response) -> String {
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_serve_versioned_routes() {
+        let res = app(LegacyRoutes::Disabled, None)
+            .oneshot(request("/v1/version", "GET"))
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::OK, res.status());
+        assert!(res.headers().get("deprecation").is_none());
+        assert_eq!("V1", body_string(res).await);
+    }
+
+    #[tokio::test]
+    async fn should_serve_legacy_routes_with_deprecation_headers() {
+        let sunset = DateTime::parse_from_rfc3339("2024-06-30T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let res = app(LegacyRoutes::Serve, Some(sunset))
+            .oneshot(request("/version?lang=ja", "GET"))
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("true", res.headers()["deprecation"]);
+        assert_eq!(
+            "</v1/version?lang=ja>; rel=\"successor-version\"",
+            res.headers()[LINK]
+        );
+        assert_eq!("Sun, 30 Jun 2024 00:00:00 GMT", res.headers()["sunset"]);
+        assert_eq!("V1", body_string(res).await);
+    }
+
+    #[tokio::test]
+    async fn should_redirect_legacy_routes_keeping_method() {
+        let res = app(LegacyRoutes::Redirect, None)
+            .oneshot(request("/echo?dry_run=true", "POST"))
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::PERMANENT_REDIRECT, res.status());
+        assert_eq!("/v1/echo?dry_run=true", res.headers()[LOCATION]);
+        assert_eq!("true", res.headers()["deprecation"]);
+    }
+
+    #[tokio::test]
+    async fn should_not_redirect_unknown_versioned_routes() {
+        let res = app(LegacyRoutes::Redirect, None)
+            .oneshot(request("/v1/missing", "GET"))
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_not_serve_legacy_routes_when_disabled() {
+        let res = app(LegacyRoutes::Disabled, None)
+            .oneshot(request("/version", "GET"))
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+}
@@ -1,5 +1,224 @@
+pub mod achievement;
+pub mod api_key;
+pub mod audit;
+pub mod ban;
+pub mod bookmark;
+pub mod cached_quest;
+pub mod cascade;
+pub mod certificate;
 pub mod challenge;
+pub mod device;
+pub mod factory;
+pub mod feature_flag;
+pub mod follow;
+pub mod hint;
+pub mod id;
+pub mod idempotency;
+pub mod job;
+pub mod metadata;
+pub mod point;
+pub mod preference;
 pub mod quest;
+pub mod quest_template;
+pub mod review;
+pub mod scheduled_job;
+pub mod session;
+pub mod stats;
+pub mod translation;
+pub mod unit_of_work;
 pub mod user;
 pub mod user_challenge;
 pub mod user_quest;
+pub mod webhook;
+
+use log::LevelFilter;
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    ConnectOptions, PgPool,
+};
+use std::{str::FromStr, sync::Arc};
+
+use crate::config::PoolConfig;
+
+use self::{
+    achievement::{AchievementRepository, AchievementRepositoryForDb},
+    api_key::{ApiKeyRepository, ApiKeyRepositoryForDb},
+    audit::{AuditRepository, AuditRepositoryForDb},
+    ban::{BanRepository, BanRepositoryForDb},
+    bookmark::{BookmarkRepository, BookmarkRepositoryForDb},
+    certificate::{CertificateRepository, CertificateRepositoryForDb},
+    challenge::{ChallengeRepository, ChallengeRepositoryForDb},
+    device::{DeviceRepository, DeviceRepositoryForDb},
+    feature_flag::{FeatureFlagRepository, FeatureFlagRepositoryForDb},
+    follow::{FollowRepository, FollowRepositoryForDb},
+    hint::{HintRepository, HintRepositoryForDb},
+    id::{default_id_generator, IdGenerator},
+    idempotency::{IdempotencyRepository, IdempotencyRepositoryForDb},
+    job::{JobRepository, JobRepositoryForDb},
+    point::{PointRepository, PointRepositoryForDb},
+    preference::{PreferenceRepository, PreferenceRepositoryForDb},
+    quest::{QuestRepository, QuestRepositoryForDb},
+    quest_template::{QuestTemplateRepository, QuestTemplateRepositoryForDb},
+    review::{ReviewRepository, ReviewRepositoryForDb},
+    scheduled_job::{ScheduledJobRepository, ScheduledJobRepositoryForDb},
+    session::{SessionRepository, SessionRepositoryForDb},
+    stats::{StatsRepository, StatsRepositoryForDb},
+    translation::{TranslationRepository, TranslationRepositoryForDb},
+    unit_of_work::{UnitOfWork, UnitOfWorkForDb},
+    user::{UserRepository, UserRepositoryForDb},
+    user_challenge::{UserChallengeRepository, UserChallengeRepositoryForDb},
+    user_quest::{UserQuestRepository, UserQuestRepositoryForDb},
+    webhook::{WebhookRepository, WebhookRepositoryForDb},
+};
+
+/// 設定に従ってDBに接続する。しきい値を超えたクエリはSQL付きでwarnログに出す
+pub async fn connect(database_url: &str, config: &PoolConfig) -> anyhow::Result<PgPool> {
+    let (pool_options, options) = pool_options(database_url, config)?;
+    let pool = pool_options.connect_with(options).await?;
+
+    Ok(pool)
+}
+
+/// connectと同じ設定で、最初に使うときまで接続しないプールを作る
+/// URLの形式だけを確認するので、DBに届かなくてもここでは失敗しない
+pub fn connect_lazy(database_url: &str, config: &PoolConfig) -> anyhow::Result<PgPool> {
+    let (pool_options, options) = pool_options(database_url, config)?;
+
+    Ok(pool_options.connect_lazy_with(options))
+}
+
+fn pool_options(
+    database_url: &str,
+    config: &PoolConfig,
+) -> anyhow::Result<(PgPoolOptions, PgConnectOptions)> {
+    let mut options = PgConnectOptions::from_str(database_url)?;
+    options.log_slow_statements(LevelFilter::Warn, config.slow_query_threshold);
+
+    let pool_options = PgPoolOptions::new()
+        .max_connections(config.max_connections)
+        .acquire_timeout(config.acquire_timeout)
+        .idle_timeout(config.idle_timeout);
+
+    Ok((pool_options, options))
+}
+
+/// 書き込み用と読み取り用のプール。レプリカを使わなければどちらも同じプールを指す
+/// 書いた直後に読み直すクエリやトランザクションの中はwriteを使う
+#[derive(Debug, Clone)]
+pub struct ReadWritePgPool {
+    write: PgPool,
+    read: PgPool,
+}
+
+impl ReadWritePgPool {
+    pub fn new(write: PgPool, read: Option<PgPool>) -> Self {
+        let read = read.unwrap_or_else(|| write.clone());
+        Self { write, read }
+    }
+
+    /// プライマリ。書き込みと、最新の値が必要な読み取りに使う
+    pub fn write(&self) -> &PgPool {
+        &self.write
+    }
+
+    /// リードレプリカ。数秒の遅れを許せる読み取りに使う
+    pub fn read(&self) -> &PgPool {
+        &self.read
+    }
+
+    /// 同じプールを指していれば2回目は何もしない
+    pub async fn close(&self) {
+        self.write.close().await;
+        self.read.close().await;
+    }
+}
+
+impl From<PgPool> for ReadWritePgPool {
+    fn from(pool: PgPool) -> Self {
+        Self::new(pool, None)
+    }
+}
+
+/// アプリ全体で使うリポジトリ。実装を差し替えてもcreate_appのシグネチャは変わらない
+#[derive(Clone)]
+pub struct Repositories {
+    pub quest: Arc<dyn QuestRepository>,
+    pub user: Arc<dyn UserRepository>,
+    pub challenge: Arc<dyn ChallengeRepository>,
+    pub userquest: Arc<dyn UserQuestRepository>,
+    pub userchallenge: Arc<dyn UserChallengeRepository>,
+    pub point: Arc<dyn PointRepository>,
+    pub achievement: Arc<dyn AchievementRepository>,
+    pub unit_of_work: Arc<dyn UnitOfWork>,
+    pub webhook: Arc<dyn WebhookRepository>,
+    pub device: Arc<dyn DeviceRepository>,
+    pub certificate: Arc<dyn CertificateRepository>,
+    pub audit: Arc<dyn AuditRepository>,
+    pub job: Arc<dyn JobRepository>,
+    pub scheduled_job: Arc<dyn ScheduledJobRepository>,
+    pub session: Arc<dyn SessionRepository>,
+    pub idempotency: Arc<dyn IdempotencyRepository>,
+    pub follow: Arc<dyn FollowRepository>,
+    pub bookmark: Arc<dyn BookmarkRepository>,
+    pub review: Arc<dyn ReviewRepository>,
+    pub translation: Arc<dyn TranslationRepository>,
+    pub stats: Arc<dyn StatsRepository>,
+    pub api_key: Arc<dyn ApiKeyRepository>,
+    pub feature_flag: Arc<dyn FeatureFlagRepository>,
+    pub quest_template: Arc<dyn QuestTemplateRepository>,
+    pub preference: Arc<dyn PreferenceRepository>,
+    pub hint: Arc<dyn HintRepository>,
+    pub ban: Arc<dyn BanRepository>,
+}
+
+impl Repositories {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        Self::with_id_generator(pool, default_id_generator())
+    }
+
+    /// 新しい行のIDはidsで払い出す
+    pub fn with_id_generator(pool: impl Into<ReadWritePgPool>, ids: Arc<dyn IdGenerator>) -> Self {
+        let pool = pool.into();
+        Self {
+            quest: Arc::new(QuestRepositoryForDb::new(pool.clone()).with_id_generator(ids.clone())),
+            user: Arc::new(UserRepositoryForDb::new(pool.clone()).with_id_generator(ids.clone())),
+            challenge: Arc::new(
+                ChallengeRepositoryForDb::new(pool.clone()).with_id_generator(ids.clone()),
+            ),
+            userquest: Arc::new(UserQuestRepositoryForDb::new(pool.clone())),
+            userchallenge: Arc::new(UserChallengeRepositoryForDb::new(pool.clone())),
+            point: Arc::new(PointRepositoryForDb::new(pool.clone())),
+            achievement: Arc::new(AchievementRepositoryForDb::new(pool.clone())),
+            unit_of_work: Arc::new(UnitOfWorkForDb::new(pool.clone())),
+            webhook: Arc::new(
+                WebhookRepositoryForDb::new(pool.clone()).with_id_generator(ids.clone()),
+            ),
+            device: Arc::new(DeviceRepositoryForDb::new(pool.clone())),
+            certificate: Arc::new(
+                CertificateRepositoryForDb::new(pool.clone()).with_id_generator(ids.clone()),
+            ),
+            audit: Arc::new(AuditRepositoryForDb::new(pool.clone())),
+            job: Arc::new(JobRepositoryForDb::new(pool.clone()).with_id_generator(ids.clone())),
+            scheduled_job: Arc::new(ScheduledJobRepositoryForDb::new(pool.clone())),
+            session: Arc::new(SessionRepositoryForDb::new(pool.clone())),
+            idempotency: Arc::new(IdempotencyRepositoryForDb::new(pool.clone())),
+            follow: Arc::new(FollowRepositoryForDb::new(pool.clone())),
+            bookmark: Arc::new(BookmarkRepositoryForDb::new(pool.clone())),
+            review: Arc::new(
+                ReviewRepositoryForDb::new(pool.clone()).with_id_generator(ids.clone()),
+            ),
+            translation: Arc::new(TranslationRepositoryForDb::new(pool.clone())),
+            stats: Arc::new(StatsRepositoryForDb::new(pool.clone())),
+            api_key: Arc::new(
+                ApiKeyRepositoryForDb::new(pool.clone()).with_id_generator(ids.clone()),
+            ),
+            feature_flag: Arc::new(FeatureFlagRepositoryForDb::new(pool.clone())),
+            quest_template: Arc::new(
+                QuestTemplateRepositoryForDb::new(pool.clone()).with_id_generator(ids.clone()),
+            ),
+            preference: Arc::new(PreferenceRepositoryForDb::new(pool.clone())),
+            hint: Arc::new(HintRepositoryForDb::new(pool.clone()).with_id_generator(ids)),
+            ban: Arc::new(BanRepositoryForDb::new(pool)),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection};
+
+use super::{id::UserId, ReadWritePgPool};
+
+#[async_trait]
+pub trait AchievementRepository: Send + Sync + 'static {
+    async fn all(&self) -> anyhow::Result<Vec<Achievement>>;
+    /// 付与済みの場合は何もしない
+    async fn grant(&self, user_id: UserId, achievement_id: String) -> anyhow::Result<()>;
+    async fn find_by_user_id(&self, user_id: UserId) -> anyhow::Result<Vec<UserAchievement>>;
+    async fn get_progress(&self, user_id: UserId) -> anyhow::Result<AchievementProgress>;
+}
+
+#[derive(Debug, Clone)]
+pub struct AchievementRepositoryForDb {
+    pool: ReadWritePgPool,
+}
+
+impl AchievementRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        AchievementRepositoryForDb { pool: pool.into() }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        AchievementRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl AchievementRepository for AchievementRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn all(&self) -> anyhow::Result<Vec<Achievement>> {
+        let rows = sqlx::query_as::<_, AchievementFromRow>(
+            r#"
+                select * from achievements order by kind, threshold;
+            "#,
+        )
+        .fetch_all(self.pool.read())
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                anyhow::Ok(Achievement {
+                    id: row.id,
+                    name: row.name,
+                    description: row.description,
+                    kind: row.kind.parse()?,
+                    threshold: row.threshold,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn grant(&self, user_id: UserId, achievement_id: String) -> anyhow::Result<()> {
+        let mut conn = self.pool.write().acquire().await?;
+        insert_user_achievement(&mut conn, user_id, achievement_id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_by_user_id(&self, user_id: UserId) -> anyhow::Result<Vec<UserAchievement>> {
+        let mut conn = self.pool.read().acquire().await?;
+        select_user_achievements(&mut conn, user_id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn get_progress(&self, user_id: UserId) -> anyhow::Result<AchievementProgress> {
+        let mut conn = self.pool.write().acquire().await?;
+        select_progress(&mut conn, user_id).await
+    }
+}
+
+// 以下はUnitOfWorkのトランザクションからも呼べるようにコネクションを受け取る
+
+pub(super) async fn insert_user_achievement(
+    conn: &mut PgConnection,
+    user_id: UserId,
+    achievement_id: String,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+            insert into user_achievements (user_id, achievement_id) values ($1, $2)
+            on conflict on constraint unique_user_achievement_pair do nothing
+        "#,
+    )
+    .bind(user_id)
+    .bind(achievement_id)
+    .execute(conn)
+    .await?;
+
+    anyhow::Ok(())
+}
+
+pub(super) async fn select_user_achievements(
+    conn: &mut PgConnection,
+    user_id: UserId,
+) -> anyhow::Result<Vec<UserAchievement>> {
+    let achievements = sqlx::query_as::<_, UserAchievement>(
+        r#"
+            select a.id, a.name, a.description, ua.achieved_at
+            from user_achievements ua
+            join achievements a on a.id = ua.achievement_id
+            where ua.user_id = $1
+            order by ua.achieved_at;
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(conn)
+    .await?;
+
+    anyhow::Ok(achievements)
+}
+
+pub(super) async fn select_progress(
+    conn: &mut PgConnection,
+    user_id: UserId,
+) -> anyhow::Result<AchievementProgress> {
+    let completed_challenges = sqlx::query_scalar::<_, i64>(
+        r#"
+            select count(*) from user_completed_challenges where user_id = $1;
+        "#,
+    )
+    .bind(user_id.clone())
+    .fetch_one(&mut *conn)
+    .await?;
+
+    // クエスト内のチャレンジをすべて完了していれば制覇とみなす
+    let cleared_quests = sqlx::query_scalar::<_, i64>(
+        r#"
+            select count(*) from (
+                select c.quest_id from challenges c
+                left join user_completed_challenges ucc
+                    on ucc.challenge_id = c.id and ucc.user_id = $1
+                group by c.quest_id
+                having count(*) = count(ucc.challenge_id)
+            ) cleared;
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(conn)
+    .await?;
+
+    anyhow::Ok(AchievementProgress {
+        completed_challenges,
+        cleared_quests,
+    })
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct AchievementFromRow {
+    id: String,
+    name: String,
+    description: String,
+    kind: String,
+    threshold: i32,
+}
+
+/// 実績の達成条件の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AchievementKind {
+    ChallengesCompleted,
+    QuestsCleared,
+}
+
+impl std::str::FromStr for AchievementKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "challenges_completed" => Ok(Self::ChallengesCompleted),
+            "quests_cleared" => Ok(Self::QuestsCleared),
+            _ => Err(anyhow::anyhow!("Invalid achievement kind : {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Achievement {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub kind: AchievementKind,
+    pub threshold: i32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, FromRow, PartialEq)]
+pub struct UserAchievement {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub achieved_at: DateTime<Utc>,
+}
+
+/// 実績の判定に使うユーザーの進捗
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AchievementProgress {
+    pub completed_challenges: i64,
+    pub cleared_quests: i64,
+}
@@ -0,0 +1,210 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use nanoid::nanoid;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::FromRow;
+use std::sync::Arc;
+
+use super::{
+    id::{default_id_generator, IdGenerator},
+    ReadWritePgPool,
+};
+
+const KEY_PREFIX: &str = "qk_";
+/// 一覧に出す先頭部分の長さ。KEY_PREFIXを含む
+const DISPLAY_PREFIX_LENGTH: usize = 11;
+const MAX_NAME_LENGTH: usize = 100;
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait ApiKeyRepository: Send + Sync + 'static {
+    /// 平文のキーは戻り値でしか取れない
+    async fn issue(&self, payload: IssueApiKey) -> anyhow::Result<IssuedApiKey>;
+    /// 失効したものも含めて発行順に返す
+    async fn find_all(&self) -> anyhow::Result<Vec<ApiKey>>;
+    /// 存在しないか失効済みならエラー
+    async fn revoke(&self, id: String) -> anyhow::Result<()>;
+    /// 有効なキーなら最終利用日時を更新して返す
+    async fn authenticate(&self, key: &str) -> anyhow::Result<Option<ApiKey>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// 発行時だけ返すレスポンス
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IssuedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IssueApiKey {
+    /// 連携先の名前など、どのキーか分かるもの
+    pub name: String,
+}
+
+impl IssueApiKey {
+    pub fn new(name: String) -> Self {
+        Self { name }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        if self.name.chars().count() > MAX_NAME_LENGTH {
+            return Err(format!(
+                "name must be at most {} characters",
+                MAX_NAME_LENGTH
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn generate_key() -> String {
+    format!("{}{}", KEY_PREFIX, nanoid!(40))
+}
+
+/// キーは十分に長い乱数なので、ソルトなしのSHA-256で引けるようにする
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiKeyRepositoryForDb {
+    pool: ReadWritePgPool,
+    ids: Arc<dyn IdGenerator>,
+}
+
+impl ApiKeyRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        ApiKeyRepositoryForDb {
+            pool: pool.into(),
+            ids: default_id_generator(),
+        }
+    }
+
+    pub fn with_id_generator(self, ids: Arc<dyn IdGenerator>) -> Self {
+        Self { ids, ..self }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        ApiKeyRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl ApiKeyRepository for ApiKeyRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn issue(&self, payload: IssueApiKey) -> anyhow::Result<IssuedApiKey> {
+        let key = generate_key();
+        let api_key = sqlx::query_as::<_, ApiKey>(
+            r#"
+                insert into api_keys (id, name, prefix, key_hash)
+                values ($1, $2, $3, $4)
+                returning id, name, prefix, created_at, last_used_at, revoked_at
+            "#,
+        )
+        .bind(self.ids.generate())
+        .bind(payload.name)
+        .bind(&key[..DISPLAY_PREFIX_LENGTH])
+        .bind(hash_key(&key))
+        .fetch_one(self.pool.write())
+        .await?;
+
+        Ok(IssuedApiKey { api_key, key })
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_all(&self) -> anyhow::Result<Vec<ApiKey>> {
+        let api_keys = sqlx::query_as::<_, ApiKey>(
+            r#"
+                select id, name, prefix, created_at, last_used_at, revoked_at
+                from api_keys
+                order by created_at
+            "#,
+        )
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(api_keys)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn revoke(&self, id: String) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+                update api_keys set revoked_at = now()
+                where id = $1 and revoked_at is null
+            "#,
+        )
+        .bind(id)
+        .execute(self.pool.write())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("api key not found"));
+        }
+
+        anyhow::Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn authenticate(&self, key: &str) -> anyhow::Result<Option<ApiKey>> {
+        let api_key = sqlx::query_as::<_, ApiKey>(
+            r#"
+                update api_keys set last_used_at = now()
+                where key_hash = $1 and revoked_at is null
+                returning id, name, prefix, created_at, last_used_at, revoked_at
+            "#,
+        )
+        .bind(hash_key(key))
+        .fetch_optional(self.pool.write())
+        .await?;
+
+        Ok(api_key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_generate_distinct_keys_with_prefix() {
+        let key = generate_key();
+
+        assert!(key.starts_with(KEY_PREFIX));
+        assert!(key.len() > DISPLAY_PREFIX_LENGTH);
+        assert_ne!(key, generate_key());
+    }
+
+    #[test]
+    fn should_hash_key_deterministically() {
+        let key = generate_key();
+
+        assert_eq!(hash_key(&key), hash_key(&key));
+        assert_ne!(key, hash_key(&key));
+        assert_ne!(hash_key(&key), hash_key(&generate_key()));
+    }
+
+    #[test]
+    fn should_reject_empty_name() {
+        assert!(IssueApiKey::new(" ".to_string()).validate().is_err());
+        assert!(IssueApiKey::new("partner".to_string()).validate().is_ok());
+    }
+}
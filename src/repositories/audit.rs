@@ -0,0 +1,203 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::fmt;
+
+use super::{id::UserId, ReadWritePgPool};
+
+/// 検索結果の上限。新しいものから返す
+const SEARCH_LIMIT: i64 = 100;
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait AuditRepository: Send + Sync + 'static {
+    /// actor_idは未ログインの操作ならNone
+    async fn record(
+        &self,
+        actor_id: Option<UserId>,
+        action: AuditAction,
+        target_id: String,
+    ) -> anyhow::Result<()>;
+    async fn search(&self, query: SearchAuditLogs) -> anyhow::Result<Vec<AuditLog>>;
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditRepositoryForDb {
+    pool: ReadWritePgPool,
+}
+
+impl AuditRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        AuditRepositoryForDb { pool: pool.into() }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        AuditRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl AuditRepository for AuditRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn record(
+        &self,
+        actor_id: Option<UserId>,
+        action: AuditAction,
+        target_id: String,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                insert into audit_logs (actor_id, action, target_id) values ($1, $2, $3)
+            "#,
+        )
+        .bind(actor_id)
+        .bind(action.to_string())
+        .bind(target_id)
+        .execute(self.pool.write())
+        .await?;
+
+        anyhow::Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn search(&self, query: SearchAuditLogs) -> anyhow::Result<Vec<AuditLog>> {
+        // 指定のない条件は絞り込まない
+        let rows = sqlx::query_as::<_, AuditLogFromRow>(
+            r#"
+                select * from audit_logs
+                where ($1::text is null or actor_id = $1)
+                and ($2::text is null or action = $2)
+                and ($3::timestamptz is null or created_at >= $3)
+                order by created_at desc, id desc
+                limit $4
+            "#,
+        )
+        .bind(query.actor)
+        .bind(query.action.map(|action| action.to_string()))
+        .bind(query.from)
+        .bind(SEARCH_LIMIT)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(AuditLog {
+                    id: row.id,
+                    actor_id: row.actor_id,
+                    action: row.action.parse()?,
+                    target_id: row.target_id,
+                    created_at: row.created_at,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct AuditLogFromRow {
+    id: i64,
+    actor_id: Option<UserId>,
+    action: String,
+    target_id: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AuditAction {
+    #[serde(rename = "quest.create")]
+    QuestCreate,
+    #[serde(rename = "quest.update")]
+    QuestUpdate,
+    #[serde(rename = "quest.delete")]
+    QuestDelete,
+    #[serde(rename = "quest.restore")]
+    QuestRestore,
+    #[serde(rename = "user.delete")]
+    UserDelete,
+    #[serde(rename = "user.deactivate")]
+    UserDeactivate,
+    #[serde(rename = "user.unlock")]
+    UserUnlock,
+    #[serde(rename = "user.ban")]
+    UserBan,
+    #[serde(rename = "user.unban")]
+    UserUnban,
+    #[serde(rename = "user.password_reset")]
+    UserPasswordReset,
+    #[serde(rename = "user.role_change")]
+    UserRoleChange,
+    #[serde(rename = "api_key.issue")]
+    ApiKeyIssue,
+    #[serde(rename = "api_key.revoke")]
+    ApiKeyRevoke,
+    #[serde(rename = "feature_flag.update")]
+    FeatureFlagUpdate,
+}
+
+impl std::str::FromStr for AuditAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "quest.create" => Ok(Self::QuestCreate),
+            "quest.update" => Ok(Self::QuestUpdate),
+            "quest.delete" => Ok(Self::QuestDelete),
+            "quest.restore" => Ok(Self::QuestRestore),
+            "user.delete" => Ok(Self::UserDelete),
+            "user.deactivate" => Ok(Self::UserDeactivate),
+            "user.unlock" => Ok(Self::UserUnlock),
+            "user.ban" => Ok(Self::UserBan),
+            "user.unban" => Ok(Self::UserUnban),
+            "user.password_reset" => Ok(Self::UserPasswordReset),
+            "user.role_change" => Ok(Self::UserRoleChange),
+            "api_key.issue" => Ok(Self::ApiKeyIssue),
+            "api_key.revoke" => Ok(Self::ApiKeyRevoke),
+            "feature_flag.update" => Ok(Self::FeatureFlagUpdate),
+            _ => Err(anyhow::anyhow!("Invalid audit action : {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self {
+            Self::QuestCreate => "quest.create",
+            Self::QuestUpdate => "quest.update",
+            Self::QuestDelete => "quest.delete",
+            Self::QuestRestore => "quest.restore",
+            Self::UserDelete => "user.delete",
+            Self::UserDeactivate => "user.deactivate",
+            Self::UserUnlock => "user.unlock",
+            Self::UserBan => "user.ban",
+            Self::UserUnban => "user.unban",
+            Self::UserPasswordReset => "user.password_reset",
+            Self::UserRoleChange => "user.role_change",
+            Self::ApiKeyIssue => "api_key.issue",
+            Self::ApiKeyRevoke => "api_key.revoke",
+            Self::FeatureFlagUpdate => "feature_flag.update",
+        };
+        write!(f, "{}", action)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AuditLog {
+    pub id: i64,
+    pub actor_id: Option<UserId>,
+    pub action: AuditAction,
+    pub target_id: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// `GET /admin/audit_logs?actor=&action=&from=` のクエリ
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SearchAuditLogs {
+    pub actor: Option<String>,
+    pub action: Option<AuditAction>,
+    /// この時刻以降の記録に絞り込む
+    pub from: Option<DateTime<Utc>>,
+}
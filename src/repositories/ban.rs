@@ -0,0 +1,236 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use super::{id::UserId, ReadWritePgPool};
+
+const MAX_REASON_LENGTH: usize = 500;
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait BanRepository: Send + Sync + 'static {
+    /// 凍結する。凍結中なら今の凍結を解除して置き換える。ユーザーがいなければNotFound
+    async fn ban(
+        &self,
+        user_id: UserId,
+        banned_by: UserId,
+        payload: CreateBan,
+    ) -> Result<Ban, BanError>;
+    /// 凍結中でなければNotFound
+    async fn lift(&self, user_id: UserId, lifted_by: UserId) -> Result<Ban, BanError>;
+    /// nowの時点で有効な凍結
+    async fn find_active(&self, user_id: UserId, now: DateTime<Utc>)
+        -> anyhow::Result<Option<Ban>>;
+    /// 解除・期限切れも含めた履歴。新しいものから返す
+    async fn find_by_user_id(&self, user_id: UserId) -> anyhow::Result<Vec<Ban>>;
+}
+
+#[derive(Debug)]
+pub enum BanError {
+    NotFound,
+    Repository(anyhow::Error),
+}
+
+impl From<sqlx::Error> for BanError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Repository(e.into())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BanRepositoryForDb {
+    pool: ReadWritePgPool,
+}
+
+impl BanRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        BanRepositoryForDb { pool: pool.into() }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        BanRepositoryForDb::new(pool)
+    }
+}
+
+const BAN_COLUMNS: &str =
+    "id, user_id, reason, banned_by, expires_at, created_at, lifted_at, lifted_by";
+
+/// $2の時点で有効な凍結
+const ACTIVE_CONDITION: &str =
+    "user_id = $1 and lifted_at is null and (expires_at is null or expires_at > $2)";
+
+#[async_trait]
+impl BanRepository for BanRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn ban(
+        &self,
+        user_id: UserId,
+        banned_by: UserId,
+        payload: CreateBan,
+    ) -> Result<Ban, BanError> {
+        let mut tx = self.pool.write().begin().await?;
+        // 同じユーザーへの凍結を直列にして、有効な凍結が2つ残らないようにする
+        let exists = sqlx::query_scalar::<_, String>(
+            r#"
+                select id from users where id = $1 and deleted_at is null for update
+            "#,
+        )
+        .bind(user_id.clone())
+        .fetch_optional(&mut tx)
+        .await?;
+        if exists.is_none() {
+            tx.rollback().await?;
+            return Err(BanError::NotFound);
+        }
+
+        sqlx::query(&format!(
+            "update user_bans set lifted_at = $2, lifted_by = $3 where {}",
+            ACTIVE_CONDITION
+        ))
+        .bind(user_id.clone())
+        .bind(Utc::now())
+        .bind(banned_by.clone())
+        .execute(&mut tx)
+        .await?;
+        let ban = sqlx::query_as::<_, Ban>(&format!(
+            r#"
+                insert into user_bans (user_id, reason, banned_by, expires_at)
+                values ($1, $2, $3, $4)
+                returning {}
+            "#,
+            BAN_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(payload.reason.trim())
+        .bind(banned_by)
+        .bind(payload.expires_at)
+        .fetch_one(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(ban)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn lift(&self, user_id: UserId, lifted_by: UserId) -> Result<Ban, BanError> {
+        let ban = sqlx::query_as::<_, Ban>(&format!(
+            "update user_bans set lifted_at = $2, lifted_by = $3 where {} returning {}",
+            ACTIVE_CONDITION, BAN_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(Utc::now())
+        .bind(lifted_by)
+        .fetch_optional(self.pool.write())
+        .await?;
+
+        ban.ok_or(BanError::NotFound)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_active(
+        &self,
+        user_id: UserId,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<Ban>> {
+        // 凍結した直後のリクエストから弾けるよう、レプリカの遅延を避けてプライマリから読む
+        let ban = sqlx::query_as::<_, Ban>(&format!(
+            "select {} from user_bans where {} order by created_at desc limit 1",
+            BAN_COLUMNS, ACTIVE_CONDITION
+        ))
+        .bind(user_id)
+        .bind(now)
+        .fetch_optional(self.pool.write())
+        .await?;
+
+        Ok(ban)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_by_user_id(&self, user_id: UserId) -> anyhow::Result<Vec<Ban>> {
+        let bans = sqlx::query_as::<_, Ban>(&format!(
+            "select {} from user_bans where user_id = $1 order by created_at desc, id desc",
+            BAN_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(bans)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct Ban {
+    pub id: i64,
+    pub user_id: UserId,
+    /// 管理者が記録した凍結の理由
+    pub reason: String,
+    pub banned_by: Option<UserId>,
+    /// Noneなら無期限
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub lifted_at: Option<DateTime<Utc>>,
+    pub lifted_by: Option<UserId>,
+}
+
+/// `POST /admin/users/:id/ban`。expires_atを省略すると無期限
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CreateBan {
+    pub reason: String,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl CreateBan {
+    pub fn validate(&self, now: DateTime<Utc>) -> Result<(), String> {
+        if self.reason.trim().is_empty() {
+            return Err("reason must not be empty".to_string());
+        }
+        if self.reason.trim().chars().count() > MAX_REASON_LENGTH {
+            return Err(format!(
+                "reason must be at most {} characters",
+                MAX_REASON_LENGTH
+            ));
+        }
+        if let Some(expires_at) = self.expires_at {
+            if expires_at <= now {
+                return Err(format!(
+                    "expires_at must be in the future: {}",
+                    expires_at.to_rfc3339()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn should_validate_ban() {
+        let now = Utc::now();
+        let ban = |reason: &str, expires_at| CreateBan {
+            reason: reason.to_string(),
+            expires_at,
+        };
+
+        assert!(ban("spam", None).validate(now).is_ok());
+        assert!(ban("spam", Some(now + Duration::days(7)))
+            .validate(now)
+            .is_ok());
+        assert!(ban("  ", None).validate(now).is_err());
+        assert!(ban(&"a".repeat(MAX_REASON_LENGTH + 1), None)
+            .validate(now)
+            .is_err());
+        assert!(ban("spam", Some(now - Duration::days(1)))
+            .validate(now)
+            .is_err());
+    }
+}
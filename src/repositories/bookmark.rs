@@ -0,0 +1,111 @@
+use axum::async_trait;
+
+use super::{
+    id::{QuestId, UserId},
+    ReadWritePgPool,
+};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait BookmarkRepository: Send + Sync + 'static {
+    /// すでにブックマークしていても成功にする
+    async fn bookmark(&self, user_id: UserId, quest_id: QuestId) -> anyhow::Result<()>;
+    /// ブックマークしていなくても成功にする
+    async fn unbookmark(&self, user_id: UserId, quest_id: QuestId) -> anyhow::Result<()>;
+    /// ブックマークしたクエストのID。新しくブックマークした順
+    async fn find_quest_ids(&self, user_id: UserId) -> anyhow::Result<Vec<QuestId>>;
+    /// quest_idsのうちブックマークしているもの
+    async fn find_bookmarked(
+        &self,
+        user_id: UserId,
+        quest_ids: Vec<QuestId>,
+    ) -> anyhow::Result<Vec<QuestId>>;
+}
+
+#[derive(Debug, Clone)]
+pub struct BookmarkRepositoryForDb {
+    pool: ReadWritePgPool,
+}
+
+impl BookmarkRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        BookmarkRepositoryForDb { pool: pool.into() }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        BookmarkRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl BookmarkRepository for BookmarkRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn bookmark(&self, user_id: UserId, quest_id: QuestId) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                insert into user_bookmarks (user_id, quest_id) values ($1, $2)
+                on conflict do nothing
+            "#,
+        )
+        .bind(user_id)
+        .bind(quest_id)
+        .execute(self.pool.write())
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn unbookmark(&self, user_id: UserId, quest_id: QuestId) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                delete from user_bookmarks where user_id = $1 and quest_id = $2
+            "#,
+        )
+        .bind(user_id)
+        .bind(quest_id)
+        .execute(self.pool.write())
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_quest_ids(&self, user_id: UserId) -> anyhow::Result<Vec<QuestId>> {
+        let quest_ids = sqlx::query_scalar::<_, QuestId>(
+            r#"
+                select quest_id from user_bookmarks
+                where user_id = $1
+                order by created_at desc
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(quest_ids)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_bookmarked(
+        &self,
+        user_id: UserId,
+        quest_ids: Vec<QuestId>,
+    ) -> anyhow::Result<Vec<QuestId>> {
+        let quest_ids = sqlx::query_scalar::<_, QuestId>(
+            r#"
+                select quest_id from user_bookmarks
+                where user_id = $1 and quest_id = any($2)
+            "#,
+        )
+        .bind(user_id)
+        .bind(quest_ids)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(quest_ids)
+    }
+}
@@ -0,0 +1,241 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    config::{CacheInvalidation, RedisConfig},
+    infras::redis::RedisClient,
+};
+
+use super::{
+    id::{QuestId, UserId},
+    metadata::MetadataFilter,
+    quest::{
+        BulkCreateQuest, CoverImage, CreateQuest, QuestEntity, QuestRanking, QuestRepository,
+        QuestStatus, UpdateQuest, UpdateQuestError,
+    },
+};
+
+/// 一覧のキャッシュはstatusごとに持つ。開催中の絞り込みはキャッシュした一覧から行う
+const LIST_KEYS: [&str; 4] = [
+    "quests:list:all",
+    "quests:list:draft",
+    "quests:list:published",
+    "quests:list:archived",
+];
+
+/// QuestRepositoryの結果をRedisにキャッシュするデコレータ。
+/// Redisに繋がらないときはキャッシュを使わずにinnerへ委譲する
+/// チャレンジの変更では消さないので、TTLの間は古いチャレンジ一覧が返ることがある
+pub struct CachedQuestRepository {
+    inner: Arc<dyn QuestRepository>,
+    redis: RedisClient,
+    ttl: Duration,
+    invalidation: CacheInvalidation,
+}
+
+impl CachedQuestRepository {
+    pub fn new(inner: Arc<dyn QuestRepository>, redis: RedisClient, config: &RedisConfig) -> Self {
+        Self {
+            inner,
+            redis,
+            ttl: config.quest_ttl,
+            invalidation: config.invalidation,
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        match self.redis.get_json(key).await {
+            Ok(value) => value,
+            Err(e) => {
+                tracing::warn!("failed to read cache [{}]: {}", key, e);
+                None
+            }
+        }
+    }
+
+    async fn set<T: Serialize + Sync>(&self, key: &str, value: &T) {
+        if let Err(e) = self.redis.set_json(key, value, self.ttl).await {
+            tracing::warn!("failed to write cache [{}]: {}", key, e);
+        }
+    }
+
+    /// 書き込みの後に呼ぶ。どのstatusの一覧に載るかは変更前後で変わりうるので一覧は全て消す
+    async fn invalidate(&self, ids: &[QuestId]) {
+        if self.invalidation == CacheInvalidation::Ttl {
+            return;
+        }
+
+        let mut keys = LIST_KEYS
+            .iter()
+            .map(|key| key.to_string())
+            .collect::<Vec<_>>();
+        keys.extend(ids.iter().map(quest_key));
+        if let Err(e) = self.redis.delete(&keys).await {
+            tracing::warn!("failed to invalidate quest cache: {}", e);
+        }
+    }
+}
+
+fn quest_key(id: &QuestId) -> String {
+    format!("quests:{}", id)
+}
+
+fn list_key(status: Option<QuestStatus>) -> String {
+    match status {
+        Some(status) => format!("quests:list:{}", status),
+        None => "quests:list:all".to_string(),
+    }
+}
+
+#[async_trait]
+impl QuestRepository for CachedQuestRepository {
+    async fn create(&self, payload: CreateQuest) -> anyhow::Result<QuestEntity> {
+        let quest = self.inner.create(payload).await?;
+        self.invalidate(&[]).await;
+        Ok(quest)
+    }
+
+    async fn create_with_challenges(
+        &self,
+        payload: BulkCreateQuest,
+    ) -> anyhow::Result<QuestEntity> {
+        let quest = self.inner.create_with_challenges(payload).await?;
+        self.invalidate(&[]).await;
+        Ok(quest)
+    }
+
+    async fn duplicate(
+        &self,
+        id: QuestId,
+        created_by: Option<UserId>,
+    ) -> anyhow::Result<QuestEntity> {
+        let quest = self.inner.duplicate(id, created_by).await?;
+        self.invalidate(&[]).await;
+        Ok(quest)
+    }
+
+    async fn find(&self, id: QuestId) -> anyhow::Result<QuestEntity> {
+        let key = quest_key(&id);
+        if let Some(quest) = self.get(&key).await {
+            return Ok(quest);
+        }
+
+        let quest = self.inner.find(id).await?;
+        self.set(&key, &quest).await;
+        Ok(quest)
+    }
+
+    async fn find_creator(&self, id: QuestId) -> anyhow::Result<Option<UserId>> {
+        self.inner.find_creator(id).await
+    }
+
+    async fn find_by_ids(&self, ids: Vec<QuestId>) -> anyhow::Result<Vec<QuestEntity>> {
+        self.inner.find_by_ids(ids).await
+    }
+
+    async fn all(
+        &self,
+        status: Option<QuestStatus>,
+        active_at: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<Vec<QuestEntity>> {
+        // active_atは毎回変わるのでキーに含めず、statusで引いた一覧を絞り込む
+        let key = list_key(status);
+        let quests: Vec<QuestEntity> = match self.get(&key).await {
+            Some(quests) => quests,
+            None => {
+                let quests = self.inner.all(status, None).await?;
+                self.set(&key, &quests).await;
+                quests
+            }
+        };
+
+        Ok(match active_at {
+            Some(at) => quests.into_iter().filter(|q| q.is_open_at(at)).collect(),
+            None => quests,
+        })
+    }
+
+    async fn find_by_metadata(
+        &self,
+        status: Option<QuestStatus>,
+        filter: MetadataFilter,
+    ) -> anyhow::Result<Vec<QuestEntity>> {
+        self.inner.find_by_metadata(status, filter).await
+    }
+
+    /// 参加者数は更新が多いのでキャッシュしない
+    async fn rank(
+        &self,
+        quest_ids: Vec<QuestId>,
+        ranking: QuestRanking,
+    ) -> anyhow::Result<Vec<QuestId>> {
+        self.inner.rank(quest_ids, ranking).await
+    }
+
+    async fn update(
+        &self,
+        id: QuestId,
+        payload: UpdateQuest,
+    ) -> Result<QuestEntity, UpdateQuestError> {
+        let quest = self.inner.update(id.clone(), payload).await?;
+        self.invalidate(&[id]).await;
+        Ok(quest)
+    }
+
+    async fn update_cover_image(
+        &self,
+        id: QuestId,
+        cover_image: CoverImage,
+    ) -> anyhow::Result<QuestEntity> {
+        let quest = self
+            .inner
+            .update_cover_image(id.clone(), cover_image)
+            .await?;
+        self.invalidate(&[id]).await;
+        Ok(quest)
+    }
+
+    async fn archive_expired(&self, now: DateTime<Utc>) -> anyhow::Result<Vec<QuestId>> {
+        let ids = self.inner.archive_expired(now).await?;
+        if !ids.is_empty() {
+            self.invalidate(&ids).await;
+        }
+        Ok(ids)
+    }
+
+    async fn delete(&self, id: QuestId) -> anyhow::Result<()> {
+        self.inner.delete(id.clone()).await?;
+        self.invalidate(&[id]).await;
+        Ok(())
+    }
+
+    async fn restore(&self, id: QuestId) -> anyhow::Result<QuestEntity> {
+        let quest = self.inner.restore(id.clone()).await?;
+        self.invalidate(&[id]).await;
+        Ok(quest)
+    }
+
+    /// 論理削除の時点で消しているか、TTLで切れているのでキャッシュは触らない
+    async fn purge_deleted(&self, before: DateTime<Utc>) -> anyhow::Result<u64> {
+        self.inner.purge_deleted(before).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_build_list_keys_for_each_status() {
+        assert_eq!("quests:list:all", list_key(None));
+        for status in [
+            QuestStatus::Draft,
+            QuestStatus::Published,
+            QuestStatus::Archived,
+        ] {
+            assert!(LIST_KEYS.contains(&list_key(Some(status)).as_str()));
+        }
+    }
+}
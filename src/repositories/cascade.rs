@@ -0,0 +1,216 @@
+use sqlx::{Encode, Postgres, Transaction, Type};
+
+/// 親の行を消すときに、参照している行をどうするか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnDelete {
+    Delete,
+    /// 記録は残して参照だけ外す(獲得済みのポイント等)
+    SetNull,
+}
+
+/// 親のidを参照している列。childrenにはこのテーブルのidを参照している列を書く
+#[derive(Debug)]
+pub struct Dependent {
+    pub table: &'static str,
+    pub column: &'static str,
+    pub on_delete: OnDelete,
+    pub children: &'static [Dependent],
+}
+
+impl Dependent {
+    pub const fn delete(table: &'static str, column: &'static str) -> Self {
+        Self {
+            table,
+            column,
+            on_delete: OnDelete::Delete,
+            children: &[],
+        }
+    }
+
+    pub const fn set_null(table: &'static str, column: &'static str) -> Self {
+        Self {
+            table,
+            column,
+            on_delete: OnDelete::SetNull,
+            children: &[],
+        }
+    }
+
+    pub const fn with_children(mut self, children: &'static [Dependent]) -> Self {
+        self.children = children;
+        self
+    }
+}
+
+/// 親テーブルとそれを参照するテーブルの一覧
+/// テーブルを増やしたら、ここに足すだけで物理削除の対象になる
+#[derive(Debug)]
+pub struct Cascade {
+    pub table: &'static str,
+    pub dependents: &'static [Dependent],
+}
+
+/// ユーザーに紐づくもの。フォローはどちら側が消えても関係ごと消す
+pub const USER_CASCADE: Cascade = Cascade {
+    table: "users",
+    dependents: &[
+        Dependent::delete("user_follows", "follower_id"),
+        Dependent::delete("user_follows", "followee_id"),
+        Dependent::delete("user_completed_challenges", "user_id"),
+        Dependent::delete("user_participating_quests", "user_id"),
+        Dependent::delete("point_transactions", "user_id"),
+        Dependent::delete("user_achievements", "user_id"),
+        Dependent::delete("certificates", "user_id"),
+        Dependent::delete("quest_reviews", "user_id"),
+        Dependent::delete("user_bookmarks", "user_id"),
+        Dependent::delete("devices", "user_id"),
+        Dependent::delete("webhooks", "user_id"),
+        Dependent::delete("jobs", "user_id"),
+        Dependent::delete("user_preferences", "user_id"),
+        Dependent::delete("user_opened_hints", "user_id"),
+        Dependent::delete("user_bans", "user_id"),
+    ],
+};
+
+/// 差し引いたポイントは残す
+const HINT_DEPENDENTS: &[Dependent] = &[
+    Dependent::delete("user_opened_hints", "hint_id"),
+    Dependent::set_null("point_transactions", "hint_id"),
+];
+
+const CHALLENGE_DEPENDENTS: &[Dependent] = &[
+    Dependent::delete("user_completed_challenges", "challenge_id"),
+    Dependent::set_null("point_transactions", "challenge_id"),
+    Dependent::delete("challenge_translations", "challenge_id"),
+    Dependent::delete("challenge_hints", "challenge_id").with_children(HINT_DEPENDENTS),
+];
+
+/// クエストを経由せずにチャレンジだけを消すとき
+pub const CHALLENGE_CASCADE: Cascade = Cascade {
+    table: "challenges",
+    dependents: CHALLENGE_DEPENDENTS,
+};
+
+/// クエストに紐づくもの。完了記録は消すが、獲得済みのポイントはユーザーに残す
+pub const QUEST_CASCADE: Cascade = Cascade {
+    table: "quests",
+    dependents: &[
+        Dependent::delete("challenges", "quest_id").with_children(CHALLENGE_DEPENDENTS),
+        Dependent::delete("user_participating_quests", "quest_id"),
+        Dependent::delete("certificates", "quest_id"),
+        Dependent::delete("quest_reviews", "quest_id"),
+        Dependent::delete("user_bookmarks", "quest_id"),
+        Dependent::delete("quest_translations", "quest_id"),
+    ],
+};
+
+impl Cascade {
+    /// conditionに合う親の行を、参照している行ごと消す。conditionでは$1だけを使える
+    /// 外部キーを満たしたまま消せるよう、参照している側から順に実行する
+    pub async fn delete<T>(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        condition: &str,
+        value: T,
+    ) -> anyhow::Result<u64>
+    where
+        T: for<'q> Encode<'q, Postgres> + Type<Postgres> + Clone + Send + 'static,
+    {
+        let (dependents, root) = self.statements(condition);
+        for statement in dependents {
+            sqlx::query(&statement)
+                .bind(value.clone())
+                .execute(&mut *tx)
+                .await?;
+        }
+        let deleted = sqlx::query(&root)
+            .bind(value)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        Ok(deleted)
+    }
+
+    /// 参照している側のSQLと、親自身を消すSQL
+    fn statements(&self, condition: &str) -> (Vec<String>, String) {
+        let ids = format!("select id from {} where {}", self.table, condition);
+        let mut statements = Vec::new();
+        push_statements(self.dependents, &ids, &mut statements);
+        let root = format!("delete from {} where {}", self.table, condition);
+        (statements, root)
+    }
+}
+
+fn push_statements(dependents: &[Dependent], parent_ids: &str, statements: &mut Vec<String>) {
+    for dependent in dependents {
+        if !dependent.children.is_empty() {
+            let ids = format!(
+                "select id from {} where {} in ({})",
+                dependent.table, dependent.column, parent_ids
+            );
+            push_statements(dependent.children, &ids, statements);
+        }
+        statements.push(match dependent.on_delete {
+            OnDelete::Delete => format!(
+                "delete from {} where {} in ({})",
+                dependent.table, dependent.column, parent_ids
+            ),
+            OnDelete::SetNull => format!(
+                "update {} set {} = null where {} in ({})",
+                dependent.table, dependent.column, dependent.column, parent_ids
+            ),
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_delete_children_before_parents() {
+        let (dependents, root) = QUEST_CASCADE.statements("deleted_at < $1");
+
+        assert_eq!(
+            "delete from user_completed_challenges where challenge_id in (select id from challenges where quest_id in (select id from quests where deleted_at < $1))",
+            dependents[0]
+        );
+        assert_eq!(
+            "update point_transactions set challenge_id = null where challenge_id in (select id from challenges where quest_id in (select id from quests where deleted_at < $1))",
+            dependents[1]
+        );
+        assert_eq!(
+            "delete from challenge_translations where challenge_id in (select id from challenges where quest_id in (select id from quests where deleted_at < $1))",
+            dependents[2]
+        );
+        assert_eq!(
+            "delete from user_opened_hints where hint_id in (select id from challenge_hints where challenge_id in (select id from challenges where quest_id in (select id from quests where deleted_at < $1)))",
+            dependents[3]
+        );
+        assert_eq!(
+            "delete from challenge_hints where challenge_id in (select id from challenges where quest_id in (select id from quests where deleted_at < $1))",
+            dependents[5]
+        );
+        assert_eq!(
+            "delete from challenges where quest_id in (select id from quests where deleted_at < $1)",
+            dependents[6]
+        );
+        assert_eq!(12, dependents.len());
+        assert_eq!("delete from quests where deleted_at < $1", root);
+    }
+
+    #[test]
+    fn should_delete_both_sides_of_follows() {
+        let (dependents, _) = USER_CASCADE.statements("id = $1");
+
+        assert!(dependents.contains(
+            &"delete from user_follows where follower_id in (select id from users where id = $1)"
+                .to_string()
+        ));
+        assert!(dependents.contains(
+            &"delete from user_follows where followee_id in (select id from users where id = $1)"
+                .to_string()
+        ));
+    }
+}
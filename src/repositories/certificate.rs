@@ -0,0 +1,95 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::sync::Arc;
+
+use super::{
+    id::{default_id_generator, IdGenerator, QuestId, UserId},
+    ReadWritePgPool,
+};
+
+#[async_trait]
+pub trait CertificateRepository: Send + Sync + 'static {
+    /// 発行済みならその証明書を返す
+    async fn find_or_issue(
+        &self,
+        user_id: UserId,
+        quest_id: QuestId,
+    ) -> anyhow::Result<Certificate>;
+    async fn find(&self, id: String) -> anyhow::Result<Certificate>;
+}
+
+#[derive(Debug, Clone)]
+pub struct CertificateRepositoryForDb {
+    pool: ReadWritePgPool,
+    ids: Arc<dyn IdGenerator>,
+}
+
+impl CertificateRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        CertificateRepositoryForDb {
+            pool: pool.into(),
+            ids: default_id_generator(),
+        }
+    }
+
+    pub fn with_id_generator(self, ids: Arc<dyn IdGenerator>) -> Self {
+        Self { ids, ..self }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        CertificateRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl CertificateRepository for CertificateRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_or_issue(
+        &self,
+        user_id: UserId,
+        quest_id: QuestId,
+    ) -> anyhow::Result<Certificate> {
+        // 発行済みのときも行を返すように、何も変えないupdateをする
+        let certificate = sqlx::query_as::<_, Certificate>(
+            r#"
+                insert into certificates (id, user_id, quest_id) values ($1, $2, $3)
+                on conflict (user_id, quest_id) do update set user_id = excluded.user_id
+                returning *
+            "#,
+        )
+        .bind(self.ids.generate())
+        .bind(user_id)
+        .bind(quest_id)
+        .fetch_one(self.pool.write())
+        .await?;
+
+        anyhow::Ok(certificate)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find(&self, id: String) -> anyhow::Result<Certificate> {
+        let certificate = sqlx::query_as::<_, Certificate>(
+            r#"
+                select * from certificates where id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_one(self.pool.read())
+        .await?;
+
+        anyhow::Ok(certificate)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, FromRow)]
+pub struct Certificate {
+    pub id: String,
+    pub user_id: UserId,
+    pub quest_id: QuestId,
+    pub completed_at: DateTime<Utc>,
+}
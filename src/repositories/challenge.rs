@@ -1,44 +1,81 @@
-use anyhow::Ok;
 use axum::async_trait;
-use nanoid::nanoid;
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
+use sqlx::{types::Json, FromRow};
+use std::sync::Arc;
 
+use super::{
+    cascade::CHALLENGE_CASCADE,
+    id::{default_id_generator, ChallengeId, IdGenerator, QuestId},
+    metadata::{validate_metadata, Metadata, MetadataFilter},
+    ReadWritePgPool,
+};
+
+#[cfg_attr(test, mockall::automock)]
 #[async_trait]
-pub trait ChallengeRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+pub trait ChallengeRepository: std::marker::Send + std::marker::Sync + 'static {
     async fn create(&self, payload: CreateChallenge) -> anyhow::Result<Challenge>;
-    async fn find(&self, id: String) -> anyhow::Result<Challenge>;
-    async fn find_by_quest_id(&self, quest_id: String) -> anyhow::Result<Vec<Challenge>>;
+    async fn find(&self, id: ChallengeId) -> anyhow::Result<Challenge>;
+    /// 存在しないIDは結果に含まれない
+    async fn find_by_ids(&self, ids: Vec<ChallengeId>) -> anyhow::Result<Vec<Challenge>>;
+    async fn find_by_quest_id(&self, quest_id: QuestId) -> anyhow::Result<Vec<Challenge>>;
+    /// シーケンシャルモードのクエストで、直前に完了しておくべきチャレンジを返す
+    async fn find_previous(&self, id: ChallengeId) -> anyhow::Result<Option<Challenge>>;
+    /// 管理画面向け。metadataの値が全て一致するチャレンジを返す
+    async fn find_by_metadata(&self, filter: MetadataFilter) -> anyhow::Result<Vec<Challenge>>;
+    /// クエスト内のチャレンジの公開状態・受付期間をまとめて変更する
+    /// 1件でも適用できなければ何も変更せず、applied=falseの結果を返す
+    async fn bulk_update(
+        &self,
+        quest_id: QuestId,
+        payload: BulkUpdateChallenges,
+    ) -> anyhow::Result<BulkUpdateResult>;
+    /// beforeより前に作られ、クエストが存在しないチャレンジを関連する記録ごと物理削除し、件数を返す
+    async fn purge_orphans(&self, before: DateTime<Utc>) -> anyhow::Result<u64>;
 }
 
 #[derive(Debug, Clone)]
 pub struct ChallengeRepositoryForDb {
-    pool: PgPool,
+    pool: ReadWritePgPool,
+    ids: Arc<dyn IdGenerator>,
 }
 
 impl ChallengeRepositoryForDb {
-    pub fn new(pool: PgPool) -> Self {
-        ChallengeRepositoryForDb { pool }
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        ChallengeRepositoryForDb {
+            pool: pool.into(),
+            ids: default_id_generator(),
+        }
+    }
+
+    pub fn with_id_generator(self, ids: Arc<dyn IdGenerator>) -> Self {
+        Self { ids, ..self }
     }
 
     #[cfg(test)]
     /// テスト用の簡易版コンストラクタ
     pub async fn with_url(url: &str) -> Self {
-        let pool = PgPool::connect(url).await.unwrap();
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
         ChallengeRepositoryForDb::new(pool)
     }
 }
 
 #[async_trait]
 impl ChallengeRepository for ChallengeRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
     async fn create(&self, payload: CreateChallenge) -> anyhow::Result<Challenge> {
-        let challenge = sqlx::query_as::<_, Challenge>(
+        let challenge = sqlx::query_as::<_, ChallengeFromRow>(
             r#"
-				insert into challenges values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+				insert into challenges (
+					id, name, description, quest_id, latitude, longitude, stamp_name,
+					stamp_color_image_url, stamp_gray_image_url, flavor_text, order_index,
+					metadata
+				)
+				values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
 				returning *
 			"#,
         )
-        .bind(nanoid!())
+        .bind(self.ids.generate())
         .bind(payload.name)
         .bind(payload.description)
         .bind(payload.quest_id)
@@ -48,60 +85,278 @@ impl ChallengeRepository for ChallengeRepositoryForDb {
         .bind(payload.stamp_color_image_url)
         .bind(payload.stamp_gray_image_url)
         .bind(payload.flavor_text)
-        .fetch_one(&self.pool)
+        .bind(payload.order_index)
+        .bind(Json(payload.metadata))
+        .fetch_one(self.pool.write())
         .await?;
 
-        Ok(challenge)
+        Ok(challenge.into())
     }
 
-    async fn find(&self, id: String) -> anyhow::Result<Challenge> {
-        let challenge = sqlx::query_as::<_, Challenge>(
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find(&self, id: ChallengeId) -> anyhow::Result<Challenge> {
+        let challenge = sqlx::query_as::<_, ChallengeFromRow>(
             r#"
 				select * from challenges where id = $1;
 			"#,
         )
         .bind(id)
-        .fetch_one(&self.pool)
+        .fetch_one(self.pool.read())
+        .await?;
+
+        Ok(challenge.into())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_by_ids(&self, ids: Vec<ChallengeId>) -> anyhow::Result<Vec<Challenge>> {
+        let challenges = sqlx::query_as::<_, ChallengeFromRow>(
+            r#"
+                select * from challenges where id = any($1);
+            "#,
+        )
+        .bind(ids)
+        .fetch_all(self.pool.read())
         .await?;
 
-        Ok(challenge)
+        Ok(challenges.into_iter().map(Challenge::from).collect())
     }
 
-    async fn find_by_quest_id(&self, quest_id: String) -> anyhow::Result<Vec<Challenge>> {
-        let challenges = sqlx::query_as::<_, Challenge>(
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_by_quest_id(&self, quest_id: QuestId) -> anyhow::Result<Vec<Challenge>> {
+        let challenges = sqlx::query_as::<_, ChallengeFromRow>(
             r#"
-                select * from challenges where quest_id = $1;
+                select * from challenges where quest_id = $1 order by order_index;
             "#,
         )
         .bind(quest_id)
-        .fetch_all(&self.pool)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(challenges.into_iter().map(Challenge::from).collect())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_previous(&self, id: ChallengeId) -> anyhow::Result<Option<Challenge>> {
+        let previous = sqlx::query_as::<_, ChallengeFromRow>(
+            r#"
+                select prev.* from challenges c
+                join quests q on q.id = c.quest_id
+                join challenges prev
+                    on prev.quest_id = c.quest_id and prev.order_index < c.order_index
+                where c.id = $1 and q.sequential
+                order by prev.order_index desc
+                limit 1;
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(self.pool.read())
+        .await?;
+
+        Ok(previous.map(Challenge::from))
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_by_metadata(&self, filter: MetadataFilter) -> anyhow::Result<Vec<Challenge>> {
+        // 値の型に関わらず文字列にして比較する
+        let challenges = sqlx::query_as::<_, ChallengeFromRow>(
+            r#"
+                select c.* from challenges c
+                where not exists (
+                    select 1 from jsonb_each_text($1::jsonb) f
+                    where c.metadata ->> f.key is distinct from f.value
+                )
+                order by c.quest_id, c.order_index;
+            "#,
+        )
+        .bind(Json(filter.to_json()))
+        .fetch_all(self.pool.read())
         .await?;
 
-        Ok(challenges)
+        Ok(challenges.into_iter().map(Challenge::from).collect())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn bulk_update(
+        &self,
+        quest_id: QuestId,
+        payload: BulkUpdateChallenges,
+    ) -> anyhow::Result<BulkUpdateResult> {
+        let mut tx = self.pool.write().begin().await?;
+        let mut results = Vec::with_capacity(payload.updates.len());
+        let mut challenges = Vec::new();
+
+        for update in payload.updates {
+            // 別クエストのチャレンジは見つからなかったものとして扱う
+            let current = sqlx::query_as::<_, ChallengeFromRow>(
+                r#"
+                    select * from challenges where id = $1 and quest_id = $2 for update;
+                "#,
+            )
+            .bind(&update.challenge_id)
+            .bind(&quest_id)
+            .fetch_optional(&mut tx)
+            .await?;
+            let current = match current {
+                Some(row) => Challenge::from(row),
+                None => {
+                    results.push(ChallengeUpdateResult::failed(
+                        update.challenge_id,
+                        "challenge not found in quest",
+                    ));
+                    continue;
+                }
+            };
+
+            let (active, start_at, end_at) = match update.apply(&current) {
+                Result::Ok(values) => values,
+                Err(message) => {
+                    results.push(ChallengeUpdateResult::failed(update.challenge_id, message));
+                    continue;
+                }
+            };
+            let challenge = sqlx::query_as::<_, ChallengeFromRow>(
+                r#"
+                    update challenges
+                    set active = $2, start_at = $3, end_at = $4, updated_at = now(),
+                        version = version + 1
+                    where id = $1
+                    returning *;
+                "#,
+            )
+            .bind(&update.challenge_id)
+            .bind(active)
+            .bind(start_at)
+            .bind(end_at)
+            .fetch_one(&mut tx)
+            .await?;
+
+            results.push(ChallengeUpdateResult::updated(update.challenge_id));
+            challenges.push(Challenge::from(challenge));
+        }
+
+        let applied = results.iter().all(|result| result.error.is_none());
+        if applied {
+            tx.commit().await?;
+        } else {
+            tx.rollback().await?;
+            challenges.clear();
+        }
+
+        Ok(BulkUpdateResult {
+            applied,
+            results,
+            challenges,
+        })
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn purge_orphans(&self, before: DateTime<Utc>) -> anyhow::Result<u64> {
+        // challenges.quest_idには外部キーがないので、クエストだけ消すと残ってしまう
+        let mut tx = self.pool.write().begin().await?;
+        let purged = CHALLENGE_CASCADE
+            .delete(
+                &mut tx,
+                "created_at < $1 and quest_id not in (select id from quests)",
+                before,
+            )
+            .await?;
+        tx.commit().await?;
+
+        Ok(purged)
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, FromRow)]
-pub struct Challenge {
-    pub id: String,
+/// challengesテーブルの行。リポジトリの外には公開しない
+/// json_aggでまとめた行からも読めるようにDeserializeも実装する
+#[derive(Debug, Clone, FromRow, Deserialize)]
+pub(super) struct ChallengeFromRow {
+    id: ChallengeId,
     name: String,
     description: String,
-    pub quest_id: String,
+    quest_id: QuestId,
     latitude: f64,
     longitude: f64,
     stamp_name: String,
     stamp_color_image_url: String,
     stamp_gray_image_url: String,
     flavor_text: String,
+    order_index: i32,
+    metadata: Json<Metadata>,
+    active: bool,
+    start_at: Option<DateTime<Utc>>,
+    end_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    version: i32,
+}
+
+impl From<ChallengeFromRow> for Challenge {
+    fn from(row: ChallengeFromRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            description: row.description,
+            quest_id: row.quest_id,
+            latitude: row.latitude,
+            longitude: row.longitude,
+            stamp_name: row.stamp_name,
+            stamp_color_image_url: row.stamp_color_image_url,
+            stamp_gray_image_url: row.stamp_gray_image_url,
+            flavor_text: row.flavor_text,
+            order_index: row.order_index,
+            metadata: row.metadata.0,
+            active: row.active,
+            start_at: row.start_at,
+            end_at: row.end_at,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            version: row.version,
+            locked: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Challenge {
+    pub id: ChallengeId,
+    pub name: String,
+    pub description: String,
+    pub quest_id: QuestId,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub stamp_name: String,
+    pub stamp_color_image_url: String,
+    pub stamp_gray_image_url: String,
+    pub flavor_text: String,
+    pub order_index: i32,
+    #[serde(default)]
+    pub metadata: Metadata,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    /// 受付期間。NULLのときはクエストの開催期間に従う
+    #[serde(default)]
+    pub start_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub end_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub updated_at: DateTime<Utc>,
+    /// 更新のたびに増える。ChallengeUpdateのexpected_versionに渡す
+    #[serde(default)]
+    pub version: i32,
+    /// ユーザーごとの解放状態。テーブルには存在しない
+    #[serde(default)]
+    pub locked: bool,
 }
 
 impl Challenge {
     #[cfg(test)]
     pub fn new(
-        id: String,
+        id: ChallengeId,
         name: String,
         description: String,
-        quest_id: String,
+        quest_id: QuestId,
         latitude: f64,
         longitude: f64,
         stamp_name: String,
@@ -109,6 +364,7 @@ impl Challenge {
         stamp_gray_image_url: String,
         flavor_text: String,
     ) -> Self {
+        let now = Utc::now();
         Self {
             id,
             name,
@@ -120,8 +376,28 @@ impl Challenge {
             stamp_color_image_url,
             stamp_gray_image_url,
             flavor_text,
+            order_index: 0,
+            metadata: Metadata::new(),
+            active: true,
+            start_at: None,
+            end_at: None,
+            created_at: now,
+            updated_at: now,
+            version: 1,
+            locked: false,
         }
     }
+
+    /// 完了を受け付けるか。終了時刻ちょうどは期間外
+    pub fn is_available_at(&self, now: DateTime<Utc>) -> bool {
+        self.active
+            && self.start_at.map_or(true, |start_at| start_at <= now)
+            && self.end_at.map_or(true, |end_at| now < end_at)
+    }
+}
+
+fn default_active() -> bool {
+    true
 }
 
 // 各fieldが一致したとき==とみなす
@@ -137,21 +413,48 @@ impl PartialEq for Challenge {
 pub struct CreateChallenge {
     name: String,
     description: String,
-    quest_id: String,
+    quest_id: QuestId,
     latitude: f64,
     longitude: f64,
     stamp_name: String,
     stamp_color_image_url: String,
     stamp_gray_image_url: String,
     flavor_text: String,
+    #[serde(default)]
+    order_index: i32,
+    #[serde(default)]
+    metadata: Metadata,
+}
+
+impl CreateChallenge {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        if self.quest_id.as_str().trim().is_empty() {
+            return Err("quest_id must not be empty".to_string());
+        }
+        validate_coordinates(self.latitude, self.longitude)?;
+        validate_metadata(&self.metadata)
+    }
+}
+
+/// 緯度経度が有効な範囲にあるかを確認する
+pub fn validate_coordinates(latitude: f64, longitude: f64) -> Result<(), String> {
+    if !(-90.0..=90.0).contains(&latitude) {
+        return Err(format!("latitude is out of range: {}", latitude));
+    }
+    if !(-180.0..=180.0).contains(&longitude) {
+        return Err(format!("longitude is out of range: {}", longitude));
+    }
+    Ok(())
 }
 
-#[cfg(test)]
 impl CreateChallenge {
     pub fn new(
         name: String,
         description: String,
-        quest_id: String,
+        quest_id: QuestId,
         latitude: f64,
         longitude: f64,
         stamp_name: String,
@@ -169,11 +472,235 @@ impl CreateChallenge {
             stamp_color_image_url,
             stamp_gray_image_url,
             flavor_text,
+            order_index: 0,
+            metadata: Metadata::new(),
+        }
+    }
+
+    pub fn with_order_index(self, order_index: i32) -> Self {
+        Self {
+            order_index,
+            ..self
+        }
+    }
+}
+
+/// 一度に変更できるチャレンジの数
+const MAX_BULK_UPDATES: usize = 100;
+/// expected_versionが一致しなかったときのChallengeUpdateResultのerror
+const VERSION_CONFLICT: &str = "version conflict";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BulkUpdateChallenges {
+    pub updates: Vec<ChallengeUpdate>,
+}
+
+impl BulkUpdateChallenges {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.updates.is_empty() {
+            return Err("updates must not be empty".to_string());
+        }
+        if self.updates.len() > MAX_BULK_UPDATES {
+            return Err(format!(
+                "updates must have at most {} items",
+                MAX_BULK_UPDATES
+            ));
+        }
+        let mut ids = self
+            .updates
+            .iter()
+            .map(|update| update.challenge_id.as_str())
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.dedup();
+        if ids.len() != self.updates.len() {
+            return Err("challenge_id must not be duplicated".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 1件分の変更。指定しなかった項目は変えない
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChallengeUpdate {
+    pub challenge_id: ChallengeId,
+    #[serde(default)]
+    pub active: Option<bool>,
+    /// 受付期間を前後にずらす分数。期間が設定されていない側はずらさない
+    #[serde(default)]
+    pub shift_minutes: Option<i64>,
+    /// 編集を始めたときのversion。省略すると確認しない
+    #[serde(default)]
+    pub expected_version: Option<i32>,
+}
+
+impl ChallengeUpdate {
+    /// 変更後の(active, start_at, end_at)を返す
+    fn apply(
+        &self,
+        current: &Challenge,
+    ) -> Result<(bool, Option<DateTime<Utc>>, Option<DateTime<Utc>>), String> {
+        if self
+            .expected_version
+            .map_or(false, |version| version != current.version)
+        {
+            return Err(VERSION_CONFLICT.to_string());
+        }
+        let active = self.active.unwrap_or(current.active);
+        let shift = match self.shift_minutes {
+            Some(minutes) => Duration::minutes(minutes),
+            None => return Result::Ok((active, current.start_at, current.end_at)),
+        };
+        let shifted = |at: Option<DateTime<Utc>>| match at {
+            Some(at) => at
+                .checked_add_signed(shift)
+                .map(Some)
+                .ok_or_else(|| "shift_minutes is out of range".to_string()),
+            None => Result::Ok(None),
+        };
+        Result::Ok((active, shifted(current.start_at)?, shifted(current.end_at)?))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ChallengeUpdateResult {
+    pub challenge_id: ChallengeId,
+    /// 適用できなかった理由。適用できた項目はNone
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ChallengeUpdateResult {
+    fn updated(challenge_id: ChallengeId) -> Self {
+        Self {
+            challenge_id,
+            error: None,
+        }
+    }
+
+    fn failed(challenge_id: ChallengeId, error: impl Into<String>) -> Self {
+        Self {
+            challenge_id,
+            error: Some(error.into()),
         }
     }
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BulkUpdateResult {
+    /// すべての変更をcommitしたか。falseのときは何も変わっていない
+    pub applied: bool,
+    pub results: Vec<ChallengeUpdateResult>,
+    /// 変更後のチャレンジ。applied=falseのときは空
+    pub challenges: Vec<Challenge>,
+}
+
+impl BulkUpdateResult {
+    /// 他の更新と競合した項目があるか
+    pub fn has_version_conflict(&self) -> bool {
+        self.results
+            .iter()
+            .any(|result| result.error.as_deref() == Some(VERSION_CONFLICT))
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct FindChallengeByQuestId {
-    pub quest_id: String,
+    pub quest_id: QuestId,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn challenge_with_schedule(
+        start_at: Option<DateTime<Utc>>,
+        end_at: Option<DateTime<Utc>>,
+    ) -> Challenge {
+        Challenge {
+            start_at,
+            end_at,
+            ..Challenge::new(
+                "challenge".into(),
+                "Test Challenge".to_string(),
+                "description".to_string(),
+                "quest".into(),
+                35.6895,
+                139.6917,
+                "stamp".to_string(),
+                "color".to_string(),
+                "gray".to_string(),
+                "flavor".to_string(),
+            )
+        }
+    }
+
+    fn update(active: Option<bool>, shift_minutes: Option<i64>) -> ChallengeUpdate {
+        ChallengeUpdate {
+            challenge_id: "challenge".into(),
+            active,
+            shift_minutes,
+            expected_version: None,
+        }
+    }
+
+    #[test]
+    fn should_shift_only_configured_bounds() {
+        let start_at = Utc.with_ymd_and_hms(2023, 11, 1, 9, 0, 0).unwrap();
+        let challenge = challenge_with_schedule(Some(start_at), None);
+
+        let (active, start, end) = update(None, Some(90)).apply(&challenge).unwrap();
+
+        assert!(active);
+        assert_eq!(Some(start_at + Duration::minutes(90)), start);
+        assert_eq!(None, end);
+    }
+
+    #[test]
+    fn should_keep_schedule_when_only_deactivating() {
+        let end_at = Utc.with_ymd_and_hms(2023, 11, 30, 18, 0, 0).unwrap();
+        let challenge = challenge_with_schedule(None, Some(end_at));
+
+        let (active, start, end) = update(Some(false), None).apply(&challenge).unwrap();
+
+        assert!(!active);
+        assert_eq!(None, start);
+        assert_eq!(Some(end_at), end);
+    }
+
+    #[test]
+    fn should_be_unavailable_outside_window_or_inactive() {
+        let start_at = Utc.with_ymd_and_hms(2023, 11, 1, 9, 0, 0).unwrap();
+        let end_at = Utc.with_ymd_and_hms(2023, 11, 30, 18, 0, 0).unwrap();
+        let challenge = challenge_with_schedule(Some(start_at), Some(end_at));
+
+        assert!(challenge.is_available_at(start_at));
+        assert!(!challenge.is_available_at(end_at));
+        let inactive = Challenge {
+            active: false,
+            ..challenge
+        };
+        assert!(!inactive.is_available_at(start_at));
+    }
+
+    #[test]
+    fn should_reject_stale_expected_version() {
+        let challenge = challenge_with_schedule(None, None);
+        let mut stale = update(Some(false), None);
+        stale.expected_version = Some(challenge.version + 1);
+
+        assert_eq!(Err(VERSION_CONFLICT.to_string()), stale.apply(&challenge));
+        stale.expected_version = Some(challenge.version);
+        assert!(stale.apply(&challenge).is_ok());
+    }
+
+    #[test]
+    fn should_reject_duplicated_challenge_ids() {
+        let payload = BulkUpdateChallenges {
+            updates: vec![update(Some(true), None), update(None, Some(10))],
+        };
+
+        assert!(payload.validate().is_err());
+    }
 }
@@ -0,0 +1,158 @@
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::fmt;
+
+use super::{
+    id::{QuestId, UserId},
+    ReadWritePgPool,
+};
+
+#[async_trait]
+pub trait DeviceRepository: Send + Sync + 'static {
+    /// 登録済みのトークンなら持ち主を付け替える
+    async fn register(&self, user_id: UserId, payload: RegisterDevice) -> anyhow::Result<Device>;
+    /// クエストの参加者の端末トークン。通知設定で絞れるよう持ち主も返す
+    async fn find_tokens_by_quest_id(&self, quest_id: QuestId) -> anyhow::Result<Vec<DeviceToken>>;
+    async fn delete_token(&self, token: String) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceRepositoryForDb {
+    pool: ReadWritePgPool,
+}
+
+impl DeviceRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        DeviceRepositoryForDb { pool: pool.into() }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        DeviceRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl DeviceRepository for DeviceRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn register(&self, user_id: UserId, payload: RegisterDevice) -> anyhow::Result<Device> {
+        let row = sqlx::query_as::<_, DeviceFromRow>(
+            r#"
+                insert into devices (token, user_id, platform) values ($1, $2, $3)
+                on conflict (token) do update
+                    set user_id = excluded.user_id,
+                        platform = excluded.platform,
+                        updated_at = now()
+                returning token, platform
+            "#,
+        )
+        .bind(payload.token)
+        .bind(user_id)
+        .bind(payload.platform.to_string())
+        .fetch_one(self.pool.write())
+        .await?;
+
+        anyhow::Ok(Device {
+            token: row.token,
+            platform: row.platform.parse()?,
+        })
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_tokens_by_quest_id(&self, quest_id: QuestId) -> anyhow::Result<Vec<DeviceToken>> {
+        let tokens = sqlx::query_as::<_, DeviceToken>(
+            r#"
+                select d.user_id, d.token from devices d
+                join user_participating_quests p on p.user_id = d.user_id
+                where p.quest_id = $1;
+            "#,
+        )
+        .bind(quest_id)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        anyhow::Ok(tokens)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn delete_token(&self, token: String) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                delete from devices where token = $1
+            "#,
+        )
+        .bind(token)
+        .execute(self.pool.write())
+        .await?;
+
+        anyhow::Ok(())
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct DeviceFromRow {
+    token: String,
+    platform: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Ios,
+    Android,
+    Web,
+}
+
+impl std::str::FromStr for Platform {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ios" => Ok(Self::Ios),
+            "android" => Ok(Self::Android),
+            "web" => Ok(Self::Web),
+            _ => Err(anyhow::anyhow!("Invalid platform : {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let platform = match self {
+            Platform::Ios => "ios",
+            Platform::Android => "android",
+            Platform::Web => "web",
+        };
+        write!(f, "{}", platform)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Device {
+    pub token: String,
+    pub platform: Platform,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct DeviceToken {
+    pub user_id: UserId,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RegisterDevice {
+    token: String,
+    platform: Platform,
+}
+
+impl RegisterDevice {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.token.trim().is_empty() {
+            return Err("token must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
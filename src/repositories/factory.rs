@@ -0,0 +1,252 @@
+use nanoid::nanoid;
+use std::str::FromStr;
+
+use super::{
+    challenge::CreateChallenge,
+    id::UserId,
+    quest::{CreateQuest, QuestEntity, QuestStatus},
+    user::{RegisterUser, UserEntity},
+    Repositories,
+};
+
+/// 投入したユーザーは全員このパスワードでログインできる
+pub const SEED_PASSWORD: &str = "seed_password";
+
+/// 東京駅を起点にチャレンジを並べる
+const BASE_LATITUDE: f64 = 35.6812;
+const BASE_LONGITUDE: f64 = 139.7671;
+
+/// 投入するユーザーの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scenario {
+    /// 登録しただけで、どのクエストにも参加していない
+    NewUsers,
+    /// すべてのクエストに参加し、チャレンジもすべて完了している
+    HeavyUsers,
+    /// 新規ユーザーとヘビーユーザーを半分ずつ
+    Mixed,
+}
+
+impl FromStr for Scenario {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "new" => Ok(Self::NewUsers),
+            "heavy" => Ok(Self::HeavyUsers),
+            "mixed" => Ok(Self::Mixed),
+            _ => Err(format!("unknown scenario: {} (new, heavy, mixed)", s)),
+        }
+    }
+}
+
+impl Scenario {
+    /// n人目をヘビーユーザーにするか
+    fn is_heavy(&self, n: usize) -> bool {
+        match self {
+            Self::NewUsers => false,
+            Self::HeavyUsers => true,
+            Self::Mixed => n % 2 == 1,
+        }
+    }
+}
+
+/// 何をどれだけ投入するか
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedPlan {
+    pub scenario: Scenario,
+    pub users: usize,
+    pub quests: usize,
+    pub challenges_per_quest: usize,
+}
+
+impl Default for SeedPlan {
+    fn default() -> Self {
+        Self {
+            scenario: Scenario::Mixed,
+            users: 10,
+            quests: 3,
+            challenges_per_quest: 5,
+        }
+    }
+}
+
+impl SeedPlan {
+    /// `--scenario heavy --users 100 --quests 5 --challenges 10` の形式。省略した項目は既定値
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut plan = Self::default();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {}", flag))?;
+            match flag.as_str() {
+                "--scenario" => plan.scenario = value.parse()?,
+                "--users" => plan.users = parse_count(&flag, &value)?,
+                "--quests" => plan.quests = parse_count(&flag, &value)?,
+                "--challenges" => plan.challenges_per_quest = parse_count(&flag, &value)?,
+                _ => return Err(format!("unknown option: {}", flag)),
+            }
+        }
+        Ok(plan)
+    }
+}
+
+fn parse_count(flag: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} must be a non-negative integer: {}", flag, value))
+}
+
+/// 投入した件数
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeedReport {
+    pub users: usize,
+    pub quests: usize,
+    pub challenges: usize,
+    pub participations: usize,
+    pub completions: usize,
+}
+
+/// 開発用のデータを作る。APIと同じリポジトリを通すので、パスワードのハッシュ化などもそのまま行われる
+pub struct Factory {
+    repositories: Repositories,
+}
+
+impl Factory {
+    pub fn new(repositories: Repositories) -> Self {
+        Self { repositories }
+    }
+
+    /// メールアドレスは重複しないよう毎回変える
+    pub async fn user(&self, n: usize) -> anyhow::Result<UserEntity> {
+        self.repositories
+            .user
+            .register(RegisterUser::new(
+                format!("seed_user_{}", n),
+                format!("seed+{}@example.com", nanoid!(10)),
+                SEED_PASSWORD.to_string(),
+            ))
+            .await
+    }
+
+    /// 公開済みのクエストを作り、チャレンジを東に向かって並べる
+    pub async fn quest(&self, n: usize, challenges: usize) -> anyhow::Result<QuestEntity> {
+        let mut quest = self
+            .repositories
+            .quest
+            .create(
+                CreateQuest::new(
+                    format!("シードクエスト{}", n),
+                    "開発用に投入したクエストです".to_string(),
+                )
+                .with_status(QuestStatus::Published),
+            )
+            .await?;
+        for i in 0..challenges {
+            let challenge = self
+                .repositories
+                .challenge
+                .create(CreateChallenge::new(
+                    format!("シードチャレンジ{}-{}", n, i),
+                    "開発用に投入したチャレンジです".to_string(),
+                    quest.id.clone(),
+                    BASE_LATITUDE + n as f64 * 0.01,
+                    BASE_LONGITUDE + i as f64 * 0.01,
+                    format!("スタンプ{}-{}", n, i),
+                    "http://localhost:4566/quest-app-images-bucket/seed_color.png".to_string(),
+                    "http://localhost:4566/quest-app-images-bucket/seed_gray.png".to_string(),
+                    "開発用のスタンプです".to_string(),
+                ))
+                .await?;
+            quest.challenges.push(challenge);
+        }
+        Ok(quest)
+    }
+
+    /// クエストに参加し、チャレンジをすべて完了する。参加数と完了数を返す
+    pub async fn complete_quests(
+        &self,
+        user_id: &UserId,
+        quests: &[QuestEntity],
+    ) -> anyhow::Result<(usize, usize)> {
+        let mut completions = 0;
+        for quest in quests {
+            self.repositories
+                .userquest
+                .save_quest_participate_event(user_id.clone(), quest.id.clone())
+                .await?;
+            for challenge in &quest.challenges {
+                self.repositories
+                    .userchallenge
+                    .save_challenge_complete_event(user_id.clone(), challenge.id.clone())
+                    .await?;
+                completions += 1;
+            }
+        }
+        Ok((quests.len(), completions))
+    }
+
+    pub async fn seed(&self, plan: &SeedPlan) -> anyhow::Result<SeedReport> {
+        let mut report = SeedReport::default();
+        let mut quests = Vec::with_capacity(plan.quests);
+        for n in 0..plan.quests {
+            let quest = self.quest(n, plan.challenges_per_quest).await?;
+            report.quests += 1;
+            report.challenges += quest.challenges.len();
+            quests.push(quest);
+        }
+        for n in 0..plan.users {
+            let user = self.user(n).await?;
+            report.users += 1;
+            if plan.scenario.is_heavy(n) {
+                let (participations, completions) = self.complete_quests(&user.id, &quests).await?;
+                report.participations += participations;
+                report.completions += completions;
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn should_parse_plan_from_args() {
+        let plan = SeedPlan::from_args(args("--scenario heavy --users 100 --challenges 2"));
+
+        assert_eq!(
+            Ok(SeedPlan {
+                scenario: Scenario::HeavyUsers,
+                users: 100,
+                quests: 3,
+                challenges_per_quest: 2,
+            }),
+            plan
+        );
+        assert_eq!(Ok(SeedPlan::default()), SeedPlan::from_args(args("")));
+    }
+
+    #[test]
+    fn should_reject_invalid_args() {
+        assert!(SeedPlan::from_args(args("--scenario light")).is_err());
+        assert!(SeedPlan::from_args(args("--users -1")).is_err());
+        assert!(SeedPlan::from_args(args("--users")).is_err());
+        assert!(SeedPlan::from_args(args("--verbose 1")).is_err());
+    }
+
+    #[test]
+    fn should_alternate_users_in_mixed_scenario() {
+        let heavy = (0..4).filter(|n| Scenario::Mixed.is_heavy(*n)).count();
+
+        assert_eq!(2, heavy);
+        assert!(!Scenario::NewUsers.is_heavy(1));
+        assert!(Scenario::HeavyUsers.is_heavy(0));
+    }
+}
@@ -0,0 +1,136 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::fmt;
+
+use super::ReadWritePgPool;
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait FeatureFlagRepository: Send + Sync + 'static {
+    /// 管理APIで上書きされた値だけを返す。今は使わない名前の行は読み飛ばす
+    async fn all(&self) -> anyhow::Result<Vec<FeatureFlag>>;
+    async fn set(&self, feature: Feature, enabled: bool) -> anyhow::Result<FeatureFlag>;
+}
+
+/// 段階的に公開する機能
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    Reviews,
+    Points,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 2] = [Feature::Reviews, Feature::Points];
+}
+
+impl std::str::FromStr for Feature {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reviews" => Ok(Self::Reviews),
+            "points" => Ok(Self::Points),
+            _ => Err(anyhow::anyhow!("Invalid feature : {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let feature = match self {
+            Feature::Reviews => "reviews",
+            Feature::Points => "points",
+        };
+        write!(f, "{}", feature)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeatureFlag {
+    pub name: Feature,
+    pub enabled: bool,
+    /// 既定値のままならNone
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SetFeatureFlag {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct FeatureFlagFromRow {
+    name: String,
+    enabled: bool,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FeatureFlagRepositoryForDb {
+    pool: ReadWritePgPool,
+}
+
+impl FeatureFlagRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        FeatureFlagRepositoryForDb { pool: pool.into() }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        FeatureFlagRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl FeatureFlagRepository for FeatureFlagRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn all(&self) -> anyhow::Result<Vec<FeatureFlag>> {
+        let rows = sqlx::query_as::<_, FeatureFlagFromRow>(
+            r#"
+                select name, enabled, updated_at from feature_flags order by name;
+            "#,
+        )
+        .fetch_all(self.pool.read())
+        .await?;
+
+        anyhow::Ok(
+            rows.into_iter()
+                .filter_map(|row| {
+                    Some(FeatureFlag {
+                        name: row.name.parse().ok()?,
+                        enabled: row.enabled,
+                        updated_at: Some(row.updated_at),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn set(&self, feature: Feature, enabled: bool) -> anyhow::Result<FeatureFlag> {
+        let row = sqlx::query_as::<_, FeatureFlagFromRow>(
+            r#"
+                insert into feature_flags (name, enabled) values ($1, $2)
+                on conflict (name) do update
+                    set enabled = excluded.enabled,
+                        updated_at = now()
+                returning name, enabled, updated_at
+            "#,
+        )
+        .bind(feature.to_string())
+        .bind(enabled)
+        .fetch_one(self.pool.write())
+        .await?;
+
+        anyhow::Ok(FeatureFlag {
+            name: feature,
+            enabled: row.enabled,
+            updated_at: Some(row.updated_at),
+        })
+    }
+}
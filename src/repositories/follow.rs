@@ -0,0 +1,247 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use super::{
+    id::{ChallengeId, QuestId, UserId},
+    ReadWritePgPool,
+};
+
+/// フィードの1ページの件数
+const FEED_DEFAULT_LIMIT: i64 = 20;
+const FEED_MAX_LIMIT: i64 = 100;
+
+#[async_trait]
+pub trait FollowRepository: Send + Sync + 'static {
+    /// すでにフォローしていても成功にする
+    async fn follow(&self, follower_id: UserId, followee_id: UserId) -> anyhow::Result<()>;
+    /// フォローしていなくても成功にする
+    async fn unfollow(&self, follower_id: UserId, followee_id: UserId) -> anyhow::Result<()>;
+    /// user_idがフォローしているユーザー。新しくフォローした順
+    async fn find_following(&self, user_id: UserId) -> anyhow::Result<Vec<FollowUser>>;
+    /// user_idをフォローしているユーザー。新しくフォローされた順
+    async fn find_followers(&self, user_id: UserId) -> anyhow::Result<Vec<FollowUser>>;
+    /// フォロー中のユーザーのチャレンジ完了を新しい順に返す
+    async fn find_feed(&self, user_id: UserId, query: FeedQuery) -> anyhow::Result<Vec<Activity>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct FollowUser {
+    pub id: String,
+    pub username: String,
+    pub followed_at: DateTime<Utc>,
+}
+
+/// beforeに前のページの最後のcompleted_atを渡すと続きを返す
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FeedQuery {
+    pub before: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+impl FeedQuery {
+    fn limit(&self) -> i64 {
+        self.limit
+            .unwrap_or(FEED_DEFAULT_LIMIT)
+            .clamp(1, FEED_MAX_LIMIT)
+    }
+}
+
+/// フィードの1件。種類が増えてもクライアントがtypeで分岐できるようにしておく
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Activity {
+    ChallengeCompleted {
+        user_id: UserId,
+        username: String,
+        challenge_id: ChallengeId,
+        challenge_name: String,
+        quest_id: QuestId,
+        quest_title: String,
+        completed_at: DateTime<Utc>,
+    },
+}
+
+#[derive(FromRow)]
+struct ChallengeCompletedRow {
+    user_id: UserId,
+    username: String,
+    challenge_id: ChallengeId,
+    challenge_name: String,
+    quest_id: QuestId,
+    quest_title: String,
+    completed_at: DateTime<Utc>,
+}
+
+impl From<ChallengeCompletedRow> for Activity {
+    fn from(row: ChallengeCompletedRow) -> Self {
+        Self::ChallengeCompleted {
+            user_id: row.user_id,
+            username: row.username,
+            challenge_id: row.challenge_id,
+            challenge_name: row.challenge_name,
+            quest_id: row.quest_id,
+            quest_title: row.quest_title,
+            completed_at: row.completed_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FollowRepositoryForDb {
+    pool: ReadWritePgPool,
+}
+
+impl FollowRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        FollowRepositoryForDb { pool: pool.into() }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        FollowRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl FollowRepository for FollowRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn follow(&self, follower_id: UserId, followee_id: UserId) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                insert into user_follows (follower_id, followee_id) values ($1, $2)
+                on conflict do nothing
+            "#,
+        )
+        .bind(follower_id)
+        .bind(followee_id)
+        .execute(self.pool.write())
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn unfollow(&self, follower_id: UserId, followee_id: UserId) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                delete from user_follows where follower_id = $1 and followee_id = $2
+            "#,
+        )
+        .bind(follower_id)
+        .bind(followee_id)
+        .execute(self.pool.write())
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_following(&self, user_id: UserId) -> anyhow::Result<Vec<FollowUser>> {
+        let users = sqlx::query_as::<_, FollowUser>(
+            r#"
+                select u.id, u.username, f.created_at as followed_at
+                from user_follows f
+                join users u on u.id = f.followee_id and u.deleted_at is null
+                where f.follower_id = $1
+                order by f.created_at desc
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(users)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_followers(&self, user_id: UserId) -> anyhow::Result<Vec<FollowUser>> {
+        let users = sqlx::query_as::<_, FollowUser>(
+            r#"
+                select u.id, u.username, f.created_at as followed_at
+                from user_follows f
+                join users u on u.id = f.follower_id and u.deleted_at is null
+                where f.followee_id = $1
+                order by f.created_at desc
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(users)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_feed(&self, user_id: UserId, query: FeedQuery) -> anyhow::Result<Vec<Activity>> {
+        let rows = sqlx::query_as::<_, ChallengeCompletedRow>(
+            r#"
+                select
+                    u.id as user_id,
+                    u.username,
+                    c.id as challenge_id,
+                    c.name as challenge_name,
+                    q.id as quest_id,
+                    q.title as quest_title,
+                    ucc.completed_at
+                from user_follows f
+                join user_completed_challenges ucc on ucc.user_id = f.followee_id
+                join users u on u.id = ucc.user_id and u.deleted_at is null
+                join challenges c on c.id = ucc.challenge_id
+                join quests q on q.id = c.quest_id and q.deleted_at is null
+                where f.follower_id = $1
+                    and ($2::timestamptz is null or ucc.completed_at < $2)
+                order by ucc.completed_at desc
+                limit $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(query.before)
+        .bind(query.limit())
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(rows.into_iter().map(Activity::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_clamp_feed_limit() {
+        assert_eq!(FEED_DEFAULT_LIMIT, FeedQuery::default().limit());
+        let query = FeedQuery {
+            before: None,
+            limit: Some(1000),
+        };
+        assert_eq!(FEED_MAX_LIMIT, query.limit());
+        let query = FeedQuery {
+            before: None,
+            limit: Some(0),
+        };
+        assert_eq!(1, query.limit());
+    }
+
+    #[test]
+    fn should_tag_activity_with_type() {
+        let activity = Activity::ChallengeCompleted {
+            user_id: "user".into(),
+            username: "test_user".to_string(),
+            challenge_id: "challenge".into(),
+            challenge_name: "Test Challenge".to_string(),
+            quest_id: "quest".into(),
+            quest_title: "Test Quest".to_string(),
+            completed_at: Utc::now(),
+        };
+
+        let json = serde_json::to_value(&activity).unwrap();
+
+        assert_eq!("challenge_completed", json["type"]);
+        assert_eq!("test_user", json["username"]);
+    }
+}
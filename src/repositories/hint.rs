@@ -0,0 +1,274 @@
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::sync::Arc;
+
+use super::{
+    id::{default_id_generator, ChallengeId, HintId, IdGenerator, UserId},
+    ReadWritePgPool,
+};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait HintRepository: Send + Sync + 'static {
+    /// チャレンジがなければNotFound
+    async fn create(
+        &self,
+        challenge_id: ChallengeId,
+        payload: CreateHint,
+    ) -> Result<Hint, HintError>;
+    /// order_indexの順。user_idが開いていないヒントは本文を返さない
+    async fn find_by_challenge_id(
+        &self,
+        challenge_id: ChallengeId,
+        user_id: Option<UserId>,
+    ) -> anyhow::Result<Vec<Hint>>;
+    /// 前のヒントをすべて開いていれば開き、costの分だけポイントを差し引く
+    /// 開いたことのあるヒントは差し引かずにそのまま返す
+    async fn open(
+        &self,
+        user_id: UserId,
+        challenge_id: ChallengeId,
+        hint_id: HintId,
+    ) -> Result<Hint, HintError>;
+}
+
+#[derive(Debug)]
+pub enum HintError {
+    NotFound,
+    /// 前のヒントをまだ開いていない
+    Locked,
+    InsufficientPoints {
+        balance: i64,
+        cost: i32,
+    },
+    Repository(anyhow::Error),
+}
+
+impl From<sqlx::Error> for HintError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Repository(e.into())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HintRepositoryForDb {
+    pool: ReadWritePgPool,
+    ids: Arc<dyn IdGenerator>,
+}
+
+impl HintRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        HintRepositoryForDb {
+            pool: pool.into(),
+            ids: default_id_generator(),
+        }
+    }
+
+    pub fn with_id_generator(self, ids: Arc<dyn IdGenerator>) -> Self {
+        Self { ids, ..self }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        HintRepositoryForDb::new(pool)
+    }
+}
+
+/// 開いていないヒントは本文をNULLにする。$2がNULLなら全て開いていない扱い
+const HINTS_QUERY: &str = r#"
+    select
+        h.id,
+        h.challenge_id,
+        h.order_index,
+        h.cost,
+        o.user_id is not null as opened,
+        case when o.user_id is not null then h.body end as body
+    from challenge_hints h
+    left join user_opened_hints o on o.hint_id = h.id and o.user_id = $2
+    where h.challenge_id = $1
+"#;
+
+#[async_trait]
+impl HintRepository for HintRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn create(
+        &self,
+        challenge_id: ChallengeId,
+        payload: CreateHint,
+    ) -> Result<Hint, HintError> {
+        let hint = sqlx::query_as::<_, Hint>(
+            r#"
+                insert into challenge_hints (id, challenge_id, body, order_index, cost)
+                select $1, $2, $3, $4, $5
+                where exists (select 1 from challenges where id = $2)
+                returning id, challenge_id, order_index, cost, true as opened, body
+            "#,
+        )
+        .bind(self.ids.generate())
+        .bind(challenge_id)
+        .bind(payload.body)
+        .bind(payload.order_index)
+        .bind(payload.cost)
+        .fetch_optional(self.pool.write())
+        .await?;
+
+        hint.ok_or(HintError::NotFound)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_by_challenge_id(
+        &self,
+        challenge_id: ChallengeId,
+        user_id: Option<UserId>,
+    ) -> anyhow::Result<Vec<Hint>> {
+        let hints = sqlx::query_as::<_, Hint>(&format!(
+            "{} order by h.order_index, h.created_at, h.id;",
+            HINTS_QUERY
+        ))
+        .bind(challenge_id)
+        .bind(user_id)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(hints)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn open(
+        &self,
+        user_id: UserId,
+        challenge_id: ChallengeId,
+        hint_id: HintId,
+    ) -> Result<Hint, HintError> {
+        let mut tx = self.pool.write().begin().await?;
+        // 同じユーザーの開封を直列にして、残高の確認から差し引きまでの間に割り込まれないようにする
+        sqlx::query("select 1 from users where id = $1 for update")
+            .bind(user_id.clone())
+            .execute(&mut tx)
+            .await?;
+
+        let hint = sqlx::query_as::<_, Hint>(&format!("{} and h.id = $3;", HINTS_QUERY))
+            .bind(challenge_id.clone())
+            .bind(user_id.clone())
+            .bind(hint_id.clone())
+            .fetch_optional(&mut tx)
+            .await?;
+        let hint = match hint {
+            Some(hint) if hint.opened => {
+                tx.rollback().await?;
+                return Ok(hint);
+            }
+            Some(hint) => hint,
+            None => {
+                tx.rollback().await?;
+                return Err(HintError::NotFound);
+            }
+        };
+
+        let locked = sqlx::query_scalar::<_, bool>(
+            r#"
+                select exists (
+                    select 1 from challenge_hints h
+                    where h.challenge_id = $1 and h.order_index < $2
+                    and not exists (
+                        select 1 from user_opened_hints o
+                        where o.hint_id = h.id and o.user_id = $3
+                    )
+                )
+            "#,
+        )
+        .bind(challenge_id.clone())
+        .bind(hint.order_index)
+        .bind(user_id.clone())
+        .fetch_one(&mut tx)
+        .await?;
+        if locked {
+            tx.rollback().await?;
+            return Err(HintError::Locked);
+        }
+
+        if hint.cost > 0 {
+            let balance = sqlx::query_scalar::<_, i64>(
+                r#"
+                    select coalesce(sum(amount), 0) from point_transactions where user_id = $1;
+                "#,
+            )
+            .bind(user_id.clone())
+            .fetch_one(&mut tx)
+            .await?;
+            if balance < hint.cost as i64 {
+                tx.rollback().await?;
+                return Err(HintError::InsufficientPoints {
+                    balance,
+                    cost: hint.cost,
+                });
+            }
+            sqlx::query(
+                r#"
+                    insert into point_transactions (user_id, hint_id, amount) values ($1, $2, $3)
+                "#,
+            )
+            .bind(user_id.clone())
+            .bind(hint_id.clone())
+            .bind(-hint.cost)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        sqlx::query(
+            r#"
+                insert into user_opened_hints (user_id, hint_id) values ($1, $2)
+            "#,
+        )
+        .bind(user_id.clone())
+        .bind(hint_id.clone())
+        .execute(&mut tx)
+        .await?;
+
+        let hint = sqlx::query_as::<_, Hint>(&format!("{} and h.id = $3;", HINTS_QUERY))
+            .bind(challenge_id)
+            .bind(user_id)
+            .bind(hint_id)
+            .fetch_one(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(hint)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct Hint {
+    pub id: HintId,
+    pub challenge_id: ChallengeId,
+    pub order_index: i32,
+    /// 開くときに差し引くポイント
+    pub cost: i32,
+    pub opened: bool,
+    /// 開いていなければNone
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CreateHint {
+    pub body: String,
+    #[serde(default)]
+    pub order_index: i32,
+    #[serde(default)]
+    pub cost: i32,
+}
+
+impl CreateHint {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.body.trim().is_empty() {
+            return Err("body must not be empty".to_string());
+        }
+        if self.cost < 0 {
+            return Err(format!("cost must not be negative: {}", self.cost));
+        }
+        Ok(())
+    }
+}
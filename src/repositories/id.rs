@@ -0,0 +1,226 @@
+use nanoid::nanoid;
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgHasArrayType, PgTypeInfo, PgValueRef},
+    Postgres,
+};
+#[cfg(test)]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::{borrow::Borrow, convert::Infallible, fmt, str::FromStr, sync::Arc};
+
+/// 文字列のIDを種類ごとの型に包む。JSONやDBでは中身の文字列そのものとして扱う
+/// 取り違えたIDを渡すとコンパイルエラーになる
+macro_rules! define_id {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(transparent)]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn new(id: impl Into<String>) -> Self {
+                Self(id.into())
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            pub fn into_string(self) -> String {
+                self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(id: String) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(id: &str) -> Self {
+                Self(id.to_string())
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        /// CLIの引数などから読む。形式は問わない
+        impl FromStr for $name {
+            type Err = Infallible;
+
+            fn from_str(id: &str) -> Result<Self, Self::Err> {
+                Ok(Self::from(id))
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Borrow<str> for $name {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl PartialEq<String> for $name {
+            fn eq(&self, other: &String) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl PartialEq<$name> for &str {
+            fn eq(&self, other: &$name) -> bool {
+                *self == other.0
+            }
+        }
+
+        impl PartialEq<$name> for String {
+            fn eq(&self, other: &$name) -> bool {
+                *self == other.0
+            }
+        }
+
+        impl sqlx::Type<Postgres> for $name {
+            fn type_info() -> PgTypeInfo {
+                <String as sqlx::Type<Postgres>>::type_info()
+            }
+
+            fn compatible(ty: &PgTypeInfo) -> bool {
+                <String as sqlx::Type<Postgres>>::compatible(ty)
+            }
+        }
+
+        impl PgHasArrayType for $name {
+            fn array_type_info() -> PgTypeInfo {
+                <String as PgHasArrayType>::array_type_info()
+            }
+        }
+
+        impl sqlx::Encode<'_, Postgres> for $name {
+            fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+                <String as sqlx::Encode<Postgres>>::encode_by_ref(&self.0, buf)
+            }
+        }
+
+        impl<'r> sqlx::Decode<'r, Postgres> for $name {
+            fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+                <String as sqlx::Decode<Postgres>>::decode(value).map(Self)
+            }
+        }
+    };
+}
+
+define_id!(
+    /// quests.id
+    QuestId
+);
+define_id!(
+    /// users.id
+    UserId
+);
+define_id!(
+    /// challenges.id
+    ChallengeId
+);
+define_id!(
+    /// challenge_hints.id
+    HintId
+);
+
+/// 新しい行のIDを払い出す。テストでは決まった順のIDに差し替えられる
+pub trait IdGenerator: fmt::Debug + Send + Sync + 'static {
+    fn generate(&self) -> String;
+}
+
+/// 本番で使う既定の実装
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NanoIdGenerator;
+
+impl IdGenerator for NanoIdGenerator {
+    fn generate(&self) -> String {
+        nanoid!()
+    }
+}
+
+pub fn default_id_generator() -> Arc<dyn IdGenerator> {
+    Arc::new(NanoIdGenerator)
+}
+
+/// `{prefix}-1`, `{prefix}-2`, ... の順に払い出す。結果のIDをテストで決め打ちしたいとき用
+#[cfg(test)]
+#[derive(Debug)]
+pub struct SequentialIdGenerator {
+    prefix: String,
+    next: AtomicU64,
+}
+
+#[cfg(test)]
+impl SequentialIdGenerator {
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            next: AtomicU64::new(1),
+        }
+    }
+}
+
+#[cfg(test)]
+impl IdGenerator for SequentialIdGenerator {
+    fn generate(&self) -> String {
+        format!(
+            "{}-{}",
+            self.prefix,
+            self.next.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_generate_sequential_ids() {
+        let ids = SequentialIdGenerator::new("quest");
+
+        assert_eq!("quest-1", ids.generate());
+        assert_eq!("quest-2", ids.generate());
+    }
+
+    #[test]
+    fn should_serialize_id_as_plain_string() {
+        let id = QuestId::new("abc");
+
+        assert_eq!("\"abc\"", serde_json::to_string(&id).unwrap());
+        assert_eq!(id, serde_json::from_str::<QuestId>("\"abc\"").unwrap());
+        assert_eq!("abc", id.to_string());
+    }
+}
@@ -0,0 +1,196 @@
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{types::Json, FromRow};
+
+use super::ReadWritePgPool;
+
+/// キーを保持する時間。過ぎたキーは新しいリクエストとして扱い、定期処理で消す
+pub const KEY_TTL_HOURS: i64 = 24;
+
+/// 処理中のまま残ったキー(プロセスが落ちた等)は、この時間を過ぎたら引き継げる
+const IN_PROGRESS_TIMEOUT_SECS: i64 = 5 * 60;
+
+#[async_trait]
+pub trait IdempotencyRepository: Send + Sync + 'static {
+    /// キーを処理中として押さえる。すでに使われていればその記録を返す
+    async fn begin(
+        &self,
+        scope: &str,
+        key: &str,
+        request_hash: &str,
+    ) -> anyhow::Result<Option<IdempotencyRecord>>;
+    /// 処理結果を保存する
+    async fn complete(
+        &self,
+        scope: &str,
+        key: &str,
+        response: &StoredResponse,
+    ) -> anyhow::Result<()>;
+    /// 同じキーで再試行できるように押さえたキーを手放す
+    async fn release(&self, scope: &str, key: &str) -> anyhow::Result<()>;
+    /// beforeより前のキーを消し、件数を返す
+    async fn delete_expired(&self, before: DateTime<Utc>) -> anyhow::Result<u64>;
+}
+
+/// 使用済みのキー。responseがNoneならまだ処理中
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotencyRecord {
+    pub request_hash: String,
+    pub response: Option<StoredResponse>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(FromRow)]
+struct IdempotencyRow {
+    request_hash: String,
+    status: Option<i16>,
+    headers: Option<Json<Vec<(String, String)>>>,
+    body: Option<Vec<u8>>,
+}
+
+impl From<IdempotencyRow> for IdempotencyRecord {
+    fn from(row: IdempotencyRow) -> Self {
+        let response = row.status.map(|status| StoredResponse {
+            status: status as u16,
+            headers: row.headers.map(|headers| headers.0).unwrap_or_default(),
+            body: row.body.unwrap_or_default(),
+        });
+        Self {
+            request_hash: row.request_hash,
+            response,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IdempotencyRepositoryForDb {
+    pool: ReadWritePgPool,
+}
+
+impl IdempotencyRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        IdempotencyRepositoryForDb { pool: pool.into() }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        IdempotencyRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepository for IdempotencyRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn begin(
+        &self,
+        scope: &str,
+        key: &str,
+        request_hash: &str,
+    ) -> anyhow::Result<Option<IdempotencyRecord>> {
+        let now = Utc::now();
+        // 期限切れのキーと処理中のまま放置されたキーは上書きして押さえ直す
+        let acquired = sqlx::query_scalar::<_, String>(
+            r#"
+                insert into idempotency_keys (scope, key, request_hash, created_at)
+                values ($1, $2, $3, $4)
+                on conflict (scope, key) do update
+                set request_hash = excluded.request_hash,
+                    status = null,
+                    headers = null,
+                    body = null,
+                    created_at = excluded.created_at
+                where idempotency_keys.created_at < $5
+                    or (idempotency_keys.status is null and idempotency_keys.created_at < $6)
+                returning key
+            "#,
+        )
+        .bind(scope)
+        .bind(key)
+        .bind(request_hash)
+        .bind(now)
+        .bind(now - Duration::hours(KEY_TTL_HOURS))
+        .bind(now - Duration::seconds(IN_PROGRESS_TIMEOUT_SECS))
+        .fetch_optional(self.pool.write())
+        .await?;
+
+        if acquired.is_some() {
+            return Ok(None);
+        }
+
+        let row = sqlx::query_as::<_, IdempotencyRow>(
+            r#"
+                select request_hash, status, headers, body from idempotency_keys
+                where scope = $1 and key = $2
+            "#,
+        )
+        .bind(scope)
+        .bind(key)
+        .fetch_one(self.pool.write())
+        .await?;
+
+        Ok(Some(row.into()))
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn complete(
+        &self,
+        scope: &str,
+        key: &str,
+        response: &StoredResponse,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                update idempotency_keys set status = $3, headers = $4, body = $5
+                where scope = $1 and key = $2
+            "#,
+        )
+        .bind(scope)
+        .bind(key)
+        .bind(response.status as i16)
+        .bind(Json(&response.headers))
+        .bind(&response.body)
+        .execute(self.pool.write())
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn release(&self, scope: &str, key: &str) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                delete from idempotency_keys
+                where scope = $1 and key = $2 and status is null
+            "#,
+        )
+        .bind(scope)
+        .bind(key)
+        .execute(self.pool.write())
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn delete_expired(&self, before: DateTime<Utc>) -> anyhow::Result<u64> {
+        let deleted = sqlx::query(
+            r#"
+                delete from idempotency_keys where created_at < $1
+            "#,
+        )
+        .bind(before)
+        .execute(self.pool.write())
+        .await?
+        .rows_affected();
+
+        Ok(deleted)
+    }
+}
@@ -0,0 +1,306 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::{fmt, sync::Arc};
+
+use super::{
+    id::{default_id_generator, IdGenerator, UserId},
+    ReadWritePgPool,
+};
+
+/// `GET /jobs` で返す件数
+const LIST_LIMIT: i64 = 50;
+
+/// 状態の更新はrunningのジョブにだけ効く。キャンセル後に完了した結果で上書きしないため
+#[async_trait]
+pub trait JobRepository: Send + Sync + 'static {
+    async fn create(&self, user_id: UserId, kind: JobKind) -> anyhow::Result<Job>;
+    async fn find(&self, id: String) -> anyhow::Result<Job>;
+    /// 新しいものから返す
+    async fn find_by_user_id(&self, user_id: UserId) -> anyhow::Result<Vec<Job>>;
+    /// まだ実行中ならtrue
+    async fn update_progress(&self, id: String, progress: i16) -> anyhow::Result<bool>;
+    async fn succeed(&self, id: String, output: JobOutput) -> anyhow::Result<()>;
+    async fn fail(&self, id: String, error: String) -> anyhow::Result<()>;
+    /// キャンセルできたらtrue。終わっているジョブや他人のジョブはfalse
+    async fn cancel(&self, id: String, user_id: UserId) -> anyhow::Result<bool>;
+    async fn find_output(&self, id: String) -> anyhow::Result<Option<JobOutput>>;
+}
+
+#[derive(Debug, Clone)]
+pub struct JobRepositoryForDb {
+    pool: ReadWritePgPool,
+    ids: Arc<dyn IdGenerator>,
+}
+
+impl JobRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        JobRepositoryForDb {
+            pool: pool.into(),
+            ids: default_id_generator(),
+        }
+    }
+
+    pub fn with_id_generator(self, ids: Arc<dyn IdGenerator>) -> Self {
+        Self { ids, ..self }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        JobRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl JobRepository for JobRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn create(&self, user_id: UserId, kind: JobKind) -> anyhow::Result<Job> {
+        let row = sqlx::query_as::<_, JobFromRow>(
+            r#"
+                insert into jobs (id, user_id, kind, status) values ($1, $2, $3, 'running')
+                returning id, user_id, kind, status, progress, error, created_at, updated_at
+            "#,
+        )
+        .bind(self.ids.generate())
+        .bind(user_id)
+        .bind(kind.to_string())
+        .fetch_one(self.pool.write())
+        .await?;
+
+        row.try_into()
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find(&self, id: String) -> anyhow::Result<Job> {
+        // 作成した直後からポーリングされるのでプライマリから読む
+        let row = sqlx::query_as::<_, JobFromRow>(
+            r#"
+                select id, user_id, kind, status, progress, error, created_at, updated_at
+                from jobs where id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_one(self.pool.write())
+        .await?;
+
+        row.try_into()
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_by_user_id(&self, user_id: UserId) -> anyhow::Result<Vec<Job>> {
+        let rows = sqlx::query_as::<_, JobFromRow>(
+            r#"
+                select id, user_id, kind, status, progress, error, created_at, updated_at
+                from jobs where user_id = $1
+                order by created_at desc
+                limit $2
+            "#,
+        )
+        .bind(user_id)
+        .bind(LIST_LIMIT)
+        .fetch_all(self.pool.write())
+        .await?;
+
+        rows.into_iter().map(Job::try_from).collect()
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn update_progress(&self, id: String, progress: i16) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+                update jobs set progress = $2, updated_at = now()
+                where id = $1 and status = 'running'
+            "#,
+        )
+        .bind(id)
+        .bind(progress)
+        .execute(self.pool.write())
+        .await?;
+
+        anyhow::Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn succeed(&self, id: String, output: JobOutput) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                update jobs
+                set status = 'succeeded', progress = 100, result_content_type = $2,
+                    result_filename = $3, result = $4, updated_at = now()
+                where id = $1 and status = 'running'
+            "#,
+        )
+        .bind(id)
+        .bind(output.content_type)
+        .bind(output.filename)
+        .bind(output.body)
+        .execute(self.pool.write())
+        .await?;
+
+        anyhow::Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn fail(&self, id: String, error: String) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                update jobs set status = 'failed', error = $2, updated_at = now()
+                where id = $1 and status = 'running'
+            "#,
+        )
+        .bind(id)
+        .bind(error)
+        .execute(self.pool.write())
+        .await?;
+
+        anyhow::Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn cancel(&self, id: String, user_id: UserId) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+                update jobs set status = 'cancelled', updated_at = now()
+                where id = $1 and user_id = $2 and status = 'running'
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(self.pool.write())
+        .await?;
+
+        anyhow::Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_output(&self, id: String) -> anyhow::Result<Option<JobOutput>> {
+        let output = sqlx::query_as::<_, JobOutput>(
+            r#"
+                select result_content_type as content_type, result_filename as filename,
+                    result as body
+                from jobs where id = $1 and status = 'succeeded'
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(self.pool.write())
+        .await?;
+
+        anyhow::Ok(output)
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct JobFromRow {
+    id: String,
+    user_id: UserId,
+    kind: String,
+    status: String,
+    progress: i16,
+    error: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl TryFrom<JobFromRow> for Job {
+    type Error = anyhow::Error;
+
+    fn try_from(row: JobFromRow) -> Result<Self, Self::Error> {
+        let status: JobStatus = row.status.parse()?;
+        // 結果はジョブ本体とは別に取得する
+        let result_url =
+            (status == JobStatus::Succeeded).then(|| format!("/jobs/{}/result", row.id));
+
+        Ok(Job {
+            id: row.id,
+            user_id: row.user_id,
+            kind: row.kind.parse()?,
+            status,
+            progress: row.progress,
+            error: row.error,
+            result_url,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    CsvExport,
+    CsvImport,
+    UserExport,
+}
+
+impl std::str::FromStr for JobKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv_export" => Ok(Self::CsvExport),
+            "csv_import" => Ok(Self::CsvImport),
+            "user_export" => Ok(Self::UserExport),
+            _ => Err(anyhow::anyhow!("Invalid job kind : {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for JobKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Self::CsvExport => "csv_export",
+            Self::CsvImport => "csv_import",
+            Self::UserExport => "user_export",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl std::str::FromStr for JobStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "running" => Ok(Self::Running),
+            "succeeded" => Ok(Self::Succeeded),
+            "failed" => Ok(Self::Failed),
+            "cancelled" => Ok(Self::Cancelled),
+            _ => Err(anyhow::anyhow!("Invalid job status : {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub user_id: UserId,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    /// 0〜100
+    pub progress: i16,
+    pub error: Option<String>,
+    /// 成功したときだけ結果の取得先が入る
+    pub result_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// ジョブの成果物。`GET /jobs/:id/result` でそのまま返す
+#[derive(Debug, Clone, FromRow)]
+pub struct JobOutput {
+    pub content_type: String,
+    pub filename: Option<String>,
+    pub body: Vec<u8>,
+}
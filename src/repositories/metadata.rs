@@ -0,0 +1,118 @@
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// 主催者がクエスト・チャレンジに付ける任意の情報(スポンサー、外部ID等)
+pub type Metadata = Map<String, Value>;
+
+const MAX_KEYS: usize = 32;
+const MAX_KEY_LENGTH: usize = 64;
+/// JSONにしたときのバイト数
+const MAX_SIZE: usize = 4096;
+const FILTER_PREFIX: &str = "metadata.";
+
+/// キーは`?metadata.key=value`で指定できるよう英数字と`_`/`-`に限る
+pub fn validate_metadata(metadata: &Metadata) -> Result<(), String> {
+    if metadata.len() > MAX_KEYS {
+        return Err(format!("metadata must have at most {} keys", MAX_KEYS));
+    }
+    for key in metadata.keys() {
+        if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+            return Err(format!(
+                "metadata key must be 1 to {} characters: {}",
+                MAX_KEY_LENGTH, key
+            ));
+        }
+        if !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(format!("metadata key has invalid characters: {}", key));
+        }
+    }
+    let size = serde_json::to_vec(metadata)
+        .map_err(|e| e.to_string())?
+        .len();
+    if size > MAX_SIZE {
+        return Err(format!("metadata must be at most {} bytes", MAX_SIZE));
+    }
+    Ok(())
+}
+
+/// `?metadata.key=value`による絞り込み。値は文字列として比較する
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetadataFilter(HashMap<String, String>);
+
+impl MetadataFilter {
+    pub fn from_query(params: &HashMap<String, String>) -> Self {
+        Self(
+            params
+                .iter()
+                .filter_map(|(key, value)| {
+                    key.strip_prefix(FILTER_PREFIX)
+                        .map(|key| (key.to_string(), value.clone()))
+                })
+                .collect(),
+        )
+    }
+
+    /// jsonb_each_textに渡すオブジェクト
+    pub fn to_json(&self) -> Value {
+        Value::Object(
+            self.0
+                .iter()
+                .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn metadata(value: Value) -> Metadata {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn should_accept_nested_metadata() {
+        let metadata = metadata(json!({
+            "sponsor": { "name": "Acme", "url": "https://example.com" },
+            "external_id": "ext-001",
+        }));
+
+        assert!(validate_metadata(&metadata).is_ok());
+    }
+
+    #[test]
+    fn should_reject_invalid_keys() {
+        assert!(validate_metadata(&metadata(json!({ "": 1 }))).is_err());
+        assert!(validate_metadata(&metadata(json!({ "has.dot": 1 }))).is_err());
+        let too_long_key = Metadata::from_iter([("a".repeat(MAX_KEY_LENGTH + 1), Value::from(1))]);
+        assert!(validate_metadata(&too_long_key).is_err());
+    }
+
+    #[test]
+    fn should_reject_too_large_metadata() {
+        let too_many_keys = (0..=MAX_KEYS)
+            .map(|i| (format!("key{}", i), Value::from(i)))
+            .collect::<Metadata>();
+        assert!(validate_metadata(&too_many_keys).is_err());
+
+        let too_large = metadata(json!({ "text": "a".repeat(MAX_SIZE) }));
+        assert!(validate_metadata(&too_large).is_err());
+    }
+
+    #[test]
+    fn should_build_filter_from_query() {
+        let params = HashMap::from([
+            ("metadata.external_id".to_string(), "ext-001".to_string()),
+            ("status".to_string(), "all".to_string()),
+        ]);
+
+        let filter = MetadataFilter::from_query(&params);
+
+        assert_eq!(json!({ "external_id": "ext-001" }), filter.to_json());
+    }
+}
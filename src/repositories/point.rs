@@ -0,0 +1,221 @@
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection};
+
+use super::{
+    id::{ChallengeId, HintId, UserId},
+    ReadWritePgPool,
+};
+
+#[async_trait]
+pub trait PointRepository: std::marker::Send + std::marker::Sync + 'static {
+    /// チャレンジ完了のポイントを付与する。付与済みの場合は何もしない
+    async fn award_for_challenge(
+        &self,
+        user_id: UserId,
+        challenge_id: ChallengeId,
+        amount: i32,
+    ) -> anyhow::Result<()>;
+    async fn get_balance(&self, user_id: UserId) -> anyhow::Result<i64>;
+    async fn get_history(&self, user_id: UserId) -> anyhow::Result<Vec<PointTransaction>>;
+    /// since以降に獲得したポイントの上位limit件。sinceがNoneなら全期間
+    async fn leaderboard(
+        &self,
+        since: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<LeaderboardEntry>>;
+    /// ユーザーの順位。期間内にポイントがなければNone
+    async fn find_rank(
+        &self,
+        user_id: UserId,
+        since: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<Option<LeaderboardEntry>>;
+}
+
+/// 同点は同じ順位にする
+const RANKING_QUERY: &str = r#"
+    select
+        rank() over (order by sum(p.amount) desc) as rank,
+        u.id as user_id,
+        u.username,
+        sum(p.amount) as points
+    from point_transactions p
+    join users u on u.id = p.user_id
+    where ($1::timestamptz is null or p.created_at >= $1)
+    group by u.id, u.username
+"#;
+
+#[derive(Debug, Clone)]
+pub struct PointRepositoryForDb {
+    pool: ReadWritePgPool,
+}
+
+impl PointRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        PointRepositoryForDb { pool: pool.into() }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        PointRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl PointRepository for PointRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn award_for_challenge(
+        &self,
+        user_id: UserId,
+        challenge_id: ChallengeId,
+        amount: i32,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.pool.write().acquire().await?;
+        insert_challenge_award(&mut conn, user_id, challenge_id, amount).await
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn get_balance(&self, user_id: UserId) -> anyhow::Result<i64> {
+        let balance = sqlx::query_scalar::<_, i64>(
+            r#"
+                select coalesce(sum(amount), 0) from point_transactions where user_id=$1;
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(self.pool.read())
+        .await?;
+
+        anyhow::Ok(balance)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn get_history(&self, user_id: UserId) -> anyhow::Result<Vec<PointTransaction>> {
+        let transactions = sqlx::query_as::<_, PointTransaction>(
+            r#"
+                select challenge_id, hint_id, amount, created_at from point_transactions
+                where user_id=$1 order by created_at desc;
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        anyhow::Ok(transactions)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn leaderboard(
+        &self,
+        since: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<LeaderboardEntry>> {
+        let entries = sqlx::query_as::<_, LeaderboardEntry>(&format!(
+            "select * from ({}) ranking order by rank, user_id limit $2;",
+            RANKING_QUERY
+        ))
+        .bind(since)
+        .bind(limit)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        anyhow::Ok(entries)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_rank(
+        &self,
+        user_id: UserId,
+        since: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<Option<LeaderboardEntry>> {
+        let entry = sqlx::query_as::<_, LeaderboardEntry>(&format!(
+            "select * from ({}) ranking where user_id = $2;",
+            RANKING_QUERY
+        ))
+        .bind(since)
+        .bind(user_id)
+        .fetch_optional(self.pool.read())
+        .await?;
+
+        anyhow::Ok(entry)
+    }
+}
+
+/// UnitOfWorkのトランザクションからも呼べるようにコネクションを受け取る
+pub(super) async fn insert_challenge_award(
+    conn: &mut PgConnection,
+    user_id: UserId,
+    challenge_id: ChallengeId,
+    amount: i32,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+            insert into point_transactions (user_id, challenge_id, amount) values ($1, $2, $3)
+            on conflict on constraint unique_user_challenge_point do nothing
+        "#,
+    )
+    .bind(user_id)
+    .bind(challenge_id)
+    .bind(amount)
+    .execute(conn)
+    .await?;
+
+    anyhow::Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, FromRow, PartialEq)]
+pub struct PointTransaction {
+    pub challenge_id: Option<ChallengeId>,
+    /// ヒントを開いて差し引いたとき。amountは負になる
+    pub hint_id: Option<HintId>,
+    pub amount: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct PointSummary {
+    pub balance: i64,
+    pub history: Vec<PointTransaction>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, FromRow, PartialEq)]
+pub struct LeaderboardEntry {
+    pub rank: i64,
+    pub user_id: UserId,
+    pub username: String,
+    pub points: i64,
+}
+
+/// `GET /leaderboard?period=` の集計期間
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum LeaderboardPeriod {
+    Weekly,
+    #[default]
+    All,
+}
+
+impl LeaderboardPeriod {
+    /// 集計の開始日時。Allは全期間なのでNone
+    pub fn since(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            LeaderboardPeriod::Weekly => Some(now - Duration::days(7)),
+            LeaderboardPeriod::All => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FindLeaderboard {
+    #[serde(default)]
+    pub period: LeaderboardPeriod,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Leaderboard {
+    pub entries: Vec<LeaderboardEntry>,
+    /// ログインしていない、または期間内にポイントがない場合はNone
+    pub me: Option<LeaderboardEntry>,
+}
@@ -0,0 +1,167 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use super::{id::UserId, ReadWritePgPool};
+use crate::middleware::language::is_valid_language_tag;
+
+const MAX_DISPLAY_NAME_LENGTH: usize = 50;
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait PreferenceRepository: Send + Sync + 'static {
+    /// ユーザーが変えた値だけを返す。一度も変えていなければすべてNone
+    async fn find(&self, user_id: UserId) -> anyhow::Result<StoredPreferences>;
+    /// 指定した項目だけを書き換え、ほかの項目はそのまま残す
+    async fn update(
+        &self,
+        user_id: UserId,
+        payload: UpdatePreferences,
+    ) -> anyhow::Result<StoredPreferences>;
+    /// 渡したユーザーのうち、通知をOFFにしているユーザー
+    async fn find_notification_disabled(
+        &self,
+        user_ids: Vec<UserId>,
+    ) -> anyhow::Result<Vec<UserId>>;
+}
+
+#[derive(Debug, Clone)]
+pub struct PreferenceRepositoryForDb {
+    pool: ReadWritePgPool,
+}
+
+impl PreferenceRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        PreferenceRepositoryForDb { pool: pool.into() }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        PreferenceRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl PreferenceRepository for PreferenceRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find(&self, user_id: UserId) -> anyhow::Result<StoredPreferences> {
+        let row = sqlx::query_as::<_, StoredPreferences>(
+            r#"
+                select notifications_enabled, language, display_name, updated_at
+                from user_preferences where user_id = $1;
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(self.pool.read())
+        .await?;
+
+        anyhow::Ok(row.unwrap_or_default())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn update(
+        &self,
+        user_id: UserId,
+        payload: UpdatePreferences,
+    ) -> anyhow::Result<StoredPreferences> {
+        let row = sqlx::query_as::<_, StoredPreferences>(
+            r#"
+                insert into user_preferences (user_id, notifications_enabled, language, display_name)
+                values ($1, $2, $3, $4)
+                on conflict (user_id) do update
+                    set notifications_enabled = coalesce(
+                            excluded.notifications_enabled,
+                            user_preferences.notifications_enabled
+                        ),
+                        language = coalesce(excluded.language, user_preferences.language),
+                        display_name = coalesce(
+                            excluded.display_name,
+                            user_preferences.display_name
+                        ),
+                        updated_at = now()
+                returning notifications_enabled, language, display_name, updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(payload.notifications_enabled)
+        .bind(payload.language)
+        .bind(payload.display_name.map(|name| name.trim().to_string()))
+        .fetch_one(self.pool.write())
+        .await?;
+
+        anyhow::Ok(row)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_notification_disabled(
+        &self,
+        user_ids: Vec<UserId>,
+    ) -> anyhow::Result<Vec<UserId>> {
+        let user_ids = sqlx::query_scalar::<_, UserId>(
+            r#"
+                select user_id from user_preferences
+                where user_id = any($1) and notifications_enabled = false;
+            "#,
+        )
+        .bind(user_ids)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        anyhow::Ok(user_ids)
+    }
+}
+
+/// user_preferencesの行。Noneの項目は既定値を使う
+#[derive(Debug, Clone, Default, PartialEq, Eq, FromRow)]
+pub struct StoredPreferences {
+    pub notifications_enabled: Option<bool>,
+    pub language: Option<String>,
+    pub display_name: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// 既定値とマージした設定。`GET /me/preferences` で返す
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserPreferences {
+    pub notifications_enabled: bool,
+    pub language: String,
+    pub display_name: String,
+    /// 一度も変えていなければNone
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// `PATCH /me/preferences`。省略した項目は変えない
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdatePreferences {
+    #[serde(default)]
+    pub notifications_enabled: Option<bool>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+}
+
+impl UpdatePreferences {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(language) = &self.language {
+            if !is_valid_language_tag(language) {
+                return Err(format!("invalid language: {}", language));
+            }
+        }
+        if let Some(display_name) = &self.display_name {
+            if display_name.trim().is_empty() {
+                return Err("display_name must not be empty".to_string());
+            }
+            if display_name.trim().chars().count() > MAX_DISPLAY_NAME_LENGTH {
+                return Err(format!(
+                    "display_name must be at most {} characters",
+                    MAX_DISPLAY_NAME_LENGTH
+                ));
+            }
+        }
+        Ok(())
+    }
+}
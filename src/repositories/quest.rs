@@ -1,110 +1,456 @@
-use anyhow::Ok;
 use axum::async_trait;
-use nanoid::nanoid;
-use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::{types::Json, FromRow};
+use std::{collections::HashSet, fmt, sync::Arc};
 
-use super::challenge::Challenge;
+use super::{
+    cascade::QUEST_CASCADE,
+    challenge::{validate_coordinates, Challenge, ChallengeFromRow},
+    id::{default_id_generator, IdGenerator, QuestId, UserId},
+    metadata::{validate_metadata, Metadata, MetadataFilter},
+    ReadWritePgPool,
+};
 
+/// `POST /quests/batch` で1回に指定できるIDの数
+const MAX_BATCH_IDS: usize = 100;
+
+#[cfg_attr(test, mockall::automock)]
 #[async_trait]
-pub trait QuestRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+pub trait QuestRepository: std::marker::Send + std::marker::Sync + 'static {
     async fn create(&self, payload: CreateQuest) -> anyhow::Result<QuestEntity>;
-    async fn find(&self, id: String) -> anyhow::Result<QuestEntity>;
-    async fn all(&self) -> anyhow::Result<Vec<QuestEntity>>;
-    async fn update(&self, id: String, payload: UpdateQuest) -> anyhow::Result<QuestEntity>;
-    async fn delete(&self, id: String) -> anyhow::Result<()>;
+    async fn create_with_challenges(&self, payload: BulkCreateQuest)
+        -> anyhow::Result<QuestEntity>;
+    /// チャレンジも含めて複製する。タイトルに" (copy)"を付け、下書きとして作る
+    /// 作成者は複製元ではなくcreated_byにする
+    async fn duplicate(
+        &self,
+        id: QuestId,
+        created_by: Option<UserId>,
+    ) -> anyhow::Result<QuestEntity>;
+    async fn find(&self, id: QuestId) -> anyhow::Result<QuestEntity>;
+    /// 作成者を返す。作成者が記録されていないクエストはNone、存在しないクエストはエラー
+    async fn find_creator(&self, id: QuestId) -> anyhow::Result<Option<UserId>>;
+    /// 存在しないIDは結果に含まれない
+    async fn find_by_ids(&self, ids: Vec<QuestId>) -> anyhow::Result<Vec<QuestEntity>>;
+    /// active_atを指定するとその時点で開催中のクエストに絞り込む
+    async fn all(
+        &self,
+        status: Option<QuestStatus>,
+        active_at: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<Vec<QuestEntity>>;
+    /// 管理画面向け。metadataの値が全て一致するクエストを返す
+    async fn find_by_metadata(
+        &self,
+        status: Option<QuestStatus>,
+        filter: MetadataFilter,
+    ) -> anyhow::Result<Vec<QuestEntity>>;
+    /// quest_idsをrankingの順に並べたIDを返す。同じ値のときはIDの順
+    async fn rank(
+        &self,
+        quest_ids: Vec<QuestId>,
+        ranking: QuestRanking,
+    ) -> anyhow::Result<Vec<QuestId>>;
+    /// expected_versionを指定したときは、保存済みのversionと一致しなければ更新しない
+    async fn update(
+        &self,
+        id: QuestId,
+        payload: UpdateQuest,
+    ) -> Result<QuestEntity, UpdateQuestError>;
+    /// カバー画像を差し替える。古い画像はキャッシュ済みのレスポンスから参照されうるので消さない
+    async fn update_cover_image(
+        &self,
+        id: QuestId,
+        cover_image: CoverImage,
+    ) -> anyhow::Result<QuestEntity>;
+    /// 終了日時を過ぎた公開中のクエストをアーカイブし、そのIDを返す
+    async fn archive_expired(&self, now: DateTime<Utc>) -> anyhow::Result<Vec<QuestId>>;
+    /// 論理削除する。削除したクエストは他のメソッドからは見えなくなる
+    /// 存在しないか削除済みならエラー
+    async fn delete(&self, id: QuestId) -> anyhow::Result<()>;
+    /// 論理削除したクエストを戻す。削除されていなければエラー
+    async fn restore(&self, id: QuestId) -> anyhow::Result<QuestEntity>;
+    /// beforeより前に論理削除したクエストを関連する記録ごと物理削除し、件数を返す
+    async fn purge_deleted(&self, before: DateTime<Utc>) -> anyhow::Result<u64>;
+}
+
+#[derive(Debug)]
+pub enum UpdateQuestError {
+    NotFound,
+    /// 読み込んだ後に他の更新があった
+    VersionConflict,
+    Repository(anyhow::Error),
+}
+
+impl From<sqlx::Error> for UpdateQuestError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Repository(e.into())
+    }
+}
+
+impl From<anyhow::Error> for UpdateQuestError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Repository(e)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct QuestRepositoryForDb {
-    pool: PgPool,
+    pool: ReadWritePgPool,
+    ids: Arc<dyn IdGenerator>,
 }
 
 impl QuestRepositoryForDb {
-    pub fn new(pool: PgPool) -> Self {
-        QuestRepositoryForDb { pool }
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        QuestRepositoryForDb {
+            pool: pool.into(),
+            ids: default_id_generator(),
+        }
+    }
+
+    pub fn with_id_generator(self, ids: Arc<dyn IdGenerator>) -> Self {
+        Self { ids, ..self }
     }
 
     #[cfg(test)]
     /// テスト用の簡易版コンストラクタ
     pub async fn with_url(url: &str) -> Self {
-        let pool = PgPool::connect(url).await.unwrap();
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
         QuestRepositoryForDb::new(pool)
     }
 }
 
 #[async_trait]
 impl QuestRepository for QuestRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
     async fn create(&self, payload: CreateQuest) -> anyhow::Result<QuestEntity> {
         let row = sqlx::query_as::<_, QuestFromRow>(
             r#"
-                insert into quests values ($1, $2, $3)
+                insert into quests (
+                    id, title, description, status, sequential, start_at, end_at, metadata,
+                    created_by
+                )
+                values ($1, $2, $3, $4, $5, $6, $7, $8, $9)
                 returning *
             "#,
         )
-        .bind(nanoid!())
+        .bind(self.ids.generate())
         .bind(payload.title)
         .bind(payload.description)
-        .fetch_one(&self.pool)
+        .bind(payload.status.unwrap_or(QuestStatus::Draft).to_string())
+        .bind(payload.sequential)
+        .bind(payload.start_at)
+        .bind(payload.end_at)
+        .bind(Json(payload.metadata))
+        .bind(payload.created_by)
+        .fetch_one(self.pool.write())
         .await?;
 
-        let quest = QuestEntity::new(row.id, row.title, row.description);
+        let quest = QuestEntity {
+            id: row.id,
+            title: row.title,
+            description: row.description,
+            status: row.status.parse()?,
+            sequential: row.sequential,
+            start_at: row.start_at,
+            end_at: row.end_at,
+            metadata: row.metadata.0,
+            cover_image_url: row.cover_image_url,
+            cover_thumbnail_url: row.cover_thumbnail_url,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            version: row.version,
+            challenges: Vec::new(),
+        };
 
         Ok(quest)
     }
 
-    async fn find(&self, id: String) -> anyhow::Result<QuestEntity> {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn create_with_challenges(
+        &self,
+        payload: BulkCreateQuest,
+    ) -> anyhow::Result<QuestEntity> {
+        // 途中で失敗したらクエストもチャレンジもすべてロールバックする
+        let mut tx = self.pool.write().begin().await?;
+
+        let quest = payload.quest;
         let row = sqlx::query_as::<_, QuestFromRow>(
             r#"
-                select * from quests where id = $1;
+                insert into quests (
+                    id, title, description, status, sequential, start_at, end_at, metadata,
+                    created_by
+                )
+                values ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                returning *
             "#,
         )
+        .bind(self.ids.generate())
+        .bind(quest.title)
+        .bind(quest.description)
+        .bind(quest.status.unwrap_or(QuestStatus::Draft).to_string())
+        .bind(quest.sequential)
+        .bind(quest.start_at)
+        .bind(quest.end_at)
+        .bind(Json(quest.metadata))
+        .bind(quest.created_by)
+        .fetch_one(&mut tx)
+        .await?;
+
+        let mut challenges = Vec::new();
+        for challenge in payload.challenges {
+            let challenge = sqlx::query_as::<_, ChallengeFromRow>(
+                r#"
+                    insert into challenges (
+                        id, name, description, quest_id, latitude, longitude, stamp_name,
+                        stamp_color_image_url, stamp_gray_image_url, flavor_text, order_index,
+                        metadata
+                    )
+                    values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                    returning *
+                "#,
+            )
+            .bind(self.ids.generate())
+            .bind(challenge.name)
+            .bind(challenge.description)
+            .bind(row.id.clone())
+            .bind(challenge.latitude)
+            .bind(challenge.longitude)
+            .bind(challenge.stamp_name)
+            .bind(challenge.stamp_color_image_url)
+            .bind(challenge.stamp_gray_image_url)
+            .bind(challenge.flavor_text)
+            .bind(challenge.order_index)
+            .bind(Json(challenge.metadata))
+            .fetch_one(&mut tx)
+            .await?;
+            challenges.push(Challenge::from(challenge));
+        }
+
+        tx.commit().await?;
+
+        let quest = QuestEntity {
+            id: row.id,
+            title: row.title,
+            description: row.description,
+            status: row.status.parse()?,
+            sequential: row.sequential,
+            start_at: row.start_at,
+            end_at: row.end_at,
+            metadata: row.metadata.0,
+            cover_image_url: row.cover_image_url,
+            cover_thumbnail_url: row.cover_thumbnail_url,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            version: row.version,
+            challenges,
+        };
+
+        Ok(quest)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn duplicate(
+        &self,
+        id: QuestId,
+        created_by: Option<UserId>,
+    ) -> anyhow::Result<QuestEntity> {
+        // 途中で失敗したらクエストもチャレンジもすべてロールバックする
+        let mut tx = self.pool.write().begin().await?;
+
+        let row = sqlx::query_as::<_, QuestFromRow>(
+            r#"
+                insert into quests (
+                    id, title, description, status, sequential, start_at, end_at, metadata,
+                    cover_image_url, cover_thumbnail_url, created_by
+                )
+                select $1, title || ' (copy)', description, $2, sequential, start_at, end_at,
+                    metadata, cover_image_url, cover_thumbnail_url, $4
+                from quests where id = $3 and deleted_at is null
+                returning *
+            "#,
+        )
+        .bind(self.ids.generate())
+        .bind(QuestStatus::Draft.to_string())
         .bind(id.clone())
-        .fetch_one(&self.pool)
+        .bind(created_by)
+        .fetch_optional(&mut tx)
+        .await?;
+        let row = match row {
+            Some(row) => row,
+            None => {
+                tx.rollback().await?;
+                return Err(anyhow::anyhow!("quest not found: {}", id));
+            }
+        };
+
+        let sources = sqlx::query_as::<_, ChallengeFromRow>(
+            r#"
+                select * from challenges where quest_id = $1 order by order_index;
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&mut tx)
         .await?;
 
-        let challenges = sqlx::query_as::<_, Challenge>(
+        let mut challenges = Vec::new();
+        for source in sources.into_iter().map(Challenge::from) {
+            let challenge = sqlx::query_as::<_, ChallengeFromRow>(
+                r#"
+                    insert into challenges (
+                        id, name, description, quest_id, latitude, longitude, stamp_name,
+                        stamp_color_image_url, stamp_gray_image_url, flavor_text, order_index,
+                        metadata, active, start_at, end_at
+                    )
+                    values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+                    returning *
+                "#,
+            )
+            .bind(self.ids.generate())
+            .bind(source.name)
+            .bind(source.description)
+            .bind(row.id.clone())
+            .bind(source.latitude)
+            .bind(source.longitude)
+            .bind(source.stamp_name)
+            .bind(source.stamp_color_image_url)
+            .bind(source.stamp_gray_image_url)
+            .bind(source.flavor_text)
+            .bind(source.order_index)
+            .bind(Json(source.metadata))
+            .bind(source.active)
+            .bind(source.start_at)
+            .bind(source.end_at)
+            .fetch_one(&mut tx)
+            .await?;
+            challenges.push(Challenge::from(challenge));
+        }
+
+        tx.commit().await?;
+
+        let quest = QuestEntity {
+            id: row.id,
+            title: row.title,
+            description: row.description,
+            status: row.status.parse()?,
+            sequential: row.sequential,
+            start_at: row.start_at,
+            end_at: row.end_at,
+            metadata: row.metadata.0,
+            cover_image_url: row.cover_image_url,
+            cover_thumbnail_url: row.cover_thumbnail_url,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            version: row.version,
+            challenges,
+        };
+
+        Ok(quest)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find(&self, id: QuestId) -> anyhow::Result<QuestEntity> {
+        // キャッシュに載せるので、更新直後の古い値を読まないようプライマリから読む
+        let row = sqlx::query_as::<_, QuestFromRow>(
             r#"
-                select * from challenges where quest_id = $1;
+                select * from quests where id = $1 and deleted_at is null;
             "#,
         )
         .bind(id.clone())
-        .fetch_all(&self.pool)
+        .fetch_one(self.pool.write())
         .await?;
 
+        let challenges = sqlx::query_as::<_, ChallengeFromRow>(
+            r#"
+                select * from challenges where quest_id = $1 order by order_index;
+            "#,
+        )
+        .bind(id.clone())
+        .fetch_all(self.pool.write())
+        .await?
+        .into_iter()
+        .map(Challenge::from)
+        .collect();
+
         let quest = QuestEntity {
             id: row.id,
             title: row.title,
             description: row.description,
+            status: row.status.parse()?,
+            sequential: row.sequential,
+            start_at: row.start_at,
+            end_at: row.end_at,
+            metadata: row.metadata.0,
+            cover_image_url: row.cover_image_url,
+            cover_thumbnail_url: row.cover_thumbnail_url,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            version: row.version,
             challenges,
         };
 
         Ok(quest)
     }
 
-    async fn all(&self) -> anyhow::Result<Vec<QuestEntity>> {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_creator(&self, id: QuestId) -> anyhow::Result<Option<UserId>> {
+        // 権限の確認に使うので、作成直後でも読めるようプライマリから読む
+        let created_by = sqlx::query_scalar::<_, Option<UserId>>(
+            r#"
+                select created_by from quests where id = $1 and deleted_at is null;
+            "#,
+        )
+        .bind(id)
+        .fetch_one(self.pool.write())
+        .await?;
+
+        Ok(created_by)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_by_ids(&self, ids: Vec<QuestId>) -> anyhow::Result<Vec<QuestEntity>> {
         let quest_rows = sqlx::query_as::<_, QuestFromRow>(
             r#"
-                select * from quests;
+                select * from quests where id = any($1) and deleted_at is null;
             "#,
         )
-        .fetch_all(&self.pool)
+        .bind(&ids)
+        .fetch_all(self.pool.read())
         .await?;
 
-        let challenge_rows = sqlx::query_as::<_, Challenge>(
+        let challenge_rows = sqlx::query_as::<_, ChallengeFromRow>(
             r#"
-                select * from challenges;
+                select * from challenges where quest_id = any($1) order by order_index;
             "#,
         )
-        .fetch_all(&self.pool)
+        .bind(&ids)
+        .fetch_all(self.pool.read())
         .await?;
 
         let mut quests = quest_rows
             .into_iter()
-            .map(|row| QuestEntity::new(row.id, row.title, row.description))
-            .collect::<Vec<QuestEntity>>();
+            .map(|row| {
+                Ok(QuestEntity {
+                    id: row.id,
+                    title: row.title,
+                    description: row.description,
+                    status: row.status.parse()?,
+                    sequential: row.sequential,
+                    start_at: row.start_at,
+                    end_at: row.end_at,
+                    metadata: row.metadata.0,
+                    cover_image_url: row.cover_image_url,
+                    cover_thumbnail_url: row.cover_thumbnail_url,
+                    created_at: row.created_at,
+                    updated_at: row.updated_at,
+                    version: row.version,
+                    challenges: Vec::new(),
+                })
+            })
+            .collect::<anyhow::Result<Vec<QuestEntity>>>()?;
 
-        for challenge in challenge_rows {
+        for challenge in challenge_rows.into_iter().map(Challenge::from) {
             if let Some(quest) = quests.iter_mut().find(|q| q.id == challenge.quest_id) {
                 quest.challenges.push(challenge)
             }
@@ -113,77 +459,462 @@ impl QuestRepository for QuestRepositoryForDb {
         Ok(quests)
     }
 
-    async fn update(&self, id: String, payload: UpdateQuest) -> anyhow::Result<QuestEntity> {
-        let old_quest = self.find(id.clone()).await?;
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn all(
+        &self,
+        status: Option<QuestStatus>,
+        active_at: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<Vec<QuestEntity>> {
+        // チャレンジはクエストごとにjson_aggでまとめて1クエリで取る
+        // 行はストリームで受け取り、届いた順にQuestEntityへ変換する
+        // findと同じくキャッシュに載せるのでプライマリから読む
+        let quests: Vec<QuestEntity> = sqlx::query_as::<_, QuestWithChallengesFromRow>(
+            r#"
+                select
+                    q.*,
+                    coalesce(
+                        json_agg(c.* order by c.order_index) filter (where c.id is not null),
+                        '[]'
+                    ) as challenges
+                from quests q
+                left join challenges c on c.quest_id = q.id
+                where q.deleted_at is null
+                and ($1::text is null or q.status = $1)
+                and (
+                    $2::timestamptz is null
+                    or ((q.start_at is null or q.start_at <= $2) and (q.end_at is null or $2 < q.end_at))
+                )
+                group by q.id;
+            "#,
+        )
+        .bind(status.map(|s| s.to_string()))
+        .bind(active_at)
+        .fetch(self.pool.write())
+        .map_err(anyhow::Error::from)
+        .and_then(|row| async move { QuestEntity::try_from(row) })
+        .try_collect()
+        .await?;
+
+        Ok(quests)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_by_metadata(
+        &self,
+        status: Option<QuestStatus>,
+        filter: MetadataFilter,
+    ) -> anyhow::Result<Vec<QuestEntity>> {
+        // 値の型に関わらず文字列にして比較する
+        let ids = sqlx::query_scalar::<_, QuestId>(
+            r#"
+                select q.id from quests q
+                where q.deleted_at is null
+                and ($1::text is null or q.status = $1)
+                and not exists (
+                    select 1 from jsonb_each_text($2::jsonb) f
+                    where q.metadata ->> f.key is distinct from f.value
+                )
+                order by q.id;
+            "#,
+        )
+        .bind(status.map(|s| s.to_string()))
+        .bind(Json(filter.to_json()))
+        .fetch_all(self.pool.read())
+        .await?;
+
+        self.find_by_ids(ids).await
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn rank(
+        &self,
+        quest_ids: Vec<QuestId>,
+        ranking: QuestRanking,
+    ) -> anyhow::Result<Vec<QuestId>> {
+        let ids = match ranking {
+            QuestRanking::Popular => {
+                sqlx::query_scalar::<_, QuestId>(
+                    r#"
+                        select q.id from quests q
+                        left join (
+                            select quest_id, count(*) as participants
+                            from user_participating_quests
+                            where quest_id = any($1)
+                            group by quest_id
+                        ) p on p.quest_id = q.id
+                        where q.id = any($1)
+                        order by coalesce(p.participants, 0) desc, q.id;
+                    "#,
+                )
+                .bind(&quest_ids)
+                .fetch_all(self.pool.read())
+                .await?
+            }
+            // チャレンジの緯度経度の平均を代表座標とし、大圏距離で比べる
+            QuestRanking::Nearby {
+                latitude,
+                longitude,
+            } => {
+                sqlx::query_scalar::<_, QuestId>(
+                    r#"
+                        select q.id from quests q
+                        left join (
+                            select quest_id, avg(latitude) as latitude, avg(longitude) as longitude
+                            from challenges
+                            where quest_id = any($1)
+                            group by quest_id
+                        ) c on c.quest_id = q.id
+                        where q.id = any($1)
+                        order by asin(sqrt(
+                            power(sin(radians(c.latitude - $2) / 2), 2)
+                            + cos(radians($2)) * cos(radians(c.latitude))
+                                * power(sin(radians(c.longitude - $3) / 2), 2)
+                        )) nulls last, q.id;
+                    "#,
+                )
+                .bind(&quest_ids)
+                .bind(latitude)
+                .bind(longitude)
+                .fetch_all(self.pool.read())
+                .await?
+            }
+        };
+
+        Ok(ids)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn update(
+        &self,
+        id: QuestId,
+        payload: UpdateQuest,
+    ) -> Result<QuestEntity, UpdateQuestError> {
+        let old_quest = self
+            .find(id.clone())
+            .await
+            .map_err(|_| UpdateQuestError::NotFound)?;
+        if payload
+            .expected_version
+            .map_or(false, |version| version != old_quest.version)
+        {
+            return Err(UpdateQuestError::VersionConflict);
+        }
+        // 指定がなくても、読み込んでから書き込むまでの間の更新は上書きしない
         let row = sqlx::query_as::<_, QuestFromRow>(
             r#"
-                update quests set title=$1, description=$2 where id=$3
+                update quests
+                set title=$1, description=$2, status=$3, sequential=$4, start_at=$5, end_at=$6,
+                    metadata=$7, updated_at=now(), version=version + 1
+                where id=$8 and deleted_at is null and version=$9
                 returning *
             "#,
         )
         .bind(payload.title.unwrap_or(old_quest.title))
         .bind(payload.description.unwrap_or(old_quest.description))
+        .bind(payload.status.unwrap_or(old_quest.status).to_string())
+        .bind(payload.sequential.unwrap_or(old_quest.sequential))
+        .bind(payload.start_at.unwrap_or(old_quest.start_at))
+        .bind(payload.end_at.unwrap_or(old_quest.end_at))
+        .bind(Json(payload.metadata.unwrap_or(old_quest.metadata)))
         .bind(id)
-        .fetch_one(&self.pool)
-        .await?;
+        .bind(old_quest.version)
+        .fetch_optional(self.pool.write())
+        .await?
+        .ok_or(UpdateQuestError::VersionConflict)?;
 
         let quest = QuestEntity {
             id: row.id,
             title: row.title,
             description: row.description,
+            status: row.status.parse()?,
+            sequential: row.sequential,
+            start_at: row.start_at,
+            end_at: row.end_at,
+            metadata: row.metadata.0,
+            cover_image_url: row.cover_image_url,
+            cover_thumbnail_url: row.cover_thumbnail_url,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            version: row.version,
             challenges: old_quest.challenges,
         };
 
         Ok(quest)
     }
 
-    async fn delete(&self, id: String) -> anyhow::Result<()> {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn update_cover_image(
+        &self,
+        id: QuestId,
+        cover_image: CoverImage,
+    ) -> anyhow::Result<QuestEntity> {
         sqlx::query(
             r#"
-                delete from quests where id=$1
+                update quests
+                set cover_image_url = $2, cover_thumbnail_url = $3, updated_at = now(),
+                    version = version + 1
+                where id = $1 and deleted_at is null
+                returning id
             "#,
         )
         .bind(id.clone())
-        .execute(&self.pool)
+        .bind(cover_image.url)
+        .bind(cover_image.thumbnail_url)
+        .fetch_one(self.pool.write())
         .await?;
 
-        sqlx::query(
+        self.find(id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn archive_expired(&self, now: DateTime<Utc>) -> anyhow::Result<Vec<QuestId>> {
+        let ids = sqlx::query_scalar::<_, QuestId>(
             r#"
-                delete from challenges where quest_id=$1
+                update quests set status = $1, updated_at = now(), version = version + 1
+                where status = $2 and end_at <= $3 and deleted_at is null
+                returning id
+            "#,
+        )
+        .bind(QuestStatus::Archived.to_string())
+        .bind(QuestStatus::Published.to_string())
+        .bind(now)
+        .fetch_all(self.pool.write())
+        .await?;
+
+        Ok(ids)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn delete(&self, id: QuestId) -> anyhow::Result<()> {
+        // チャレンジや参加記録は復元できるように残す
+        let result = sqlx::query(
+            r#"
+                update quests set deleted_at = now() where id=$1 and deleted_at is null
             "#,
         )
         .bind(id.clone())
-        .execute(&self.pool)
+        .execute(self.pool.write())
         .await?;
 
+        // 存在しないか削除済みの場合は更新対象がない
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("quest not found: {}", id));
+        }
+
         Ok(())
     }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn restore(&self, id: QuestId) -> anyhow::Result<QuestEntity> {
+        sqlx::query(
+            r#"
+                update quests set deleted_at = null, updated_at = now(), version = version + 1
+                where id=$1 and deleted_at is not null
+                returning id
+            "#,
+        )
+        .bind(id.clone())
+        .fetch_one(self.pool.write())
+        .await?;
+
+        self.find(id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn purge_deleted(&self, before: DateTime<Utc>) -> anyhow::Result<u64> {
+        let mut tx = self.pool.write().begin().await?;
+        let purged = QUEST_CASCADE
+            .delete(&mut tx, "deleted_at < $1", before)
+            .await?;
+        tx.commit().await?;
+
+        Ok(purged)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct QuestFromRow {
-    pub id: String,
+    pub id: QuestId,
     pub title: String,
     pub description: String,
+    pub status: String,
+    pub sequential: bool,
+    pub start_at: Option<DateTime<Utc>>,
+    pub end_at: Option<DateTime<Utc>>,
+    pub metadata: Json<Metadata>,
+    pub cover_image_url: Option<String>,
+    pub cover_thumbnail_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub version: i32,
+}
+
+/// チャレンジをjson_aggでまとめた行
+#[derive(Debug, Clone, FromRow)]
+struct QuestWithChallengesFromRow {
+    #[sqlx(flatten)]
+    quest: QuestFromRow,
+    challenges: Json<Vec<ChallengeFromRow>>,
+}
+
+impl TryFrom<QuestWithChallengesFromRow> for QuestEntity {
+    type Error = anyhow::Error;
+
+    fn try_from(row: QuestWithChallengesFromRow) -> anyhow::Result<Self> {
+        let quest = row.quest;
+        Ok(QuestEntity {
+            id: quest.id,
+            title: quest.title,
+            description: quest.description,
+            status: quest.status.parse()?,
+            sequential: quest.sequential,
+            start_at: quest.start_at,
+            end_at: quest.end_at,
+            metadata: quest.metadata.0,
+            cover_image_url: quest.cover_image_url,
+            cover_thumbnail_url: quest.cover_thumbnail_url,
+            created_at: quest.created_at,
+            updated_at: quest.updated_at,
+            version: quest.version,
+            challenges: row.challenges.0.into_iter().map(Challenge::from).collect(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuestStatus {
+    Draft,
+    Published,
+    Archived,
+}
+
+impl std::str::FromStr for QuestStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "draft" => Ok(Self::Draft),
+            "published" => Ok(Self::Published),
+            "archived" => Ok(Self::Archived),
+            _ => Err(anyhow::anyhow!("Invalid quest status : {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for QuestStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            Self::Draft => "draft",
+            Self::Published => "published",
+            Self::Archived => "archived",
+        };
+        write!(f, "{}", status)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct QuestEntity {
-    pub id: String,
+    pub id: QuestId,
     pub title: String,
     pub description: String,
+    pub status: QuestStatus,
+    /// 順番に完了しないと次のチャレンジが解放されないモード
+    pub sequential: bool,
+    /// 開催期間。Noneは期限なし
+    pub start_at: Option<DateTime<Utc>>,
+    pub end_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub metadata: Metadata,
+    /// 詳細画面用のフル画像。未設定ならNone
+    #[serde(default)]
+    pub cover_image_url: Option<String>,
+    /// 一覧用の縮小画像
+    #[serde(default)]
+    pub cover_thumbnail_url: Option<String>,
+    #[serde(default)]
+    pub created_at: DateTime<Utc>,
+    /// クエスト自体を更新した日時。チャレンジや評価の変更では変わらない
+    #[serde(default)]
+    pub updated_at: DateTime<Utc>,
+    /// 更新のたびに増える。UpdateQuestのexpected_versionに渡す
+    #[serde(default)]
+    pub version: i32,
     pub challenges: Vec<Challenge>,
 }
 
 impl QuestEntity {
-    pub fn new(id: String, title: String, description: String) -> Self {
+    pub fn new(id: QuestId, title: String, description: String, status: QuestStatus) -> Self {
+        let now = Utc::now();
         Self {
             id,
             title,
             description,
+            status,
+            sequential: false,
+            start_at: None,
+            end_at: None,
+            metadata: Metadata::new(),
+            cover_image_url: None,
+            cover_thumbnail_url: None,
+            created_at: now,
+            updated_at: now,
+            version: 1,
             challenges: Vec::new(),
         }
     }
+
+    /// 開催期間内か。終了時刻ちょうどは期間外
+    pub fn is_open_at(&self, now: DateTime<Utc>) -> bool {
+        self.start_at.map_or(true, |start_at| start_at <= now)
+            && self.end_at.map_or(true, |end_at| now < end_at)
+    }
+}
+
+/// `GET /quests` の1件。件数が多いのでカバー画像はサムネイルだけ返し、フル画像は詳細で返す
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestSummary {
+    pub id: QuestId,
+    pub title: String,
+    pub description: String,
+    pub status: QuestStatus,
+    pub sequential: bool,
+    pub start_at: Option<DateTime<Utc>>,
+    pub end_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub metadata: Metadata,
+    pub cover_thumbnail_url: Option<String>,
+    #[serde(default)]
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub version: i32,
+    pub challenges: Vec<Challenge>,
+    /// `with_bookmarks=true` でログイン中のときだけ返す
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_bookmarked: Option<bool>,
+}
+
+impl From<QuestEntity> for QuestSummary {
+    fn from(quest: QuestEntity) -> Self {
+        Self {
+            id: quest.id,
+            title: quest.title,
+            description: quest.description,
+            status: quest.status,
+            sequential: quest.sequential,
+            start_at: quest.start_at,
+            end_at: quest.end_at,
+            metadata: quest.metadata,
+            cover_thumbnail_url: quest.cover_thumbnail_url,
+            created_at: quest.created_at,
+            updated_at: quest.updated_at,
+            version: quest.version,
+            challenges: quest.challenges,
+            is_bookmarked: None,
+        }
+    }
 }
 
 // 各fieldが一致したとき==とみなす
@@ -197,17 +928,551 @@ impl PartialEq for QuestEntity {
 pub struct CreateQuest {
     title: String,
     description: String,
+    /// 省略時はdraftで作成する
+    status: Option<QuestStatus>,
+    #[serde(default)]
+    sequential: bool,
+    #[serde(default)]
+    start_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    metadata: Metadata,
+    /// リクエストでは受け取らず、ログイン中のユーザーをサービス側で入れる
+    #[serde(skip)]
+    created_by: Option<UserId>,
 }
 
-#[cfg(test)]
 impl CreateQuest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.title.trim().is_empty() {
+            return Err("title must not be empty".to_string());
+        }
+        validate_metadata(&self.metadata)?;
+        validate_schedule(self.start_at, self.end_at)
+    }
+
     pub fn new(title: String, description: String) -> Self {
-        Self { title, description }
+        Self {
+            title,
+            description,
+            status: None,
+            sequential: false,
+            start_at: None,
+            end_at: None,
+            metadata: Metadata::new(),
+            created_by: None,
+        }
+    }
+
+    pub fn with_creator(self, created_by: Option<UserId>) -> Self {
+        Self { created_by, ..self }
     }
+
+    pub fn with_status(self, status: QuestStatus) -> Self {
+        Self {
+            status: Some(status),
+            ..self
+        }
+    }
+
+    pub fn with_sequential(self, sequential: bool) -> Self {
+        Self { sequential, ..self }
+    }
+
+    #[cfg(test)]
+    pub fn with_metadata(self, metadata: Metadata) -> Self {
+        Self { metadata, ..self }
+    }
+
+    #[cfg(test)]
+    pub fn with_schedule(
+        self,
+        start_at: Option<DateTime<Utc>>,
+        end_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            start_at,
+            end_at,
+            ..self
+        }
+    }
+}
+
+fn validate_schedule(
+    start_at: Option<DateTime<Utc>>,
+    end_at: Option<DateTime<Utc>>,
+) -> Result<(), String> {
+    match (start_at, end_at) {
+        (Some(start_at), Some(end_at)) if start_at >= end_at => {
+            Err("start_at must be before end_at".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// フィールドが省略されたときはNone、nullのときはSome(None)にする
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// `POST /quests/bulk` でクエストとチャレンジをまとめて作成する
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkCreateQuest {
+    #[serde(flatten)]
+    quest: CreateQuest,
+    challenges: Vec<BulkCreateChallenge>,
+}
+
+impl BulkCreateQuest {
+    pub fn validate(&self) -> Result<(), String> {
+        self.quest.validate()?;
+        for challenge in &self.challenges {
+            if challenge.name.trim().is_empty() {
+                return Err("name must not be empty".to_string());
+            }
+            validate_coordinates(challenge.latitude, challenge.longitude)?;
+            validate_metadata(&challenge.metadata)?;
+        }
+        Ok(())
+    }
+
+    pub fn with_creator(self, created_by: Option<UserId>) -> Self {
+        Self {
+            quest: self.quest.with_creator(created_by),
+            ..self
+        }
+    }
+}
+
+/// テンプレートに保存する内容。開催期間は使い回さないので含めず、下書きとして作る
+impl From<QuestEntity> for BulkCreateQuest {
+    fn from(quest: QuestEntity) -> Self {
+        Self {
+            quest: CreateQuest {
+                sequential: quest.sequential,
+                metadata: quest.metadata,
+                ..CreateQuest::new(quest.title, quest.description)
+            },
+            challenges: quest
+                .challenges
+                .into_iter()
+                .map(|challenge| BulkCreateChallenge {
+                    name: challenge.name,
+                    description: challenge.description,
+                    latitude: challenge.latitude,
+                    longitude: challenge.longitude,
+                    stamp_name: challenge.stamp_name,
+                    stamp_color_image_url: challenge.stamp_color_image_url,
+                    stamp_gray_image_url: challenge.stamp_gray_image_url,
+                    flavor_text: challenge.flavor_text,
+                    order_index: challenge.order_index,
+                    metadata: challenge.metadata,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// quest_idは作成したクエストのものを使う
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkCreateChallenge {
+    name: String,
+    description: String,
+    latitude: f64,
+    longitude: f64,
+    stamp_name: String,
+    stamp_color_image_url: String,
+    stamp_gray_image_url: String,
+    flavor_text: String,
+    #[serde(default)]
+    order_index: i32,
+    #[serde(default)]
+    metadata: Metadata,
+}
+
+/// アップロードした画像から作ったカバー画像のURL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoverImage {
+    pub url: String,
+    pub thumbnail_url: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateQuest {
     title: Option<String>,
     description: Option<String>,
+    status: Option<QuestStatus>,
+    sequential: Option<bool>,
+    /// nullを指定すると期限なしに戻す
+    #[serde(default, deserialize_with = "double_option")]
+    start_at: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "double_option")]
+    end_at: Option<Option<DateTime<Utc>>>,
+    /// 指定すると丸ごと置き換える
+    metadata: Option<Metadata>,
+    /// 編集を始めたときのversion。省略すると確認しない
+    #[serde(default)]
+    expected_version: Option<i32>,
+}
+
+impl UpdateQuest {
+    /// 公開状態だけを変える
+    pub fn status_only(status: QuestStatus) -> Self {
+        Self {
+            title: None,
+            description: None,
+            status: Some(status),
+            sequential: None,
+            start_at: None,
+            end_at: None,
+            metadata: None,
+            expected_version: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.title {
+            Some(title) if title.trim().is_empty() => {
+                return Err("title must not be empty".to_string())
+            }
+            _ => {}
+        }
+        if let Some(metadata) = &self.metadata {
+            validate_metadata(metadata)?;
+        }
+        // 片方だけの更新は保存済みの値と合わせてDBの制約で弾く
+        match (self.start_at, self.end_at) {
+            (Some(start_at), Some(end_at)) => validate_schedule(start_at, end_at),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// `POST /quests/batch` のボディ
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchFindQuests {
+    ids: Vec<QuestId>,
+}
+
+impl BatchFindQuests {
+    #[cfg(test)]
+    pub fn new(ids: Vec<QuestId>) -> Self {
+        Self { ids }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.ids.is_empty() {
+            return Err("ids must not be empty".to_string());
+        }
+        if self.ids.len() > MAX_BATCH_IDS {
+            return Err(format!("ids must be at most {}", MAX_BATCH_IDS));
+        }
+        Ok(())
+    }
+
+    /// 重複を除いて指定順に返す
+    pub fn into_ids(self) -> Vec<QuestId> {
+        let mut seen = HashSet::new();
+        self.ids
+            .into_iter()
+            .filter(|id| seen.insert(id.clone()))
+            .collect()
+    }
+}
+
+/// `GET /quests?status=` のクエリ
+#[derive(Debug, Clone, Deserialize)]
+pub struct FindQuests {
+    pub status: Option<QuestStatusFilter>,
+    /// trueのときは開催期間中のクエストだけを返す
+    #[serde(default)]
+    pub active: bool,
+    pub sort: Option<QuestSort>,
+    /// trueのときはログイン中のユーザーがブックマークしているかを付ける
+    #[serde(default)]
+    pub with_bookmarks: bool,
+    /// `sort=nearby` のときの現在地
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl FindQuests {
+    /// sortがpopularかnearbyのときだけ返す。nearbyで現在地がなければエラー
+    pub fn ranking(&self) -> Result<Option<QuestRanking>, String> {
+        let key = match self.sort {
+            Some(sort) if sort.ranking_required() => sort.key,
+            _ => return Ok(None),
+        };
+        if key == QuestSortKey::Popular {
+            return Ok(Some(QuestRanking::Popular));
+        }
+        match (self.latitude, self.longitude) {
+            (Some(latitude), Some(longitude)) => {
+                validate_coordinates(latitude, longitude)?;
+                Ok(Some(QuestRanking::Nearby {
+                    latitude,
+                    longitude,
+                }))
+            }
+            _ => Err("latitude and longitude are required to sort by nearby".to_string()),
+        }
+    }
+}
+
+/// `?sort=created_at:desc` の形式。方向を省略すると昇順
+/// newestはcreated_at:descと同じ。popularとnearbyは方向を指定できない
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct QuestSort {
+    key: QuestSortKey,
+    descending: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QuestSortKey {
+    CreatedAt,
+    UpdatedAt,
+    Title,
+    Popular,
+    Nearby,
+}
+
+impl TryFrom<String> for QuestSort {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (key, direction) = match value.split_once(':') {
+            Some((key, direction)) => (key, Some(direction)),
+            None => (value.as_str(), None),
+        };
+        let key = match key {
+            "newest" if direction.is_none() => {
+                return Ok(Self {
+                    key: QuestSortKey::CreatedAt,
+                    descending: true,
+                })
+            }
+            "popular" | "nearby" if direction.is_some() => {
+                return Err(format!("{} cannot have a sort direction", key))
+            }
+            "created_at" => QuestSortKey::CreatedAt,
+            "updated_at" => QuestSortKey::UpdatedAt,
+            "title" => QuestSortKey::Title,
+            "popular" => QuestSortKey::Popular,
+            "nearby" => QuestSortKey::Nearby,
+            _ => return Err(format!("unknown sort key: {}", key)),
+        };
+        let descending = match direction.unwrap_or("asc") {
+            "asc" => false,
+            "desc" => true,
+            direction => return Err(format!("unknown sort direction: {}", direction)),
+        };
+        Ok(Self { key, descending })
+    }
+}
+
+impl QuestSort {
+    /// 同じ値のときはIDで並べて、リクエストごとに順番が変わらないようにする
+    /// popularとnearbyはQuestRepository::rankで並べるので、ここでは並べ替えない
+    pub fn sort(&self, quests: &mut [QuestEntity]) {
+        if self.ranking_required() {
+            return;
+        }
+        quests.sort_by(|a, b| {
+            let ordering = match self.key {
+                QuestSortKey::CreatedAt => a.created_at.cmp(&b.created_at),
+                QuestSortKey::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+                QuestSortKey::Title => a.title.cmp(&b.title),
+                QuestSortKey::Popular | QuestSortKey::Nearby => std::cmp::Ordering::Equal,
+            }
+            .then_with(|| a.id.cmp(&b.id));
+            if self.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+
+    fn ranking_required(&self) -> bool {
+        matches!(self.key, QuestSortKey::Popular | QuestSortKey::Nearby)
+    }
+}
+
+/// クエスト自身の値では決まらず、DBで集計して並べる順
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuestRanking {
+    /// 参加者の多い順
+    Popular,
+    /// 指定した地点から近い順。チャレンジのないクエストは最後
+    Nearby { latitude: f64, longitude: f64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuestStatusFilter {
+    All,
+    Draft,
+    Published,
+    Archived,
+}
+
+impl QuestStatusFilter {
+    /// 指定がなければ公開中のクエストのみ
+    pub fn resolve(filter: Option<Self>) -> Option<QuestStatus> {
+        match filter.unwrap_or(Self::Published) {
+            Self::All => None,
+            Self::Draft => Some(QuestStatus::Draft),
+            Self::Published => Some(QuestStatus::Published),
+            Self::Archived => Some(QuestStatus::Archived),
+        }
+    }
+
+    /// 公開中以外を含む指定か。管理者だけが使える
+    pub fn is_restricted(filter: Option<Self>) -> bool {
+        !matches!(filter, None | Some(Self::Published))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Duration;
+
+    fn build_quest(start_at: Option<DateTime<Utc>>, end_at: Option<DateTime<Utc>>) -> QuestEntity {
+        let mut quest = QuestEntity::new(
+            "quest".into(),
+            "Test Quest".to_string(),
+            "This is a test quest.".to_string(),
+            QuestStatus::Published,
+        );
+        quest.start_at = start_at;
+        quest.end_at = end_at;
+        quest
+    }
+
+    #[test]
+    fn should_be_open_without_schedule() {
+        assert!(build_quest(None, None).is_open_at(Utc::now()));
+    }
+
+    #[test]
+    fn should_check_schedule() {
+        let now = Utc::now();
+        let quest = build_quest(Some(now), Some(now + Duration::days(1)));
+
+        assert!(!quest.is_open_at(now - Duration::seconds(1)));
+        assert!(quest.is_open_at(now));
+        assert!(!quest.is_open_at(now + Duration::days(1)));
+    }
+
+    #[test]
+    fn should_reject_schedule_ending_before_start() {
+        let now = Utc::now();
+        let payload = CreateQuest::new("Test Quest".to_string(), "".to_string())
+            .with_schedule(Some(now), Some(now - Duration::days(1)));
+
+        assert!(payload.validate().is_err());
+    }
+
+    #[test]
+    fn should_distinguish_null_from_missing_schedule() {
+        let payload: UpdateQuest = serde_json::from_str(r#"{"start_at":null}"#).unwrap();
+
+        assert_eq!(Some(None), payload.start_at);
+        assert_eq!(None, payload.end_at);
+    }
+
+    #[test]
+    fn should_limit_batch_ids() {
+        assert!(BatchFindQuests::new(Vec::new()).validate().is_err());
+        assert!(BatchFindQuests::new(vec!["a".into(); MAX_BATCH_IDS])
+            .validate()
+            .is_ok());
+        assert!(BatchFindQuests::new(vec!["a".into(); MAX_BATCH_IDS + 1])
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn should_dedup_batch_ids_in_order() {
+        let payload = BatchFindQuests::new(vec!["b".into(), "a".into(), "b".into()]);
+
+        assert_eq!(vec!["b".to_string(), "a".to_string()], payload.into_ids());
+    }
+
+    #[test]
+    fn should_sort_quests_by_created_at_desc() {
+        let now = Utc::now();
+        let mut quests = vec![
+            build_quest(None, None),
+            QuestEntity {
+                id: "newer".into(),
+                created_at: now + Duration::days(1),
+                ..build_quest(None, None)
+            },
+        ];
+        let sort = QuestSort::try_from("created_at:desc".to_string()).unwrap();
+
+        sort.sort(&mut quests);
+
+        assert_eq!("newer", quests[0].id);
+    }
+
+    #[test]
+    fn should_parse_sort_with_default_direction() {
+        assert_eq!(
+            QuestSort {
+                key: QuestSortKey::Title,
+                descending: false,
+            },
+            QuestSort::try_from("title".to_string()).unwrap()
+        );
+        assert!(QuestSort::try_from("password:desc".to_string()).is_err());
+        assert!(QuestSort::try_from("title:up".to_string()).is_err());
+    }
+
+    #[test]
+    fn should_parse_newest_as_created_at_desc() {
+        assert_eq!(
+            QuestSort::try_from("created_at:desc".to_string()).unwrap(),
+            QuestSort::try_from("newest".to_string()).unwrap()
+        );
+        assert!(QuestSort::try_from("newest:asc".to_string()).is_err());
+        assert!(QuestSort::try_from("popular:desc".to_string()).is_err());
+    }
+
+    #[test]
+    fn should_require_location_to_sort_by_nearby() {
+        let query = |sort: &str, latitude: Option<f64>, longitude: Option<f64>| FindQuests {
+            status: None,
+            active: false,
+            sort: Some(QuestSort::try_from(sort.to_string()).unwrap()),
+            with_bookmarks: false,
+            latitude,
+            longitude,
+        };
+
+        assert_eq!(
+            Ok(Some(QuestRanking::Nearby {
+                latitude: 35.68,
+                longitude: 139.76,
+            })),
+            query("nearby", Some(35.68), Some(139.76)).ranking()
+        );
+        assert!(query("nearby", Some(35.68), None).ranking().is_err());
+        assert!(query("nearby", Some(135.0), Some(139.76))
+            .ranking()
+            .is_err());
+        assert_eq!(
+            Ok(Some(QuestRanking::Popular)),
+            query("popular", None, None).ranking()
+        );
+        assert_eq!(Ok(None), query("title", None, None).ranking());
+    }
 }
@@ -0,0 +1,137 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, FromRow};
+use std::sync::Arc;
+
+use super::{
+    id::{default_id_generator, IdGenerator},
+    quest::BulkCreateQuest,
+    ReadWritePgPool,
+};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait QuestTemplateRepository: Send + Sync + 'static {
+    async fn create(&self, name: String, quest: BulkCreateQuest) -> anyhow::Result<QuestTemplate>;
+    /// 新しく保存した順
+    async fn all(&self) -> anyhow::Result<Vec<QuestTemplate>>;
+    async fn find(&self, id: String) -> anyhow::Result<QuestTemplate>;
+}
+
+#[derive(Debug, Clone)]
+pub struct QuestTemplateRepositoryForDb {
+    pool: ReadWritePgPool,
+    ids: Arc<dyn IdGenerator>,
+}
+
+impl QuestTemplateRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        QuestTemplateRepositoryForDb {
+            pool: pool.into(),
+            ids: default_id_generator(),
+        }
+    }
+
+    pub fn with_id_generator(self, ids: Arc<dyn IdGenerator>) -> Self {
+        Self { ids, ..self }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        QuestTemplateRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl QuestTemplateRepository for QuestTemplateRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn create(&self, name: String, quest: BulkCreateQuest) -> anyhow::Result<QuestTemplate> {
+        let row = sqlx::query_as::<_, QuestTemplateFromRow>(
+            r#"
+                insert into quest_templates (id, name, quest)
+                values ($1, $2, $3)
+                returning *
+            "#,
+        )
+        .bind(self.ids.generate())
+        .bind(name)
+        .bind(Json(quest))
+        .fetch_one(self.pool.write())
+        .await?;
+
+        Ok(row.into())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn all(&self) -> anyhow::Result<Vec<QuestTemplate>> {
+        let rows = sqlx::query_as::<_, QuestTemplateFromRow>(
+            r#"
+                select * from quest_templates order by created_at desc, id;
+            "#,
+        )
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(rows.into_iter().map(QuestTemplate::from).collect())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find(&self, id: String) -> anyhow::Result<QuestTemplate> {
+        let row = sqlx::query_as::<_, QuestTemplateFromRow>(
+            r#"
+                select * from quest_templates where id = $1;
+            "#,
+        )
+        .bind(id)
+        .fetch_one(self.pool.read())
+        .await?;
+
+        Ok(row.into())
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct QuestTemplateFromRow {
+    id: String,
+    name: String,
+    quest: Json<BulkCreateQuest>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<QuestTemplateFromRow> for QuestTemplate {
+    fn from(row: QuestTemplateFromRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            quest: row.quest.0,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// 保存した時点のクエストとチャレンジ。元のクエストを変えてもテンプレートは変わらない
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestTemplate {
+    pub id: String,
+    pub name: String,
+    pub quest: BulkCreateQuest,
+    pub created_at: DateTime<Utc>,
+}
+
+/// `POST /quests/:id/template`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveQuestTemplate {
+    pub name: String,
+}
+
+impl SaveQuestTemplate {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
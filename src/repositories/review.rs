@@ -0,0 +1,255 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use super::{
+    id::{default_id_generator, IdGenerator, QuestId, UserId},
+    ReadWritePgPool,
+};
+
+const MAX_COMMENT_LENGTH: usize = 1000;
+
+/// `GET /quests/:id/reviews` で返す件数
+const LIST_LIMIT: i64 = 100;
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait ReviewRepository: Send + Sync + 'static {
+    /// 参加済みのユーザーだけが、1クエストに1件まで投稿できる
+    async fn create(
+        &self,
+        user_id: UserId,
+        quest_id: QuestId,
+        payload: CreateReview,
+    ) -> Result<Review, CreateReviewError>;
+    /// 新しいものから返す
+    async fn find_by_quest_id(&self, quest_id: QuestId) -> anyhow::Result<Vec<Review>>;
+    /// レビューのないクエストは含まれない
+    async fn summarize(
+        &self,
+        quest_ids: &[QuestId],
+    ) -> anyhow::Result<HashMap<QuestId, RatingSummary>>;
+}
+
+pub enum CreateReviewError {
+    NotParticipating,
+    AlreadyReviewed,
+    Repository(anyhow::Error),
+}
+
+impl From<sqlx::Error> for CreateReviewError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Repository(e.into())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct Review {
+    pub id: String,
+    pub quest_id: QuestId,
+    pub user_id: UserId,
+    pub username: String,
+    pub rating: i16,
+    pub comment: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CreateReview {
+    pub rating: i16,
+    #[serde(default)]
+    pub comment: String,
+}
+
+impl CreateReview {
+    pub fn new(rating: i16, comment: String) -> Self {
+        Self { rating, comment }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=5).contains(&self.rating) {
+            return Err(format!("rating must be between 1 and 5: {}", self.rating));
+        }
+        if self.comment.chars().count() > MAX_COMMENT_LENGTH {
+            return Err(format!(
+                "comment must be at most {} characters",
+                MAX_COMMENT_LENGTH
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// クエストの評価。レビューがなければaverageはNone
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RatingSummary {
+    pub average: Option<f64>,
+    pub count: i64,
+}
+
+#[derive(FromRow)]
+struct RatingSummaryRow {
+    quest_id: QuestId,
+    average: f64,
+    count: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReviewRepositoryForDb {
+    pool: ReadWritePgPool,
+    ids: Arc<dyn IdGenerator>,
+}
+
+impl ReviewRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        ReviewRepositoryForDb {
+            pool: pool.into(),
+            ids: default_id_generator(),
+        }
+    }
+
+    pub fn with_id_generator(self, ids: Arc<dyn IdGenerator>) -> Self {
+        Self { ids, ..self }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        ReviewRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl ReviewRepository for ReviewRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn create(
+        &self,
+        user_id: UserId,
+        quest_id: QuestId,
+        payload: CreateReview,
+    ) -> Result<Review, CreateReviewError> {
+        // 参加確認と挿入を1文にして、確認後に参加を取り消された場合も弾く
+        let review = sqlx::query_as::<_, Review>(
+            r#"
+                with inserted as (
+                    insert into quest_reviews (id, quest_id, user_id, rating, comment)
+                    select $1, $2, $3, $4, $5
+                    where exists (
+                        select 1 from user_participating_quests
+                        where quest_id = $2 and user_id = $3
+                    )
+                    on conflict (quest_id, user_id) do nothing
+                    returning *
+                )
+                select i.id, i.quest_id, i.user_id, u.username, i.rating, i.comment, i.created_at
+                from inserted i
+                join users u on u.id = i.user_id
+            "#,
+        )
+        .bind(self.ids.generate())
+        .bind(quest_id.clone())
+        .bind(user_id.clone())
+        .bind(payload.rating)
+        .bind(payload.comment)
+        .fetch_optional(self.pool.write())
+        .await?;
+        if let Some(review) = review {
+            return Ok(review);
+        }
+
+        // 挿入されなかった理由を調べる
+        let participating = sqlx::query_scalar::<_, bool>(
+            r#"
+                select exists (
+                    select 1 from user_participating_quests
+                    where quest_id = $1 and user_id = $2
+                )
+            "#,
+        )
+        .bind(quest_id)
+        .bind(user_id)
+        .fetch_one(self.pool.write())
+        .await?;
+        if participating {
+            Err(CreateReviewError::AlreadyReviewed)
+        } else {
+            Err(CreateReviewError::NotParticipating)
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_by_quest_id(&self, quest_id: QuestId) -> anyhow::Result<Vec<Review>> {
+        let reviews = sqlx::query_as::<_, Review>(
+            r#"
+                select r.id, r.quest_id, r.user_id, u.username, r.rating, r.comment, r.created_at
+                from quest_reviews r
+                join users u on u.id = r.user_id and u.deleted_at is null
+                where r.quest_id = $1
+                order by r.created_at desc
+                limit $2
+            "#,
+        )
+        .bind(quest_id)
+        .bind(LIST_LIMIT)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(reviews)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn summarize(
+        &self,
+        quest_ids: &[QuestId],
+    ) -> anyhow::Result<HashMap<QuestId, RatingSummary>> {
+        let rows = sqlx::query_as::<_, RatingSummaryRow>(
+            r#"
+                select r.quest_id, avg(r.rating)::float8 as average, count(*) as count
+                from quest_reviews r
+                join users u on u.id = r.user_id and u.deleted_at is null
+                where r.quest_id = any($1)
+                group by r.quest_id
+            "#,
+        )
+        .bind(quest_ids)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.quest_id,
+                    RatingSummary {
+                        average: Some(row.average),
+                        count: row.count,
+                    },
+                )
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_validate_rating_range() {
+        assert!(CreateReview::new(1, String::new()).validate().is_ok());
+        assert!(CreateReview::new(5, "great".to_string()).validate().is_ok());
+        assert!(CreateReview::new(0, String::new()).validate().is_err());
+        assert!(CreateReview::new(6, String::new()).validate().is_err());
+    }
+
+    #[test]
+    fn should_reject_too_long_comment() {
+        let comment = "あ".repeat(MAX_COMMENT_LENGTH + 1);
+
+        assert!(CreateReview::new(3, comment).validate().is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Postgres, Transaction};
+use std::fmt;
+
+use super::ReadWritePgPool;
+
+/// アプリ内の他のアドバイザリロックと衝突しないように、定期処理のロックはこの名前空間を使う
+const LOCK_NAMESPACE: i32 = 0x5343_4844;
+
+/// `GET /admin/scheduled_jobs/runs` で返す件数
+const LIST_LIMIT: i64 = 100;
+
+#[async_trait]
+pub trait ScheduledJobRepository: Send + Sync + 'static {
+    /// 他のインスタンスが同じジョブを実行中ならNone
+    async fn try_lock(&self, job_name: &str) -> anyhow::Result<Option<Box<dyn ScheduledJobLock>>>;
+    /// 実行中として履歴に残し、そのIDを返す
+    async fn start_run(&self, job_name: &str) -> anyhow::Result<i64>;
+    /// 成功時は要約、失敗時はエラーを残す
+    async fn finish_run(&self, id: i64, result: Result<String, String>) -> anyhow::Result<()>;
+    /// 新しいものから返す
+    async fn find_runs(&self, job_name: Option<String>) -> anyhow::Result<Vec<ScheduledJobRun>>;
+}
+
+/// 実行中はロックを持ち続ける。releaseせずにdropしても解放される
+#[async_trait]
+pub trait ScheduledJobLock: Send {
+    async fn release(self: Box<Self>) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Clone)]
+pub struct ScheduledJobRepositoryForDb {
+    pool: ReadWritePgPool,
+}
+
+impl ScheduledJobRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        ScheduledJobRepositoryForDb { pool: pool.into() }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        ScheduledJobRepositoryForDb::new(pool)
+    }
+}
+
+/// トランザクション単位のロックなので、commitかrollback(drop)で解放される
+/// セッション単位のロックと違い、プールに戻したコネクションにロックが残ることがない
+struct AdvisoryLock {
+    tx: Transaction<'static, Postgres>,
+}
+
+#[async_trait]
+impl ScheduledJobLock for AdvisoryLock {
+    async fn release(self: Box<Self>) -> anyhow::Result<()> {
+        self.tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ScheduledJobRepository for ScheduledJobRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn try_lock(&self, job_name: &str) -> anyhow::Result<Option<Box<dyn ScheduledJobLock>>> {
+        let mut tx = self.pool.write().begin().await?;
+        let locked = sqlx::query_scalar::<_, bool>(
+            r#"
+                select pg_try_advisory_xact_lock($1, hashtext($2));
+            "#,
+        )
+        .bind(LOCK_NAMESPACE)
+        .bind(job_name)
+        .fetch_one(&mut tx)
+        .await?;
+
+        if !locked {
+            return Ok(None);
+        }
+        Ok(Some(Box::new(AdvisoryLock { tx })))
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn start_run(&self, job_name: &str) -> anyhow::Result<i64> {
+        let id = sqlx::query_scalar::<_, i64>(
+            r#"
+                insert into scheduled_job_runs (job_name, status) values ($1, 'running')
+                returning id
+            "#,
+        )
+        .bind(job_name)
+        .fetch_one(self.pool.write())
+        .await?;
+
+        Ok(id)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn finish_run(&self, id: i64, result: Result<String, String>) -> anyhow::Result<()> {
+        let (status, message) = match result {
+            Ok(summary) => (ScheduledJobStatus::Succeeded, summary),
+            Err(error) => (ScheduledJobStatus::Failed, error),
+        };
+        sqlx::query(
+            r#"
+                update scheduled_job_runs set status = $2, message = $3, finished_at = now()
+                where id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(status.to_string())
+        .bind(message)
+        .execute(self.pool.write())
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_runs(&self, job_name: Option<String>) -> anyhow::Result<Vec<ScheduledJobRun>> {
+        let rows = sqlx::query_as::<_, ScheduledJobRunFromRow>(
+            r#"
+                select * from scheduled_job_runs
+                where ($1::text is null or job_name = $1)
+                order by started_at desc, id desc
+                limit $2;
+            "#,
+        )
+        .bind(job_name)
+        .bind(LIST_LIMIT)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        rows.into_iter().map(ScheduledJobRun::try_from).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduledJobStatus {
+    /// 終了前にプロセスが落ちた回もrunningのまま残る
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl std::str::FromStr for ScheduledJobStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "running" => Ok(Self::Running),
+            "succeeded" => Ok(Self::Succeeded),
+            "failed" => Ok(Self::Failed),
+            _ => Err(anyhow::anyhow!("Invalid scheduled job status : {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ScheduledJobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct ScheduledJobRunFromRow {
+    id: i64,
+    job_name: String,
+    status: String,
+    message: Option<String>,
+    started_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ScheduledJobRun {
+    pub id: i64,
+    pub job_name: String,
+    pub status: ScheduledJobStatus,
+    pub message: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<ScheduledJobRunFromRow> for ScheduledJobRun {
+    type Error = anyhow::Error;
+
+    fn try_from(row: ScheduledJobRunFromRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.id,
+            job_name: row.job_name,
+            status: row.status.parse()?,
+            message: row.message,
+            started_at: row.started_at,
+            finished_at: row.finished_at,
+        })
+    }
+}
@@ -0,0 +1,40 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::ReadWritePgPool;
+
+/// async_sessionsテーブルの掃除だけを行う。セッションの読み書きはライブラリ側が持つ
+#[async_trait]
+pub trait SessionRepository: Send + Sync + 'static {
+    /// 期限切れのセッションを消し、件数を返す
+    async fn delete_expired(&self, now: DateTime<Utc>) -> anyhow::Result<u64>;
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionRepositoryForDb {
+    pool: ReadWritePgPool,
+}
+
+impl SessionRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        SessionRepositoryForDb { pool: pool.into() }
+    }
+}
+
+#[async_trait]
+impl SessionRepository for SessionRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn delete_expired(&self, now: DateTime<Utc>) -> anyhow::Result<u64> {
+        let deleted = sqlx::query(
+            r#"
+                delete from async_sessions where expires < $1
+            "#,
+        )
+        .bind(now)
+        .execute(self.pool.write())
+        .await?
+        .rows_affected();
+
+        Ok(deleted)
+    }
+}
@@ -0,0 +1,249 @@
+use axum::async_trait;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use super::{
+    id::{ChallengeId, QuestId},
+    ReadWritePgPool,
+};
+
+/// 日別の集計の区切り。マイグレーションのビューと合わせて日本時間にする
+const STATS_UTC_OFFSET_SECS: i32 = 9 * 60 * 60;
+
+/// `GET /admin/stats/daily_active_users` で期間を省略したときの日数
+const DEFAULT_DAILY_RANGE_DAYS: i64 = 30;
+/// 1回に返す日数の上限
+const MAX_DAILY_RANGE_DAYS: i64 = 366;
+
+/// 重い集計はマテリアライズドビューから読む。値はrefreshした時点のもの
+#[async_trait]
+pub trait StatsRepository: Send + Sync + 'static {
+    /// 件数だけなのでその場で数える
+    async fn overview(&self) -> anyhow::Result<StatsOverview>;
+    /// 参加者の多い順
+    async fn find_quest_stats(&self) -> anyhow::Result<Vec<QuestStats>>;
+    /// 完了数の多い順。quest_idを指定するとそのクエストのチャレンジだけ返す
+    async fn find_challenge_stats(
+        &self,
+        quest_id: Option<QuestId>,
+    ) -> anyhow::Result<Vec<ChallengeStats>>;
+    /// fromからtoまでの日付順。アクティブユーザーのいない日は0で埋める
+    async fn find_daily_active_users(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> anyhow::Result<Vec<DailyActiveUsers>>;
+    /// 集計用のビューを作り直す。読み込みは止めない
+    async fn refresh(&self) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct StatsOverview {
+    /// 退会・削除済みを除いたユーザー数
+    pub users: i64,
+    pub quests: i64,
+    pub challenges: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct QuestStats {
+    pub quest_id: QuestId,
+    pub title: String,
+    pub participants: i64,
+    /// 修了証を発行した人数
+    pub completers: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct ChallengeStats {
+    pub challenge_id: ChallengeId,
+    pub quest_id: QuestId,
+    pub name: String,
+    pub completions: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct DailyActiveUsers {
+    pub date: NaiveDate,
+    pub active_users: i64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DailyActiveUsersQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl DailyActiveUsersQuery {
+    /// 省略された端を埋める。toの省略時は今日、fromの省略時はtoまでの30日間
+    pub fn range(&self, today: NaiveDate) -> Result<(NaiveDate, NaiveDate), String> {
+        let to = self.to.unwrap_or(today);
+        let from = self
+            .from
+            .unwrap_or(to - Duration::days(DEFAULT_DAILY_RANGE_DAYS - 1));
+        if from > to {
+            return Err(format!("from must not be after to: {} > {}", from, to));
+        }
+        if (to - from).num_days() >= MAX_DAILY_RANGE_DAYS {
+            return Err(format!(
+                "range must be at most {} days",
+                MAX_DAILY_RANGE_DAYS
+            ));
+        }
+        Ok((from, to))
+    }
+}
+
+/// 集計の区切りでの今日の日付
+pub fn stats_today(now: DateTime<Utc>) -> NaiveDate {
+    let offset = FixedOffset::east_opt(STATS_UTC_OFFSET_SECS).unwrap();
+    now.with_timezone(&offset).date_naive()
+}
+
+#[derive(Debug, Clone)]
+pub struct StatsRepositoryForDb {
+    pool: ReadWritePgPool,
+}
+
+impl StatsRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        StatsRepositoryForDb { pool: pool.into() }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        StatsRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl StatsRepository for StatsRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn overview(&self) -> anyhow::Result<StatsOverview> {
+        let overview = sqlx::query_as::<_, StatsOverview>(
+            r#"
+                select
+                    (select count(*) from users
+                     where deleted_at is null and deactivated_at is null) as users,
+                    (select count(*) from quests where deleted_at is null) as quests,
+                    (select count(*) from challenges c
+                     join quests q on q.id = c.quest_id
+                     where q.deleted_at is null) as challenges
+            "#,
+        )
+        .fetch_one(self.pool.read())
+        .await?;
+
+        Ok(overview)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_quest_stats(&self) -> anyhow::Result<Vec<QuestStats>> {
+        let stats = sqlx::query_as::<_, QuestStats>(
+            r#"
+                select quest_id, title, participants, completers
+                from quest_stats
+                order by participants desc, quest_id
+            "#,
+        )
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(stats)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_challenge_stats(
+        &self,
+        quest_id: Option<QuestId>,
+    ) -> anyhow::Result<Vec<ChallengeStats>> {
+        let stats = sqlx::query_as::<_, ChallengeStats>(
+            r#"
+                select challenge_id, quest_id, name, completions
+                from challenge_stats
+                where $1::text is null or quest_id = $1
+                order by completions desc, challenge_id
+            "#,
+        )
+        .bind(quest_id)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(stats)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_daily_active_users(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> anyhow::Result<Vec<DailyActiveUsers>> {
+        let stats = sqlx::query_as::<_, DailyActiveUsers>(
+            r#"
+                select d::date as date, coalesce(s.active_users, 0) as active_users
+                from generate_series($1::date, $2::date, interval '1 day') d
+                left join daily_active_user_stats s on s.date = d::date
+                order by d
+            "#,
+        )
+        .bind(from)
+        .bind(to)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(stats)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn refresh(&self) -> anyhow::Result<()> {
+        for view in ["quest_stats", "challenge_stats", "daily_active_user_stats"] {
+            sqlx::query(&format!("refresh materialized view concurrently {}", view))
+                .execute(self.pool.write())
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn should_default_to_last_30_days() {
+        let range = DailyActiveUsersQuery::default().range(date(2024, 1, 31));
+
+        assert_eq!(Ok((date(2024, 1, 2), date(2024, 1, 31))), range);
+    }
+
+    #[test]
+    fn should_reject_invalid_range() {
+        let reversed = DailyActiveUsersQuery {
+            from: Some(date(2024, 2, 1)),
+            to: Some(date(2024, 1, 1)),
+        };
+        assert!(reversed.range(date(2024, 2, 1)).is_err());
+
+        let too_long = DailyActiveUsersQuery {
+            from: Some(date(2022, 12, 31)),
+            to: Some(date(2024, 1, 1)),
+        };
+        assert!(too_long.range(date(2024, 1, 1)).is_err());
+    }
+
+    #[test]
+    fn should_split_days_in_japan_time() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 15, 0, 0).unwrap();
+
+        assert_eq!(date(2024, 1, 2), stats_today(now));
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use super::{
+    id::{ChallengeId, QuestId},
+    ReadWritePgPool,
+};
+
+/// 言語ごとのクエスト・チャレンジの本文
+/// 取得はlangsの順に探し、最初に見つかった言語のものを返す
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait TranslationRepository: Send + Sync + 'static {
+    async fn upsert_quest(
+        &self,
+        quest_id: QuestId,
+        lang: String,
+        payload: QuestTranslation,
+    ) -> anyhow::Result<()>;
+    async fn upsert_challenge(
+        &self,
+        challenge_id: ChallengeId,
+        lang: String,
+        payload: ChallengeTranslation,
+    ) -> anyhow::Result<()>;
+    /// 翻訳のないクエストは含まれない
+    async fn find_quests(
+        &self,
+        quest_ids: &[QuestId],
+        langs: &[String],
+    ) -> anyhow::Result<HashMap<QuestId, QuestTranslation>>;
+    /// 翻訳のないチャレンジは含まれない
+    async fn find_challenges(
+        &self,
+        challenge_ids: &[ChallengeId],
+        langs: &[String],
+    ) -> anyhow::Result<HashMap<ChallengeId, ChallengeTranslation>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuestTranslation {
+    pub title: String,
+    pub description: String,
+}
+
+impl QuestTranslation {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.title.trim().is_empty() {
+            return Err("title must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChallengeTranslation {
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub flavor_text: String,
+}
+
+impl ChallengeTranslation {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(FromRow)]
+struct QuestTranslationRow {
+    quest_id: QuestId,
+    title: String,
+    description: String,
+}
+
+#[derive(FromRow)]
+struct ChallengeTranslationRow {
+    challenge_id: ChallengeId,
+    name: String,
+    description: String,
+    flavor_text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TranslationRepositoryForDb {
+    pool: ReadWritePgPool,
+}
+
+impl TranslationRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        TranslationRepositoryForDb { pool: pool.into() }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        TranslationRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl TranslationRepository for TranslationRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn upsert_quest(
+        &self,
+        quest_id: QuestId,
+        lang: String,
+        payload: QuestTranslation,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                insert into quest_translations (quest_id, lang, title, description)
+                values ($1, $2, $3, $4)
+                on conflict (quest_id, lang)
+                do update set title = excluded.title, description = excluded.description
+            "#,
+        )
+        .bind(quest_id)
+        .bind(lang)
+        .bind(payload.title)
+        .bind(payload.description)
+        .execute(self.pool.write())
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn upsert_challenge(
+        &self,
+        challenge_id: ChallengeId,
+        lang: String,
+        payload: ChallengeTranslation,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                insert into challenge_translations
+                    (challenge_id, lang, name, description, flavor_text)
+                values ($1, $2, $3, $4, $5)
+                on conflict (challenge_id, lang)
+                do update set name = excluded.name, description = excluded.description,
+                    flavor_text = excluded.flavor_text
+            "#,
+        )
+        .bind(challenge_id)
+        .bind(lang)
+        .bind(payload.name)
+        .bind(payload.description)
+        .bind(payload.flavor_text)
+        .execute(self.pool.write())
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_quests(
+        &self,
+        quest_ids: &[QuestId],
+        langs: &[String],
+    ) -> anyhow::Result<HashMap<QuestId, QuestTranslation>> {
+        let rows = sqlx::query_as::<_, QuestTranslationRow>(
+            r#"
+                select distinct on (quest_id) quest_id, title, description
+                from quest_translations
+                where quest_id = any($1) and lang = any($2)
+                order by quest_id, array_position($2, lang)
+            "#,
+        )
+        .bind(quest_ids)
+        .bind(langs)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.quest_id,
+                    QuestTranslation {
+                        title: row.title,
+                        description: row.description,
+                    },
+                )
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_challenges(
+        &self,
+        challenge_ids: &[ChallengeId],
+        langs: &[String],
+    ) -> anyhow::Result<HashMap<ChallengeId, ChallengeTranslation>> {
+        let rows = sqlx::query_as::<_, ChallengeTranslationRow>(
+            r#"
+                select distinct on (challenge_id) challenge_id, name, description, flavor_text
+                from challenge_translations
+                where challenge_id = any($1) and lang = any($2)
+                order by challenge_id, array_position($2, lang)
+            "#,
+        )
+        .bind(challenge_ids)
+        .bind(langs)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.challenge_id,
+                    ChallengeTranslation {
+                        name: row.name,
+                        description: row.description,
+                        flavor_text: row.flavor_text,
+                    },
+                )
+            })
+            .collect())
+    }
+}
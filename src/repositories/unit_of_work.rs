@@ -0,0 +1,142 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, Transaction};
+
+use super::{
+    achievement::{
+        insert_user_achievement, select_progress, select_user_achievements, AchievementProgress,
+    },
+    id::{ChallengeId, UserId},
+    point::insert_challenge_award,
+    user_challenge::insert_challenge_complete_event,
+    ReadWritePgPool,
+};
+
+/// 複数リポジトリにまたがる書き込みを1つのトランザクションで行うための入口
+#[async_trait]
+pub trait UnitOfWork: Send + Sync + 'static {
+    async fn begin(&self) -> anyhow::Result<Box<dyn TransactionScope>>;
+}
+
+/// トランザクション内の操作。commitせずにdropするとロールバックされる
+#[async_trait]
+pub trait TransactionScope: Send {
+    async fn save_challenge_complete_event(
+        &mut self,
+        user_id: UserId,
+        challenge_id: ChallengeId,
+        photo_url: Option<String>,
+        completed_at: DateTime<Utc>,
+    ) -> anyhow::Result<()>;
+    async fn award_for_challenge(
+        &mut self,
+        user_id: UserId,
+        challenge_id: ChallengeId,
+        amount: i32,
+    ) -> anyhow::Result<()>;
+    async fn get_achievement_progress(
+        &mut self,
+        user_id: UserId,
+    ) -> anyhow::Result<AchievementProgress>;
+    async fn get_achieved_ids(&mut self, user_id: UserId) -> anyhow::Result<Vec<String>>;
+    async fn grant_achievement(
+        &mut self,
+        user_id: UserId,
+        achievement_id: String,
+    ) -> anyhow::Result<()>;
+    async fn commit(self: Box<Self>) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Clone)]
+pub struct UnitOfWorkForDb {
+    pool: ReadWritePgPool,
+}
+
+impl UnitOfWorkForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        UnitOfWorkForDb { pool: pool.into() }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        UnitOfWorkForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl UnitOfWork for UnitOfWorkForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn begin(&self) -> anyhow::Result<Box<dyn TransactionScope>> {
+        let tx = self.pool.write().begin().await?;
+
+        anyhow::Ok(Box::new(TransactionScopeForDb { tx }))
+    }
+}
+
+struct TransactionScopeForDb {
+    tx: Transaction<'static, Postgres>,
+}
+
+#[async_trait]
+impl TransactionScope for TransactionScopeForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn save_challenge_complete_event(
+        &mut self,
+        user_id: UserId,
+        challenge_id: ChallengeId,
+        photo_url: Option<String>,
+        completed_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        insert_challenge_complete_event(
+            &mut self.tx,
+            user_id,
+            challenge_id,
+            photo_url,
+            completed_at,
+        )
+        .await
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn award_for_challenge(
+        &mut self,
+        user_id: UserId,
+        challenge_id: ChallengeId,
+        amount: i32,
+    ) -> anyhow::Result<()> {
+        insert_challenge_award(&mut self.tx, user_id, challenge_id, amount).await
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn get_achievement_progress(
+        &mut self,
+        user_id: UserId,
+    ) -> anyhow::Result<AchievementProgress> {
+        select_progress(&mut self.tx, user_id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn get_achieved_ids(&mut self, user_id: UserId) -> anyhow::Result<Vec<String>> {
+        let achievements = select_user_achievements(&mut self.tx, user_id).await?;
+
+        anyhow::Ok(achievements.into_iter().map(|a| a.id).collect())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn grant_achievement(
+        &mut self,
+        user_id: UserId,
+        achievement_id: String,
+    ) -> anyhow::Result<()> {
+        insert_user_achievement(&mut self.tx, user_id, achievement_id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn commit(self: Box<Self>) -> anyhow::Result<()> {
+        self.tx.commit().await?;
+
+        anyhow::Ok(())
+    }
+}
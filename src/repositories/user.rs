@@ -1,161 +1,527 @@
 use anyhow::anyhow;
 use axum::async_trait;
-use bcrypt::{hash, verify, DEFAULT_COST};
-use nanoid::nanoid;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
+use sqlx::FromRow;
+use std::{fmt, sync::Arc};
 
+use super::{
+    cascade::USER_CASCADE,
+    id::{default_id_generator, ChallengeId, IdGenerator, QuestId, UserId},
+    ReadWritePgPool,
+};
+use crate::{config::LoginLockoutConfig, services::password::PasswordHasher};
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+#[cfg_attr(test, mockall::automock)]
 #[async_trait]
-pub trait UserRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+pub trait UserRepository: std::marker::Send + std::marker::Sync + 'static {
     async fn register(&self, payload: RegisterUser) -> anyhow::Result<UserEntity>;
-    async fn login(&self, payload: LoginUser) -> anyhow::Result<UserEntity>;
-    async fn find(&self, id: String) -> anyhow::Result<UserEntity>;
-    async fn delete(&self, id: String) -> anyhow::Result<()>;
+    /// 失敗が続くとロックする。ロック中は正しいパスワードでもLockedを返す
+    async fn login(&self, payload: LoginUser) -> Result<UserEntity, LoginError>;
+    /// ログインのロックを解除し、失敗回数を0に戻す
+    async fn unlock(&self, id: UserId) -> anyhow::Result<()>;
+    /// 運用向け。パスワードを置き換え、ログインのロックも解除する
+    async fn reset_password(&self, id: UserId, password: String) -> anyhow::Result<()>;
+    /// 運用向け。削除・退会していないユーザーを登録順に返す
+    async fn all(&self) -> anyhow::Result<Vec<UserEntity>>;
+    async fn find(&self, id: UserId) -> anyhow::Result<UserEntity>;
+    /// 論理削除する。一定期間後にpurge_deletedで物理削除するまでメールアドレスは再登録できない
+    async fn delete(&self, id: UserId) -> anyhow::Result<()>;
+    /// beforeより前に論理削除したユーザーを関連する記録ごと物理削除し、件数を返す
+    async fn purge_deleted(&self, before: DateTime<Utc>) -> anyhow::Result<u64>;
+    /// 個人情報を匿名化して退会させる。参加・完了・レビューの記録は匿名のまま残す
+    async fn deactivate(&self, id: UserId) -> anyhow::Result<()>;
+    /// データエクスポート用に、ユーザーに紐づく記録をまとめて返す
+    async fn find_export_data(&self, id: UserId) -> anyhow::Result<UserExportData>;
+    /// 権限の確認用。ユーザーがいなければNone
+    async fn find_role(&self, id: UserId) -> anyhow::Result<Option<UserRole>>;
+    /// 運用向け。管理者の付与・剥奪に使う
+    async fn set_role(&self, id: UserId, role: UserRole) -> anyhow::Result<()>;
+}
+
+/// 退会後の表示名。レビュー等には退会したユーザーとして残る
+const DEACTIVATED_USERNAME: &str = "deactivated_user";
+
+#[derive(Debug)]
+pub enum LoginError {
+    /// ユーザーの有無を推測されないよう、パスワード違いと区別しない
+    InvalidCredentials,
+    Locked {
+        until: DateTime<Utc>,
+    },
+    Repository(anyhow::Error),
+}
+
+impl From<sqlx::Error> for LoginError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Repository(e.into())
+    }
+}
+
+impl From<anyhow::Error> for LoginError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Repository(e)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct UserRepositoryForDb {
-    pool: PgPool,
+    pool: ReadWritePgPool,
+    lockout: LoginLockoutConfig,
+    hasher: PasswordHasher,
+    ids: Arc<dyn IdGenerator>,
 }
 
 impl UserRepositoryForDb {
-    pub fn new(pool: PgPool) -> Self {
-        UserRepositoryForDb { pool }
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        UserRepositoryForDb {
+            pool: pool.into(),
+            lockout: LoginLockoutConfig::default(),
+            hasher: PasswordHasher::default(),
+            ids: default_id_generator(),
+        }
+    }
+
+    pub fn with_id_generator(self, ids: Arc<dyn IdGenerator>) -> Self {
+        Self { ids, ..self }
+    }
+
+    pub fn with_lockout(self, lockout: LoginLockoutConfig) -> Self {
+        Self { lockout, ..self }
+    }
+
+    pub fn with_hasher(self, hasher: PasswordHasher) -> Self {
+        Self { hasher, ..self }
+    }
+
+    /// 旧形式・旧コストのハッシュを作り直す。その間にパスワードが変わっていたら何もしない
+    async fn rehash(&self, id: &UserId, password: &str, old_hash: &str) -> anyhow::Result<()> {
+        let new_hash = self.hasher.hash(password)?;
+        sqlx::query(
+            r#"
+                update users set password = $3 where id = $1 and password = $2
+            "#,
+        )
+        .bind(id)
+        .bind(old_hash)
+        .bind(new_hash)
+        .execute(self.pool.write())
+        .await?;
+
+        Ok(())
     }
 
     #[cfg(test)]
     /// テスト用の簡易版コンストラクタ
     pub async fn with_url(url: &str) -> anyhow::Result<Self> {
-        let pool = PgPool::connect(url).await?;
+        let pool = sqlx::PgPool::connect(url).await?;
         Ok(UserRepositoryForDb::new(pool))
     }
 }
 
 #[async_trait]
 impl UserRepository for UserRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
     async fn register(&self, payload: RegisterUser) -> anyhow::Result<UserEntity> {
-        let hashed_password = hash(payload.password, DEFAULT_COST)?;
+        let hashed_password = self.hasher.hash(&payload.password)?;
         let row = sqlx::query_as::<_, UserFromRow>(
             r#"
                 insert into users values ($1, $2, $3, $4)
                 returning *
             "#,
         )
-        .bind(nanoid!())
+        .bind(self.ids.generate())
         .bind(payload.username)
         .bind(payload.email)
         .bind(hashed_password)
-        .fetch_one(&self.pool)
+        .fetch_one(self.pool.write())
         .await?;
 
-        let user = UserEntity::new(row.id, row.username, row.email);
-
-        anyhow::Ok(user)
+        anyhow::Ok(UserEntity::from(row))
     }
 
-    async fn login(&self, payload: LoginUser) -> anyhow::Result<UserEntity> {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn login(&self, payload: LoginUser) -> Result<UserEntity, LoginError> {
         let user_row = sqlx::query_as::<_, UserFromRow>(
             r#"
-                select * from users where email=$1;
+                select * from users where email=$1 and deleted_at is null and deactivated_at is null;
             "#,
         )
         .bind(payload.email)
-        .fetch_one(&self.pool)
-        .await?;
+        .fetch_optional(self.pool.write())
+        .await?
+        .ok_or(LoginError::InvalidCredentials)?;
 
-        let verified = verify(payload.password, &user_row.password)?;
+        let now = Utc::now();
+        if let Some(until) = user_row.locked_until.filter(|until| now < *until) {
+            return Err(LoginError::Locked { until });
+        }
+
+        let verified = self.hasher.verify(&payload.password, &user_row.password)?;
         if !verified {
-            return Err(anyhow!("Invalid Password"));
+            // 上限に達したらロックし、解除後は0から数え直す
+            let lock_until = now + chrono::Duration::from_std(self.lockout.lock_duration).unwrap();
+            let locked_until = sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+                r#"
+                    update users set
+                        failed_login_attempts = case
+                            when failed_login_attempts + 1 >= $2 then 0
+                            else failed_login_attempts + 1
+                        end,
+                        locked_until = case
+                            when failed_login_attempts + 1 >= $2 then $3
+                            else locked_until
+                        end
+                    where id = $1
+                    returning locked_until
+                "#,
+            )
+            .bind(&user_row.id)
+            .bind(self.lockout.max_attempts)
+            .bind(lock_until)
+            .fetch_one(self.pool.write())
+            .await?;
+            return match locked_until.filter(|until| now < *until) {
+                Some(until) => Err(LoginError::Locked { until }),
+                None => Err(LoginError::InvalidCredentials),
+            };
         }
 
-        let user = UserEntity {
-            id: user_row.id.clone(),
-            username: user_row.username.clone(),
-            email: user_row.email.clone(),
-        };
+        if user_row.failed_login_attempts > 0 || user_row.locked_until.is_some() {
+            sqlx::query(
+                r#"
+                    update users set failed_login_attempts = 0, locked_until = null where id = $1
+                "#,
+            )
+            .bind(&user_row.id)
+            .execute(self.pool.write())
+            .await?;
+        }
 
-        anyhow::Ok(user)
+        // 平文のパスワードが手元にあるのはログイン時だけなので、ここで新しい形式に移す
+        if self.hasher.needs_rehash(&user_row.password) {
+            if let Err(e) = self
+                .rehash(&user_row.id, &payload.password, &user_row.password)
+                .await
+            {
+                tracing::warn!("failed to rehash password of {}: {}", user_row.id, e);
+            }
+        }
+
+        Ok(UserEntity::from(user_row))
     }
 
-    async fn find(&self, id: String) -> anyhow::Result<UserEntity> {
-        let user_row = sqlx::query_as::<_, UserFromRow>(
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn unlock(&self, id: UserId) -> anyhow::Result<()> {
+        let result = sqlx::query(
             r#"
-                select * from users where id=$1;
+                update users set failed_login_attempts = 0, locked_until = null
+                where id = $1 and deleted_at is null and deactivated_at is null
             "#,
         )
         .bind(id.clone())
-        .fetch_one(&self.pool)
+        .execute(self.pool.write())
         .await?;
+        if result.rows_affected() == 0 {
+            return Err(anyhow!("user not found: {}", id));
+        }
 
-        let user = UserEntity {
-            id: user_row.id.clone(),
-            username: user_row.username.clone(),
-            email: user_row.email.clone(),
-        };
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn reset_password(&self, id: UserId, password: String) -> anyhow::Result<()> {
+        let hashed_password = self.hasher.hash(&password)?;
+        let result = sqlx::query(
+            r#"
+                update users set
+                    password = $2,
+                    failed_login_attempts = 0,
+                    locked_until = null,
+                    updated_at = now()
+                where id = $1 and deleted_at is null and deactivated_at is null
+            "#,
+        )
+        .bind(id.clone())
+        .bind(hashed_password)
+        .execute(self.pool.write())
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(anyhow!("user not found: {}", id));
+        }
 
-        anyhow::Ok(user)
+        Ok(())
     }
 
-    async fn delete(&self, id: String) -> anyhow::Result<()> {
-        let tx = self.pool.begin().await?;
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn all(&self) -> anyhow::Result<Vec<UserEntity>> {
+        let rows = sqlx::query_as::<_, UserFromRow>(
+            r#"
+                select * from users
+                where deleted_at is null and deactivated_at is null
+                order by created_at, id;
+            "#,
+        )
+        .fetch_all(self.pool.read())
+        .await?;
+
+        anyhow::Ok(rows.into_iter().map(UserEntity::from).collect())
+    }
 
-        // user_challengesの削除
-        sqlx::query(
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find(&self, id: UserId) -> anyhow::Result<UserEntity> {
+        let user_row = sqlx::query_as::<_, UserFromRow>(
             r#"
-                delete from user_completed_challenges where user_id=$1
+                select * from users where id=$1 and deleted_at is null and deactivated_at is null;
             "#,
         )
         .bind(id.clone())
-        .execute(&self.pool)
+        .fetch_one(self.pool.read())
         .await?;
 
-        // user_questsの削除
+        anyhow::Ok(UserEntity::from(user_row))
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn delete(&self, id: UserId) -> anyhow::Result<()> {
+        // 参加・完了の記録は復元できるように物理削除まで残す
         sqlx::query(
             r#"
-                delete from user_participating_quests where user_id=$1
+                update users set deleted_at = now() where id=$1 and deleted_at is null
             "#,
         )
         .bind(id.clone())
-        .execute(&self.pool)
+        .execute(self.pool.write())
         .await?;
 
-        // userの削除
-        sqlx::query(
+        anyhow::Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn purge_deleted(&self, before: DateTime<Utc>) -> anyhow::Result<u64> {
+        let mut tx = self.pool.write().begin().await?;
+        let purged = USER_CASCADE
+            .delete(&mut tx, "deleted_at < $1", before)
+            .await?;
+        tx.commit().await?;
+
+        anyhow::Ok(purged)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn deactivate(&self, id: UserId) -> anyhow::Result<()> {
+        let mut tx = self.pool.write().begin().await?;
+
+        // メールアドレスは再登録できるように使えない値に置き換え、パスワードではログインできなくする
+        let result = sqlx::query(
             r#"
-                delete from users where id=$1
+                update users
+                set username = $2, email = $3, password = '', deactivated_at = now(),
+                    updated_at = now()
+                where id = $1 and deleted_at is null and deactivated_at is null
             "#,
         )
         .bind(id.clone())
-        .execute(&self.pool)
+        .bind(DEACTIVATED_USERNAME)
+        .bind(format!("deactivated+{}@invalid", id))
+        .execute(&mut tx)
         .await?;
+        if result.rows_affected() == 0 {
+            // dropに任せるとロールバックが接続の次の利用まで遅れ、ロックが残る
+            tx.rollback().await?;
+            return Err(anyhow!("user not found: {}", id));
+        }
+        // 通知先・連携先・フォロー関係・表示名は本人を特定できるので消す
+        for (table, column) in [
+            ("devices", "user_id"),
+            ("webhooks", "user_id"),
+            ("user_preferences", "user_id"),
+            ("user_follows", "follower_id"),
+            ("user_follows", "followee_id"),
+        ] {
+            sqlx::query(&format!("delete from {} where {} = $1", table, column))
+                .bind(id.clone())
+                .execute(&mut tx)
+                .await?;
+        }
 
         tx.commit().await?;
 
         anyhow::Ok(())
     }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_export_data(&self, id: UserId) -> anyhow::Result<UserExportData> {
+        let profile = self.find(id.clone()).await?;
+        let participated_quests = sqlx::query_as::<_, ExportedParticipation>(
+            r#"
+                select q.id as quest_id, q.title as quest_title
+                from user_participating_quests upq
+                join quests q on q.id = upq.quest_id
+                where upq.user_id = $1
+                order by q.title
+            "#,
+        )
+        .bind(id.clone())
+        .fetch_all(self.pool.read())
+        .await?;
+        let completed_challenges = sqlx::query_as::<_, ExportedCompletion>(
+            r#"
+                select c.id as challenge_id, c.name as challenge_name, c.quest_id,
+                    ucc.completed_at, ucc.photo_url
+                from user_completed_challenges ucc
+                join challenges c on c.id = ucc.challenge_id
+                where ucc.user_id = $1
+                order by ucc.completed_at
+            "#,
+        )
+        .bind(id)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        anyhow::Ok(UserExportData {
+            profile,
+            participated_quests,
+            completed_challenges,
+        })
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_role(&self, id: UserId) -> anyhow::Result<Option<UserRole>> {
+        // 剥奪がすぐ効くように、レプリカではなくプライマリから読む
+        let role = sqlx::query_scalar::<_, String>(
+            r#"
+                select role from users
+                where id = $1 and deleted_at is null and deactivated_at is null
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(self.pool.write())
+        .await?;
+
+        role.map(|role| role.parse()).transpose()
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn set_role(&self, id: UserId, role: UserRole) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+                update users set role = $2, updated_at = now()
+                where id = $1 and deleted_at is null and deactivated_at is null
+            "#,
+        )
+        .bind(id.clone())
+        .bind(role.to_string())
+        .execute(self.pool.write())
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(anyhow!("user not found: {}", id));
+        }
+
+        Ok(())
+    }
+}
+
+/// 本人がダウンロードできる自分の記録
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserExportData {
+    pub profile: UserEntity,
+    pub participated_quests: Vec<ExportedParticipation>,
+    pub completed_challenges: Vec<ExportedCompletion>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct ExportedParticipation {
+    pub quest_id: QuestId,
+    pub quest_title: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct ExportedCompletion {
+    pub challenge_id: ChallengeId,
+    pub challenge_name: String,
+    pub quest_id: QuestId,
+    pub completed_at: DateTime<Utc>,
+    pub photo_url: Option<String>,
+}
+
+/// 管理APIを呼べるかどうか。登録直後はUser
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UserRole {
+    User,
+    Admin,
+}
+
+impl std::str::FromStr for UserRole {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(Self::User),
+            "admin" => Ok(Self::Admin),
+            _ => Err(anyhow!("Invalid user role : {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for UserRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let role = match self {
+            Self::User => "user",
+            Self::Admin => "admin",
+        };
+        write!(f, "{}", role)
+    }
 }
 
 #[derive(Debug, Clone, FromRow)]
 struct UserFromRow {
-    id: String,
+    id: UserId,
     username: String,
     email: String,
     password: String,
+    failed_login_attempts: i32,
+    locked_until: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct UserEntity {
-    pub id: String,
+    pub id: UserId,
     pub username: String,
     pub email: String,
+    #[serde(default)]
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub updated_at: DateTime<Utc>,
 }
 
 impl UserEntity {
-    pub fn new(id: String, username: String, email: String) -> Self {
+    #[cfg(test)]
+    pub fn new(id: UserId, username: String, email: String) -> Self {
+        let now = Utc::now();
         Self {
             id,
             username,
             email,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+impl From<UserFromRow> for UserEntity {
+    fn from(row: UserFromRow) -> Self {
+        Self {
+            id: row.id,
+            username: row.username,
+            email: row.email,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
         }
     }
 }
@@ -173,17 +539,45 @@ pub struct RegisterUser {
     username: String,
     email: String,
     password: String,
+    /// CAPTCHAが有効な環境でのみ必須
+    #[serde(default)]
+    captcha_token: Option<String>,
 }
 
-#[cfg(test)]
 impl RegisterUser {
     pub fn new(username: String, email: String, password: String) -> Self {
         Self {
             username,
             email,
             password,
+            captcha_token: None,
         }
     }
+
+    pub fn captcha_token(&self) -> Option<&str> {
+        self.captcha_token.as_deref()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.username.trim().is_empty() {
+            return Err("username must not be empty".to_string());
+        }
+        if !self.email.contains('@') {
+            return Err(format!("invalid email: {}", self.email));
+        }
+        validate_password(&self.password)
+    }
+}
+
+/// 登録時とパスワードのリセット時で同じ条件を使う
+pub fn validate_password(password: &str) -> Result<(), String> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(format!(
+            "password must be at least {} characters",
+            MIN_PASSWORD_LENGTH
+        ));
+    }
+    Ok(())
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
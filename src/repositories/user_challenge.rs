@@ -1,34 +1,53 @@
 use axum::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
+use sqlx::{FromRow, PgConnection};
 
+use super::{
+    id::{ChallengeId, UserId},
+    ReadWritePgPool,
+};
+
+/// `GET /challenges/:id/photos` で返す件数
+const PHOTO_LIST_LIMIT: i64 = 100;
+
+#[cfg_attr(test, mockall::automock)]
 #[async_trait]
-pub trait UserChallengeRepository: Clone + Send + Sync + 'static {
+pub trait UserChallengeRepository: Send + Sync + 'static {
     async fn save_challenge_complete_event(
         &self,
-        user_id: String,
-        challenge_id: String,
+        user_id: UserId,
+        challenge_id: ChallengeId,
     ) -> anyhow::Result<()>;
     async fn get_completed_challenges_by_user_id(
         &self,
-        user_id: String,
-    ) -> anyhow::Result<Vec<String>>;
+        user_id: UserId,
+    ) -> anyhow::Result<Vec<ChallengeId>>;
+    /// 完了時に投稿された写真。新しいものから返す
+    async fn find_photos_by_challenge_id(
+        &self,
+        challenge_id: ChallengeId,
+    ) -> anyhow::Result<Vec<ChallengePhoto>>;
+    /// 獲得したスタンプの種類数。同じスタンプを使うチャレンジは1つと数える
+    async fn count_stamps(&self, user_id: UserId) -> anyhow::Result<i64>;
+    /// チャレンジを完了した日付(日本時間)。新しい日から重複なしで返す
+    async fn find_completed_dates(&self, user_id: UserId) -> anyhow::Result<Vec<NaiveDate>>;
 }
 
 #[derive(Debug, Clone)]
 pub struct UserChallengeRepositoryForDb {
-    pool: PgPool,
+    pool: ReadWritePgPool,
 }
 
 impl UserChallengeRepositoryForDb {
-    pub fn new(pool: PgPool) -> Self {
-        UserChallengeRepositoryForDb { pool }
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        UserChallengeRepositoryForDb { pool: pool.into() }
     }
 
     #[cfg(test)]
     /// テスト用の簡易版コンストラクタ
     pub async fn with_url(url: &str) -> Self {
-        let pool = PgPool::connect(url).await.unwrap();
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
         UserChallengeRepositoryForDb::new(pool)
     }
 
@@ -36,15 +55,15 @@ impl UserChallengeRepositoryForDb {
     /// テスト用の確認メソッド
     pub async fn query_user_completed_challenges(
         &self,
-        user_id: String,
-    ) -> anyhow::Result<Vec<String>> {
+        user_id: UserId,
+    ) -> anyhow::Result<Vec<ChallengeId>> {
         let challenges = sqlx::query_as::<_, CompleteChallenge>(
             r#"
                 select * from user_completed_challenges where user_id = $1;
             "#,
         )
         .bind(user_id)
-        .fetch_all(&self.pool)
+        .fetch_all(self.pool.write())
         .await?;
 
         Ok(challenges.into_iter().map(|c| c.challenge_id).collect())
@@ -53,36 +72,29 @@ impl UserChallengeRepositoryForDb {
 
 #[async_trait]
 impl UserChallengeRepository for UserChallengeRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
     async fn save_challenge_complete_event(
         &self,
-        user_id: String,
-        challenge_id: String,
+        user_id: UserId,
+        challenge_id: ChallengeId,
     ) -> anyhow::Result<()> {
-        sqlx::query_as::<_, CompleteChallenge>(
-            r#"
-                insert into user_completed_challenges (user_id, challenge_id) values ($1, $2)
-                returning *
-            "#,
-        )
-        .bind(user_id)
-        .bind(challenge_id)
-        .fetch_one(&self.pool)
-        .await?;
-
-        anyhow::Ok(())
+        let mut conn = self.pool.write().acquire().await?;
+        insert_challenge_complete_event(&mut conn, user_id, challenge_id, None, Utc::now()).await
     }
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
     async fn get_completed_challenges_by_user_id(
         &self,
-        user_id: String,
-    ) -> anyhow::Result<Vec<String>> {
+        user_id: UserId,
+    ) -> anyhow::Result<Vec<ChallengeId>> {
+        // 完了した直後に次のチャレンジのロックやクエストの制覇を判定するのでプライマリから読む
         let challenges = sqlx::query_as::<_, UserChallengeFromRow>(
             r#"
                 select * from user_completed_challenges where user_id=$1;
             "#,
         )
         .bind(user_id)
-        .fetch_all(&self.pool)
+        .fetch_all(self.pool.write())
         .await
         .map_err(|_| Vec::<UserChallengeFromRow>::new())
         .unwrap();
@@ -91,22 +103,107 @@ impl UserChallengeRepository for UserChallengeRepositoryForDb {
 
         anyhow::Ok(quest_ids)
     }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_photos_by_challenge_id(
+        &self,
+        challenge_id: ChallengeId,
+    ) -> anyhow::Result<Vec<ChallengePhoto>> {
+        let photos = sqlx::query_as::<_, ChallengePhoto>(
+            r#"
+                select ucc.user_id, u.username, ucc.photo_url, ucc.completed_at
+                from user_completed_challenges ucc
+                join users u on u.id = ucc.user_id and u.deleted_at is null
+                where ucc.challenge_id = $1 and ucc.photo_url is not null
+                order by ucc.completed_at desc
+                limit $2
+            "#,
+        )
+        .bind(challenge_id)
+        .bind(PHOTO_LIST_LIMIT)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(photos)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn count_stamps(&self, user_id: UserId) -> anyhow::Result<i64> {
+        let count = sqlx::query_scalar::<_, i64>(
+            r#"
+                select count(distinct c.stamp_name) from user_completed_challenges ucc
+                join challenges c on c.id = ucc.challenge_id
+                where ucc.user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(self.pool.read())
+        .await?;
+
+        Ok(count)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_completed_dates(&self, user_id: UserId) -> anyhow::Result<Vec<NaiveDate>> {
+        // 日別の集計(stats)と同じく日本時間で区切る
+        let dates = sqlx::query_scalar::<_, NaiveDate>(
+            r#"
+                select distinct (completed_at at time zone 'Asia/Tokyo')::date as date
+                from user_completed_challenges
+                where user_id = $1
+                order by date desc
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        Ok(dates)
+    }
+}
+
+/// UnitOfWorkのトランザクションからも呼べるようにコネクションを受け取る
+pub(super) async fn insert_challenge_complete_event(
+    conn: &mut PgConnection,
+    user_id: UserId,
+    challenge_id: ChallengeId,
+    photo_url: Option<String>,
+    completed_at: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    sqlx::query_as::<_, CompleteChallenge>(
+        r#"
+            insert into user_completed_challenges (user_id, challenge_id, photo_url, completed_at)
+            values ($1, $2, $3, $4)
+            returning *
+        "#,
+    )
+    .bind(user_id)
+    .bind(challenge_id)
+    .bind(photo_url)
+    .bind(completed_at)
+    .fetch_one(conn)
+    .await?;
+
+    anyhow::Ok(())
 }
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, FromRow)]
 struct UserChallengeFromRow {
-    user_id: String,
-    challenge_id: String,
+    user_id: UserId,
+    challenge_id: ChallengeId,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, FromRow, PartialEq)]
 pub struct CompleteChallenge {
-    pub user_id: String,
-    pub challenge_id: String,
+    pub user_id: UserId,
+    pub challenge_id: ChallengeId,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct CompleteChallengePayload {
-    pub user_id: String,
+#[derive(Debug, Clone, Deserialize, Serialize, FromRow, PartialEq)]
+pub struct ChallengePhoto {
+    pub user_id: UserId,
+    pub username: String,
+    pub photo_url: String,
+    pub completed_at: DateTime<Utc>,
 }
@@ -1,49 +1,87 @@
 use axum::async_trait;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
+use sqlx::FromRow;
+use std::collections::HashMap;
 
+use super::{
+    id::{QuestId, UserId},
+    ReadWritePgPool,
+};
+
+#[cfg_attr(test, mockall::automock)]
 #[async_trait]
-pub trait UserQuestRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+pub trait UserQuestRepository: std::marker::Send + std::marker::Sync + 'static {
     async fn save_quest_participate_event(
         &self,
-        user_id: String,
-        quest_id: String,
+        user_id: UserId,
+        quest_id: QuestId,
     ) -> anyhow::Result<()>;
     async fn get_participated_quests_by_user_id(
         &self,
-        user_id: String,
-    ) -> anyhow::Result<Vec<String>>;
+        user_id: UserId,
+    ) -> anyhow::Result<Vec<QuestId>>;
+    /// 参加していなければNotParticipating
+    async fn delete_quest_participate_event(
+        &self,
+        user_id: UserId,
+        quest_id: QuestId,
+    ) -> Result<(), LeaveQuestError>;
+    async fn count_participants(&self, quest_id: QuestId) -> anyhow::Result<i64>;
+    /// 参加者のいないクエストは結果に含まれない
+    async fn count_participants_by_quest_ids(
+        &self,
+        quest_ids: Vec<QuestId>,
+    ) -> anyhow::Result<HashMap<QuestId, i64>>;
+    /// 管理画面向け。参加した順に返す。退会済みのユーザーは含まない
+    async fn find_participants(
+        &self,
+        quest_id: QuestId,
+        page: FindParticipants,
+    ) -> anyhow::Result<ParticipantsPage>;
+}
+
+#[derive(Debug)]
+pub enum LeaveQuestError {
+    NotParticipating,
+    Repository(anyhow::Error),
+}
+
+impl From<sqlx::Error> for LeaveQuestError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Repository(e.into())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct UserQuestRepositoryForDb {
-    pool: PgPool,
+    pool: ReadWritePgPool,
 }
 
 impl UserQuestRepositoryForDb {
-    pub fn new(pool: PgPool) -> Self {
-        UserQuestRepositoryForDb { pool }
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        UserQuestRepositoryForDb { pool: pool.into() }
     }
 
     #[cfg(test)]
     /// テスト用の簡易版コンストラクタ
     pub async fn with_url(url: &str) -> Self {
-        let pool = PgPool::connect(url).await.unwrap();
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
         UserQuestRepositoryForDb::new(pool)
     }
 
     #[cfg(test)]
     pub async fn query_user_participating_quests(
         &self,
-        user_id: String,
-    ) -> anyhow::Result<Vec<String>> {
+        user_id: UserId,
+    ) -> anyhow::Result<Vec<QuestId>> {
         let quests = sqlx::query_as::<_, ParticipateQuest>(
             r#"
                 select * from user_participating_quests where user_id = $1;
             "#,
         )
         .bind(user_id)
-        .fetch_all(&self.pool)
+        .fetch_all(self.pool.write())
         .await?;
 
         Ok(quests.into_iter().map(|c| c.quest_id).collect())
@@ -52,10 +90,11 @@ impl UserQuestRepositoryForDb {
 
 #[async_trait]
 impl UserQuestRepository for UserQuestRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
     async fn save_quest_participate_event(
         &self,
-        user_id: String,
-        quest_id: String,
+        user_id: UserId,
+        quest_id: QuestId,
     ) -> anyhow::Result<()> {
         sqlx::query_as::<_, ParticipateQuest>(
             r#"
@@ -65,23 +104,25 @@ impl UserQuestRepository for UserQuestRepositoryForDb {
         )
         .bind(user_id)
         .bind(quest_id)
-        .fetch_one(&self.pool)
+        .fetch_one(self.pool.write())
         .await?;
 
         anyhow::Ok(())
     }
 
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
     async fn get_participated_quests_by_user_id(
         &self,
-        user_id: String,
-    ) -> anyhow::Result<Vec<String>> {
+        user_id: UserId,
+    ) -> anyhow::Result<Vec<QuestId>> {
+        // 参加済みかどうかの判定に使うのでプライマリから読む
         let quests = sqlx::query_as::<_, UserQuestFromRow>(
             r#"
                 select * from user_participating_quests where user_id=$1;
             "#,
         )
         .bind(user_id)
-        .fetch_all(&self.pool)
+        .fetch_all(self.pool.write())
         .await
         .map_err(|_| Vec::<UserQuestFromRow>::new())
         .unwrap();
@@ -90,22 +131,215 @@ impl UserQuestRepository for UserQuestRepositoryForDb {
 
         anyhow::Ok(quest_ids)
     }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn delete_quest_participate_event(
+        &self,
+        user_id: UserId,
+        quest_id: QuestId,
+    ) -> Result<(), LeaveQuestError> {
+        let result = sqlx::query(
+            r#"
+                delete from user_participating_quests where user_id=$1 and quest_id=$2
+            "#,
+        )
+        .bind(user_id)
+        .bind(quest_id)
+        .execute(self.pool.write())
+        .await?;
+
+        // 参加していなかった場合は削除対象がない
+        if result.rows_affected() == 0 {
+            return Err(LeaveQuestError::NotParticipating);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn count_participants(&self, quest_id: QuestId) -> anyhow::Result<i64> {
+        let count = sqlx::query_scalar::<_, i64>(
+            r#"
+                select count(*) from user_participating_quests where quest_id=$1;
+            "#,
+        )
+        .bind(quest_id)
+        .fetch_one(self.pool.read())
+        .await?;
+
+        anyhow::Ok(count)
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn count_participants_by_quest_ids(
+        &self,
+        quest_ids: Vec<QuestId>,
+    ) -> anyhow::Result<HashMap<QuestId, i64>> {
+        let counts = sqlx::query_as::<_, (QuestId, i64)>(
+            r#"
+                select quest_id, count(*) from user_participating_quests
+                where quest_id = any($1)
+                group by quest_id;
+            "#,
+        )
+        .bind(quest_ids)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        anyhow::Ok(counts.into_iter().collect())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_participants(
+        &self,
+        quest_id: QuestId,
+        page: FindParticipants,
+    ) -> anyhow::Result<ParticipantsPage> {
+        let total = sqlx::query_scalar::<_, i64>(
+            r#"
+                select count(*) from user_participating_quests p
+                join users u on u.id = p.user_id and u.deleted_at is null
+                where p.quest_id = $1;
+            "#,
+        )
+        .bind(quest_id.clone())
+        .fetch_one(self.pool.read())
+        .await?;
+
+        let rows = sqlx::query_as::<_, ParticipantFromRow>(
+            r#"
+                select
+                    u.id as user_id,
+                    u.username,
+                    p.joined_at,
+                    (
+                        select count(*) from user_completed_challenges uc
+                        join challenges c on c.id = uc.challenge_id
+                        where c.quest_id = p.quest_id and uc.user_id = p.user_id
+                    ) as completed_challenges,
+                    (
+                        select count(*) from challenges c where c.quest_id = p.quest_id
+                    ) as total_challenges
+                from user_participating_quests p
+                join users u on u.id = p.user_id and u.deleted_at is null
+                where p.quest_id = $1
+                order by p.joined_at, u.id
+                limit $2 offset $3;
+            "#,
+        )
+        .bind(quest_id)
+        .bind(page.limit())
+        .bind(page.offset())
+        .fetch_all(self.pool.read())
+        .await?;
+
+        anyhow::Ok(ParticipantsPage {
+            total,
+            participants: rows.into_iter().map(QuestParticipant::from).collect(),
+        })
+    }
 }
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, FromRow)]
 struct UserQuestFromRow {
-    user_id: String,
-    quest_id: String,
+    user_id: UserId,
+    quest_id: QuestId,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, FromRow, PartialEq)]
 pub struct ParticipateQuest {
-    pub user_id: String,
-    pub quest_id: String,
+    pub user_id: UserId,
+    pub quest_id: QuestId,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ParticipantsCount {
+    pub count: i64,
+}
+
+const DEFAULT_PARTICIPANTS_LIMIT: i64 = 50;
+const MAX_PARTICIPANTS_LIMIT: i64 = 100;
+
+/// `GET /quests/:id/participants?limit=&offset=`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FindParticipants {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct ParticipateQuestPayload {
-    pub user_id: String,
+impl FindParticipants {
+    /// 上限を超える値は上限に丸める
+    pub fn limit(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_PARTICIPANTS_LIMIT)
+            .clamp(1, MAX_PARTICIPANTS_LIMIT)
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset.unwrap_or(0).max(0)
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct ParticipantFromRow {
+    user_id: UserId,
+    username: String,
+    joined_at: DateTime<Utc>,
+    completed_challenges: i64,
+    total_challenges: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct QuestParticipant {
+    pub user_id: UserId,
+    pub username: String,
+    pub joined_at: DateTime<Utc>,
+    pub completed_challenges: i64,
+    /// 0.0〜1.0。チャレンジのないクエストは0
+    pub progress: f64,
+}
+
+impl From<ParticipantFromRow> for QuestParticipant {
+    fn from(row: ParticipantFromRow) -> Self {
+        let progress = if row.total_challenges == 0 {
+            0.0
+        } else {
+            row.completed_challenges as f64 / row.total_challenges as f64
+        };
+        Self {
+            user_id: row.user_id,
+            username: row.username,
+            joined_at: row.joined_at,
+            completed_challenges: row.completed_challenges,
+            progress,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ParticipantsPage {
+    /// ページに関係なく、参加者全体の人数
+    pub total: i64,
+    pub participants: Vec<QuestParticipant>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_clamp_participants_page() {
+        let page = FindParticipants::default();
+        assert_eq!(
+            (DEFAULT_PARTICIPANTS_LIMIT, 0),
+            (page.limit(), page.offset())
+        );
+
+        let page = FindParticipants {
+            limit: Some(1000),
+            offset: Some(-1),
+        };
+        assert_eq!((MAX_PARTICIPANTS_LIMIT, 0), (page.limit(), page.offset()));
+    }
 }
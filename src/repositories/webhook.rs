@@ -0,0 +1,236 @@
+use axum::async_trait;
+use nanoid::nanoid;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::{fmt, sync::Arc};
+
+use super::{
+    id::{default_id_generator, IdGenerator, UserId},
+    ReadWritePgPool,
+};
+
+#[async_trait]
+pub trait WebhookRepository: Send + Sync + 'static {
+    async fn create(&self, user_id: UserId, payload: CreateWebhook) -> anyhow::Result<Webhook>;
+    async fn find_by_user_id(&self, user_id: UserId) -> anyhow::Result<Vec<Webhook>>;
+    async fn delete(&self, user_id: UserId, id: String) -> anyhow::Result<()>;
+    /// eventを購読しているwebhook。ownerを指定した場合はそのユーザーのものだけ
+    async fn find_by_event(
+        &self,
+        event: WebhookEvent,
+        owner: Option<UserId>,
+    ) -> anyhow::Result<Vec<Webhook>>;
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookRepositoryForDb {
+    pool: ReadWritePgPool,
+    ids: Arc<dyn IdGenerator>,
+}
+
+impl WebhookRepositoryForDb {
+    pub fn new(pool: impl Into<ReadWritePgPool>) -> Self {
+        WebhookRepositoryForDb {
+            pool: pool.into(),
+            ids: default_id_generator(),
+        }
+    }
+
+    pub fn with_id_generator(self, ids: Arc<dyn IdGenerator>) -> Self {
+        Self { ids, ..self }
+    }
+
+    #[cfg(test)]
+    /// テスト用の簡易版コンストラクタ
+    pub async fn with_url(url: &str) -> Self {
+        let pool = sqlx::PgPool::connect(url).await.unwrap();
+        WebhookRepositoryForDb::new(pool)
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for WebhookRepositoryForDb {
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn create(&self, user_id: UserId, payload: CreateWebhook) -> anyhow::Result<Webhook> {
+        let events = payload
+            .events
+            .iter()
+            .map(|event| event.to_string())
+            .collect::<Vec<_>>();
+        let row = sqlx::query_as::<_, WebhookFromRow>(
+            r#"
+                insert into webhooks (id, user_id, url, secret, events)
+                values ($1, $2, $3, $4, $5)
+                returning id, url, secret, events
+            "#,
+        )
+        .bind(self.ids.generate())
+        .bind(user_id)
+        .bind(payload.url)
+        .bind(nanoid!(32))
+        .bind(events)
+        .fetch_one(self.pool.write())
+        .await?;
+
+        row.try_into()
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_by_user_id(&self, user_id: UserId) -> anyhow::Result<Vec<Webhook>> {
+        let rows = sqlx::query_as::<_, WebhookFromRow>(
+            r#"
+                select id, url, secret, events from webhooks
+                where user_id = $1 order by created_at;
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        rows.into_iter().map(Webhook::try_from).collect()
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn delete(&self, user_id: UserId, id: String) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+                delete from webhooks where id = $1 and user_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(self.pool.write())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("webhook not found"));
+        }
+
+        anyhow::Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn find_by_event(
+        &self,
+        event: WebhookEvent,
+        owner: Option<UserId>,
+    ) -> anyhow::Result<Vec<Webhook>> {
+        let rows = sqlx::query_as::<_, WebhookFromRow>(
+            r#"
+                select id, url, secret, events from webhooks
+                where $1 = any(events) and ($2::text is null or user_id = $2);
+            "#,
+        )
+        .bind(event.to_string())
+        .bind(owner)
+        .fetch_all(self.pool.read())
+        .await?;
+
+        rows.into_iter().map(Webhook::try_from).collect()
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct WebhookFromRow {
+    id: String,
+    url: String,
+    secret: String,
+    events: Vec<String>,
+}
+
+impl TryFrom<WebhookFromRow> for Webhook {
+    type Error = anyhow::Error;
+
+    fn try_from(row: WebhookFromRow) -> Result<Self, Self::Error> {
+        let events = row
+            .events
+            .iter()
+            .map(|event| event.parse())
+            .collect::<anyhow::Result<Vec<WebhookEvent>>>()?;
+
+        Ok(Webhook {
+            id: row.id,
+            url: row.url,
+            secret: row.secret,
+            events,
+        })
+    }
+}
+
+/// webhookで通知するドメインイベント
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "quest.created")]
+    QuestCreated,
+    #[serde(rename = "challenge.completed")]
+    ChallengeCompleted,
+}
+
+impl std::str::FromStr for WebhookEvent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "quest.created" => Ok(Self::QuestCreated),
+            "challenge.completed" => Ok(Self::ChallengeCompleted),
+            _ => Err(anyhow::anyhow!("Invalid webhook event : {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebhookEvent::QuestCreated => write!(f, "quest.created"),
+            WebhookEvent::ChallengeCompleted => write!(f, "challenge.completed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    /// 署名の検証に使う。作成時のレスポンスでだけ返す
+    #[serde(skip_serializing, default)]
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+}
+
+/// `POST /webhooks` のレスポンス
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct CreatedWebhook {
+    pub id: String,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+}
+
+impl From<Webhook> for CreatedWebhook {
+    fn from(webhook: Webhook) -> Self {
+        Self {
+            id: webhook.id,
+            url: webhook.url,
+            secret: webhook.secret,
+            events: webhook.events,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CreateWebhook {
+    url: String,
+    events: Vec<WebhookEvent>,
+}
+
+impl CreateWebhook {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.url.starts_with("https://") || self.url.starts_with("http://")) {
+            return Err(format!("invalid url: {}", self.url));
+        }
+        if self.events.is_empty() {
+            return Err("events must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
@@ -1 +1,34 @@
+pub mod achievement;
+pub mod audit;
+pub mod captcha;
+pub mod certificate;
+pub mod challenge;
+pub mod cover_image;
+pub mod csv_transfer;
+pub mod event_bus;
+pub mod export;
+pub mod feature_flag;
+pub mod follow;
+pub mod i18n;
+pub mod idempotency;
+pub mod image_url;
+pub mod job;
+pub mod leaderboard;
+pub mod mail;
+pub mod notification;
+pub mod offline_pack;
+pub mod password;
+pub mod photo;
+pub mod preference;
+pub mod purge;
+pub mod quest;
+pub mod quest_template;
+pub mod realtime;
+pub mod review;
+pub mod scheduler;
+pub mod session;
+pub mod session_cookie;
+pub mod stats;
+pub mod summary;
 pub mod user;
+pub mod webhook;
@@ -0,0 +1,97 @@
+use crate::repositories::{
+    achievement::{Achievement, AchievementKind, AchievementProgress, AchievementRepository},
+    id::UserId,
+    unit_of_work::TransactionScope,
+};
+
+/// 進捗が条件を満たしていて、まだ付与されていない実績を返す
+pub fn newly_achieved(
+    achievements: Vec<Achievement>,
+    progress: &AchievementProgress,
+    achieved_ids: &[String],
+) -> Vec<Achievement> {
+    achievements
+        .into_iter()
+        .filter(|achievement| !achieved_ids.contains(&achievement.id))
+        .filter(|achievement| {
+            let count = match achievement.kind {
+                AchievementKind::ChallengesCompleted => progress.completed_challenges,
+                AchievementKind::QuestsCleared => progress.cleared_quests,
+            };
+            count >= achievement.threshold as i64
+        })
+        .collect()
+}
+
+/// 完了イベントのたびに呼び出し、条件を満たした実績を付与する
+/// 進捗は同じトランザクション内で記録した完了イベントを含めて判定する
+pub async fn evaluate_achievements(
+    repository: &dyn AchievementRepository,
+    scope: &mut dyn TransactionScope,
+    user_id: UserId,
+) -> anyhow::Result<Vec<Achievement>> {
+    let achievements = repository.all().await?;
+    let progress = scope.get_achievement_progress(user_id.clone()).await?;
+    let achieved_ids = scope.get_achieved_ids(user_id.clone()).await?;
+
+    let granted = newly_achieved(achievements, &progress, &achieved_ids);
+    for achievement in granted.iter() {
+        scope
+            .grant_achievement(user_id.clone(), achievement.id.clone())
+            .await?;
+    }
+
+    Ok(granted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn achievement(id: &str, kind: AchievementKind, threshold: i32) -> Achievement {
+        Achievement {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: id.to_string(),
+            kind,
+            threshold,
+        }
+    }
+
+    fn ids(achievements: Vec<Achievement>) -> Vec<String> {
+        achievements.into_iter().map(|a| a.id).collect()
+    }
+
+    #[test]
+    fn should_grant_achievements_reaching_threshold() {
+        let achievements = vec![
+            achievement("first_challenge", AchievementKind::ChallengesCompleted, 1),
+            achievement("ten_challenges", AchievementKind::ChallengesCompleted, 10),
+            achievement("first_quest", AchievementKind::QuestsCleared, 1),
+        ];
+        let progress = AchievementProgress {
+            completed_challenges: 10,
+            cleared_quests: 0,
+        };
+
+        assert_eq!(
+            vec!["first_challenge".to_string(), "ten_challenges".to_string()],
+            ids(newly_achieved(achievements, &progress, &[]))
+        );
+    }
+
+    #[test]
+    fn should_not_grant_achieved_twice() {
+        let achievements = vec![achievement(
+            "first_quest",
+            AchievementKind::QuestsCleared,
+            1,
+        )];
+        let progress = AchievementProgress {
+            completed_challenges: 3,
+            cleared_quests: 1,
+        };
+
+        assert!(newly_achieved(achievements, &progress, &["first_quest".to_string()]).is_empty());
+    }
+}
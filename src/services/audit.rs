@@ -0,0 +1,25 @@
+use crate::repositories::{
+    audit::{AuditAction, AuditRepository},
+    id::UserId,
+};
+
+/// 操作自体は完了しているので、記録に失敗してもエラーにせずログに残す
+pub async fn record_audit(
+    repository: &dyn AuditRepository,
+    actor_id: Option<UserId>,
+    action: AuditAction,
+    target_id: String,
+) {
+    if let Err(e) = repository
+        .record(actor_id.clone(), action, target_id.clone())
+        .await
+    {
+        tracing::error!(
+            "failed to record audit log {} on {} by {:?}: {}",
+            action,
+            target_id,
+            actor_id,
+            e
+        );
+    }
+}
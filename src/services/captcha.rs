@@ -0,0 +1,51 @@
+use axum::async_trait;
+use serde::Deserialize;
+
+use crate::config::{CaptchaConfig, CaptchaProvider};
+
+#[async_trait]
+pub trait CaptchaVerifier: Send + Sync + 'static {
+    /// フロントのウィジェットが発行したトークンが有効ならtrue
+    async fn verify(&self, token: &str) -> anyhow::Result<bool>;
+}
+
+/// Turnstile/hCaptchaのsiteverify APIで検証する。どちらも同じ形式で問い合わせられる
+pub struct SiteverifyCaptcha {
+    client: reqwest::Client,
+    config: CaptchaConfig,
+}
+
+impl SiteverifyCaptcha {
+    pub fn new(client: reqwest::Client, config: CaptchaConfig) -> Self {
+        Self { client, config }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SiteverifyResponse {
+    success: bool,
+}
+
+fn siteverify_url(provider: CaptchaProvider) -> &'static str {
+    match provider {
+        CaptchaProvider::Turnstile => "https://challenges.cloudflare.com/turnstile/v0/siteverify",
+        CaptchaProvider::HCaptcha => "https://api.hcaptcha.com/siteverify",
+    }
+}
+
+#[async_trait]
+impl CaptchaVerifier for SiteverifyCaptcha {
+    async fn verify(&self, token: &str) -> anyhow::Result<bool> {
+        let res: SiteverifyResponse = self
+            .client
+            .post(siteverify_url(self.config.provider))
+            .form(&[("secret", self.config.secret.as_str()), ("response", token)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(res.success)
+    }
+}
@@ -0,0 +1,175 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::{
+    repositories::{
+        certificate::{Certificate, CertificateRepository},
+        challenge::ChallengeRepository,
+        id::{QuestId, UserId},
+        user_challenge::UserChallengeRepository,
+    },
+    services::{challenge::is_cleared, webhook::sign},
+};
+
+pub enum IssueCertificateError {
+    /// クエストのチャレンジをすべて完了していない
+    NotCompleted,
+    Repository(anyhow::Error),
+}
+
+/// クエストを制覇していれば修了証を発行する。発行済みなら同じものを返す
+pub async fn issue_certificate(
+    challenge_repository: &dyn ChallengeRepository,
+    userchallenge_repository: &dyn UserChallengeRepository,
+    certificate_repository: &dyn CertificateRepository,
+    user_id: UserId,
+    quest_id: QuestId,
+) -> Result<Certificate, IssueCertificateError> {
+    let challenges = challenge_repository
+        .find_by_quest_id(quest_id.clone())
+        .await
+        .map_err(IssueCertificateError::Repository)?;
+    let completed_ids = userchallenge_repository
+        .get_completed_challenges_by_user_id(user_id.clone())
+        .await
+        .map_err(IssueCertificateError::Repository)?;
+    if !is_cleared(&challenges, &completed_ids) {
+        return Err(IssueCertificateError::NotCompleted);
+    }
+
+    certificate_repository
+        .find_or_issue(user_id, quest_id)
+        .await
+        .map_err(IssueCertificateError::Repository)
+}
+
+/// 証明書に埋め込む署名。証明書の内容を書き換えると一致しなくなる
+pub fn sign_certificate(secret: &str, certificate: &Certificate) -> String {
+    sign(secret, signing_input(certificate).as_bytes())
+}
+
+pub fn verify_signature(secret: &str, certificate: &Certificate, signature: &str) -> bool {
+    let signature = match hex::decode(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(signing_input(certificate).as_bytes());
+    // 比較にかかる時間から署名を推測されないようにverify_sliceを使う
+    mac.verify_slice(&signature).is_ok()
+}
+
+fn signing_input(certificate: &Certificate) -> String {
+    format!(
+        "{}:{}:{}:{}",
+        certificate.id,
+        certificate.user_id,
+        certificate.quest_id,
+        certificate.completed_at.timestamp()
+    )
+}
+
+/// `GET /certificates/:id/verify` のレスポンス。署名が一致しないときは中身を返さない
+#[derive(Debug, Serialize)]
+pub struct CertificateVerification {
+    pub valid: bool,
+    #[serde(flatten)]
+    pub details: Option<CertificateDetails>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CertificateDetails {
+    pub username: String,
+    pub quest_title: String,
+    pub completed_at: DateTime<Utc>,
+}
+
+/// 修了証の画像(SVG)
+pub fn render_certificate(
+    certificate: &Certificate,
+    details: &CertificateDetails,
+    signature: &str,
+) -> String {
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="800" height="560" viewBox="0 0 800 560">
+  <rect x="16" y="16" width="768" height="528" fill="#fffdf5" stroke="#b08d3c" stroke-width="8"/>
+  <text x="400" y="120" font-size="44" text-anchor="middle" font-family="serif">修了証</text>
+  <text x="400" y="220" font-size="32" text-anchor="middle" font-family="serif">{username}</text>
+  <text x="400" y="290" font-size="22" text-anchor="middle" font-family="serif">「{quest_title}」を制覇したことを証明します</text>
+  <text x="400" y="350" font-size="18" text-anchor="middle" font-family="serif">{completed_on}</text>
+  <text x="400" y="470" font-size="11" text-anchor="middle" font-family="monospace">/v1/certificates/{id}/verify?signature={signature}</text>
+</svg>
+"##,
+        username = escape_xml(&details.username),
+        quest_title = escape_xml(&details.quest_title),
+        completed_on = details.completed_at.format("%Y年%m月%d日"),
+        id = escape_xml(&certificate.id),
+        signature = signature,
+    )
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn build_certificate() -> Certificate {
+        Certificate {
+            id: "certificate".to_string(),
+            user_id: "user".into(),
+            quest_id: "quest".into(),
+            completed_at: Utc.with_ymd_and_hms(2023, 10, 7, 9, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn should_verify_own_signature() {
+        let certificate = build_certificate();
+        let signature = sign_certificate("secret", &certificate);
+
+        assert!(verify_signature("secret", &certificate, &signature));
+        assert!(!verify_signature("other_secret", &certificate, &signature));
+        assert!(!verify_signature("secret", &certificate, "not-hex"));
+    }
+
+    #[test]
+    fn should_reject_tampered_certificate() {
+        let certificate = build_certificate();
+        let signature = sign_certificate("secret", &certificate);
+        let tampered = Certificate {
+            user_id: "other_user".into(),
+            ..certificate
+        };
+
+        assert!(!verify_signature("secret", &tampered, &signature));
+    }
+
+    #[test]
+    fn should_escape_user_input_in_svg() {
+        let certificate = build_certificate();
+        let details = CertificateDetails {
+            username: "<script>".to_string(),
+            quest_title: "Tom & Jerry".to_string(),
+            completed_at: certificate.completed_at,
+        };
+
+        let svg = render_certificate(&certificate, &details, "abcd");
+
+        assert!(svg.contains("&lt;script&gt;"));
+        assert!(svg.contains("Tom &amp; Jerry"));
+        assert!(svg.contains("2023年10月07日"));
+        assert!(svg.contains("/v1/certificates/certificate/verify?signature=abcd"));
+    }
+}
@@ -0,0 +1,627 @@
+use std::{collections::HashSet, sync::Arc};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    middleware::language::Language,
+    repositories::{
+        achievement::AchievementRepository,
+        challenge::{
+            validate_coordinates, BulkUpdateChallenges, BulkUpdateResult, Challenge,
+            ChallengeRepository, CreateChallenge,
+        },
+        id::{ChallengeId, QuestId, UserId},
+        quest::{QuestEntity, QuestRepository, QuestStatus},
+        translation::TranslationRepository,
+        unit_of_work::{TransactionScope, UnitOfWork},
+        user_challenge::{ChallengePhoto, UserChallengeRepository},
+    },
+    services::{
+        achievement::evaluate_achievements,
+        event_bus::{DomainEvent, EventBus},
+        i18n::translate_challenges,
+    },
+};
+
+/// チャレンジ完了時に付与するポイント
+pub const CHALLENGE_COMPLETION_POINTS: i32 = 10;
+/// オフライン同期で1回に送れる件数
+const MAX_SYNC_COMPLETIONS: usize = 100;
+/// オフラインで完了した位置がチャレンジの地点からこれ以上離れていれば記録しない
+const OFFLINE_COMPLETION_RADIUS_METERS: f64 = 300.0;
+/// 端末の時計のずれとして、この分だけ未来の完了時刻も受け付ける
+const CLOCK_SKEW_MINUTES: i64 = 5;
+/// これより前に完了したものは同期しない
+const MAX_OFFLINE_DAYS: i64 = 7;
+
+#[derive(Debug)]
+pub enum CompleteChallengeError {
+    /// 前のチャレンジが未完了で、まだ解放されていない
+    Locked,
+    /// クエストが下書きかアーカイブ済み
+    NotPublished,
+    /// クエストの開催期間外
+    OutOfSchedule,
+    /// チャレンジが非公開か受付期間外
+    Unavailable,
+    Repository(anyhow::Error),
+    /// トランザクションの開始・commitに失敗した
+    Transaction(anyhow::Error),
+}
+
+/// クエストの公開状態と開催期間、チャレンジの受付期間とシーケンシャルモードの前のチャレンジの完了を確認してから完了を記録し、
+/// ポイントと条件を満たした実績を付与する
+/// 書き込みはすべてscopeのトランザクションで行うので、commitは呼び出し側で行う
+pub async fn complete_challenge(
+    challenge_repository: &dyn ChallengeRepository,
+    quest_repository: &dyn QuestRepository,
+    userchallenge_repository: &dyn UserChallengeRepository,
+    achievement_repository: &dyn AchievementRepository,
+    scope: &mut dyn TransactionScope,
+    user_id: UserId,
+    challenge_id: ChallengeId,
+    photo_url: Option<String>,
+    completed_at: DateTime<Utc>,
+) -> Result<(), CompleteChallengeError> {
+    let challenge = challenge_repository
+        .find(challenge_id.clone())
+        .await
+        .map_err(CompleteChallengeError::Repository)?;
+    let quest = quest_repository
+        .find(challenge.quest_id.clone())
+        .await
+        .map_err(CompleteChallengeError::Repository)?;
+    if quest.status != QuestStatus::Published {
+        return Err(CompleteChallengeError::NotPublished);
+    }
+    if !quest.is_open_at(completed_at) {
+        return Err(CompleteChallengeError::OutOfSchedule);
+    }
+    if !challenge.is_available_at(completed_at) {
+        return Err(CompleteChallengeError::Unavailable);
+    }
+
+    let previous = challenge_repository
+        .find_previous(challenge_id.clone())
+        .await
+        .map_err(CompleteChallengeError::Repository)?;
+
+    if let Some(previous) = previous {
+        let completed_ids = userchallenge_repository
+            .get_completed_challenges_by_user_id(user_id.clone())
+            .await
+            .map_err(CompleteChallengeError::Repository)?;
+        if !completed_ids.contains(&previous.id) {
+            return Err(CompleteChallengeError::Locked);
+        }
+    }
+
+    scope
+        .save_challenge_complete_event(
+            user_id.clone(),
+            challenge_id.clone(),
+            photo_url,
+            completed_at,
+        )
+        .await
+        .map_err(CompleteChallengeError::Repository)?;
+
+    scope
+        .award_for_challenge(user_id.clone(), challenge_id, CHALLENGE_COMPLETION_POINTS)
+        .await
+        .map_err(CompleteChallengeError::Repository)?;
+
+    evaluate_achievements(achievement_repository, scope, user_id)
+        .await
+        .map(|_| ())
+        .map_err(CompleteChallengeError::Repository)
+}
+
+/// 完了記録・ポイント・実績を1つのトランザクションで反映し、commit後にイベントを発行する
+/// RESTとGraphQLのどちらから完了しても同じ流れになるようにここにまとめる
+/// photo_urlは現地で撮った写真。保存済みのURLを完了記録と一緒に残す
+/// 開催期間・受付期間はcompleted_atの時点で判定する
+pub async fn complete_challenge_and_publish(
+    challenge_repository: &dyn ChallengeRepository,
+    quest_repository: &dyn QuestRepository,
+    userchallenge_repository: &dyn UserChallengeRepository,
+    achievement_repository: &dyn AchievementRepository,
+    unit_of_work: &dyn UnitOfWork,
+    event_bus: &EventBus,
+    user_id: UserId,
+    challenge_id: ChallengeId,
+    photo_url: Option<String>,
+    completed_at: DateTime<Utc>,
+) -> Result<(), CompleteChallengeError> {
+    let mut scope = unit_of_work
+        .begin()
+        .await
+        .map_err(CompleteChallengeError::Transaction)?;
+
+    complete_challenge(
+        challenge_repository,
+        quest_repository,
+        userchallenge_repository,
+        achievement_repository,
+        scope.as_mut(),
+        user_id.clone(),
+        challenge_id.clone(),
+        photo_url,
+        completed_at,
+    )
+    .await?;
+
+    scope
+        .commit()
+        .await
+        .map_err(CompleteChallengeError::Transaction)?;
+
+    event_bus.publish(DomainEvent::ChallengeCompleted {
+        user_id: user_id.clone(),
+        challenge_id: challenge_id.clone(),
+    });
+
+    // 制覇の判定に失敗しても完了自体は成功しているので、通知を諦めるだけにする
+    match find_cleared_quest(
+        challenge_repository,
+        userchallenge_repository,
+        user_id.clone(),
+        challenge_id,
+    )
+    .await
+    {
+        Ok(Some(quest_id)) => event_bus.publish(DomainEvent::QuestCompleted { user_id, quest_id }),
+        Ok(None) => {}
+        Err(e) => tracing::warn!("failed to check quest completion: {}", e),
+    }
+
+    Ok(())
+}
+
+/// 電波の届かない場所で完了し、端末に貯めておいたもの
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OfflineCompletion {
+    pub challenge_id: ChallengeId,
+    /// 端末で記録した完了時刻
+    pub completed_at: DateTime<Utc>,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SyncCompletedChallenges {
+    pub completions: Vec<OfflineCompletion>,
+}
+
+impl SyncCompletedChallenges {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.completions.is_empty() {
+            return Err("completions must not be empty".to_string());
+        }
+        if self.completions.len() > MAX_SYNC_COMPLETIONS {
+            return Err(format!(
+                "completions must be at most {}",
+                MAX_SYNC_COMPLETIONS
+            ));
+        }
+        for completion in &self.completions {
+            validate_coordinates(completion.latitude, completion.longitude)?;
+        }
+        Ok(())
+    }
+}
+
+/// 1件ごとの同期結果。失敗したものがあっても残りは記録する
+#[derive(Debug)]
+pub enum SyncOutcome {
+    Completed,
+    /// 以前の同期などで完了済み。端末からは消してよい
+    AlreadyCompleted,
+    /// 同じリクエストに同じチャレンジが複数あった。完了時刻が最も早いものだけを扱う
+    Duplicate,
+    /// 完了時刻が未来か、古すぎる
+    InvalidTime,
+    /// 完了した位置がチャレンジの地点から離れすぎている
+    TooFar,
+    NotFound,
+    Failed(CompleteChallengeError),
+}
+
+/// オフラインで完了したチャレンジをまとめて記録する
+/// 順に解放されるクエストでも通るよう完了時刻の順に1件ずつcommitし、結果はリクエストと同じ順で返す
+pub async fn sync_completed_challenges(
+    challenge_repository: &dyn ChallengeRepository,
+    quest_repository: &dyn QuestRepository,
+    userchallenge_repository: &dyn UserChallengeRepository,
+    achievement_repository: &dyn AchievementRepository,
+    unit_of_work: &dyn UnitOfWork,
+    event_bus: &EventBus,
+    user_id: UserId,
+    completions: &[OfflineCompletion],
+) -> anyhow::Result<Vec<SyncOutcome>> {
+    let mut completed_ids = userchallenge_repository
+        .get_completed_challenges_by_user_id(user_id.clone())
+        .await?;
+    let now = Utc::now();
+    let mut order: Vec<usize> = (0..completions.len()).collect();
+    order.sort_by_key(|&i| completions[i].completed_at);
+
+    let mut seen = HashSet::new();
+    let mut outcomes = Vec::with_capacity(completions.len());
+    for i in order {
+        let completion = &completions[i];
+        let outcome = if !seen.insert(completion.challenge_id.as_str()) {
+            SyncOutcome::Duplicate
+        } else if completed_ids.contains(&completion.challenge_id) {
+            SyncOutcome::AlreadyCompleted
+        } else if completion.completed_at > now + Duration::minutes(CLOCK_SKEW_MINUTES)
+            || completion.completed_at < now - Duration::days(MAX_OFFLINE_DAYS)
+        {
+            SyncOutcome::InvalidTime
+        } else {
+            match challenge_repository
+                .find(completion.challenge_id.clone())
+                .await
+            {
+                Err(_) => SyncOutcome::NotFound,
+                Ok(challenge)
+                    if distance_meters(
+                        (challenge.latitude, challenge.longitude),
+                        (completion.latitude, completion.longitude),
+                    ) > OFFLINE_COMPLETION_RADIUS_METERS =>
+                {
+                    SyncOutcome::TooFar
+                }
+                Ok(_) => match complete_challenge_and_publish(
+                    challenge_repository,
+                    quest_repository,
+                    userchallenge_repository,
+                    achievement_repository,
+                    unit_of_work,
+                    event_bus,
+                    user_id.clone(),
+                    completion.challenge_id.clone(),
+                    None,
+                    completion.completed_at,
+                )
+                .await
+                {
+                    Ok(()) => {
+                        completed_ids.push(completion.challenge_id.clone());
+                        SyncOutcome::Completed
+                    }
+                    Err(e) => SyncOutcome::Failed(e),
+                },
+            }
+        };
+        outcomes.push((i, outcome));
+    }
+
+    outcomes.sort_by_key(|(i, _)| *i);
+    Ok(outcomes.into_iter().map(|(_, outcome)| outcome).collect())
+}
+
+#[derive(Debug)]
+pub enum ChallengeError {
+    NotFound,
+    Repository(anyhow::Error),
+}
+
+/// チャレンジの作成・取得と完了の記録をまとめる。ハンドラはリポジトリを直接呼ばずにこれを通す
+#[derive(Clone)]
+pub struct ChallengeService {
+    challenge_repository: Arc<dyn ChallengeRepository>,
+    quest_repository: Arc<dyn QuestRepository>,
+    userchallenge_repository: Arc<dyn UserChallengeRepository>,
+    achievement_repository: Arc<dyn AchievementRepository>,
+    translation_repository: Arc<dyn TranslationRepository>,
+    unit_of_work: Arc<dyn UnitOfWork>,
+    event_bus: EventBus,
+}
+
+impl ChallengeService {
+    pub fn new(
+        challenge_repository: Arc<dyn ChallengeRepository>,
+        quest_repository: Arc<dyn QuestRepository>,
+        userchallenge_repository: Arc<dyn UserChallengeRepository>,
+        achievement_repository: Arc<dyn AchievementRepository>,
+        translation_repository: Arc<dyn TranslationRepository>,
+        unit_of_work: Arc<dyn UnitOfWork>,
+        event_bus: EventBus,
+    ) -> Self {
+        Self {
+            challenge_repository,
+            quest_repository,
+            userchallenge_repository,
+            achievement_repository,
+            translation_repository,
+            unit_of_work,
+            event_bus,
+        }
+    }
+
+    pub async fn create(&self, payload: CreateChallenge) -> anyhow::Result<Challenge> {
+        let challenge = self.challenge_repository.create(payload).await?;
+        self.event_bus.publish(DomainEvent::ChallengeCreated {
+            challenge: challenge.clone(),
+        });
+
+        Ok(challenge)
+    }
+
+    pub async fn find(
+        &self,
+        id: ChallengeId,
+        language: &Language,
+    ) -> Result<Challenge, ChallengeError> {
+        let mut challenge = self
+            .challenge_repository
+            .find(id)
+            .await
+            .or(Err(ChallengeError::NotFound))?;
+        translate_challenges(
+            self.translation_repository.as_ref(),
+            language,
+            std::slice::from_mut(&mut challenge),
+        )
+        .await
+        .map_err(ChallengeError::Repository)?;
+
+        Ok(challenge)
+    }
+
+    pub async fn find_by_quest_id(
+        &self,
+        quest_id: QuestId,
+        language: &Language,
+    ) -> Result<Vec<Challenge>, ChallengeError> {
+        let mut challenges = self
+            .challenge_repository
+            .find_by_quest_id(quest_id)
+            .await
+            .or(Err(ChallengeError::NotFound))?;
+        translate_challenges(
+            self.translation_repository.as_ref(),
+            language,
+            &mut challenges,
+        )
+        .await
+        .map_err(ChallengeError::Repository)?;
+
+        Ok(challenges)
+    }
+
+    /// 適用できたときだけイベントを発行する
+    pub async fn bulk_update(
+        &self,
+        quest_id: QuestId,
+        payload: BulkUpdateChallenges,
+    ) -> anyhow::Result<BulkUpdateResult> {
+        let result = self
+            .challenge_repository
+            .bulk_update(quest_id.clone(), payload)
+            .await?;
+        if result.applied {
+            self.event_bus.publish(DomainEvent::ChallengesUpdated {
+                quest_id,
+                challenges: result.challenges.clone(),
+            });
+        }
+
+        Ok(result)
+    }
+
+    /// チャレンジに投稿された写真
+    pub async fn photos(&self, id: ChallengeId) -> Result<Vec<ChallengePhoto>, ChallengeError> {
+        self.challenge_repository
+            .find(id.clone())
+            .await
+            .or(Err(ChallengeError::NotFound))?;
+
+        self.userchallenge_repository
+            .find_photos_by_challenge_id(id)
+            .await
+            .map_err(ChallengeError::Repository)
+    }
+
+    /// 完了記録・ポイント・実績はすべて成功したときだけ反映する
+    pub async fn complete(
+        &self,
+        user_id: UserId,
+        challenge_id: ChallengeId,
+        photo_url: Option<String>,
+        completed_at: DateTime<Utc>,
+    ) -> Result<(), CompleteChallengeError> {
+        complete_challenge_and_publish(
+            self.challenge_repository.as_ref(),
+            self.quest_repository.as_ref(),
+            self.userchallenge_repository.as_ref(),
+            self.achievement_repository.as_ref(),
+            self.unit_of_work.as_ref(),
+            &self.event_bus,
+            user_id,
+            challenge_id,
+            photo_url,
+            completed_at,
+        )
+        .await
+    }
+
+    pub async fn sync(
+        &self,
+        user_id: UserId,
+        completions: &[OfflineCompletion],
+    ) -> anyhow::Result<Vec<SyncOutcome>> {
+        sync_completed_challenges(
+            self.challenge_repository.as_ref(),
+            self.quest_repository.as_ref(),
+            self.userchallenge_repository.as_ref(),
+            self.achievement_repository.as_ref(),
+            self.unit_of_work.as_ref(),
+            &self.event_bus,
+            user_id,
+            completions,
+        )
+        .await
+    }
+}
+
+/// 2点間の大圏距離(メートル)
+fn distance_meters(from: (f64, f64), to: (f64, f64)) -> f64 {
+    const EARTH_RADIUS_METERS: f64 = 6_371_000.0;
+    let (lat1, lat2) = (from.0.to_radians(), to.0.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lng = (to.1 - from.1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lng / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_METERS * a.sqrt().asin()
+}
+
+/// チャレンジの完了でそのクエストを制覇したなら、クエストIDを返す
+/// 完了の記録がcommitされた後に呼ぶこと
+async fn find_cleared_quest(
+    challenge_repository: &dyn ChallengeRepository,
+    userchallenge_repository: &dyn UserChallengeRepository,
+    user_id: UserId,
+    challenge_id: ChallengeId,
+) -> anyhow::Result<Option<QuestId>> {
+    let challenge = challenge_repository.find(challenge_id).await?;
+    let challenges = challenge_repository
+        .find_by_quest_id(challenge.quest_id.clone())
+        .await?;
+    let completed_ids = userchallenge_repository
+        .get_completed_challenges_by_user_id(user_id)
+        .await?;
+
+    Ok(is_cleared(&challenges, &completed_ids).then_some(challenge.quest_id))
+}
+
+pub fn is_cleared(challenges: &[Challenge], completed_challenge_ids: &[ChallengeId]) -> bool {
+    !challenges.is_empty()
+        && challenges
+            .iter()
+            .all(|c| completed_challenge_ids.contains(&c.id))
+}
+
+/// 完了済みのチャレンジから各チャレンジの解放状態を設定する
+/// challengesはorder_index順に並んでいる前提
+pub fn apply_lock_states(quest: &mut QuestEntity, completed_challenge_ids: &[ChallengeId]) {
+    if !quest.sequential {
+        return;
+    }
+
+    let mut previous_completed = true;
+    for challenge in quest.challenges.iter_mut() {
+        challenge.locked = !previous_completed;
+        previous_completed = completed_challenge_ids.contains(&challenge.id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn build_quest(sequential: bool) -> QuestEntity {
+        let mut quest = QuestEntity::new(
+            "quest".into(),
+            "Test Quest".to_string(),
+            "This is a test quest.".to_string(),
+            QuestStatus::Published,
+        );
+        quest.sequential = sequential;
+        quest.challenges = (0..3)
+            .map(|i| {
+                let mut challenge = Challenge::new(
+                    format!("challenge{}", i).into(),
+                    "Test Challenge".to_string(),
+                    "This is a test challenge".to_string(),
+                    "quest".into(),
+                    35.6895,
+                    139.6917,
+                    "Test Stamp".to_string(),
+                    "test-stamp-image-color".to_string(),
+                    "test-stamp-image-gray".to_string(),
+                    "This is a test stamp".to_string(),
+                );
+                challenge.order_index = i;
+                challenge
+            })
+            .collect();
+        quest
+    }
+
+    fn locked_states(quest: &QuestEntity) -> Vec<bool> {
+        quest.challenges.iter().map(|c| c.locked).collect()
+    }
+
+    #[test]
+    fn should_unlock_only_first_challenge_when_nothing_completed() {
+        let mut quest = build_quest(true);
+        apply_lock_states(&mut quest, &[]);
+
+        assert_eq!(vec![false, true, true], locked_states(&quest));
+    }
+
+    #[test]
+    fn should_unlock_next_challenge_after_completion() {
+        let mut quest = build_quest(true);
+        apply_lock_states(&mut quest, &["challenge0".into()]);
+
+        assert_eq!(vec![false, false, true], locked_states(&quest));
+    }
+
+    #[test]
+    fn should_unlock_all_challenges_when_not_sequential() {
+        let mut quest = build_quest(false);
+        apply_lock_states(&mut quest, &[]);
+
+        assert_eq!(vec![false, false, false], locked_states(&quest));
+    }
+
+    #[test]
+    fn should_clear_quest_only_when_all_challenges_completed() {
+        let quest = build_quest(false);
+        let completed: Vec<ChallengeId> = vec!["challenge0".into(), "challenge1".into()];
+        assert!(!is_cleared(&quest.challenges, &completed));
+
+        let completed: Vec<ChallengeId> = quest.challenges.iter().map(|c| c.id.clone()).collect();
+        assert!(is_cleared(&quest.challenges, &completed));
+    }
+
+    #[test]
+    fn should_not_clear_quest_without_challenges() {
+        assert!(!is_cleared(&[], &["challenge0".into()]));
+    }
+
+    #[test]
+    fn should_measure_distance_between_points() {
+        // 東京駅から皇居外苑までおよそ1km
+        let distance = distance_meters((35.6812, 139.7671), (35.6806, 139.7576));
+        assert!((800.0..1000.0).contains(&distance), "{}", distance);
+        assert_eq!(0.0, distance_meters((35.0, 139.0), (35.0, 139.0)));
+    }
+
+    #[test]
+    fn should_reject_empty_or_too_many_completions() {
+        let completion = OfflineCompletion {
+            challenge_id: "challenge0".into(),
+            completed_at: Utc::now(),
+            latitude: 35.6895,
+            longitude: 139.6917,
+        };
+        let sync = |count: usize| SyncCompletedChallenges {
+            completions: vec![completion.clone(); count],
+        };
+
+        assert!(sync(0).validate().is_err());
+        assert!(sync(1).validate().is_ok());
+        assert!(sync(MAX_SYNC_COMPLETIONS + 1).validate().is_err());
+        let invalid = SyncCompletedChallenges {
+            completions: vec![OfflineCompletion {
+                latitude: 91.0,
+                ..completion
+            }],
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,192 @@
+use std::{io::Cursor, sync::Arc};
+
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, ColorType, DynamicImage};
+use nanoid::nanoid;
+
+use crate::{
+    repositories::{id::QuestId, quest::CoverImage},
+    services::photo::{Photo, PhotoStore},
+};
+
+/// 写真なので再エンコードはJPEGで揃える
+const JPEG_QUALITY: u8 = 85;
+
+/// アップロードされた画像から作るサイズ。長辺がこのピクセル数を超えるときだけ縮小する
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverImageSize {
+    /// 一覧のカードに並べる
+    Thumbnail,
+    /// 詳細画面のヘッダ
+    Full,
+}
+
+impl CoverImageSize {
+    pub const ALL: [Self; 2] = [Self::Thumbnail, Self::Full];
+
+    pub fn max_edge(&self) -> u32 {
+        match self {
+            Self::Thumbnail => 320,
+            Self::Full => 1600,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Thumbnail => "thumbnail",
+            Self::Full => "full",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CoverImageError {
+    /// 画像として読めない。HEICもここで弾く
+    Unsupported,
+    Store(anyhow::Error),
+}
+
+/// サイズごとに縮小したJPEGを作る
+fn resize(bytes: &[u8]) -> Result<Vec<(CoverImageSize, Photo)>, CoverImageError> {
+    let image = image::load_from_memory(bytes).or(Err(CoverImageError::Unsupported))?;
+    CoverImageSize::ALL
+        .iter()
+        .map(|size| {
+            let photo =
+                encode_jpeg(&shrink(&image, size.max_edge())).map_err(CoverImageError::Store)?;
+            Ok((*size, photo))
+        })
+        .collect()
+}
+
+/// 縦横比は保つ。小さい画像は引き伸ばさない
+fn shrink(image: &DynamicImage, max_edge: u32) -> DynamicImage {
+    if image.width() <= max_edge && image.height() <= max_edge {
+        return image.clone();
+    }
+    image.resize(max_edge, max_edge, FilterType::Lanczos3)
+}
+
+fn encode_jpeg(image: &DynamicImage) -> anyhow::Result<Photo> {
+    // JPEGは透過を持てないのでRGBにしてから書き出す
+    let rgb = image.to_rgb8();
+    let mut bytes = Cursor::new(Vec::new());
+    JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY).encode(
+        rgb.as_raw(),
+        rgb.width(),
+        rgb.height(),
+        ColorType::Rgb8,
+    )?;
+    Ok(Photo::new("image/jpeg", bytes.into_inner()).unwrap())
+}
+
+/// クエストのカバー画像をリサイズして写真と同じ保存先に置く
+#[derive(Clone)]
+pub struct CoverImageUploader {
+    store: Arc<dyn PhotoStore>,
+    max_bytes: usize,
+}
+
+impl CoverImageUploader {
+    pub fn new(store: Arc<dyn PhotoStore>, max_bytes: usize) -> Self {
+        Self { store, max_bytes }
+    }
+
+    /// 写真と同じく、multipartを読む時点でこれを超えたら打ち切る
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// アップロードごとにディレクトリを分け、差し替えてもCDNの古いキャッシュと衝突しないようにする
+    pub async fn upload(
+        &self,
+        quest_id: &QuestId,
+        photo: Photo,
+    ) -> Result<CoverImage, CoverImageError> {
+        // デコードと縮小は重いのでランタイムのワーカーを塞がないようにする
+        let resized = tokio::task::spawn_blocking(move || resize(&photo.bytes))
+            .await
+            .map_err(|e| CoverImageError::Store(e.into()))??;
+
+        let prefix = format!("quest_covers/{}/{}", quest_id, nanoid!());
+        let mut url = String::new();
+        let mut thumbnail_url = String::new();
+        for (size, photo) in resized {
+            let key = format!("{}/{}.jpg", prefix, size.name());
+            let uploaded = self
+                .store
+                .put(&key, &photo)
+                .await
+                .map_err(CoverImageError::Store)?;
+            match size {
+                CoverImageSize::Thumbnail => thumbnail_url = uploaded,
+                CoverImageSize::Full => url = uploaded,
+            }
+        }
+
+        Ok(CoverImage { url, thumbnail_url })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::services::photo::InMemoryPhotoStore;
+    use image::{ImageOutputFormat, RgbaImage};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        DynamicImage::ImageRgba8(RgbaImage::new(width, height))
+            .write_to(&mut bytes, ImageOutputFormat::Png)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    fn dimensions(photo: &Photo) -> (u32, u32) {
+        let image = image::load_from_memory(&photo.bytes).unwrap();
+        (image.width(), image.height())
+    }
+
+    #[test]
+    fn should_shrink_to_each_size_keeping_aspect_ratio() {
+        let resized = resize(&png(3200, 1600)).unwrap();
+
+        assert_eq!(CoverImageSize::Thumbnail, resized[0].0);
+        assert_eq!((320, 160), dimensions(&resized[0].1));
+        assert_eq!(CoverImageSize::Full, resized[1].0);
+        assert_eq!((1600, 800), dimensions(&resized[1].1));
+        assert_eq!("image/jpeg", resized[1].1.content_type);
+    }
+
+    #[test]
+    fn should_not_enlarge_small_image() {
+        let resized = resize(&png(200, 100)).unwrap();
+
+        assert_eq!((200, 100), dimensions(&resized[0].1));
+        assert_eq!((200, 100), dimensions(&resized[1].1));
+    }
+
+    #[test]
+    fn should_reject_non_image() {
+        assert!(matches!(
+            resize(b"not an image"),
+            Err(CoverImageError::Unsupported)
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_store_every_size() {
+        let store = Arc::new(InMemoryPhotoStore::default());
+        let uploader = CoverImageUploader::new(store.clone(), 1024 * 1024);
+        let photo = Photo::new("image/png", png(800, 600)).unwrap();
+
+        let cover_image = uploader
+            .upload(&QuestId::from("quest"), photo)
+            .await
+            .unwrap();
+
+        assert!(cover_image.url.starts_with("memory://quest_covers/quest/"));
+        assert!(cover_image.url.ends_with("/full.jpg"));
+        assert!(cover_image.thumbnail_url.ends_with("/thumbnail.jpg"));
+        assert_eq!(2, store.stored_count());
+    }
+}
@@ -0,0 +1,250 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    repositories::{
+        challenge::{Challenge, ChallengeRepository, CreateChallenge},
+        quest::{CreateQuest, QuestEntity, QuestRepository, QuestStatus},
+    },
+    services::job::JobProgress,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CsvEntity {
+    Quests,
+    Challenges,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CsvEntityQuery {
+    pub entity: CsvEntity,
+}
+
+/// 何行目がなぜ失敗したか
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RowError {
+    pub line: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub errors: Vec<RowError>,
+}
+
+pub trait Validate {
+    fn validate(&self) -> Result<(), String>;
+}
+
+impl Validate for CreateQuest {
+    fn validate(&self) -> Result<(), String> {
+        CreateQuest::validate(self)
+    }
+}
+
+impl Validate for CreateChallenge {
+    fn validate(&self) -> Result<(), String> {
+        CreateChallenge::validate(self)
+    }
+}
+
+/// CSVをパース・バリデーションする。1件でも失敗した行があればその一覧を返す
+pub fn parse_rows<T: DeserializeOwned + Validate>(
+    body: &[u8],
+) -> Result<Vec<(u64, T)>, Vec<RowError>> {
+    let mut reader = csv::Reader::from_reader(body);
+    let headers = reader
+        .headers()
+        .map_err(|e| {
+            vec![RowError {
+                line: 1,
+                message: e.to_string(),
+            }]
+        })?
+        .clone();
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    for result in reader.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                errors.push(RowError {
+                    line: e.position().map_or(0, |p| p.line()),
+                    message: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        let line = record.position().map_or(0, |p| p.line());
+        let row = record
+            .deserialize::<T>(Some(&headers))
+            .map_err(|e| e.to_string())
+            .and_then(|row| row.validate().map(|_| row));
+        match row {
+            Ok(row) => rows.push((line, row)),
+            Err(message) => errors.push(RowError { line, message }),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(rows)
+    } else {
+        Err(errors)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct QuestRecord<'a> {
+    id: &'a str,
+    title: &'a str,
+    description: &'a str,
+    status: QuestStatus,
+    sequential: bool,
+}
+
+pub fn export_quests(quests: &[QuestEntity]) -> anyhow::Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for quest in quests {
+        writer.serialize(QuestRecord {
+            id: quest.id.as_str(),
+            title: &quest.title,
+            description: &quest.description,
+            status: quest.status,
+            sequential: quest.sequential,
+        })?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+/// metadataは入れ子になりうるのでCSVには出さない
+#[derive(Debug, Clone, Serialize)]
+struct ChallengeRecord<'a> {
+    id: &'a str,
+    name: &'a str,
+    description: &'a str,
+    quest_id: &'a str,
+    latitude: f64,
+    longitude: f64,
+    stamp_name: &'a str,
+    stamp_color_image_url: &'a str,
+    stamp_gray_image_url: &'a str,
+    flavor_text: &'a str,
+    order_index: i32,
+    locked: bool,
+}
+
+pub fn export_challenges(challenges: &[Challenge]) -> anyhow::Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for challenge in challenges {
+        writer.serialize(ChallengeRecord {
+            id: challenge.id.as_str(),
+            name: &challenge.name,
+            description: &challenge.description,
+            quest_id: challenge.quest_id.as_str(),
+            latitude: challenge.latitude,
+            longitude: challenge.longitude,
+            stamp_name: &challenge.stamp_name,
+            stamp_color_image_url: &challenge.stamp_color_image_url,
+            stamp_gray_image_url: &challenge.stamp_gray_image_url,
+            flavor_text: &challenge.flavor_text,
+            order_index: challenge.order_index,
+            locked: challenge.locked,
+        })?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+/// ファイル名とCSVの中身を返す
+pub async fn export_entity(
+    quest_repository: &dyn QuestRepository,
+    entity: CsvEntity,
+) -> anyhow::Result<(&'static str, Vec<u8>)> {
+    let quests = quest_repository.all(None, None).await?;
+
+    match entity {
+        CsvEntity::Quests => Ok(("quests.csv", export_quests(&quests)?)),
+        CsvEntity::Challenges => {
+            let challenges = quests
+                .into_iter()
+                .flat_map(|quest| quest.challenges)
+                .collect::<Vec<_>>();
+            Ok(("challenges.csv", export_challenges(&challenges)?))
+        }
+    }
+}
+
+/// バリデーションエラーが1行でもあれば何も登録せずにその一覧を返す
+/// 登録時のエラーは行ごとに記録して残りの行を続ける
+pub async fn import_entity(
+    quest_repository: &dyn QuestRepository,
+    challenge_repository: &dyn ChallengeRepository,
+    entity: CsvEntity,
+    body: &[u8],
+    progress: &JobProgress,
+) -> Result<ImportReport, Vec<RowError>> {
+    let mut imported = 0;
+    let mut errors = Vec::new();
+    match entity {
+        CsvEntity::Quests => {
+            let rows = parse_rows::<CreateQuest>(body)?;
+            let total = rows.len();
+            for (i, (line, row)) in rows.into_iter().enumerate() {
+                match quest_repository.create(row).await {
+                    Ok(_) => imported += 1,
+                    Err(e) => errors.push(RowError {
+                        line,
+                        message: e.to_string(),
+                    }),
+                }
+                progress.set(i + 1, total);
+            }
+        }
+        CsvEntity::Challenges => {
+            let rows = parse_rows::<CreateChallenge>(body)?;
+            let total = rows.len();
+            for (i, (line, row)) in rows.into_iter().enumerate() {
+                match challenge_repository.create(row).await {
+                    Ok(_) => imported += 1,
+                    Err(e) => errors.push(RowError {
+                        line,
+                        message: e.to_string(),
+                    }),
+                }
+                progress.set(i + 1, total);
+            }
+        }
+    }
+
+    Ok(ImportReport { imported, errors })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_parse_quest_rows() {
+        let body = "title,description\nTest Quest,This is a test quest.\n";
+        let rows = parse_rows::<CreateQuest>(body.as_bytes()).unwrap();
+
+        assert_eq!(1, rows.len());
+        assert_eq!(2, rows[0].0);
+    }
+
+    #[test]
+    fn should_report_failed_lines() {
+        let body = "name,description,quest_id,latitude,longitude,stamp_name,stamp_color_image_url,stamp_gray_image_url,flavor_text\n\
+            Test Challenge,desc,quest,35.6895,139.6917,stamp,color,gray,flavor\n\
+            Test Challenge,desc,quest,not-a-number,139.6917,stamp,color,gray,flavor\n\
+            Test Challenge,desc,quest,135.0,139.6917,stamp,color,gray,flavor\n";
+        let errors = parse_rows::<CreateChallenge>(body.as_bytes()).unwrap_err();
+
+        assert_eq!(
+            vec![3, 4],
+            errors.iter().map(|e| e.line).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+use tokio::sync::broadcast;
+
+use crate::repositories::{
+    challenge::Challenge,
+    id::{ChallengeId, QuestId, UserId},
+    quest::QuestEntity,
+    user::UserEntity,
+};
+
+/// ハンドラから発行するドメインイベント
+#[derive(Debug, Clone)]
+pub enum DomainEvent {
+    UserRegistered {
+        user: UserEntity,
+    },
+    QuestCreated {
+        quest: QuestEntity,
+    },
+    QuestUpdated {
+        quest: QuestEntity,
+    },
+    QuestParticipated {
+        user_id: UserId,
+        quest_id: QuestId,
+    },
+    ChallengeCreated {
+        challenge: Challenge,
+    },
+    ChallengeCompleted {
+        user_id: UserId,
+        challenge_id: ChallengeId,
+    },
+    /// 公開状態や受付期間をまとめて変更した
+    ChallengesUpdated {
+        quest_id: QuestId,
+        challenges: Vec<Challenge>,
+    },
+    /// クエスト内のチャレンジをすべて完了した
+    QuestCompleted {
+        user_id: UserId,
+        quest_id: QuestId,
+    },
+}
+
+/// イベントの購読側。通知など本処理の成否に影響しない副作用をここに書く
+#[async_trait]
+pub trait EventHandler: Send + Sync + 'static {
+    async fn handle(&self, event: &DomainEvent);
+}
+
+/// 購読者が処理しきれずに溜められるイベント数
+const EVENT_BUS_CAPACITY: usize = 256;
+
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<DomainEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUS_CAPACITY);
+        Self { sender }
+    }
+
+    /// 購読者がいなければイベントは捨てられる
+    pub fn publish(&self, event: DomainEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// 購読者ごとにタスクを起動する。tokioのランタイム上で呼ぶこと
+    pub fn subscribe(&self, handler: Arc<dyn EventHandler>) {
+        let mut receiver = self.sender.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => handler.handle(&event).await,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!("event handler lagged, skipped {} events", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    struct ForwardHandler {
+        sender: mpsc::UnboundedSender<ChallengeId>,
+    }
+
+    #[async_trait]
+    impl EventHandler for ForwardHandler {
+        async fn handle(&self, event: &DomainEvent) {
+            if let DomainEvent::ChallengeCompleted { challenge_id, .. } = event {
+                self.sender.send(challenge_id.clone()).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn should_deliver_events_to_each_subscriber() {
+        let bus = EventBus::new();
+        let (first_sender, mut first_receiver) = mpsc::unbounded_channel();
+        let (second_sender, mut second_receiver) = mpsc::unbounded_channel();
+        bus.subscribe(Arc::new(ForwardHandler {
+            sender: first_sender,
+        }));
+        bus.subscribe(Arc::new(ForwardHandler {
+            sender: second_sender,
+        }));
+
+        bus.publish(DomainEvent::ChallengeCompleted {
+            user_id: "user".into(),
+            challenge_id: "challenge".into(),
+        });
+
+        assert_eq!(Some("challenge".into()), first_receiver.recv().await);
+        assert_eq!(Some("challenge".into()), second_receiver.recv().await);
+    }
+
+    #[test]
+    fn should_ignore_events_without_subscribers() {
+        let bus = EventBus::new();
+        bus.publish(DomainEvent::QuestParticipated {
+            user_id: "user".into(),
+            quest_id: "quest".into(),
+        });
+    }
+}
@@ -0,0 +1,114 @@
+use std::{collections::HashMap, sync::RwLock, time::Duration};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use nanoid::nanoid;
+use serde::{Deserialize, Serialize};
+
+use crate::repositories::{
+    id::UserId,
+    user::{UserExportData, UserRepository},
+};
+
+/// ダウンロードされるファイルの中身
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserExport {
+    pub exported_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub data: UserExportData,
+}
+
+/// エクスポートジョブの結果。URLは期限が切れたら作り直してもらう
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportLink {
+    pub url: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// 生成したエクスポートの保存先。本人以外に見えないよう、期限付きのURLでだけ渡す
+#[async_trait]
+pub trait ExportStore: Send + Sync + 'static {
+    async fn put(&self, key: &str, body: Vec<u8>) -> anyhow::Result<()>;
+    async fn signed_url(&self, key: &str, expires_in: Duration) -> anyhow::Result<String>;
+}
+
+/// ローカル開発用。プロセス内に保持するだけで、返すURLからは取得できない
+#[derive(Default)]
+pub struct InMemoryExportStore {
+    exports: RwLock<HashMap<String, Vec<u8>>>,
+}
+
+impl InMemoryExportStore {
+    #[cfg(test)]
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.exports.read().unwrap().get(key).cloned()
+    }
+}
+
+#[async_trait]
+impl ExportStore for InMemoryExportStore {
+    async fn put(&self, key: &str, body: Vec<u8>) -> anyhow::Result<()> {
+        self.exports.write().unwrap().insert(key.to_string(), body);
+        Ok(())
+    }
+
+    async fn signed_url(&self, key: &str, _expires_in: Duration) -> anyhow::Result<String> {
+        Ok(format!("memory://{}", key))
+    }
+}
+
+/// 推測されないように毎回ランダムなキーにする
+pub fn export_key(user_id: &UserId) -> String {
+    format!("user_exports/{}/{}.json", user_id, nanoid!())
+}
+
+/// ユーザーの記録をJSONにまとめて保存し、ダウンロード用のURLを返す
+pub async fn export_user(
+    user_repository: &dyn UserRepository,
+    store: &dyn ExportStore,
+    user_id: UserId,
+    url_ttl: Duration,
+    now: DateTime<Utc>,
+) -> anyhow::Result<ExportLink> {
+    let key = export_key(&user_id);
+    let data = user_repository.find_export_data(user_id).await?;
+    let body = serde_json::to_vec_pretty(&UserExport {
+        exported_at: now,
+        data,
+    })?;
+    store.put(&key, body).await?;
+    let url = store.signed_url(&key, url_ttl).await?;
+
+    Ok(ExportLink {
+        url,
+        expires_at: now + chrono::Duration::from_std(url_ttl)?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_make_unique_key_per_user() {
+        let key = export_key(&UserId::from("user"));
+
+        assert!(key.starts_with("user_exports/user/"));
+        assert!(key.ends_with(".json"));
+        assert_ne!(key, export_key(&UserId::from("user")));
+    }
+
+    #[tokio::test]
+    async fn should_return_memory_url_for_stored_export() {
+        let store = InMemoryExportStore::default();
+
+        store.put("key", b"{}".to_vec()).await.unwrap();
+        let url = store
+            .signed_url("key", Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        assert_eq!("memory://key", url);
+        assert_eq!(Some(b"{}".to_vec()), store.get("key"));
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::{
+    config::FeatureFlagConfig,
+    repositories::feature_flag::{Feature, FeatureFlag, FeatureFlagRepository},
+};
+
+/// 機能ごとのON/OFFを判定する。環境変数の既定値をDBの値で上書きする
+/// リクエストのたびにDBを引かないよう、上書きした値は一定時間キャッシュする
+pub struct FeatureFlagService {
+    repository: Arc<dyn FeatureFlagRepository>,
+    config: FeatureFlagConfig,
+    overrides: Mutex<Option<(Instant, HashMap<Feature, FeatureFlag>)>>,
+}
+
+impl FeatureFlagService {
+    pub fn new(repository: Arc<dyn FeatureFlagRepository>, config: FeatureFlagConfig) -> Self {
+        Self {
+            repository,
+            config,
+            overrides: Mutex::new(None),
+        }
+    }
+
+    fn get(&self, now: Instant) -> Option<HashMap<Feature, FeatureFlag>> {
+        let overrides = self.overrides.lock().unwrap();
+        overrides
+            .as_ref()
+            .filter(|(cached_at, _)| now.duration_since(*cached_at) < self.config.cache_ttl)
+            .map(|(_, flags)| flags.clone())
+    }
+
+    fn put(&self, now: Instant, flags: HashMap<Feature, FeatureFlag>) {
+        *self.overrides.lock().unwrap() = Some((now, flags));
+    }
+
+    fn default_flag(&self, feature: Feature) -> FeatureFlag {
+        FeatureFlag {
+            name: feature,
+            enabled: !self.config.disabled.contains(&feature),
+            updated_at: None,
+        }
+    }
+
+    async fn load_overrides(&self) -> anyhow::Result<HashMap<Feature, FeatureFlag>> {
+        if let Some(flags) = self.get(Instant::now()) {
+            return Ok(flags);
+        }
+
+        let flags: HashMap<_, _> = self
+            .repository
+            .all()
+            .await?
+            .into_iter()
+            .map(|flag| (flag.name, flag))
+            .collect();
+        self.put(Instant::now(), flags.clone());
+
+        Ok(flags)
+    }
+
+    /// DBを読めなければ既定値で判定する。フラグのせいで機能全体を止めない
+    pub async fn is_enabled(&self, feature: Feature) -> bool {
+        match self.load_overrides().await {
+            Ok(flags) => flags
+                .get(&feature)
+                .map_or_else(|| self.default_flag(feature).enabled, |flag| flag.enabled),
+            Err(e) => {
+                tracing::warn!("failed to load feature flags: {}", e);
+                self.default_flag(feature).enabled
+            }
+        }
+    }
+
+    /// すべての機能の現在の値。上書きしていないものは既定値
+    pub async fn all(&self) -> anyhow::Result<Vec<FeatureFlag>> {
+        let flags = self.load_overrides().await?;
+
+        Ok(Feature::ALL
+            .into_iter()
+            .map(|feature| {
+                flags
+                    .get(&feature)
+                    .cloned()
+                    .unwrap_or_else(|| self.default_flag(feature))
+            })
+            .collect())
+    }
+
+    /// このインスタンスにはすぐ反映し、ほかのインスタンスにはキャッシュが切れてから反映される
+    pub async fn set(&self, feature: Feature, enabled: bool) -> anyhow::Result<FeatureFlag> {
+        let flag = self.repository.set(feature, enabled).await?;
+        *self.overrides.lock().unwrap() = None;
+
+        Ok(flag)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::feature_flag::MockFeatureFlagRepository;
+    use chrono::Utc;
+    use std::time::Duration;
+
+    fn service(
+        repository: MockFeatureFlagRepository,
+        disabled: Vec<Feature>,
+    ) -> FeatureFlagService {
+        FeatureFlagService::new(
+            Arc::new(repository),
+            FeatureFlagConfig {
+                disabled,
+                cache_ttl: Duration::from_secs(60),
+            },
+        )
+    }
+
+    fn overridden(feature: Feature, enabled: bool) -> FeatureFlag {
+        FeatureFlag {
+            name: feature,
+            enabled,
+            updated_at: Some(Utc::now()),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_override_defaults_with_stored_flags() {
+        let mut repository = MockFeatureFlagRepository::new();
+        repository
+            .expect_all()
+            .times(1)
+            .returning(|| Ok(vec![overridden(Feature::Reviews, true)]));
+        let service = service(repository, vec![Feature::Reviews, Feature::Points]);
+
+        assert!(service.is_enabled(Feature::Reviews).await);
+        assert!(!service.is_enabled(Feature::Points).await);
+    }
+
+    #[tokio::test]
+    async fn should_fall_back_to_defaults_when_repository_fails() {
+        let mut repository = MockFeatureFlagRepository::new();
+        repository
+            .expect_all()
+            .returning(|| Err(anyhow::anyhow!("connection refused")));
+        let service = service(repository, vec![Feature::Points]);
+
+        assert!(service.is_enabled(Feature::Reviews).await);
+        assert!(!service.is_enabled(Feature::Points).await);
+    }
+
+    #[tokio::test]
+    async fn should_reload_flags_after_set() {
+        let mut repository = MockFeatureFlagRepository::new();
+        let mut loaded = vec![vec![overridden(Feature::Points, false)], vec![]];
+        repository
+            .expect_all()
+            .times(2)
+            .returning(move || Ok(loaded.pop().unwrap()));
+        repository
+            .expect_set()
+            .withf(|feature, enabled| *feature == Feature::Points && !*enabled)
+            .returning(|feature, enabled| Ok(overridden(feature, enabled)));
+        let service = service(repository, vec![]);
+
+        assert!(service.is_enabled(Feature::Points).await);
+        service.set(Feature::Points, false).await.unwrap();
+        assert!(!service.is_enabled(Feature::Points).await);
+    }
+
+    #[tokio::test]
+    async fn should_list_all_features() {
+        let mut repository = MockFeatureFlagRepository::new();
+        repository.expect_all().returning(|| Ok(vec![]));
+        let service = service(repository, vec![Feature::Points]);
+
+        let flags = service.all().await.unwrap();
+        assert_eq!(
+            vec![(Feature::Reviews, true), (Feature::Points, false)],
+            flags
+                .iter()
+                .map(|flag| (flag.name, flag.enabled))
+                .collect::<Vec<_>>()
+        );
+        assert!(flags.iter().all(|flag| flag.updated_at.is_none()));
+    }
+}
@@ -0,0 +1,29 @@
+use crate::repositories::{follow::FollowRepository, id::UserId, user::UserRepository};
+
+pub enum FollowError {
+    /// 自分自身はフォローできない
+    SelfFollow,
+    UserNotFound,
+    Repository(anyhow::Error),
+}
+
+/// 退会済みを含め、存在しないユーザーはフォローできない
+pub async fn follow_user(
+    user_repository: &dyn UserRepository,
+    follow_repository: &dyn FollowRepository,
+    follower_id: UserId,
+    followee_id: UserId,
+) -> Result<(), FollowError> {
+    if follower_id == followee_id {
+        return Err(FollowError::SelfFollow);
+    }
+    user_repository
+        .find(followee_id.clone())
+        .await
+        .or(Err(FollowError::UserNotFound))?;
+
+    follow_repository
+        .follow(follower_id, followee_id)
+        .await
+        .map_err(FollowError::Repository)
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use crate::{
+    middleware::language::Language,
+    repositories::{
+        challenge::Challenge,
+        id::{ChallengeId, QuestId},
+        quest::QuestEntity,
+        translation::{ChallengeTranslation, QuestTranslation, TranslationRepository},
+    },
+};
+
+/// クエストと含まれるチャレンジの本文を翻訳に差し替える。翻訳がなければ本文のまま
+/// キャッシュには本文だけを持たせ、言語ごとの差し替えは返すときに行う
+pub async fn translate_quests(
+    translation_repository: &dyn TranslationRepository,
+    language: &Language,
+    quests: &mut [QuestEntity],
+) -> anyhow::Result<()> {
+    if language.candidates().is_empty() || quests.is_empty() {
+        return Ok(());
+    }
+    let quest_ids: Vec<QuestId> = quests.iter().map(|quest| quest.id.clone()).collect();
+    let translations = translation_repository
+        .find_quests(&quest_ids, language.candidates())
+        .await?;
+    apply_quest_translations(quests, translations);
+
+    let mut challenges: Vec<&mut Challenge> = quests
+        .iter_mut()
+        .flat_map(|quest| quest.challenges.iter_mut())
+        .collect();
+    let challenge_ids: Vec<ChallengeId> = challenges
+        .iter()
+        .map(|challenge| challenge.id.clone())
+        .collect();
+    if challenge_ids.is_empty() {
+        return Ok(());
+    }
+    let translations = translation_repository
+        .find_challenges(&challenge_ids, language.candidates())
+        .await?;
+    apply_challenge_translations(
+        challenges.iter_mut().map(|challenge| &mut **challenge),
+        translations,
+    );
+
+    Ok(())
+}
+
+pub async fn translate_challenges(
+    translation_repository: &dyn TranslationRepository,
+    language: &Language,
+    challenges: &mut [Challenge],
+) -> anyhow::Result<()> {
+    if language.candidates().is_empty() || challenges.is_empty() {
+        return Ok(());
+    }
+    let challenge_ids: Vec<ChallengeId> = challenges
+        .iter()
+        .map(|challenge| challenge.id.clone())
+        .collect();
+    let translations = translation_repository
+        .find_challenges(&challenge_ids, language.candidates())
+        .await?;
+    apply_challenge_translations(challenges.iter_mut(), translations);
+
+    Ok(())
+}
+
+fn apply_quest_translations(
+    quests: &mut [QuestEntity],
+    mut translations: HashMap<QuestId, QuestTranslation>,
+) {
+    for quest in quests {
+        if let Some(translation) = translations.remove(&quest.id) {
+            quest.title = translation.title;
+            quest.description = translation.description;
+        }
+    }
+}
+
+fn apply_challenge_translations<'a>(
+    challenges: impl Iterator<Item = &'a mut Challenge>,
+    mut translations: HashMap<ChallengeId, ChallengeTranslation>,
+) {
+    for challenge in challenges {
+        if let Some(translation) = translations.remove(&challenge.id) {
+            challenge.name = translation.name;
+            challenge.description = translation.description;
+            // フレーバーテキストは翻訳しないこともある
+            if !translation.flavor_text.is_empty() {
+                challenge.flavor_text = translation.flavor_text;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::quest::QuestStatus;
+
+    fn quest(id: &str) -> QuestEntity {
+        QuestEntity::new(
+            id.into(),
+            "テストクエスト".to_string(),
+            "テスト用のクエストです".to_string(),
+            QuestStatus::Published,
+        )
+    }
+
+    #[test]
+    fn should_keep_original_when_translation_is_missing() {
+        let mut quests = vec![quest("translated"), quest("untranslated")];
+        let translations = HashMap::from([(
+            "translated".into(),
+            QuestTranslation {
+                title: "Test Quest".to_string(),
+                description: "This is a test quest.".to_string(),
+            },
+        )]);
+
+        apply_quest_translations(&mut quests, translations);
+
+        assert_eq!("Test Quest", quests[0].title);
+        assert_eq!("This is a test quest.", quests[0].description);
+        assert_eq!("テストクエスト", quests[1].title);
+    }
+}
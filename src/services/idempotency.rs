@@ -0,0 +1,39 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::async_trait;
+use chrono::Utc;
+
+use crate::{
+    repositories::idempotency::{IdempotencyRepository, KEY_TTL_HOURS},
+    services::scheduler::ScheduledJob,
+};
+
+/// 保持期間を過ぎた冪等キーは使われないので定期的に消す
+pub struct DeleteExpiredIdempotencyKeysJob {
+    idempotency_repository: Arc<dyn IdempotencyRepository>,
+}
+
+impl DeleteExpiredIdempotencyKeysJob {
+    pub fn new(idempotency_repository: Arc<dyn IdempotencyRepository>) -> Self {
+        Self {
+            idempotency_repository,
+        }
+    }
+}
+
+#[async_trait]
+impl ScheduledJob for DeleteExpiredIdempotencyKeysJob {
+    fn name(&self) -> &'static str {
+        "delete_expired_idempotency_keys"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+
+    async fn run(&self) -> anyhow::Result<String> {
+        let before = Utc::now() - chrono::Duration::hours(KEY_TTL_HOURS);
+        let deleted = self.idempotency_repository.delete_expired(before).await?;
+        Ok(format!("deleted {} idempotency keys", deleted))
+    }
+}
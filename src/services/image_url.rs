@@ -0,0 +1,75 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// 配信用の画像URLに署名する
+pub trait ImageUrlSigner: Send + Sync + 'static {
+    /// 署名の対象外のURLはNoneを返し、そのまま使う
+    fn sign(&self, url: &str, now: DateTime<Utc>) -> Option<String>;
+}
+
+/// JSONの中の文字列をすべて見て、署名できるURLを置き換える。置き換えたものがあればtrue
+pub fn sign_image_urls(value: &mut Value, signer: &dyn ImageUrlSigner, now: DateTime<Utc>) -> bool {
+    match value {
+        Value::String(url) => match signer.sign(url, now) {
+            Some(signed) => {
+                *url = signed;
+                true
+            }
+            None => false,
+        },
+        Value::Array(values) => values.iter_mut().fold(false, |signed, value| {
+            sign_image_urls(value, signer, now) || signed
+        }),
+        Value::Object(map) => map.values_mut().fold(false, |signed, value| {
+            sign_image_urls(value, signer, now) || signed
+        }),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    struct PrefixSigner;
+
+    impl ImageUrlSigner for PrefixSigner {
+        fn sign(&self, url: &str, _now: DateTime<Utc>) -> Option<String> {
+            url.starts_with("https://cdn/")
+                .then(|| format!("{}?signed", url))
+        }
+    }
+
+    #[test]
+    fn should_sign_nested_urls() {
+        let mut value = json!({
+            "title": "quest",
+            "cover_image_url": "https://cdn/cover.jpg",
+            "challenges": [
+                { "stamp_color_image_url": "https://cdn/color.png" },
+                { "stamp_color_image_url": "https://other/color.png" },
+            ],
+        });
+
+        assert!(sign_image_urls(&mut value, &PrefixSigner, Utc::now()));
+        assert_eq!(
+            json!({
+                "title": "quest",
+                "cover_image_url": "https://cdn/cover.jpg?signed",
+                "challenges": [
+                    { "stamp_color_image_url": "https://cdn/color.png?signed" },
+                    { "stamp_color_image_url": "https://other/color.png" },
+                ],
+            }),
+            value
+        );
+    }
+
+    #[test]
+    fn should_report_unchanged_json() {
+        let mut value = json!({ "title": "quest", "count": 1 });
+
+        assert!(!sign_image_urls(&mut value, &PrefixSigner, Utc::now()));
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::repositories::{
+    id::UserId,
+    job::{Job, JobKind, JobOutput, JobRepository},
+};
+
+/// 進捗をDBに書き込む間隔。キャンセルの検知もこの間隔で行う
+const PROGRESS_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// ジョブの処理から進捗を報告するためのハンドル。書き込みはJobRunnerがまとめて行う
+#[derive(Debug, Clone, Default)]
+pub struct JobProgress(Arc<AtomicU8>);
+
+impl JobProgress {
+    pub fn set(&self, done: usize, total: usize) {
+        let percent = if total == 0 {
+            100
+        } else {
+            (done.min(total) * 100 / total) as u8
+        };
+        self.0.store(percent, Ordering::Relaxed);
+    }
+
+    pub fn percent(&self) -> u8 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 時間のかかる処理をバックグラウンドで実行し、状態・進捗・結果をjobsテーブルに残す
+/// キャンセルはDBの状態で伝わるので、別のインスタンスで動いているジョブも止められる
+#[derive(Clone)]
+pub struct JobRunner {
+    repository: Arc<dyn JobRepository>,
+}
+
+impl JobRunner {
+    pub fn new(repository: Arc<dyn JobRepository>) -> Self {
+        Self { repository }
+    }
+
+    /// ジョブを登録してすぐに返す。処理は裏で続く
+    pub async fn spawn<F, Fut>(
+        &self,
+        user_id: UserId,
+        kind: JobKind,
+        work: F,
+    ) -> anyhow::Result<Job>
+    where
+        F: FnOnce(JobProgress) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<JobOutput>> + Send + 'static,
+    {
+        let job = self.repository.create(user_id, kind).await?;
+
+        let repository = self.repository.clone();
+        let id = job.id.clone();
+        tokio::spawn(async move {
+            let progress = JobProgress::default();
+            let work = work(progress.clone());
+            let outcome = run_until_cancelled(repository.as_ref(), &id, &progress, work).await;
+            let result = match outcome {
+                Some(result) => result,
+                None => {
+                    tracing::info!("job {} was cancelled", id);
+                    return;
+                }
+            };
+
+            let finished = match result {
+                Ok(output) => repository.succeed(id.clone(), output).await,
+                Err(e) => {
+                    tracing::warn!("job {} failed: {}", id, e);
+                    repository.fail(id.clone(), e.to_string()).await
+                }
+            };
+            if let Err(e) = finished {
+                tracing::error!("failed to record result of job {}: {}", id, e);
+            }
+        });
+
+        Ok(job)
+    }
+}
+
+/// 進捗を書き込みながら処理を待つ。途中でキャンセルされたら処理を捨ててNoneを返す
+async fn run_until_cancelled<Fut>(
+    repository: &dyn JobRepository,
+    id: &str,
+    progress: &JobProgress,
+    work: Fut,
+) -> Option<Fut::Output>
+where
+    Fut: Future,
+{
+    tokio::pin!(work);
+    let mut interval = tokio::time::interval(PROGRESS_FLUSH_INTERVAL);
+    loop {
+        tokio::select! {
+            output = &mut work => return Some(output),
+            _ = interval.tick() => {
+                match repository.update_progress(id.to_string(), i16::from(progress.percent())).await {
+                    Ok(true) => {}
+                    Ok(false) => return None,
+                    // 書き込めなくても処理は続ける
+                    Err(e) => tracing::warn!("failed to update progress of job {}: {}", id, e),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_report_progress_in_percent() {
+        let progress = JobProgress::default();
+        assert_eq!(0, progress.percent());
+
+        progress.set(1, 3);
+        assert_eq!(33, progress.percent());
+
+        progress.set(5, 3);
+        assert_eq!(100, progress.percent());
+
+        progress.set(0, 0);
+        assert_eq!(100, progress.percent());
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+
+use crate::repositories::point::{LeaderboardEntry, LeaderboardPeriod, PointRepository};
+
+/// リーダーボードに表示する件数
+pub const LEADERBOARD_SIZE: i64 = 10;
+
+/// 集計クエリの負荷を抑えるため、期間ごとの上位ランキングを一定時間キャッシュする
+pub struct LeaderboardCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<LeaderboardPeriod, (Instant, Vec<LeaderboardEntry>)>>,
+}
+
+impl LeaderboardCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, period: LeaderboardPeriod, now: Instant) -> Option<Vec<LeaderboardEntry>> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&period)
+            .filter(|(cached_at, _)| now.duration_since(*cached_at) < self.ttl)
+            .map(|(_, entries)| entries.clone())
+    }
+
+    fn put(&self, period: LeaderboardPeriod, now: Instant, entries: Vec<LeaderboardEntry>) {
+        self.entries.lock().unwrap().insert(period, (now, entries));
+    }
+
+    /// キャッシュが有効ならそれを返し、切れていれば集計し直す
+    pub async fn top_entries(
+        &self,
+        repository: &dyn PointRepository,
+        period: LeaderboardPeriod,
+    ) -> anyhow::Result<Vec<LeaderboardEntry>> {
+        if let Some(entries) = self.get(period, Instant::now()) {
+            return Ok(entries);
+        }
+
+        let entries = repository
+            .leaderboard(period.since(Utc::now()), LEADERBOARD_SIZE)
+            .await?;
+        self.put(period, Instant::now(), entries.clone());
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(rank: i64, points: i64) -> LeaderboardEntry {
+        LeaderboardEntry {
+            rank,
+            user_id: format!("user{}", rank).into(),
+            username: format!("User {}", rank),
+            points,
+        }
+    }
+
+    #[test]
+    fn should_return_cached_entries_within_ttl() {
+        let cache = LeaderboardCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+        cache.put(LeaderboardPeriod::All, now, vec![entry(1, 30)]);
+
+        let cached = cache.get(LeaderboardPeriod::All, now + Duration::from_secs(59));
+        assert_eq!(Some(vec![entry(1, 30)]), cached);
+    }
+
+    #[test]
+    fn should_expire_entries_after_ttl() {
+        let cache = LeaderboardCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+        cache.put(LeaderboardPeriod::All, now, vec![entry(1, 30)]);
+
+        assert_eq!(
+            None,
+            cache.get(LeaderboardPeriod::All, now + Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    fn should_cache_each_period_separately() {
+        let cache = LeaderboardCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+        cache.put(LeaderboardPeriod::All, now, vec![entry(1, 30)]);
+
+        assert_eq!(None, cache.get(LeaderboardPeriod::Weekly, now));
+    }
+}
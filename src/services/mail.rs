@@ -0,0 +1,195 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::async_trait;
+
+use crate::{
+    repositories::{quest::QuestRepository, user::UserRepository},
+    services::event_bus::{DomainEvent, EventHandler},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync + 'static {
+    async fn send(&self, mail: &Mail) -> anyhow::Result<()>;
+}
+
+/// ローカル開発用。送信せずに内容をログに出す
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, mail: &Mail) -> anyhow::Result<()> {
+        tracing::info!("mail to {}: {}\n{}", mail.to, mail.subject, mail.body);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MailTemplate {
+    Welcome,
+    QuestCompleted,
+}
+
+impl MailTemplate {
+    fn subject(&self) -> &'static str {
+        match self {
+            MailTemplate::Welcome => "【Quest】ご登録ありがとうございます",
+            MailTemplate::QuestCompleted => "【Quest】「{{quest_title}}」を制覇しました",
+        }
+    }
+
+    fn body(&self) -> &'static str {
+        match self {
+            MailTemplate::Welcome => {
+                "{{username}} さん\n\nQuestへのご登録ありがとうございます。\nさっそく近くのクエストに参加してみましょう。\n"
+            }
+            MailTemplate::QuestCompleted => {
+                "{{username}} さん\n\n「{{quest_title}}」のチャレンジをすべて完了しました。おめでとうございます！\n"
+            }
+        }
+    }
+
+    /// プレースホルダーを埋めたメールを作る
+    pub fn render(&self, to: String, vars: &HashMap<&str, String>) -> Mail {
+        Mail {
+            to,
+            subject: fill_placeholders(self.subject(), vars),
+            body: fill_placeholders(self.body(), vars),
+        }
+    }
+}
+
+/// {{key}}をvarsの値で置き換える。varsにないキーはそのまま残す
+fn fill_placeholders(template: &str, vars: &HashMap<&str, String>) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        let after_open = &rest[start + 2..];
+        match after_open.find("}}") {
+            Some(end) => {
+                let key = after_open[..end].trim();
+                match vars.get(key) {
+                    Some(value) => result.push_str(value),
+                    None => result.push_str(&rest[start..start + 2 + end + 2]),
+                }
+                rest = &after_open[end + 2..];
+            }
+            None => {
+                result.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// 登録完了・クエスト制覇のメールを送る
+pub struct MailNotifier {
+    user_repository: Arc<dyn UserRepository>,
+    quest_repository: Arc<dyn QuestRepository>,
+    mailer: Arc<dyn Mailer>,
+}
+
+impl MailNotifier {
+    pub fn new(
+        user_repository: Arc<dyn UserRepository>,
+        quest_repository: Arc<dyn QuestRepository>,
+        mailer: Arc<dyn Mailer>,
+    ) -> Self {
+        Self {
+            user_repository,
+            quest_repository,
+            mailer,
+        }
+    }
+
+    async fn build_mail(&self, event: &DomainEvent) -> anyhow::Result<Option<Mail>> {
+        match event {
+            DomainEvent::UserRegistered { user } => {
+                let vars = HashMap::from([("username", user.username.clone())]);
+                Ok(Some(
+                    MailTemplate::Welcome.render(user.email.clone(), &vars),
+                ))
+            }
+            DomainEvent::QuestCompleted { user_id, quest_id } => {
+                let user = self.user_repository.find(user_id.clone()).await?;
+                let quest = self.quest_repository.find(quest_id.clone()).await?;
+                let vars =
+                    HashMap::from([("username", user.username), ("quest_title", quest.title)]);
+                Ok(Some(MailTemplate::QuestCompleted.render(user.email, &vars)))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for MailNotifier {
+    async fn handle(&self, event: &DomainEvent) {
+        let mail = match self.build_mail(event).await {
+            Ok(Some(mail)) => mail,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::error!("failed to build mail: {}", e);
+                return;
+            }
+        };
+
+        if let Err(e) = self.mailer.send(&mail).await {
+            tracing::error!("failed to send mail to {}: {}", mail.to, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_fill_placeholders() {
+        let vars = HashMap::from([
+            ("username", "taro".to_string()),
+            ("quest_title", "浅草めぐり".to_string()),
+        ]);
+
+        assert_eq!(
+            "taro さんが「浅草めぐり」を制覇",
+            fill_placeholders("{{username}} さんが「{{ quest_title }}」を制覇", &vars)
+        );
+    }
+
+    #[test]
+    fn should_keep_unknown_or_unclosed_placeholders() {
+        let vars = HashMap::from([("username", "taro".to_string())]);
+
+        assert_eq!(
+            "{{unknown}} taro {{username",
+            fill_placeholders("{{unknown}} {{username}} {{username", &vars)
+        );
+    }
+
+    #[test]
+    fn should_render_quest_completed_mail() {
+        let vars = HashMap::from([
+            ("username", "taro".to_string()),
+            ("quest_title", "浅草めぐり".to_string()),
+        ]);
+
+        let mail = MailTemplate::QuestCompleted.render("taro@example.com".to_string(), &vars);
+
+        assert_eq!("taro@example.com", mail.to);
+        assert_eq!("【Quest】「浅草めぐり」を制覇しました", mail.subject);
+        assert!(mail.body.starts_with("taro さん"));
+        assert!(!mail.body.contains("{{"));
+    }
+}
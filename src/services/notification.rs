@@ -0,0 +1,260 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use axum::async_trait;
+use serde::Serialize;
+
+use crate::{
+    repositories::{device::DeviceRepository, id::QuestId},
+    services::{
+        event_bus::{DomainEvent, EventHandler},
+        preference::PreferenceService,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PushMessage {
+    pub title: String,
+    pub body: String,
+    /// アプリ側で遷移先を決めるための値
+    pub data: HashMap<String, String>,
+}
+
+pub enum PushError {
+    /// アンインストールなどでトークンが無効になった
+    Unregistered,
+    Other(anyhow::Error),
+}
+
+#[async_trait]
+pub trait PushSender: Send + Sync + 'static {
+    async fn send(&self, token: &str, message: &PushMessage) -> Result<(), PushError>;
+}
+
+/// ローカル開発用。FCMの設定がなければ送信内容をログに出すだけにする
+pub struct LogPushSender;
+
+#[async_trait]
+impl PushSender for LogPushSender {
+    async fn send(&self, token: &str, message: &PushMessage) -> Result<(), PushError> {
+        tracing::info!(
+            "push to {}: {} / {} {:?}",
+            token,
+            message.title,
+            message.body,
+            message.data
+        );
+        Ok(())
+    }
+}
+
+/// クエストの更新やチャレンジの追加を参加者の端末に通知する
+/// 通知をOFFにしている参加者には送らない
+pub struct NotificationService {
+    device_repository: Arc<dyn DeviceRepository>,
+    preferences: PreferenceService,
+    sender: Arc<dyn PushSender>,
+}
+
+impl NotificationService {
+    pub fn new(
+        device_repository: Arc<dyn DeviceRepository>,
+        preferences: PreferenceService,
+        sender: Arc<dyn PushSender>,
+    ) -> Self {
+        Self {
+            device_repository,
+            preferences,
+            sender,
+        }
+    }
+
+    async fn notify_participants(&self, quest_id: QuestId, message: PushMessage) {
+        let devices = match self
+            .device_repository
+            .find_tokens_by_quest_id(quest_id.clone())
+            .await
+        {
+            Ok(devices) => devices,
+            Err(e) => {
+                tracing::error!("failed to find devices for quest {}: {}", quest_id, e);
+                return;
+            }
+        };
+        let user_ids: HashSet<_> = devices
+            .iter()
+            .map(|device| device.user_id.clone())
+            .collect();
+        // 設定を読めないときは、OFFにしたユーザーに送ってしまわないよう送信をやめる
+        let notifiable = match self
+            .preferences
+            .notifiable(user_ids.into_iter().collect())
+            .await
+        {
+            Ok(notifiable) => notifiable,
+            Err(e) => {
+                tracing::error!("failed to load preferences for quest {}: {}", quest_id, e);
+                return;
+            }
+        };
+
+        for device in devices
+            .into_iter()
+            .filter(|device| notifiable.contains(&device.user_id))
+        {
+            let token = device.token;
+            match self.sender.send(&token, &message).await {
+                Ok(()) => {}
+                Err(PushError::Unregistered) => {
+                    if let Err(e) = self.device_repository.delete_token(token).await {
+                        tracing::error!("failed to delete unregistered device: {}", e);
+                    }
+                }
+                Err(PushError::Other(e)) => tracing::warn!("failed to send push: {}", e),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for NotificationService {
+    async fn handle(&self, event: &DomainEvent) {
+        if let Some((quest_id, message)) = build_message(event) {
+            self.notify_participants(quest_id, message).await;
+        }
+    }
+}
+
+/// 通知対象のイベントなら、送り先のクエストIDと通知内容を返す
+fn build_message(event: &DomainEvent) -> Option<(QuestId, PushMessage)> {
+    match event {
+        DomainEvent::QuestUpdated { quest } => Some((
+            quest.id.clone(),
+            PushMessage {
+                title: "クエストが更新されました".to_string(),
+                body: quest.title.clone(),
+                data: HashMap::from([("quest_id".to_string(), quest.id.to_string())]),
+            },
+        )),
+        DomainEvent::ChallengeCreated { challenge } => Some((
+            challenge.quest_id.clone(),
+            PushMessage {
+                title: "新しいチャレンジが追加されました".to_string(),
+                body: challenge.name.clone(),
+                data: HashMap::from([
+                    ("quest_id".to_string(), challenge.quest_id.to_string()),
+                    ("challenge_id".to_string(), challenge.id.to_string()),
+                ]),
+            },
+        )),
+        DomainEvent::ChallengesUpdated {
+            quest_id,
+            challenges,
+        } => Some((
+            quest_id.clone(),
+            PushMessage {
+                title: "チャレンジの開催状況が変わりました".to_string(),
+                body: challenges
+                    .iter()
+                    .map(|challenge| challenge.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join("、"),
+                data: HashMap::from([("quest_id".to_string(), quest_id.to_string())]),
+            },
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        challenge::Challenge,
+        quest::{QuestEntity, QuestStatus},
+    };
+
+    #[test]
+    fn should_notify_participants_of_updated_quest() {
+        let quest = QuestEntity::new(
+            "quest".into(),
+            "Test Quest".to_string(),
+            "description".to_string(),
+            QuestStatus::Published,
+        );
+
+        let (quest_id, message) = build_message(&DomainEvent::QuestUpdated { quest }).unwrap();
+
+        assert_eq!("quest", quest_id);
+        assert_eq!("Test Quest", message.body);
+        assert_eq!(Some(&"quest".to_string()), message.data.get("quest_id"));
+    }
+
+    #[test]
+    fn should_notify_participants_of_new_challenge() {
+        let challenge = Challenge::new(
+            "challenge".into(),
+            "Test Challenge".to_string(),
+            "description".to_string(),
+            "quest".into(),
+            35.6895,
+            139.6917,
+            "stamp".to_string(),
+            "color".to_string(),
+            "gray".to_string(),
+            "flavor".to_string(),
+        );
+
+        let (quest_id, message) =
+            build_message(&DomainEvent::ChallengeCreated { challenge }).unwrap();
+
+        assert_eq!("quest", quest_id);
+        assert_eq!("Test Challenge", message.body);
+        assert_eq!(
+            Some(&"challenge".to_string()),
+            message.data.get("challenge_id")
+        );
+    }
+
+    #[test]
+    fn should_notify_participants_of_updated_challenges() {
+        let challenges = ["First", "Second"]
+            .into_iter()
+            .map(|name| {
+                Challenge::new(
+                    name.to_lowercase().into(),
+                    name.to_string(),
+                    "description".to_string(),
+                    "quest".into(),
+                    35.6895,
+                    139.6917,
+                    "stamp".to_string(),
+                    "color".to_string(),
+                    "gray".to_string(),
+                    "flavor".to_string(),
+                )
+            })
+            .collect();
+
+        let (quest_id, message) = build_message(&DomainEvent::ChallengesUpdated {
+            quest_id: "quest".into(),
+            challenges,
+        })
+        .unwrap();
+
+        assert_eq!("quest", quest_id);
+        assert_eq!("First、Second", message.body);
+    }
+
+    #[test]
+    fn should_not_notify_on_participation() {
+        let event = DomainEvent::QuestParticipated {
+            user_id: "user".into(),
+            quest_id: "quest".into(),
+        };
+
+        assert!(build_message(&event).is_none());
+    }
+}
@@ -0,0 +1,256 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    repositories::quest::{QuestEntity, QuestRepository, QuestStatus},
+    services::scheduler::ScheduledJob,
+};
+
+/// 地域はクエストのmetadataのこのキーで指定する
+pub const REGION_METADATA_KEY: &str = "region";
+
+/// 旅行前に地域ごとまとめてダウンロードするためのクエスト一式
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OfflinePack {
+    pub region: String,
+    pub generated_at: DateTime<Utc>,
+    pub quests: Vec<QuestEntity>,
+    /// 事前に取得しておく画像のURL
+    pub assets: Vec<String>,
+}
+
+/// 生成したパックの保存先
+#[async_trait]
+pub trait OfflinePackStore: Send + Sync + 'static {
+    async fn put(&self, pack: &OfflinePack) -> anyhow::Result<()>;
+    /// まだ生成されていない地域はNone
+    async fn get(&self, region: &str) -> anyhow::Result<Option<OfflinePack>>;
+}
+
+/// ローカル開発用。プロセス内に保持するだけなのでインスタンス間で共有されない
+#[derive(Default)]
+pub struct InMemoryOfflinePackStore {
+    packs: RwLock<HashMap<String, OfflinePack>>,
+}
+
+#[async_trait]
+impl OfflinePackStore for InMemoryOfflinePackStore {
+    async fn put(&self, pack: &OfflinePack) -> anyhow::Result<()> {
+        self.packs
+            .write()
+            .unwrap()
+            .insert(pack.region.clone(), pack.clone());
+        Ok(())
+    }
+
+    async fn get(&self, region: &str) -> anyhow::Result<Option<OfflinePack>> {
+        Ok(self.packs.read().unwrap().get(region).cloned())
+    }
+}
+
+/// URLのパスとストレージのキーにそのまま使うので英小文字・数字と`-`/`_`に限る
+pub fn is_valid_region(region: &str) -> bool {
+    !region.is_empty()
+        && region.len() <= 64
+        && region
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// 公開中のクエストを地域ごとにまとめる。地域のないクエストと非公開のチャレンジは含めない
+pub fn build_offline_packs(quests: Vec<QuestEntity>, now: DateTime<Utc>) -> Vec<OfflinePack> {
+    let mut regions: BTreeMap<String, Vec<QuestEntity>> = BTreeMap::new();
+    for mut quest in quests {
+        let region = match quest
+            .metadata
+            .get(REGION_METADATA_KEY)
+            .and_then(|region| region.as_str())
+        {
+            Some(region) if is_valid_region(region) => region.to_string(),
+            _ => continue,
+        };
+        quest.challenges.retain(|challenge| challenge.active);
+        regions.entry(region).or_default().push(quest);
+    }
+
+    regions
+        .into_iter()
+        .map(|(region, quests)| {
+            let assets = quests
+                .iter()
+                .flat_map(|quest| quest.challenges.iter())
+                .flat_map(|challenge| {
+                    [
+                        challenge.stamp_color_image_url.clone(),
+                        challenge.stamp_gray_image_url.clone(),
+                    ]
+                })
+                .filter(|url| !url.is_empty())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+            OfflinePack {
+                region,
+                generated_at: now,
+                quests,
+                assets,
+            }
+        })
+        .collect()
+}
+
+/// すべての地域のパックを作り直す。保存できた地域の数を返す
+pub async fn regenerate_offline_packs(
+    quest_repository: &dyn QuestRepository,
+    store: &dyn OfflinePackStore,
+) -> anyhow::Result<usize> {
+    let quests = quest_repository
+        .all(Some(QuestStatus::Published), None)
+        .await?;
+    let packs = build_offline_packs(quests, Utc::now());
+
+    let mut saved = 0;
+    for pack in &packs {
+        // 1地域の失敗で他の地域を止めない
+        match store.put(pack).await {
+            Ok(()) => saved += 1,
+            Err(e) => tracing::warn!("failed to save offline pack [{}]: {}", pack.region, e),
+        }
+    }
+    Ok(saved)
+}
+
+/// 定期的にすべての地域のパックを作り直す
+pub struct RegenerateOfflinePacksJob {
+    quest_repository: Arc<dyn QuestRepository>,
+    store: Arc<dyn OfflinePackStore>,
+    interval: Duration,
+}
+
+impl RegenerateOfflinePacksJob {
+    pub fn new(
+        quest_repository: Arc<dyn QuestRepository>,
+        store: Arc<dyn OfflinePackStore>,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            quest_repository,
+            store,
+            interval,
+        }
+    }
+}
+
+#[async_trait]
+impl ScheduledJob for RegenerateOfflinePacksJob {
+    fn name(&self) -> &'static str {
+        "regenerate_offline_packs"
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    async fn run(&self) -> anyhow::Result<String> {
+        let saved =
+            regenerate_offline_packs(self.quest_repository.as_ref(), self.store.as_ref()).await?;
+        Ok(format!("regenerated {} offline packs", saved))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{challenge::Challenge, metadata::Metadata};
+    use serde_json::Value;
+
+    fn quest(id: &str, region: Option<&str>, challenges: Vec<Challenge>) -> QuestEntity {
+        let metadata = region
+            .map(|region| {
+                Metadata::from_iter([(REGION_METADATA_KEY.to_string(), Value::from(region))])
+            })
+            .unwrap_or_default();
+        QuestEntity {
+            metadata,
+            challenges,
+            ..QuestEntity::new(
+                id.into(),
+                "Test Quest".to_string(),
+                "description".to_string(),
+                QuestStatus::Published,
+            )
+        }
+    }
+
+    fn challenge(id: &str, image: &str, active: bool) -> Challenge {
+        Challenge {
+            active,
+            ..Challenge::new(
+                id.into(),
+                "Test Challenge".to_string(),
+                "description".to_string(),
+                "quest".into(),
+                35.6895,
+                139.6917,
+                "stamp".to_string(),
+                format!("{}-color", image),
+                format!("{}-gray", image),
+                "flavor".to_string(),
+            )
+        }
+    }
+
+    #[test]
+    fn should_group_quests_by_region() {
+        let quests = vec![
+            quest("kyoto-1", Some("kyoto"), vec![]),
+            quest("tokyo-1", Some("tokyo"), vec![]),
+            quest("kyoto-2", Some("kyoto"), vec![]),
+            quest("nowhere", None, vec![]),
+        ];
+
+        let packs = build_offline_packs(quests, Utc::now());
+
+        let regions = packs
+            .iter()
+            .map(|pack| (pack.region.as_str(), pack.quests.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![("kyoto", 2), ("tokyo", 1)], regions);
+    }
+
+    #[test]
+    fn should_list_assets_of_active_challenges_once() {
+        let quests = vec![quest(
+            "quest",
+            Some("kyoto"),
+            vec![
+                challenge("first", "shared", true),
+                challenge("second", "shared", true),
+                challenge("inactive", "hidden", false),
+            ],
+        )];
+
+        let packs = build_offline_packs(quests, Utc::now());
+
+        assert_eq!(2, packs[0].quests[0].challenges.len());
+        assert_eq!(
+            vec!["shared-color".to_string(), "shared-gray".to_string()],
+            packs[0].assets
+        );
+    }
+
+    #[test]
+    fn should_reject_region_unusable_as_key() {
+        assert!(is_valid_region("kyoto-city_1"));
+        assert!(!is_valid_region(""));
+        assert!(!is_valid_region("../tokyo"));
+        assert!(!is_valid_region("Tokyo"));
+    }
+}
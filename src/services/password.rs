@@ -0,0 +1,159 @@
+use anyhow::anyhow;
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, SaltString},
+    Algorithm, Argon2, Params, PasswordHasher as _, PasswordVerifier, Version,
+};
+
+use crate::config::PasswordHashConfig;
+
+/// 保存済みハッシュの形式。ハッシュ先頭の識別子で見分ける
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordAlgorithm {
+    /// 移行前の形式。ログインに成功したらArgon2idで作り直す
+    Bcrypt,
+    Argon2id,
+}
+
+impl PasswordAlgorithm {
+    /// 退会済みユーザーの空文字など、どれにも当たらなければNone
+    pub fn detect(hash: &str) -> Option<Self> {
+        if hash.starts_with("$argon2id$") {
+            Some(Self::Argon2id)
+        } else if ["$2a$", "$2b$", "$2x$", "$2y$"]
+            .iter()
+            .any(|prefix| hash.starts_with(prefix))
+        {
+            Some(Self::Bcrypt)
+        } else {
+            None
+        }
+    }
+}
+
+/// 新しいハッシュはArgon2idのPHC文字列($argon2id$v=19$m=..,t=..,p=..$salt$hash)で作る
+#[derive(Debug, Clone)]
+pub struct PasswordHasher {
+    params: Params,
+}
+
+impl Default for PasswordHasher {
+    fn default() -> Self {
+        Self::new(PasswordHashConfig::default()).unwrap()
+    }
+}
+
+impl PasswordHasher {
+    pub fn new(config: PasswordHashConfig) -> anyhow::Result<Self> {
+        let params = Params::new(
+            config.memory_kib,
+            config.iterations,
+            config.parallelism,
+            None,
+        )
+        .map_err(|e| anyhow!("invalid argon2 params: {}", e))?;
+        Ok(Self { params })
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+
+    pub fn hash(&self, password: &str) -> anyhow::Result<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = self
+            .argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| anyhow!("failed to hash password: {}", e))?;
+        Ok(hash.to_string())
+    }
+
+    /// ハッシュの形式に合わせて検証する。Argon2idはハッシュに入っているコストで検証する
+    pub fn verify(&self, password: &str, hash: &str) -> anyhow::Result<bool> {
+        match PasswordAlgorithm::detect(hash) {
+            Some(PasswordAlgorithm::Bcrypt) => Ok(bcrypt::verify(password, hash)?),
+            Some(PasswordAlgorithm::Argon2id) => {
+                let parsed =
+                    PasswordHash::new(hash).map_err(|e| anyhow!("invalid argon2 hash: {}", e))?;
+                Ok(self
+                    .argon2()
+                    .verify_password(password.as_bytes(), &parsed)
+                    .is_ok())
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// bcryptのものと、今の設定と違うコストで作ったものは作り直す
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        if PasswordAlgorithm::detect(hash) != Some(PasswordAlgorithm::Argon2id) {
+            return true;
+        }
+        let params = PasswordHash::new(hash)
+            .ok()
+            .and_then(|parsed| Params::try_from(&parsed).ok());
+        match params {
+            Some(params) => {
+                params.m_cost() != self.params.m_cost()
+                    || params.t_cost() != self.params.t_cost()
+                    || params.p_cost() != self.params.p_cost()
+            }
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// テストが遅くならないよう最小のコストにする
+    fn hasher(iterations: u32) -> PasswordHasher {
+        PasswordHasher::new(PasswordHashConfig {
+            memory_kib: 8,
+            iterations,
+            parallelism: 1,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn should_hash_with_argon2id() {
+        let hasher = hasher(1);
+        let hash = hasher.hash("password").unwrap();
+
+        assert!(hash.starts_with("$argon2id$v=19$m=8,t=1,p=1$"));
+        assert!(hasher.verify("password", &hash).unwrap());
+        assert!(!hasher.verify("wrong", &hash).unwrap());
+        assert!(!hasher.needs_rehash(&hash));
+    }
+
+    #[test]
+    fn should_verify_legacy_bcrypt_hash_and_ask_for_rehash() {
+        let hash = bcrypt::hash("password", 4).unwrap();
+        let hasher = hasher(1);
+
+        assert_eq!(
+            Some(PasswordAlgorithm::Bcrypt),
+            PasswordAlgorithm::detect(&hash)
+        );
+        assert!(hasher.verify("password", &hash).unwrap());
+        assert!(!hasher.verify("wrong", &hash).unwrap());
+        assert!(hasher.needs_rehash(&hash));
+    }
+
+    #[test]
+    fn should_ask_for_rehash_when_cost_changed() {
+        let hash = hasher(1).hash("password").unwrap();
+
+        assert!(hasher(2).verify("password", &hash).unwrap());
+        assert!(hasher(2).needs_rehash(&hash));
+    }
+
+    #[test]
+    fn should_not_match_unknown_hash() {
+        let hasher = hasher(1);
+
+        assert_eq!(None, PasswordAlgorithm::detect(""));
+        assert!(!hasher.verify("", "").unwrap());
+    }
+}
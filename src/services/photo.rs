@@ -0,0 +1,122 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use axum::async_trait;
+use nanoid::nanoid;
+
+use crate::repositories::id::ChallengeId;
+
+/// 受け付ける画像の形式と保存時の拡張子
+const ALLOWED_TYPES: [(&str, &str); 4] = [
+    ("image/jpeg", "jpg"),
+    ("image/png", "png"),
+    ("image/webp", "webp"),
+    ("image/heic", "heic"),
+];
+
+/// チャレンジ完了時に添付された写真
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Photo {
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+impl Photo {
+    /// 対応していない形式ならNone
+    pub fn new(content_type: &str, bytes: Vec<u8>) -> Option<Self> {
+        extension(content_type)?;
+        Some(Self {
+            content_type: content_type.to_string(),
+            bytes,
+        })
+    }
+
+    /// 同じユーザーが撮り直しても上書きしないようにキーは毎回作る
+    pub fn key(&self, challenge_id: &ChallengeId) -> String {
+        format!(
+            "challenge_photos/{}/{}.{}",
+            challenge_id,
+            nanoid!(),
+            extension(&self.content_type).unwrap_or("bin")
+        )
+    }
+}
+
+fn extension(content_type: &str) -> Option<&'static str> {
+    ALLOWED_TYPES
+        .iter()
+        .find(|(allowed, _)| *allowed == content_type)
+        .map(|(_, extension)| *extension)
+}
+
+/// 写真の保存先
+#[async_trait]
+pub trait PhotoStore: Send + Sync + 'static {
+    /// 保存して公開用のURLを返す
+    async fn put(&self, key: &str, photo: &Photo) -> anyhow::Result<String>;
+    async fn delete(&self, key: &str) -> anyhow::Result<()>;
+}
+
+/// ローカル開発用。プロセス内に保持するだけで、返すURLからは取得できない
+#[derive(Default)]
+pub struct InMemoryPhotoStore {
+    photos: RwLock<HashMap<String, Photo>>,
+}
+
+impl InMemoryPhotoStore {
+    #[cfg(test)]
+    pub fn stored_count(&self) -> usize {
+        self.photos.read().unwrap().len()
+    }
+}
+
+#[async_trait]
+impl PhotoStore for InMemoryPhotoStore {
+    async fn put(&self, key: &str, photo: &Photo) -> anyhow::Result<String> {
+        self.photos
+            .write()
+            .unwrap()
+            .insert(key.to_string(), photo.clone());
+        Ok(format!("memory://{}", key))
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.photos.write().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_accept_only_images() {
+        assert!(Photo::new("image/jpeg", vec![0]).is_some());
+        assert!(Photo::new("image/heic", vec![0]).is_some());
+        assert!(Photo::new("image/gif", vec![0]).is_none());
+        assert!(Photo::new("application/pdf", vec![0]).is_none());
+    }
+
+    #[test]
+    fn should_make_unique_key_per_upload() {
+        let photo = Photo::new("image/png", vec![0]).unwrap();
+
+        let key = photo.key(&ChallengeId::from("challenge"));
+
+        assert!(key.starts_with("challenge_photos/challenge/"));
+        assert!(key.ends_with(".png"));
+        assert_ne!(key, photo.key(&ChallengeId::from("challenge")));
+    }
+
+    #[tokio::test]
+    async fn should_delete_stored_photo() {
+        let store = InMemoryPhotoStore::default();
+        let photo = Photo::new("image/jpeg", vec![0]).unwrap();
+
+        let url = store.put("key", &photo).await.unwrap();
+        assert_eq!("memory://key", url);
+        store.delete("key").await.unwrap();
+
+        assert_eq!(0, store.stored_count());
+    }
+}
@@ -0,0 +1,180 @@
+use std::{collections::HashSet, sync::Arc};
+
+use crate::{
+    config::I18nConfig,
+    repositories::{
+        id::UserId,
+        preference::{PreferenceRepository, StoredPreferences, UpdatePreferences, UserPreferences},
+        user::UserRepository,
+    },
+};
+
+pub enum PreferenceError {
+    NotFound,
+    UnsupportedLanguage(String),
+    Repository(anyhow::Error),
+}
+
+/// ユーザーごとの設定。保存した値がなければ既定値を使う
+/// 通知の送信など、ほかの機能もここを通して設定を読む
+#[derive(Clone)]
+pub struct PreferenceService {
+    preference_repository: Arc<dyn PreferenceRepository>,
+    user_repository: Arc<dyn UserRepository>,
+    i18n: I18nConfig,
+}
+
+impl PreferenceService {
+    pub fn new(
+        preference_repository: Arc<dyn PreferenceRepository>,
+        user_repository: Arc<dyn UserRepository>,
+        i18n: I18nConfig,
+    ) -> Self {
+        Self {
+            preference_repository,
+            user_repository,
+            i18n,
+        }
+    }
+
+    pub async fn find(&self, user_id: UserId) -> Result<UserPreferences, PreferenceError> {
+        let user = self
+            .user_repository
+            .find(user_id.clone())
+            .await
+            .or(Err(PreferenceError::NotFound))?;
+        let stored = self
+            .preference_repository
+            .find(user_id)
+            .await
+            .map_err(PreferenceError::Repository)?;
+
+        Ok(merge(stored, &self.i18n.default_language, &user.username))
+    }
+
+    pub async fn update(
+        &self,
+        user_id: UserId,
+        payload: UpdatePreferences,
+    ) -> Result<UserPreferences, PreferenceError> {
+        if let Some(language) = &payload.language {
+            if !self.i18n.supported_languages.contains(language) {
+                return Err(PreferenceError::UnsupportedLanguage(language.clone()));
+            }
+        }
+        let user = self
+            .user_repository
+            .find(user_id.clone())
+            .await
+            .or(Err(PreferenceError::NotFound))?;
+        let stored = self
+            .preference_repository
+            .update(user_id, payload)
+            .await
+            .map_err(PreferenceError::Repository)?;
+
+        Ok(merge(stored, &self.i18n.default_language, &user.username))
+    }
+
+    /// 通知を送ってよいユーザーだけに絞る。設定していないユーザーには送る
+    pub async fn notifiable(&self, user_ids: Vec<UserId>) -> anyhow::Result<HashSet<UserId>> {
+        let disabled: HashSet<_> = self
+            .preference_repository
+            .find_notification_disabled(user_ids.clone())
+            .await?
+            .into_iter()
+            .collect();
+
+        Ok(user_ids
+            .into_iter()
+            .filter(|user_id| !disabled.contains(user_id))
+            .collect())
+    }
+}
+
+/// 保存していない項目は既定値にする。表示名の既定値はユーザー名
+fn merge(stored: StoredPreferences, default_language: &str, username: &str) -> UserPreferences {
+    UserPreferences {
+        notifications_enabled: stored.notifications_enabled.unwrap_or(true),
+        language: stored
+            .language
+            .unwrap_or_else(|| default_language.to_string()),
+        display_name: stored.display_name.unwrap_or_else(|| username.to_string()),
+        updated_at: stored.updated_at,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{preference::MockPreferenceRepository, user::MockUserRepository};
+
+    fn service(
+        preference_repository: MockPreferenceRepository,
+        user_repository: MockUserRepository,
+    ) -> PreferenceService {
+        PreferenceService::new(
+            Arc::new(preference_repository),
+            Arc::new(user_repository),
+            I18nConfig::default(),
+        )
+    }
+
+    #[test]
+    fn should_use_defaults_for_unset_preferences() {
+        let preferences = merge(StoredPreferences::default(), "ja", "alice");
+
+        assert!(preferences.notifications_enabled);
+        assert_eq!("ja", preferences.language);
+        assert_eq!("alice", preferences.display_name);
+        assert!(preferences.updated_at.is_none());
+    }
+
+    #[test]
+    fn should_prefer_stored_values() {
+        let stored = StoredPreferences {
+            notifications_enabled: Some(false),
+            language: Some("en".to_string()),
+            display_name: None,
+            updated_at: Some(chrono::Utc::now()),
+        };
+
+        let preferences = merge(stored, "ja", "alice");
+
+        assert!(!preferences.notifications_enabled);
+        assert_eq!("en", preferences.language);
+        assert_eq!("alice", preferences.display_name);
+    }
+
+    #[tokio::test]
+    async fn should_reject_unsupported_language() {
+        let service = service(MockPreferenceRepository::new(), MockUserRepository::new());
+        let payload = UpdatePreferences {
+            language: Some("fr".to_string()),
+            ..Default::default()
+        };
+
+        let result = service.update("user".into(), payload).await;
+
+        assert!(matches!(
+            result,
+            Err(PreferenceError::UnsupportedLanguage(language)) if language == "fr"
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_exclude_users_who_disabled_notifications() {
+        let mut preference_repository = MockPreferenceRepository::new();
+        preference_repository
+            .expect_find_notification_disabled()
+            .returning(|_| Ok(vec!["muted".into()]));
+        let service = service(preference_repository, MockUserRepository::new());
+
+        let notifiable = service
+            .notifiable(vec!["muted".into(), "default".into()])
+            .await
+            .unwrap();
+
+        assert_eq!(HashSet::from([UserId::from("default")]), notifiable);
+    }
+}
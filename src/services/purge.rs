@@ -0,0 +1,63 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::{
+    repositories::{quest::QuestRepository, user::UserRepository},
+    services::scheduler::ScheduledJob,
+};
+
+/// 論理削除してから物理削除するまでの日数。この間は管理画面から復元できる
+pub const RETENTION_DAYS: i64 = 30;
+
+/// 保持期間を過ぎたクエストとユーザーを物理削除し、それぞれの件数を返す
+pub async fn purge_deleted(
+    quest_repository: &dyn QuestRepository,
+    user_repository: &dyn UserRepository,
+    now: DateTime<Utc>,
+) -> anyhow::Result<(u64, u64)> {
+    let before = now - chrono::Duration::days(RETENTION_DAYS);
+    let quests = quest_repository.purge_deleted(before).await?;
+    let users = user_repository.purge_deleted(before).await?;
+
+    Ok((quests, users))
+}
+
+pub struct PurgeDeletedJob {
+    quest_repository: Arc<dyn QuestRepository>,
+    user_repository: Arc<dyn UserRepository>,
+}
+
+impl PurgeDeletedJob {
+    pub fn new(
+        quest_repository: Arc<dyn QuestRepository>,
+        user_repository: Arc<dyn UserRepository>,
+    ) -> Self {
+        Self {
+            quest_repository,
+            user_repository,
+        }
+    }
+}
+
+#[async_trait]
+impl ScheduledJob for PurgeDeletedJob {
+    fn name(&self) -> &'static str {
+        "purge_deleted"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+
+    async fn run(&self) -> anyhow::Result<String> {
+        let (quests, users) = purge_deleted(
+            self.quest_repository.as_ref(),
+            self.user_repository.as_ref(),
+            Utc::now(),
+        )
+        .await?;
+        Ok(format!("purged {} quests and {} users", quests, users))
+    }
+}
@@ -0,0 +1,637 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use axum::{async_trait, http::StatusCode};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    middleware::language::Language,
+    repositories::{
+        audit::{AuditAction, AuditRepository},
+        id::{ChallengeId, QuestId, UserId},
+        quest::{
+            BulkCreateQuest, CreateQuest, FindQuests, QuestEntity, QuestRanking, QuestRepository,
+            QuestStatus, QuestStatusFilter, UpdateQuest, UpdateQuestError,
+        },
+        review::ReviewRepository,
+        translation::TranslationRepository,
+        user::{UserRepository, UserRole},
+        user_challenge::UserChallengeRepository,
+        user_quest::UserQuestRepository,
+    },
+    services::{
+        audit::record_audit,
+        challenge::apply_lock_states,
+        cover_image::{CoverImageError, CoverImageUploader},
+        event_bus::{DomainEvent, EventBus},
+        i18n::translate_quests,
+        photo::Photo,
+        review::{attach_ratings, RatedQuest},
+        scheduler::ScheduledJob,
+    },
+};
+
+pub enum ParticipateQuestError {
+    NotFound,
+    /// 下書きかアーカイブ済み
+    NotPublished,
+    /// クエストの開催期間外
+    OutOfSchedule,
+    AlreadyParticipating,
+    Repository(anyhow::Error),
+}
+
+/// 公開中で開催期間中のクエストにだけ、1人1回まで参加できる
+/// RESTとGraphQLのどちらから参加しても同じ確認をするようにここにまとめる
+pub async fn participate_quest(
+    quest_repository: &dyn QuestRepository,
+    userquest_repository: &dyn UserQuestRepository,
+    user_id: UserId,
+    quest_id: QuestId,
+) -> Result<(), ParticipateQuestError> {
+    let quest = quest_repository
+        .find(quest_id.clone())
+        .await
+        .or(Err(ParticipateQuestError::NotFound))?;
+    if quest.status != QuestStatus::Published {
+        return Err(ParticipateQuestError::NotPublished);
+    }
+    if !quest.is_open_at(Utc::now()) {
+        return Err(ParticipateQuestError::OutOfSchedule);
+    }
+    let participated_quest_ids = userquest_repository
+        .get_participated_quests_by_user_id(user_id.clone())
+        .await
+        .map_err(ParticipateQuestError::Repository)?;
+    if participated_quest_ids.contains(&quest_id) {
+        return Err(ParticipateQuestError::AlreadyParticipating);
+    }
+
+    userquest_repository
+        .save_quest_participate_event(user_id, quest_id)
+        .await
+        .map_err(ParticipateQuestError::Repository)
+}
+
+#[derive(Debug)]
+pub enum QuestAccessError {
+    NotFound,
+    /// 作成者でも管理者でもない
+    Forbidden,
+    Repository(anyhow::Error),
+}
+
+impl From<QuestAccessError> for ApiError {
+    fn from(e: QuestAccessError) -> Self {
+        match e {
+            QuestAccessError::NotFound => {
+                ApiError::new(StatusCode::NOT_FOUND, ErrorCode::QuestNotFound)
+            }
+            QuestAccessError::Forbidden => StatusCode::FORBIDDEN.into(),
+            QuestAccessError::Repository(e) => {
+                tracing::error!("failed to check quest access: {}", e);
+                StatusCode::SERVICE_UNAVAILABLE.into()
+            }
+        }
+    }
+}
+
+/// クエストの作成者と管理者だけに許す操作の前に呼ぶ
+/// 作成者が記録されていないクエストは管理者だけが操作できる
+pub async fn authorize_quest_owner(
+    quest_repository: &dyn QuestRepository,
+    user_repository: &dyn UserRepository,
+    quest_id: QuestId,
+    user_id: UserId,
+) -> Result<(), QuestAccessError> {
+    let created_by = quest_repository
+        .find_creator(quest_id)
+        .await
+        .or(Err(QuestAccessError::NotFound))?;
+    if created_by.as_ref() == Some(&user_id) {
+        return Ok(());
+    }
+
+    match user_repository.find_role(user_id).await {
+        Ok(Some(UserRole::Admin)) => Ok(()),
+        Ok(_) => Err(QuestAccessError::Forbidden),
+        Err(e) => Err(QuestAccessError::Repository(e)),
+    }
+}
+
+/// 管理者か。未ログインならfalse
+pub async fn is_admin(
+    user_repository: &dyn UserRepository,
+    user_id: Option<UserId>,
+) -> anyhow::Result<bool> {
+    match user_id {
+        Some(user_id) => Ok(user_repository.find_role(user_id).await? == Some(UserRole::Admin)),
+        None => Ok(false),
+    }
+}
+
+/// 下書きは作成者と管理者にだけ見せる。公開中とアーカイブ済みは誰でも見られる
+pub async fn can_view(
+    quest_repository: &dyn QuestRepository,
+    user_repository: &dyn UserRepository,
+    quest: &QuestEntity,
+    user_id: Option<UserId>,
+) -> anyhow::Result<bool> {
+    let user_id = match (quest.status, user_id) {
+        (QuestStatus::Draft, Some(user_id)) => user_id,
+        (QuestStatus::Draft, None) => return Ok(false),
+        _ => return Ok(true),
+    };
+    match authorize_quest_owner(quest_repository, user_repository, quest.id.clone(), user_id).await
+    {
+        Ok(()) => Ok(true),
+        Err(QuestAccessError::NotFound | QuestAccessError::Forbidden) => Ok(false),
+        Err(QuestAccessError::Repository(e)) => Err(e),
+    }
+}
+
+/// 参加中のクエストとその進捗
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParticipatedQuest {
+    pub quest: QuestEntity,
+    pub completed_challenges: usize,
+    pub total_challenges: usize,
+    /// 0.0〜1.0。チャレンジのないクエストは0
+    pub progress: f64,
+}
+
+impl ParticipatedQuest {
+    fn new(quest: QuestEntity, completed_challenge_ids: &[ChallengeId]) -> Self {
+        let total_challenges = quest.challenges.len();
+        let completed_challenges = quest
+            .challenges
+            .iter()
+            .filter(|challenge| completed_challenge_ids.contains(&challenge.id))
+            .count();
+        let progress = if total_challenges == 0 {
+            0.0
+        } else {
+            completed_challenges as f64 / total_challenges as f64
+        };
+
+        Self {
+            quest,
+            completed_challenges,
+            total_challenges,
+            progress,
+        }
+    }
+}
+
+/// 参加中のクエストをチャレンジ込みで参加順に返す。削除済みのクエストは含めない
+pub async fn find_participated_quests(
+    quest_repository: &dyn QuestRepository,
+    userquest_repository: &dyn UserQuestRepository,
+    userchallenge_repository: &dyn UserChallengeRepository,
+    user_id: UserId,
+) -> anyhow::Result<Vec<ParticipatedQuest>> {
+    let quest_ids = userquest_repository
+        .get_participated_quests_by_user_id(user_id.clone())
+        .await?;
+    let mut quests = quest_repository.find_by_ids(quest_ids.clone()).await?;
+    let completed_challenge_ids = userchallenge_repository
+        .get_completed_challenges_by_user_id(user_id)
+        .await?;
+
+    Ok(quest_ids
+        .into_iter()
+        .filter_map(|id| {
+            let index = quests.iter().position(|quest| quest.id == id)?;
+            Some(quests.swap_remove(index))
+        })
+        .map(|quest| ParticipatedQuest::new(quest, &completed_challenge_ids))
+        .collect())
+}
+
+#[derive(Debug)]
+pub enum QuestError {
+    NotFound,
+    /// 公開中以外のクエストの一覧を管理者以外が求めた
+    Forbidden,
+    Repository(anyhow::Error),
+}
+
+#[derive(Debug)]
+pub enum UpdateCoverImageError {
+    NotFound,
+    Image(CoverImageError),
+}
+
+/// クエストの作成・取得・更新をまとめる。ハンドラはリポジトリを直接呼ばずにこれを通す
+/// 監査ログの記録とイベントの発行もここで行う
+#[derive(Clone)]
+pub struct QuestService {
+    quest_repository: Arc<dyn QuestRepository>,
+    user_repository: Arc<dyn UserRepository>,
+    userchallenge_repository: Arc<dyn UserChallengeRepository>,
+    review_repository: Arc<dyn ReviewRepository>,
+    translation_repository: Arc<dyn TranslationRepository>,
+    audit_repository: Arc<dyn AuditRepository>,
+    event_bus: EventBus,
+}
+
+impl QuestService {
+    pub fn new(
+        quest_repository: Arc<dyn QuestRepository>,
+        user_repository: Arc<dyn UserRepository>,
+        userchallenge_repository: Arc<dyn UserChallengeRepository>,
+        review_repository: Arc<dyn ReviewRepository>,
+        translation_repository: Arc<dyn TranslationRepository>,
+        audit_repository: Arc<dyn AuditRepository>,
+        event_bus: EventBus,
+    ) -> Self {
+        Self {
+            quest_repository,
+            user_repository,
+            userchallenge_repository,
+            review_repository,
+            translation_repository,
+            audit_repository,
+            event_bus,
+        }
+    }
+
+    pub async fn create(
+        &self,
+        payload: CreateQuest,
+        actor: Option<UserId>,
+    ) -> anyhow::Result<QuestEntity> {
+        let quest = self
+            .quest_repository
+            .create(payload.with_creator(actor.clone()))
+            .await?;
+        self.created(&quest, actor).await;
+
+        Ok(quest)
+    }
+
+    pub async fn create_with_challenges(
+        &self,
+        payload: BulkCreateQuest,
+        actor: Option<UserId>,
+    ) -> anyhow::Result<QuestEntity> {
+        let quest = self
+            .quest_repository
+            .create_with_challenges(payload.with_creator(actor.clone()))
+            .await?;
+        self.created(&quest, actor).await;
+
+        Ok(quest)
+    }
+
+    /// チャレンジも含めて下書きとして複製する
+    pub async fn duplicate(
+        &self,
+        id: QuestId,
+        actor: Option<UserId>,
+    ) -> Result<QuestEntity, QuestError> {
+        let quest = self
+            .quest_repository
+            .duplicate(id, actor.clone())
+            .await
+            .or(Err(QuestError::NotFound))?;
+        self.created(&quest, actor).await;
+
+        Ok(quest)
+    }
+
+    async fn created(&self, quest: &QuestEntity, actor: Option<UserId>) {
+        record_audit(
+            self.audit_repository.as_ref(),
+            actor,
+            AuditAction::QuestCreate,
+            quest.id.to_string(),
+        )
+        .await;
+        self.event_bus.publish(DomainEvent::QuestCreated {
+            quest: quest.clone(),
+        });
+    }
+
+    /// 未ログインの場合は何も完了していないユーザーとして解放状態を返す
+    /// 見られない下書きは存在しないものとして扱う
+    pub async fn find(
+        &self,
+        id: QuestId,
+        language: &Language,
+        user_id: Option<UserId>,
+    ) -> Result<RatedQuest, QuestError> {
+        let quest = self
+            .quest_repository
+            .find(id)
+            .await
+            .or(Err(QuestError::NotFound))?;
+        if !self
+            .can_view(&quest, user_id.clone())
+            .await
+            .map_err(QuestError::Repository)?
+        {
+            return Err(QuestError::NotFound);
+        }
+
+        self.present(vec![quest], language, user_id)
+            .await
+            .map(|mut quests| quests.remove(0))
+            .map_err(QuestError::Repository)
+    }
+
+    /// 存在しないIDと見られない下書きは結果に含めない
+    pub async fn batch_find(
+        &self,
+        ids: Vec<QuestId>,
+        language: &Language,
+        user_id: Option<UserId>,
+    ) -> anyhow::Result<HashMap<QuestId, RatedQuest>> {
+        let mut quests = Vec::new();
+        for quest in self.quest_repository.find_by_ids(ids).await? {
+            if self.can_view(&quest, user_id.clone()).await? {
+                quests.push(quest);
+            }
+        }
+
+        Ok(self
+            .present(quests, language, user_id)
+            .await?
+            .into_iter()
+            .map(|quest| (quest.quest.id.clone(), quest))
+            .collect())
+    }
+
+    async fn can_view(&self, quest: &QuestEntity, user_id: Option<UserId>) -> anyhow::Result<bool> {
+        can_view(
+            self.quest_repository.as_ref(),
+            self.user_repository.as_ref(),
+            quest,
+            user_id,
+        )
+        .await
+    }
+
+    /// 翻訳し、解放状態と評価を付ける
+    async fn present(
+        &self,
+        mut quests: Vec<QuestEntity>,
+        language: &Language,
+        user_id: Option<UserId>,
+    ) -> anyhow::Result<Vec<RatedQuest>> {
+        translate_quests(self.translation_repository.as_ref(), language, &mut quests).await?;
+        let completed_challenge_ids = match user_id {
+            Some(user_id) => {
+                self.userchallenge_repository
+                    .get_completed_challenges_by_user_id(user_id)
+                    .await?
+            }
+            None => Vec::new(),
+        };
+        for quest in quests.iter_mut() {
+            apply_lock_states(quest, &completed_challenge_ids);
+        }
+
+        attach_ratings(self.review_repository.as_ref(), quests).await
+    }
+
+    /// rankingはqueryから検証済みのもの。あればsortより優先する
+    /// 公開中以外のstatusを指定できるのは管理者だけ
+    pub async fn all(
+        &self,
+        query: &FindQuests,
+        ranking: Option<QuestRanking>,
+        language: &Language,
+        user_id: Option<UserId>,
+    ) -> Result<Vec<RatedQuest>, QuestError> {
+        if QuestStatusFilter::is_restricted(query.status)
+            && !is_admin(self.user_repository.as_ref(), user_id)
+                .await
+                .map_err(QuestError::Repository)?
+        {
+            return Err(QuestError::Forbidden);
+        }
+        self.find_all(query, ranking, language)
+            .await
+            .map_err(QuestError::Repository)
+    }
+
+    async fn find_all(
+        &self,
+        query: &FindQuests,
+        ranking: Option<QuestRanking>,
+        language: &Language,
+    ) -> anyhow::Result<Vec<RatedQuest>> {
+        let status = QuestStatusFilter::resolve(query.status);
+        let active_at = query.active.then(Utc::now);
+        let mut quests = self.quest_repository.all(status, active_at).await?;
+        if let Some(ranking) = ranking {
+            let quest_ids = quests.iter().map(|quest| quest.id.clone()).collect();
+            let positions: HashMap<QuestId, usize> = self
+                .quest_repository
+                .rank(quest_ids, ranking)
+                .await?
+                .into_iter()
+                .enumerate()
+                .map(|(position, id)| (id, position))
+                .collect();
+            quests.sort_by_key(|quest| positions.get(&quest.id).copied().unwrap_or(usize::MAX));
+        } else if let Some(sort) = query.sort {
+            sort.sort(&mut quests);
+        }
+        translate_quests(self.translation_repository.as_ref(), language, &mut quests).await?;
+
+        attach_ratings(self.review_repository.as_ref(), quests).await
+    }
+
+    pub async fn update(
+        &self,
+        id: QuestId,
+        payload: UpdateQuest,
+        actor: Option<UserId>,
+    ) -> Result<QuestEntity, UpdateQuestError> {
+        let quest = self.quest_repository.update(id, payload).await?;
+        self.updated(&quest, actor).await;
+
+        Ok(quest)
+    }
+
+    /// サイズごとに縮小して保存し、クエストの表紙に設定する
+    pub async fn update_cover_image(
+        &self,
+        id: QuestId,
+        photo: Photo,
+        uploader: &CoverImageUploader,
+        actor: Option<UserId>,
+    ) -> Result<QuestEntity, UpdateCoverImageError> {
+        // 存在しないクエストの画像を保存しないように先に確かめる
+        self.quest_repository
+            .find(id.clone())
+            .await
+            .or(Err(UpdateCoverImageError::NotFound))?;
+        let cover_image = uploader
+            .upload(&id, photo)
+            .await
+            .map_err(UpdateCoverImageError::Image)?;
+        let quest = self
+            .quest_repository
+            .update_cover_image(id, cover_image)
+            .await
+            .or(Err(UpdateCoverImageError::NotFound))?;
+        self.updated(&quest, actor).await;
+
+        Ok(quest)
+    }
+
+    async fn updated(&self, quest: &QuestEntity, actor: Option<UserId>) {
+        record_audit(
+            self.audit_repository.as_ref(),
+            actor,
+            AuditAction::QuestUpdate,
+            quest.id.to_string(),
+        )
+        .await;
+        self.event_bus.publish(DomainEvent::QuestUpdated {
+            quest: quest.clone(),
+        });
+    }
+
+    /// 削除できなかったときは監査ログを残さない
+    pub async fn delete(&self, id: QuestId, actor: Option<UserId>) -> Result<(), QuestError> {
+        self.quest_repository
+            .delete(id.clone())
+            .await
+            .or(Err(QuestError::NotFound))?;
+        record_audit(
+            self.audit_repository.as_ref(),
+            actor,
+            AuditAction::QuestDelete,
+            id.into(),
+        )
+        .await;
+
+        Ok(())
+    }
+}
+
+/// 終了日時を過ぎた公開中のクエストをアーカイブする
+pub struct ArchiveExpiredQuestsJob {
+    quest_repository: Arc<dyn QuestRepository>,
+}
+
+impl ArchiveExpiredQuestsJob {
+    pub fn new(quest_repository: Arc<dyn QuestRepository>) -> Self {
+        Self { quest_repository }
+    }
+}
+
+#[async_trait]
+impl ScheduledJob for ArchiveExpiredQuestsJob {
+    fn name(&self) -> &'static str {
+        "archive_expired_quests"
+    }
+
+    /// 終了後しばらく一覧に残っても困らないので、細かくは回さない
+    fn interval(&self) -> Duration {
+        Duration::from_secs(5 * 60)
+    }
+
+    async fn run(&self) -> anyhow::Result<String> {
+        let ids = self.quest_repository.archive_expired(Utc::now()).await?;
+        Ok(format!("archived {} quests", ids.len()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        challenge::Challenge,
+        quest::{MockQuestRepository, QuestStatus},
+        user::MockUserRepository,
+    };
+
+    fn challenge(id: &str) -> Challenge {
+        Challenge::new(
+            id.into(),
+            "Test Challenge".to_string(),
+            "description".to_string(),
+            "quest".into(),
+            35.6895,
+            139.6917,
+            "stamp".to_string(),
+            "color".to_string(),
+            "gray".to_string(),
+            "flavor".to_string(),
+        )
+    }
+
+    #[test]
+    fn should_calculate_progress_from_completed_challenges() {
+        let quest = QuestEntity {
+            challenges: vec![challenge("first"), challenge("second")],
+            ..QuestEntity::new(
+                "quest".into(),
+                "Test Quest".to_string(),
+                "description".to_string(),
+                QuestStatus::Published,
+            )
+        };
+
+        let participated = ParticipatedQuest::new(quest, &["first".into(), "other".into()]);
+
+        assert_eq!(1, participated.completed_challenges);
+        assert_eq!(2, participated.total_challenges);
+        assert_eq!(0.5, participated.progress);
+    }
+
+    #[test]
+    fn should_be_zero_progress_without_challenges() {
+        let quest = QuestEntity::new(
+            "quest".into(),
+            "Test Quest".to_string(),
+            "description".to_string(),
+            QuestStatus::Published,
+        );
+
+        assert_eq!(0.0, ParticipatedQuest::new(quest, &[]).progress);
+    }
+
+    #[tokio::test]
+    async fn should_allow_only_creator_or_admin() {
+        let mut quest_repository = MockQuestRepository::new();
+        quest_repository
+            .expect_find_creator()
+            .returning(|id| match id.as_str() {
+                "quest" => Ok(Some("creator".into())),
+                _ => Err(anyhow::anyhow!("not found")),
+            });
+        let mut user_repository = MockUserRepository::new();
+        user_repository
+            .expect_find_role()
+            .returning(|user_id| match user_id.as_str() {
+                "admin" => Ok(Some(UserRole::Admin)),
+                _ => Ok(Some(UserRole::User)),
+            });
+
+        let authorize = |quest_id: &str, user_id: &str| {
+            authorize_quest_owner(
+                &quest_repository,
+                &user_repository,
+                quest_id.into(),
+                user_id.into(),
+            )
+        };
+        assert!(authorize("quest", "creator").await.is_ok());
+        assert!(authorize("quest", "admin").await.is_ok());
+        assert!(matches!(
+            authorize("quest", "other").await,
+            Err(QuestAccessError::Forbidden)
+        ));
+        assert!(matches!(
+            authorize("missing", "admin").await,
+            Err(QuestAccessError::NotFound)
+        ));
+    }
+}
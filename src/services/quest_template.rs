@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use crate::{
+    repositories::{
+        id::{QuestId, UserId},
+        quest::{BulkCreateQuest, QuestEntity, QuestRepository},
+        quest_template::{QuestTemplate, QuestTemplateRepository},
+    },
+    services::quest::{QuestError, QuestService},
+};
+
+/// クエストをテンプレートとして保存し、テンプレートから新しいクエストを作る
+#[derive(Clone)]
+pub struct QuestTemplateService {
+    template_repository: Arc<dyn QuestTemplateRepository>,
+    quest_repository: Arc<dyn QuestRepository>,
+    quests: QuestService,
+}
+
+impl QuestTemplateService {
+    pub fn new(
+        template_repository: Arc<dyn QuestTemplateRepository>,
+        quest_repository: Arc<dyn QuestRepository>,
+        quests: QuestService,
+    ) -> Self {
+        Self {
+            template_repository,
+            quest_repository,
+            quests,
+        }
+    }
+
+    /// 保存した時点の内容を写し取る。元のクエストを後で変えてもテンプレートは変わらない
+    pub async fn save(&self, quest_id: QuestId, name: String) -> Result<QuestTemplate, QuestError> {
+        let quest = self
+            .quest_repository
+            .find(quest_id)
+            .await
+            .or(Err(QuestError::NotFound))?;
+
+        self.template_repository
+            .create(name, BulkCreateQuest::from(quest))
+            .await
+            .map_err(QuestError::Repository)
+    }
+
+    pub async fn all(&self) -> anyhow::Result<Vec<QuestTemplate>> {
+        self.template_repository.all().await
+    }
+
+    /// テンプレートの内容で下書きのクエストを作る
+    pub async fn instantiate(
+        &self,
+        id: String,
+        actor: Option<UserId>,
+    ) -> Result<QuestEntity, QuestError> {
+        let template = self
+            .template_repository
+            .find(id)
+            .await
+            .or(Err(QuestError::NotFound))?;
+
+        self.quests
+            .create_with_challenges(template.quest, actor)
+            .await
+            .map_err(QuestError::Repository)
+    }
+}
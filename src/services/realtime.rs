@@ -0,0 +1,203 @@
+use std::{collections::HashSet, sync::Arc};
+
+use axum::async_trait;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::{
+    repositories::{
+        challenge::ChallengeRepository,
+        id::{ChallengeId, QuestId, UserId},
+    },
+    services::event_bus::{DomainEvent, EventHandler},
+};
+
+/// 接続中のクライアントが処理しきれずに溜められるイベント数
+const REALTIME_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RealtimeEvent {
+    QuestParticipated {
+        user_id: UserId,
+        quest_id: QuestId,
+    },
+    ChallengeCompleted {
+        user_id: UserId,
+        quest_id: QuestId,
+        challenge_id: ChallengeId,
+    },
+}
+
+/// WebSocketでクライアントに送るメッセージ
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProgressMessage {
+    ChallengeCompleted {
+        user_id: UserId,
+        quest_id: QuestId,
+        challenge_id: ChallengeId,
+    },
+}
+
+/// ドメインイベントをWebSocketの接続ごとのreceiverに中継する
+#[derive(Clone)]
+pub struct RealtimeHub {
+    sender: broadcast::Sender<RealtimeEvent>,
+    challenge_repository: Arc<dyn ChallengeRepository>,
+}
+
+impl RealtimeHub {
+    pub fn new(challenge_repository: Arc<dyn ChallengeRepository>) -> Self {
+        let (sender, _) = broadcast::channel(REALTIME_CAPACITY);
+        Self {
+            sender,
+            challenge_repository,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<RealtimeEvent> {
+        self.sender.subscribe()
+    }
+}
+
+#[async_trait]
+impl EventHandler for RealtimeHub {
+    async fn handle(&self, event: &DomainEvent) {
+        let event = match event {
+            DomainEvent::QuestParticipated { user_id, quest_id } => {
+                RealtimeEvent::QuestParticipated {
+                    user_id: user_id.clone(),
+                    quest_id: quest_id.clone(),
+                }
+            }
+            DomainEvent::ChallengeCompleted {
+                user_id,
+                challenge_id,
+            } => match self.challenge_repository.find(challenge_id.clone()).await {
+                Ok(challenge) => RealtimeEvent::ChallengeCompleted {
+                    user_id: user_id.clone(),
+                    quest_id: challenge.quest_id,
+                    challenge_id: challenge_id.clone(),
+                },
+                Err(e) => {
+                    tracing::error!("failed to find completed challenge: {}", e);
+                    return;
+                }
+            },
+            _ => return,
+        };
+
+        // 接続中のクライアントがいなければ捨てられる
+        let _ = self.sender.send(event);
+    }
+}
+
+/// 1つの接続について、参加中のクエストでの他の参加者の進捗だけを通す
+pub struct ProgressFilter {
+    user_id: UserId,
+    quest_ids: HashSet<QuestId>,
+}
+
+impl ProgressFilter {
+    pub fn new(user_id: UserId, quest_ids: HashSet<QuestId>) -> Self {
+        Self { user_id, quest_ids }
+    }
+
+    /// 接続中に参加したクエストも配信対象に加える
+    /// 退出はイベントにならないので、再接続するまで配信対象に残る
+    pub fn accept(&mut self, event: RealtimeEvent) -> Option<ProgressMessage> {
+        match event {
+            RealtimeEvent::QuestParticipated { user_id, quest_id } => {
+                if user_id == self.user_id {
+                    self.quest_ids.insert(quest_id);
+                }
+                None
+            }
+            RealtimeEvent::ChallengeCompleted {
+                user_id,
+                quest_id,
+                challenge_id,
+            } => (user_id != self.user_id && self.quest_ids.contains(&quest_id)).then_some(
+                ProgressMessage::ChallengeCompleted {
+                    user_id,
+                    quest_id,
+                    challenge_id,
+                },
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn completed(user_id: &str, quest_id: &str) -> RealtimeEvent {
+        RealtimeEvent::ChallengeCompleted {
+            user_id: user_id.into(),
+            quest_id: quest_id.into(),
+            challenge_id: "challenge".into(),
+        }
+    }
+
+    fn filter_for(user_id: &str, quest_ids: &[&str]) -> ProgressFilter {
+        ProgressFilter::new(
+            user_id.into(),
+            quest_ids.iter().map(|&id| id.into()).collect(),
+        )
+    }
+
+    #[test]
+    fn should_deliver_other_participants_progress() {
+        let mut filter = filter_for("me", &["quest"]);
+
+        assert_eq!(
+            Some(ProgressMessage::ChallengeCompleted {
+                user_id: "other".into(),
+                quest_id: "quest".into(),
+                challenge_id: "challenge".into(),
+            }),
+            filter.accept(completed("other", "quest"))
+        );
+    }
+
+    #[test]
+    fn should_skip_own_progress_and_other_quests() {
+        let mut filter = filter_for("me", &["quest"]);
+
+        assert_eq!(None, filter.accept(completed("me", "quest")));
+        assert_eq!(None, filter.accept(completed("other", "another_quest")));
+    }
+
+    #[test]
+    fn should_deliver_quests_participated_while_connected() {
+        let mut filter = filter_for("me", &[]);
+        assert_eq!(None, filter.accept(completed("other", "quest")));
+
+        filter.accept(RealtimeEvent::QuestParticipated {
+            user_id: "me".into(),
+            quest_id: "quest".into(),
+        });
+
+        assert!(filter.accept(completed("other", "quest")).is_some());
+    }
+
+    #[test]
+    fn should_serialize_message_with_type_tag() {
+        let message = ProgressMessage::ChallengeCompleted {
+            user_id: "other".into(),
+            quest_id: "quest".into(),
+            challenge_id: "challenge".into(),
+        };
+
+        assert_eq!(
+            serde_json::json!({
+                "type": "challenge_completed",
+                "user_id": "other",
+                "quest_id": "quest",
+                "challenge_id": "challenge",
+            }),
+            serde_json::to_value(message).unwrap()
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::repositories::{
+    id::QuestId,
+    quest::QuestEntity,
+    review::{RatingSummary, ReviewRepository},
+};
+
+/// 評価付きのクエスト。評価は更新が多いのでクエストのキャッシュには含めず、返すときに付ける
+/// 一覧ではQuestSummaryに変えてから返す
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RatedQuest<Q = QuestEntity> {
+    #[serde(flatten)]
+    pub quest: Q,
+    pub rating: RatingSummary,
+}
+
+impl<Q> RatedQuest<Q> {
+    pub fn map<R>(self, f: impl FnOnce(Q) -> R) -> RatedQuest<R> {
+        RatedQuest {
+            quest: f(self.quest),
+            rating: self.rating,
+        }
+    }
+}
+
+pub async fn attach_ratings(
+    review_repository: &dyn ReviewRepository,
+    quests: Vec<QuestEntity>,
+) -> anyhow::Result<Vec<RatedQuest>> {
+    let quest_ids: Vec<QuestId> = quests.iter().map(|quest| quest.id.clone()).collect();
+    let summaries = review_repository.summarize(&quest_ids).await?;
+    Ok(rate(quests, summaries))
+}
+
+fn rate(
+    quests: Vec<QuestEntity>,
+    mut summaries: HashMap<QuestId, RatingSummary>,
+) -> Vec<RatedQuest> {
+    quests
+        .into_iter()
+        .map(|quest| {
+            let rating = summaries.remove(&quest.id).unwrap_or_default();
+            RatedQuest { quest, rating }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::quest::QuestStatus;
+
+    fn quest(id: &str) -> QuestEntity {
+        QuestEntity::new(
+            id.into(),
+            "Test Quest".to_string(),
+            "This is a test quest.".to_string(),
+            QuestStatus::Published,
+        )
+    }
+
+    #[test]
+    fn should_attach_empty_rating_to_unreviewed_quest() {
+        let summaries = HashMap::from([(
+            "reviewed".into(),
+            RatingSummary {
+                average: Some(4.5),
+                count: 2,
+            },
+        )]);
+
+        let rated = rate(vec![quest("reviewed"), quest("unreviewed")], summaries);
+
+        assert_eq!(Some(4.5), rated[0].rating.average);
+        assert_eq!(2, rated[0].rating.count);
+        assert_eq!(RatingSummary::default(), rated[1].rating);
+    }
+
+    #[test]
+    fn should_flatten_quest_fields() {
+        let rated = rate(vec![quest("reviewed")], HashMap::new());
+
+        let json = serde_json::to_value(&rated[0]).unwrap();
+
+        assert_eq!("reviewed", json["id"]);
+        assert_eq!(0, json["rating"]["count"]);
+    }
+}
@@ -0,0 +1,219 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::async_trait;
+use tokio::time::MissedTickBehavior;
+
+use crate::repositories::scheduled_job::ScheduledJobRepository;
+
+/// 定期処理。Schedulerに登録するとintervalごとに実行される
+#[async_trait]
+pub trait ScheduledJob: Send + Sync + 'static {
+    /// 履歴とロックのキー。変えると実行中の別インスタンスと重複して動くことがある
+    fn name(&self) -> &'static str;
+    fn interval(&self) -> Duration;
+    /// 処理内容の要約を返す。履歴に残す
+    async fn run(&self) -> anyhow::Result<String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    /// 他のインスタンスが実行中だった
+    Skipped,
+    Succeeded,
+    Failed,
+}
+
+/// 登録したジョブをそれぞれのintervalで実行する
+/// 複数インスタンスで動かしても、アドバイザリロックで同じジョブは同時に1つしか動かない
+pub struct Scheduler {
+    repository: Arc<dyn ScheduledJobRepository>,
+    jobs: Vec<Arc<dyn ScheduledJob>>,
+}
+
+impl Scheduler {
+    pub fn new(repository: Arc<dyn ScheduledJobRepository>) -> Self {
+        Self {
+            repository,
+            jobs: Vec::new(),
+        }
+    }
+
+    pub fn register(mut self, job: Arc<dyn ScheduledJob>) -> Self {
+        self.jobs.push(job);
+        self
+    }
+
+    /// ジョブごとにタスクを起動する。最初の実行は起動直後
+    pub fn start(self) {
+        for job in self.jobs {
+            let repository = self.repository.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(job.interval());
+                // 実行が長引いても取りこぼした回をまとめて実行しない
+                interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                loop {
+                    interval.tick().await;
+                    run_job(repository.as_ref(), job.as_ref()).await;
+                }
+            });
+        }
+    }
+}
+
+/// ロックを取れたときだけ実行し、結果を履歴に残す
+pub async fn run_job(
+    repository: &dyn ScheduledJobRepository,
+    job: &dyn ScheduledJob,
+) -> RunOutcome {
+    let lock = match repository.try_lock(job.name()).await {
+        Ok(Some(lock)) => lock,
+        Ok(None) => {
+            tracing::debug!(
+                "scheduled job {} is running on another instance",
+                job.name()
+            );
+            return RunOutcome::Skipped;
+        }
+        Err(e) => {
+            tracing::warn!("failed to lock scheduled job {}: {}", job.name(), e);
+            return RunOutcome::Failed;
+        }
+    };
+
+    // 履歴を書けなくても処理は行う
+    let run_id = match repository.start_run(job.name()).await {
+        Ok(id) => Some(id),
+        Err(e) => {
+            tracing::warn!("failed to record start of {}: {}", job.name(), e);
+            None
+        }
+    };
+    let result = job.run().await.map_err(|e| e.to_string());
+    let outcome = match &result {
+        Ok(summary) => {
+            tracing::info!("scheduled job {} succeeded: {}", job.name(), summary);
+            RunOutcome::Succeeded
+        }
+        Err(e) => {
+            tracing::warn!("scheduled job {} failed: {}", job.name(), e);
+            RunOutcome::Failed
+        }
+    };
+    if let Some(id) = run_id {
+        if let Err(e) = repository.finish_run(id, result).await {
+            tracing::warn!("failed to record result of {}: {}", job.name(), e);
+        }
+    }
+
+    if let Err(e) = lock.release().await {
+        tracing::warn!("failed to release lock of {}: {}", job.name(), e);
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::scheduled_job::{ScheduledJobLock, ScheduledJobRun};
+    use std::sync::Mutex;
+
+    struct NoopLock;
+
+    #[async_trait]
+    impl ScheduledJobLock for NoopLock {
+        async fn release(self: Box<Self>) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// 他のインスタンスが実行中かどうかを切り替えられる履歴置き場
+    struct FakeRepository {
+        locked_elsewhere: bool,
+        results: Mutex<Vec<Result<String, String>>>,
+    }
+
+    impl FakeRepository {
+        fn new(locked_elsewhere: bool) -> Self {
+            Self {
+                locked_elsewhere,
+                results: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ScheduledJobRepository for FakeRepository {
+        async fn try_lock(
+            &self,
+            _job_name: &str,
+        ) -> anyhow::Result<Option<Box<dyn ScheduledJobLock>>> {
+            if self.locked_elsewhere {
+                return Ok(None);
+            }
+            Ok(Some(Box::new(NoopLock)))
+        }
+
+        async fn start_run(&self, _job_name: &str) -> anyhow::Result<i64> {
+            Ok(1)
+        }
+
+        async fn finish_run(&self, _id: i64, result: Result<String, String>) -> anyhow::Result<()> {
+            self.results.lock().unwrap().push(result);
+            Ok(())
+        }
+
+        async fn find_runs(
+            &self,
+            _job_name: Option<String>,
+        ) -> anyhow::Result<Vec<ScheduledJobRun>> {
+            Ok(Vec::new())
+        }
+    }
+
+    struct TestJob {
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl ScheduledJob for TestJob {
+        fn name(&self) -> &'static str {
+            "test_job"
+        }
+
+        fn interval(&self) -> Duration {
+            Duration::from_secs(60)
+        }
+
+        async fn run(&self) -> anyhow::Result<String> {
+            if self.fail {
+                anyhow::bail!("boom");
+            }
+            Ok("done".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn should_record_result_of_job() {
+        let repository = FakeRepository::new(false);
+
+        let succeeded = run_job(&repository, &TestJob { fail: false }).await;
+        let failed = run_job(&repository, &TestJob { fail: true }).await;
+
+        assert_eq!(RunOutcome::Succeeded, succeeded);
+        assert_eq!(RunOutcome::Failed, failed);
+        assert_eq!(
+            vec![Ok("done".to_string()), Err("boom".to_string())],
+            *repository.results.lock().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn should_skip_job_running_on_another_instance() {
+        let repository = FakeRepository::new(true);
+
+        let outcome = run_job(&repository, &TestJob { fail: false }).await;
+
+        assert_eq!(RunOutcome::Skipped, outcome);
+        assert!(repository.results.lock().unwrap().is_empty());
+    }
+}
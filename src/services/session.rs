@@ -0,0 +1,33 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::async_trait;
+use chrono::Utc;
+
+use crate::{repositories::session::SessionRepository, services::scheduler::ScheduledJob};
+
+/// 期限切れのセッションは読まれないだけで残り続けるので定期的に消す
+pub struct DeleteExpiredSessionsJob {
+    session_repository: Arc<dyn SessionRepository>,
+}
+
+impl DeleteExpiredSessionsJob {
+    pub fn new(session_repository: Arc<dyn SessionRepository>) -> Self {
+        Self { session_repository }
+    }
+}
+
+#[async_trait]
+impl ScheduledJob for DeleteExpiredSessionsJob {
+    fn name(&self) -> &'static str {
+        "delete_expired_sessions"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+
+    async fn run(&self) -> anyhow::Result<String> {
+        let deleted = self.session_repository.delete_expired(Utc::now()).await?;
+        Ok(format!("deleted {} sessions", deleted))
+    }
+}
@@ -0,0 +1,126 @@
+use chrono::{DateTime, Duration, Utc};
+use cookie::{time::OffsetDateTime, Cookie, CookieBuilder, Expiration, SameSite};
+
+use crate::config::{CookieConfig, CookieSameSite};
+
+pub const SESSION_COOKIE_NAME: &str = "session_token";
+/// ダブルサブミット用。フロントが値を読めるようHttpOnlyにしない
+pub const CSRF_COOKIE_NAME: &str = "csrf_token";
+
+/// セッションとCSRFトークンのクッキーを作る。発行と削除で属性が違うとブラウザが別のクッキーとして扱うので、必ずここを通す
+#[derive(Debug, Clone)]
+pub struct SessionCookieBuilder {
+    config: CookieConfig,
+}
+
+impl SessionCookieBuilder {
+    pub fn new(config: CookieConfig) -> Self {
+        Self { config }
+    }
+
+    /// nowに発行したセッションの有効期限。JWTのexpにも使う
+    pub fn expires_at(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now + Duration::hours(self.config.session_hours)
+    }
+
+    pub fn session(&self, token: &str, expires_at: DateTime<Utc>) -> Cookie<'static> {
+        let expires_at = OffsetDateTime::from_unix_timestamp(expires_at.timestamp())
+            .unwrap_or(OffsetDateTime::UNIX_EPOCH);
+        self.build(token.to_string(), expires_at)
+    }
+
+    /// ログアウト・退会時に上書きして消す
+    pub fn removal(&self) -> Cookie<'static> {
+        self.build(String::new(), OffsetDateTime::UNIX_EPOCH)
+    }
+
+    /// ブラウザを閉じるまで使う。SameSiteなどはセッションと揃える
+    pub fn csrf(&self, token: &str) -> Cookie<'static> {
+        self.base(CSRF_COOKIE_NAME, token.to_string()).finish()
+    }
+
+    fn build(&self, value: String, expires_at: OffsetDateTime) -> Cookie<'static> {
+        self.base(SESSION_COOKIE_NAME, value)
+            .expires(Expiration::from(expires_at))
+            .http_only(true)
+            .finish()
+    }
+
+    fn base(&self, name: &'static str, value: String) -> CookieBuilder<'static> {
+        let builder = Cookie::build(name, value)
+            .path("/")
+            .secure(self.config.secure)
+            .same_site(match self.config.same_site {
+                CookieSameSite::Strict => SameSite::Strict,
+                CookieSameSite::Lax => SameSite::Lax,
+                CookieSameSite::None => SameSite::None,
+            });
+        match &self.config.domain {
+            Some(domain) => builder.domain(domain.clone()),
+            None => builder,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn should_build_session_cookie_from_config() {
+        let builder = SessionCookieBuilder::new(CookieConfig {
+            secure: false,
+            same_site: CookieSameSite::Lax,
+            domain: Some("example.com".to_string()),
+            session_hours: 8,
+        });
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        let cookie = builder.session("token", builder.expires_at(now));
+
+        assert_eq!("token", cookie.value());
+        assert_eq!(Some(false), cookie.secure());
+        assert_eq!(Some(true), cookie.http_only());
+        assert_eq!(Some(SameSite::Lax), cookie.same_site());
+        assert_eq!(Some("example.com"), cookie.domain());
+        assert_eq!(
+            Some(
+                Utc.with_ymd_and_hms(2024, 1, 1, 8, 0, 0)
+                    .unwrap()
+                    .timestamp()
+            ),
+            cookie
+                .expires_datetime()
+                .map(|expires| expires.unix_timestamp())
+        );
+    }
+
+    #[test]
+    fn should_remove_with_same_attributes() {
+        let builder = SessionCookieBuilder::new(CookieConfig {
+            domain: Some("example.com".to_string()),
+            ..CookieConfig::default()
+        });
+
+        let cookie = builder.removal();
+
+        assert_eq!("", cookie.value());
+        assert_eq!(Some("/"), cookie.path());
+        assert_eq!(Some("example.com"), cookie.domain());
+        assert_eq!(Some(SameSite::None), cookie.same_site());
+        assert_eq!(Some(OffsetDateTime::UNIX_EPOCH), cookie.expires_datetime());
+    }
+
+    #[test]
+    fn should_expose_csrf_cookie_to_scripts() {
+        let builder = SessionCookieBuilder::new(CookieConfig::default());
+
+        let cookie = builder.csrf("token");
+
+        assert_eq!(CSRF_COOKIE_NAME, cookie.name());
+        assert_eq!(None, cookie.http_only());
+        assert_eq!(Some(true), cookie.secure());
+        assert_eq!(None, cookie.expires());
+    }
+}
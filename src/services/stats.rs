@@ -0,0 +1,32 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::async_trait;
+
+use crate::{repositories::stats::StatsRepository, services::scheduler::ScheduledJob};
+
+/// 管理画面の集計を作り直す。前日までの数字が見られれば足りるので日次で回す
+pub struct RefreshStatsJob {
+    stats_repository: Arc<dyn StatsRepository>,
+}
+
+impl RefreshStatsJob {
+    pub fn new(stats_repository: Arc<dyn StatsRepository>) -> Self {
+        Self { stats_repository }
+    }
+}
+
+#[async_trait]
+impl ScheduledJob for RefreshStatsJob {
+    fn name(&self) -> &'static str {
+        "refresh_stats"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(24 * 60 * 60)
+    }
+
+    async fn run(&self) -> anyhow::Result<String> {
+        self.stats_repository.refresh().await?;
+        Ok("refreshed stats".to_string())
+    }
+}
@@ -0,0 +1,94 @@
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::repositories::{
+    id::UserId, user_challenge::UserChallengeRepository, user_quest::UserQuestRepository,
+};
+
+/// `GET /me/summary` で返す個人の実績
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserSummary {
+    pub participated_quests: i64,
+    pub completed_challenges: i64,
+    pub stamps: i64,
+    /// チャレンジを完了した日が今日(または昨日)まで何日続いているか
+    pub streak_days: i64,
+}
+
+/// 参加・完了の記録を集めて実績をまとめる。todayは日本時間の日付
+pub async fn build_user_summary(
+    userquest_repository: &dyn UserQuestRepository,
+    userchallenge_repository: &dyn UserChallengeRepository,
+    user_id: UserId,
+    today: NaiveDate,
+) -> anyhow::Result<UserSummary> {
+    let (participated_quest_ids, completed_challenge_ids, stamps, completed_dates) = tokio::try_join!(
+        userquest_repository.get_participated_quests_by_user_id(user_id.clone()),
+        userchallenge_repository.get_completed_challenges_by_user_id(user_id.clone()),
+        userchallenge_repository.count_stamps(user_id.clone()),
+        userchallenge_repository.find_completed_dates(user_id),
+    )?;
+
+    Ok(UserSummary {
+        participated_quests: participated_quest_ids.len() as i64,
+        completed_challenges: completed_challenge_ids.len() as i64,
+        stamps,
+        streak_days: current_streak(&completed_dates, today),
+    })
+}
+
+/// 活動した日付(新しい順・重複なし)から、今日まで続いている連続日数を数える
+/// 今日まだ活動していなくても、昨日まで続いていれば途切れたことにはしない
+pub fn current_streak(dates: &[NaiveDate], today: NaiveDate) -> i64 {
+    let mut expected = match dates.first() {
+        Some(&latest) if latest == today || latest == today - Duration::days(1) => latest,
+        _ => return 0,
+    };
+    let mut streak = 0;
+    for &date in dates {
+        if date != expected {
+            break;
+        }
+        streak += 1;
+        expected = date - Duration::days(1);
+    }
+    streak
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, day).unwrap()
+    }
+
+    #[test]
+    fn should_count_consecutive_days_until_today() {
+        assert_eq!(
+            3,
+            current_streak(&[date(10), date(9), date(8), date(6)], date(10))
+        );
+    }
+
+    #[test]
+    fn should_keep_streak_until_yesterday() {
+        assert_eq!(2, current_streak(&[date(9), date(8)], date(10)));
+    }
+
+    #[test]
+    fn should_reset_streak_after_a_gap() {
+        assert_eq!(0, current_streak(&[date(8), date(7)], date(10)));
+        assert_eq!(0, current_streak(&[], date(10)));
+    }
+
+    #[test]
+    fn should_count_across_month_boundary() {
+        let dates = [
+            date(1),
+            NaiveDate::from_ymd_opt(2024, 2, 29).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 2, 28).unwrap(),
+        ];
+        assert_eq!(3, current_streak(&dates, date(1)));
+    }
+}
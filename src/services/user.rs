@@ -1,35 +1,315 @@
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use axum::http::StatusCode;
+use jsonwebtoken::{
+    decode, decode_header, encode, errors::ErrorKind, Algorithm, DecodingKey, EncodingKey, Header,
+    Validation,
+};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{
+    config::{JwtConfig, JwtKeyConfig, DEFAULT_JWT_KID},
+    error::{ApiError, ErrorCode},
+    repositories::{
+        audit::{AuditAction, AuditRepository},
+        id::UserId,
+        user::{LoginError, LoginUser, RegisterUser, UserEntity, UserRepository},
+    },
+    services::{
+        audit::record_audit,
+        event_bus::{DomainEvent, EventBus},
+    },
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
-    pub user_id: String,
+    pub user_id: UserId,
     iat: i64,
     exp: i64,
 }
 
-pub fn create_jwt(user_id: &String, iat: i64, exp: &i64, secret_key: &String) -> String {
+/// セッショントークンを受け付けなかった理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwtError {
+    /// 再ログインすれば通る
+    Expired,
+    /// 鍵が違う、または改ざんされている
+    InvalidSignature,
+    /// JWTとして読めない
+    Malformed,
+}
+
+impl JwtError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Expired => ErrorCode::TokenExpired,
+            Self::InvalidSignature => ErrorCode::InvalidToken,
+            Self::Malformed => ErrorCode::MalformedToken,
+        }
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for JwtError {
+    fn from(e: jsonwebtoken::errors::Error) -> Self {
+        match e.kind() {
+            ErrorKind::ExpiredSignature => Self::Expired,
+            ErrorKind::InvalidSignature | ErrorKind::InvalidAlgorithm => Self::InvalidSignature,
+            _ => Self::Malformed,
+        }
+    }
+}
+
+impl From<JwtError> for ApiError {
+    fn from(e: JwtError) -> Self {
+        ApiError::new(StatusCode::UNAUTHORIZED, e.code())
+    }
+}
+
+/// セッショントークンの鍵一式。鍵を入れ替えても、古い鍵で発行したトークンは期限まで使える
+#[derive(Debug, Clone)]
+pub struct JwtKeyRing {
+    /// 先頭が発行に使う鍵
+    keys: Vec<JwtKeyConfig>,
+    algorithm: Algorithm,
+}
+
+impl JwtKeyRing {
+    pub fn new(config: JwtConfig) -> Self {
+        Self {
+            keys: config.keys,
+            algorithm: config.algorithm,
+        }
+    }
+
+    /// 鍵が1つだけの鍵一式
+    pub fn from_secret(secret: &str) -> Self {
+        Self::new(JwtConfig {
+            keys: vec![JwtKeyConfig {
+                kid: DEFAULT_JWT_KID.to_string(),
+                secret: secret.to_string(),
+            }],
+            algorithm: Algorithm::HS256,
+        })
+    }
+
+    /// 発行に使う鍵。修了証の署名もこの鍵で行う
+    pub fn signing_secret(&self) -> &str {
+        &self.keys[0].secret
+    }
+
+    /// 検証に使う鍵。新しい順
+    pub fn secrets(&self) -> impl Iterator<Item = &str> {
+        self.keys.iter().map(|key| key.secret.as_str())
+    }
+
+    /// kidのないトークンは導入前に発行したものなので、すべての鍵で試す
+    fn candidates(&self, kid: Option<&str>) -> Vec<&JwtKeyConfig> {
+        match kid {
+            Some(kid) => self.keys.iter().filter(|key| key.kid == kid).collect(),
+            None => self.keys.iter().collect(),
+        }
+    }
+}
+
+pub fn create_jwt(user_id: &UserId, iat: i64, exp: &i64, key_ring: &JwtKeyRing) -> String {
     let my_claims = Claims {
         user_id: user_id.clone(),
         iat: iat,
         exp: *exp,
     };
+    let key = &key_ring.keys[0];
+    let header = Header {
+        kid: Some(key.kid.clone()),
+        ..Header::new(key_ring.algorithm)
+    };
 
     encode(
-        &Header::default(),
+        &header,
         &my_claims,
-        &EncodingKey::from_secret(secret_key.as_ref()),
+        &EncodingKey::from_secret(key.secret.as_ref()),
     )
     .expect("Failed to encode token. Likely wrong secret keys")
 }
 
-pub fn decode_jwt(
-    jwt: &str,
-    secret_key: &String,
-) -> Result<TokenData<Claims>, jsonwebtoken::errors::Error> {
-    decode::<Claims>(
-        jwt,
-        &DecodingKey::from_secret(secret_key.as_ref()),
-        &Validation::default(),
-    )
+pub fn decode_jwt(jwt: &str, key_ring: &JwtKeyRing) -> Result<Claims, JwtError> {
+    let header = decode_header(jwt)?;
+    let validation = Validation::new(key_ring.algorithm);
+    let mut result = Err(JwtError::InvalidSignature);
+    for key in key_ring.candidates(header.kid.as_deref()) {
+        result = decode::<Claims>(
+            jwt,
+            &DecodingKey::from_secret(key.secret.as_ref()),
+            &validation,
+        )
+        .map(|token| token.claims)
+        .map_err(JwtError::from);
+        // 署名が合った鍵で期限切れなら、他の鍵を試しても結果は変わらない
+        if !matches!(result, Err(JwtError::InvalidSignature)) {
+            break;
+        }
+    }
+
+    result
+}
+
+/// 登録・ログインと退会をまとめる。セッションのトークンやクッキーはハンドラで扱う
+#[derive(Clone)]
+pub struct UserService {
+    user_repository: Arc<dyn UserRepository>,
+    audit_repository: Arc<dyn AuditRepository>,
+    event_bus: EventBus,
+}
+
+impl UserService {
+    pub fn new(
+        user_repository: Arc<dyn UserRepository>,
+        audit_repository: Arc<dyn AuditRepository>,
+        event_bus: EventBus,
+    ) -> Self {
+        Self {
+            user_repository,
+            audit_repository,
+            event_bus,
+        }
+    }
+
+    pub async fn register(&self, payload: RegisterUser) -> anyhow::Result<UserEntity> {
+        let user = self.user_repository.register(payload).await?;
+        self.event_bus
+            .publish(DomainEvent::UserRegistered { user: user.clone() });
+
+        Ok(user)
+    }
+
+    pub async fn login(&self, payload: LoginUser) -> Result<UserEntity, LoginError> {
+        self.user_repository.login(payload).await
+    }
+
+    pub async fn find(&self, id: UserId) -> anyhow::Result<UserEntity> {
+        self.user_repository.find(id).await
+    }
+
+    pub async fn delete(&self, id: UserId, actor: UserId) -> anyhow::Result<()> {
+        self.user_repository.delete(id.clone()).await?;
+        record_audit(
+            self.audit_repository.as_ref(),
+            Some(actor),
+            AuditAction::UserDelete,
+            id.into(),
+        )
+        .await;
+
+        Ok(())
+    }
+
+    /// 記録は匿名化して残す
+    pub async fn deactivate(&self, id: UserId) -> anyhow::Result<()> {
+        self.user_repository.deactivate(id.clone()).await?;
+        record_audit(
+            self.audit_repository.as_ref(),
+            Some(id.clone()),
+            AuditAction::UserDeactivate,
+            id.into(),
+        )
+        .await;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn token(key_ring: &JwtKeyRing, expires_in: Duration) -> String {
+        let now = Utc::now();
+        create_jwt(
+            &UserId::from("test_user"),
+            now.timestamp(),
+            &(now + expires_in).timestamp(),
+            key_ring,
+        )
+    }
+
+    fn key_ring(keys: &[(&str, &str)]) -> JwtKeyRing {
+        JwtKeyRing::new(JwtConfig {
+            keys: keys
+                .iter()
+                .map(|(kid, secret)| JwtKeyConfig {
+                    kid: kid.to_string(),
+                    secret: secret.to_string(),
+                })
+                .collect(),
+            algorithm: Algorithm::HS256,
+        })
+    }
+
+    #[test]
+    fn should_decode_valid_token() {
+        let key_ring = JwtKeyRing::from_secret("secret_key");
+
+        let claims = decode_jwt(&token(&key_ring, Duration::hours(1)), &key_ring).unwrap();
+
+        assert_eq!("test_user", claims.user_id);
+    }
+
+    #[test]
+    fn should_distinguish_jwt_errors() {
+        let key_ring = JwtKeyRing::from_secret("secret_key");
+        let other = JwtKeyRing::from_secret("other_key");
+
+        assert_eq!(
+            Err(JwtError::Expired),
+            decode_jwt(&token(&key_ring, Duration::hours(-1)), &key_ring).map(|_| ())
+        );
+        assert_eq!(
+            Err(JwtError::InvalidSignature),
+            decode_jwt(&token(&other, Duration::hours(1)), &key_ring).map(|_| ())
+        );
+        assert_eq!(
+            Err(JwtError::Malformed),
+            decode_jwt("not-a-jwt", &key_ring).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn should_accept_tokens_signed_with_previous_key() {
+        let old = key_ring(&[("2024-01", "old_secret")]);
+        let rotated = key_ring(&[("2024-07", "new_secret"), ("2024-01", "old_secret")]);
+        let old_token = token(&old, Duration::hours(1));
+        let new_token = token(&rotated, Duration::hours(1));
+
+        assert!(decode_jwt(&old_token, &rotated).is_ok());
+        assert_eq!(
+            Some("2024-07".to_string()),
+            decode_header(&new_token).unwrap().kid
+        );
+        // 古い鍵を外すと、その鍵で発行したトークンは通らなくなる
+        let retired = key_ring(&[("2024-07", "new_secret")]);
+        assert_eq!(
+            Err(JwtError::InvalidSignature),
+            decode_jwt(&old_token, &retired).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn should_try_all_keys_for_token_without_kid() {
+        let rotated = key_ring(&[("2024-07", "new_secret"), ("2024-01", "old_secret")]);
+        let now = Utc::now();
+        let legacy_token = encode(
+            &Header::default(),
+            &Claims {
+                user_id: "test_user".into(),
+                iat: now.timestamp(),
+                exp: (now + Duration::hours(1)).timestamp(),
+            },
+            &EncodingKey::from_secret(b"old_secret"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            "test_user",
+            decode_jwt(&legacy_token, &rotated).unwrap().user_id
+        );
+    }
 }
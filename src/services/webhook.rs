@@ -0,0 +1,198 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::async_trait;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tokio::sync::mpsc;
+
+use crate::{
+    repositories::{
+        id::UserId,
+        user_challenge::CompleteChallenge,
+        webhook::{Webhook, WebhookEvent, WebhookRepository},
+    },
+    services::event_bus::{DomainEvent, EventHandler},
+};
+
+/// 受信側で `sha256=<hex>` をbodyのHMAC-SHA256と比較して検証する
+pub const SIGNATURE_HEADER: &str = "X-Quest-Signature";
+pub const EVENT_HEADER: &str = "X-Quest-Event";
+
+/// 初回を含めた配送の試行回数
+const MAX_ATTEMPTS: u32 = 4;
+
+struct Delivery {
+    event: WebhookEvent,
+    /// Noneならすべての購読者に送る
+    owner: Option<UserId>,
+    payload: Value,
+}
+
+/// ドメインイベントをキューに積み、バックグラウンドのワーカーが配送する
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    sender: mpsc::UnboundedSender<Delivery>,
+}
+
+impl WebhookDispatcher {
+    /// ワーカーを起動する。tokioのランタイム上で呼ぶこと
+    pub fn spawn(repository: Arc<dyn WebhookRepository>, client: reqwest::Client) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<Delivery>();
+
+        tokio::spawn(async move {
+            while let Some(delivery) = receiver.recv().await {
+                let webhooks = match repository
+                    .find_by_event(delivery.event, delivery.owner)
+                    .await
+                {
+                    Ok(webhooks) => webhooks,
+                    Err(e) => {
+                        tracing::error!("failed to find webhooks for {}: {}", delivery.event, e);
+                        continue;
+                    }
+                };
+
+                let body = json!({
+                    "event": delivery.event,
+                    "data": delivery.payload,
+                })
+                .to_string();
+                // 遅い送信先が他の配送を止めないよう1件ずつ別タスクにする
+                for webhook in webhooks {
+                    tokio::spawn(deliver(
+                        client.clone(),
+                        webhook,
+                        delivery.event,
+                        body.clone(),
+                    ));
+                }
+            }
+        });
+
+        Self { sender }
+    }
+
+    /// 配送は非同期で行うので、呼び出し側は結果を待たない
+    pub fn dispatch<T: Serialize>(&self, event: WebhookEvent, owner: Option<UserId>, payload: &T) {
+        let payload = match serde_json::to_value(payload) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::error!("failed to serialize webhook payload for {}: {}", event, e);
+                return;
+            }
+        };
+
+        if self
+            .sender
+            .send(Delivery {
+                event,
+                owner,
+                payload,
+            })
+            .is_err()
+        {
+            tracing::error!("webhook worker has stopped, dropped {}", event);
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for WebhookDispatcher {
+    async fn handle(&self, event: &DomainEvent) {
+        match event {
+            DomainEvent::QuestCreated { quest } => {
+                self.dispatch(WebhookEvent::QuestCreated, None, quest)
+            }
+            // 完了は本人の情報なので、本人が登録したwebhookにだけ送る
+            DomainEvent::ChallengeCompleted {
+                user_id,
+                challenge_id,
+            } => self.dispatch(
+                WebhookEvent::ChallengeCompleted,
+                Some(user_id.clone()),
+                &CompleteChallenge {
+                    user_id: user_id.clone(),
+                    challenge_id: challenge_id.clone(),
+                },
+            ),
+            DomainEvent::UserRegistered { .. }
+            | DomainEvent::QuestParticipated { .. }
+            | DomainEvent::QuestUpdated { .. }
+            | DomainEvent::ChallengeCreated { .. }
+            | DomainEvent::ChallengesUpdated { .. }
+            | DomainEvent::QuestCompleted { .. } => {}
+        }
+    }
+}
+
+async fn deliver(client: reqwest::Client, webhook: Webhook, event: WebhookEvent, body: String) {
+    let signature = format!("sha256={}", sign(&webhook.secret, body.as_bytes()));
+
+    for attempt in 0..MAX_ATTEMPTS {
+        let result = client
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event.to_string())
+            .header(SIGNATURE_HEADER, &signature)
+            .body(body.clone())
+            .send()
+            .await;
+
+        match result {
+            Ok(res) if res.status().is_success() => return,
+            Ok(res) => tracing::warn!(
+                "webhook {} responded {} (attempt {})",
+                webhook.id,
+                res.status(),
+                attempt + 1
+            ),
+            Err(e) => tracing::warn!(
+                "failed to send webhook {}: {} (attempt {})",
+                webhook.id,
+                e,
+                attempt + 1
+            ),
+        }
+
+        if attempt + 1 < MAX_ATTEMPTS {
+            tokio::time::sleep(retry_delay(attempt)).await;
+        }
+    }
+
+    tracing::error!("gave up delivering {} to webhook {}", event, webhook.id);
+}
+
+/// 1秒, 2秒, 4秒...と間隔を空けて再送する
+fn retry_delay(attempt: u32) -> Duration {
+    Duration::from_secs(2u64.pow(attempt))
+}
+
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_sign_with_hmac_sha256() {
+        // RFC 4231 Test Case 2
+        assert_eq!(
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            sign("Jefe", b"what do ya want for nothing?")
+        );
+    }
+
+    #[test]
+    fn should_back_off_exponentially() {
+        assert_eq!(Duration::from_secs(1), retry_delay(0));
+        assert_eq!(Duration::from_secs(2), retry_delay(1));
+        assert_eq!(Duration::from_secs(4), retry_delay(2));
+    }
+}